@@ -1,21 +1,39 @@
 mod assembly;
+mod cfg;
+mod optimize;
+mod peephole;
 mod pseudo_pass;
+mod regalloc;
 mod tacky_pass;
 mod x86_pass;
 
 use super::lex::Identifier;
 use crate::semantics::typed;
-use crate::semantics::SymbolTable;
+use crate::semantics::{Attr, SymbolTable};
+use crate::Optimizations;
 pub use assembly::Binary;
 use assembly::Program;
 
 pub fn generate(program: typed::Program, emit_asm: bool, mut table: SymbolTable) -> Box<[u8]> {
-    let tacky = tacky_pass::emit(program, &mut table);
+    let mut tacky = tacky_pass::emit(program, &mut table);
+    let optimizations = Optimizations::current();
+    if optimizations.any() {
+        optimize::optimize(&mut tacky, optimizations, &mut table);
+    }
 
     if emit_asm {
-        let (pseudo, table) = pseudo_pass::emit(tacky, table);
+        // volatile locals have to stay in memory
+        let volatile = (table.iter())
+            .filter(|(_, attr)| matches!(attr, Attr::Automatic(ty) if ty.is_volatile()))
+            .map(|(name, _)| name.clone())
+            .collect();
+        let (mut pseudo, table) = pseudo_pass::emit(tacky, table);
+        regalloc::allocate(&mut pseudo, &table, &volatile);
 
-        let asm = x86_pass::fix_ast(pseudo, &table);
+        let mut asm = x86_pass::fix_ast(pseudo, &table);
+        if optimizations.peephole {
+            peephole::optimize(&mut asm);
+        }
         assembly::emit(&asm)
     } else {
         Box::new([])
@@ -4,7 +4,9 @@ use super::Identifier;
 pub use crate::parse::VarType;
 use crate::semantics::Attr;
 pub use crate::semantics::StaticInit;
+use crate::Target;
 use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
 use std::io::Write;
 pub use tacky::Instruction as TackyInstruction;
 use x86::AsmType;
@@ -24,10 +26,22 @@ pub fn update_table(mut table: crate::semantics::SymbolTable) -> SymbolTable {
     tbl
 }
 
-const fn asm_type(ty: VarType) -> AsmType {
+pub fn asm_type(ty: VarType) -> AsmType {
     match ty {
-        VarType::Int => AsmType::Longword,
-        VarType::Long => AsmType::Quadword,
+        VarType::Char | VarType::SChar | VarType::UChar => AsmType::Byte,
+        VarType::Int | VarType::UInt => AsmType::Longword,
+        VarType::Long | VarType::ULong | VarType::Pointer(_) => AsmType::Quadword,
+        VarType::Double => AsmType::Double,
+        VarType::Array(..)
+        | VarType::IncompleteArray(..)
+        | VarType::Struct(_)
+        | VarType::Union(_) => AsmType::ByteArray {
+            size: ty.size(),
+            alignment: ty.alignment(),
+        },
+        VarType::Qualified(ty, _) => asm_type(*ty),
+        VarType::Void => unreachable!("nothing void gets stored anywhere"),
+        VarType::Function(_) => unreachable!("functions aren't objects"),
     }
 }
 
@@ -42,6 +56,8 @@ impl From<Attr> for BackendSymbol {
                     is_static,
                 }
             }
+            Attr::Constant { typ, .. } => Self::Constant { ty: asm_type(typ) },
+            Attr::Enumerator(_) => unreachable!("typecheck is done with enumerators"),
         }
     }
 }
@@ -51,6 +67,10 @@ pub enum BackendSymbol {
         ty: AsmType,
         is_static: bool,
     },
+    // read only, with a local label
+    Constant {
+        ty: AsmType,
+    },
     #[allow(dead_code)]
     Fn {
         defined: bool,
@@ -64,6 +84,7 @@ pub struct Program<T: InstructionSet>(pub Box<[TopLevel<T>]>);
 pub enum TopLevel<T: InstructionSet> {
     Fn(FunctionDefinition<T>),
     StaticVar(StaticVar),
+    StaticConstant(StaticConstant),
 }
 
 impl<T: InstructionSet> TopLevel<T> {
@@ -71,14 +92,15 @@ impl<T: InstructionSet> TopLevel<T> {
         match self {
             Self::StaticVar(StaticVar { global, .. })
             | Self::Fn(FunctionDefinition { global, .. }) => *global,
+            Self::StaticConstant(_) => false,
         }
     }
 
     const fn name(&self) -> &Identifier {
         match self {
-            Self::StaticVar(StaticVar { name, .. }) | Self::Fn(FunctionDefinition { name, .. }) => {
-                name
-            }
+            Self::StaticVar(StaticVar { name, .. })
+            | Self::Fn(FunctionDefinition { name, .. })
+            | Self::StaticConstant(StaticConstant { name, .. }) => name,
         }
     }
 }
@@ -87,7 +109,15 @@ impl<T: InstructionSet> TopLevel<T> {
 pub struct StaticVar {
     pub name: Identifier,
     pub global: bool,
-    pub alignment: u32,
+    pub alignment: usize,
+    pub init: Box<[StaticInit]>,
+}
+
+// only codegen makes these, so they get local labels instead of symbols
+#[derive(Debug)]
+pub struct StaticConstant {
+    pub name: Identifier,
+    pub alignment: usize,
     pub init: StaticInit,
 }
 
@@ -101,9 +131,10 @@ pub struct FunctionDefinition<T: InstructionSet> {
     pub body: Box<[T]>,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Register {
     Ax,
+    Bx,
     Cx,
     Dx,
     Di,
@@ -112,55 +143,134 @@ pub enum Register {
     R9,
     R10,
     R11,
+    R12,
+    R13,
+    R14,
+    R15,
     Sp,
+    Xmm0,
+    Xmm1,
+    Xmm2,
+    Xmm3,
+    Xmm4,
+    Xmm5,
+    Xmm6,
+    Xmm7,
+    Xmm8,
+    Xmm9,
+    Xmm10,
+    Xmm11,
+    Xmm12,
+    // scratch registers, 13 is codegen's and the other two are for fixing up operands
+    Xmm13,
+    Xmm14,
+    Xmm15,
 }
 
 pub fn emit(program: &Program<X86>) -> Box<[u8]> {
+    let target = Target::current();
     let mut bytes = Vec::new();
     for top_level in &program.0 {
+        let symbol = Symbol(top_level.name());
         if top_level.global() {
-            let _ = writeln!(bytes, "\t.globl _{}", top_level.name());
+            let _ = writeln!(bytes, "\t.globl {symbol}");
         }
         match top_level {
             TopLevel::Fn(FunctionDefinition {
-                name,
+                name: _,
                 params: _,
                 body,
                 global: _,
             }) => {
+                let _ = writeln!(bytes, "\t.text");
+                if target == Target::Linux {
+                    let _ = writeln!(bytes, "\t.type {symbol}, @function");
+                }
                 let _ = writeln!(
                     bytes,
-                    "\t.text\n{name}:\n\t{prelude}",
-                    name = format_args!("_{}", name),
+                    "{symbol}:\n\t{prelude}",
                     prelude = format_args!("pushq %rbp\n\tmovq %rsp, %rbp")
                 );
                 for instruction in body {
                     let _ = writeln!(bytes, "\t{instruction}");
                 }
+                if target == Target::Linux {
+                    let _ = writeln!(bytes, "\t.size {symbol}, .-{symbol}");
+                }
             }
 
             TopLevel::StaticVar(StaticVar {
-                name,
+                name: _,
                 global: _,
                 init,
                 alignment,
             }) => {
-                let init_is_zero = matches!(*init, StaticInit::Long(0) | StaticInit::Int(0));
+                let init_is_zero = init.iter().all(StaticInit::is_zero);
 
                 bytes.extend_from_slice(if init_is_zero {
                     b"\t.bss\n"
                 } else {
                     b"\t.data\n"
                 });
-                let _ = writeln!(bytes, "\t.balign {}\n", alignment);
+                let _ = writeln!(bytes, "\t.balign {}", alignment);
+                if target == Target::Linux {
+                    let _ = writeln!(bytes, "\t.type {symbol}, @object");
+                    let size: usize = init.iter().map(StaticInit::size).sum();
+                    let _ = writeln!(bytes, "\t.size {symbol}, {size}");
+                }
 
-                let _ = writeln!(bytes, "_{name}:\n \t {init}");
+                let _ = writeln!(bytes, "{symbol}:");
+                for init in init {
+                    let _ = writeln!(bytes, " \t {init}");
+                }
+            }
+            TopLevel::StaticConstant(StaticConstant {
+                name,
+                alignment,
+                init,
+            }) => {
+                let section = match target {
+                    Target::Linux => ".section .rodata",
+                    Target::Darwin => ".const",
+                };
+                let _ = writeln!(bytes, "\t{section}");
+                let _ = writeln!(bytes, "\t.balign {alignment}");
+                let _ = writeln!(bytes, "{}:", LocalLabel(name));
+                let _ = writeln!(bytes, " \t {init}");
             }
         }
     }
+    if target == Target::Linux {
+        // otherwise ld assumes we want an executable stack
+        let _ = writeln!(bytes, "\t.section .note.GNU-stack,\"\",@progbits");
+    }
     bytes.into()
 }
 
+// a global symbol, mangled for the current target
+pub struct Symbol<'a>(pub &'a Identifier);
+
+impl Display for Symbol<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match Target::current() {
+            Target::Darwin => write!(f, "_{}", self.0),
+            Target::Linux => write!(f, "{}", self.0),
+        }
+    }
+}
+
+// a label local to the object file, these never make it to the symbol table
+pub struct LocalLabel<'a>(pub &'a Identifier);
+
+impl Display for LocalLabel<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match Target::current() {
+            Target::Darwin => write!(f, "L{}", self.0),
+            Target::Linux => write!(f, ".L{}", self.0),
+        }
+    }
+}
+
 #[derive(Default, Debug)]
 pub struct OpVec<T: InstructionSet>(pub Vec<T>);
 
@@ -198,9 +308,35 @@ impl<T: InstructionSet> From<OpVec<T>> for Box<[T]> {
 }
 
 impl Register {
+    // a function has to leave these how it found them
+    pub const CALLEE_SAVED: [Self; 5] = [Self::Bx, Self::R12, Self::R13, Self::R14, Self::R15];
+
+    pub const fn is_xmm(self) -> bool {
+        matches!(
+            self,
+            Self::Xmm0
+                | Self::Xmm1
+                | Self::Xmm2
+                | Self::Xmm3
+                | Self::Xmm4
+                | Self::Xmm5
+                | Self::Xmm6
+                | Self::Xmm7
+                | Self::Xmm8
+                | Self::Xmm9
+                | Self::Xmm10
+                | Self::Xmm11
+                | Self::Xmm12
+                | Self::Xmm13
+                | Self::Xmm14
+                | Self::Xmm15
+        )
+    }
+
     const fn extended(self) -> &'static str {
         match self {
             Register::Ax => "%eax",
+            Register::Bx => "%ebx",
             Register::Cx => "%ecx",
             Register::Dx => "%edx",
             Register::Di => "%edi",
@@ -210,13 +346,34 @@ impl Register {
             Register::R9 => "%r9d",
             Register::R10 => "%r10d",
             Register::R11 => "%r11d",
+            Register::R12 => "%r12d",
+            Register::R13 => "%r13d",
+            Register::R14 => "%r14d",
+            Register::R15 => "%r15d",
             Register::Sp => "%esp",
+            Register::Xmm0 => "%xmm0",
+            Register::Xmm1 => "%xmm1",
+            Register::Xmm2 => "%xmm2",
+            Register::Xmm3 => "%xmm3",
+            Register::Xmm4 => "%xmm4",
+            Register::Xmm5 => "%xmm5",
+            Register::Xmm6 => "%xmm6",
+            Register::Xmm7 => "%xmm7",
+            Register::Xmm8 => "%xmm8",
+            Register::Xmm9 => "%xmm9",
+            Register::Xmm10 => "%xmm10",
+            Register::Xmm11 => "%xmm11",
+            Register::Xmm12 => "%xmm12",
+            Register::Xmm13 => "%xmm13",
+            Register::Xmm14 => "%xmm14",
+            Register::Xmm15 => "%xmm15",
         }
     }
 
     const fn eight_byte(self) -> &'static str {
         match self {
             Register::Ax => "%rax",
+            Register::Bx => "%rbx",
             Register::Cx => "%rcx",
             Register::Dx => "%rdx",
             Register::Di => "%rdi",
@@ -225,13 +382,20 @@ impl Register {
             Register::R9 => "%r9",
             Register::R10 => "%r10",
             Register::R11 => "%r11",
+            Register::R12 => "%r12",
+            Register::R13 => "%r13",
+            Register::R14 => "%r14",
+            Register::R15 => "%r15",
             Register::Sp => "%rsp",
+            // xmm registers are called the same thing whatever size we use
+            xmm => xmm.extended(),
         }
     }
 
     const fn one_byte(self) -> &'static str {
         match self {
             Register::Ax => "%al",
+            Register::Bx => "%bl",
             Register::Cx => "%cl",
             Register::Dx => "%dl",
             Register::Di => "%dil",
@@ -240,7 +404,100 @@ impl Register {
             Register::R9 => "%r9b",
             Register::R10 => "%r10b",
             Register::R11 => "%r11b",
+            Register::R12 => "%r12b",
+            Register::R13 => "%r13b",
+            Register::R14 => "%r14b",
+            Register::R15 => "%r15b",
             Register::Sp => "%spl",
+            xmm => xmm.extended(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::Identifier;
+    use super::{emit, FunctionDefinition, Program, StaticConstant, StaticInit, StaticVar};
+    use super::{TopLevel, X86};
+    use crate::Target;
+
+    fn program() -> Program<X86> {
+        let main = FunctionDefinition {
+            name: Identifier::from("main"),
+            params: Box::new([]),
+            global: true,
+            body: Box::new([
+                X86::Call {
+                    name: Identifier::from("helper"),
+                    defined: true,
+                },
+                X86::Call {
+                    name: Identifier::from("puts"),
+                    defined: false,
+                },
+                X86::Ret,
+            ]),
+        };
+        let counter = StaticVar {
+            name: Identifier::from("counter"),
+            global: false,
+            alignment: 4,
+            init: Box::new([StaticInit::Int(3)]),
+        };
+        let half = StaticConstant {
+            name: Identifier::from("half"),
+            alignment: 8,
+            init: StaticInit::Double(0.5),
+        };
+        Program(Box::new([
+            TopLevel::Fn(main),
+            TopLevel::StaticVar(counter),
+            TopLevel::StaticConstant(half),
+        ]))
+    }
+
+    fn emitted(target: Target) -> String {
+        Target::set_for_test(target);
+        String::from_utf8(emit(&program()).into()).unwrap()
+    }
+
+    #[test]
+    fn targets() {
+        let linux = emitted(Target::Linux);
+        for line in [
+            "\t.globl main",
+            "\t.type main, @function",
+            "main:",
+            "\tcall helper",
+            "\tcall puts@PLT",
+            "\t.size main, .-main",
+            "\t.type counter, @object",
+            "\t.size counter, 4",
+            "counter:",
+            "\t.section .rodata",
+            ".Lhalf:",
+            "\t.section .note.GNU-stack,\"\",@progbits",
+        ] {
+            assert!(linux.lines().any(|l| l == line), "no `{line}` in\n{linux}");
+        }
+
+        let darwin = emitted(Target::Darwin);
+        for line in [
+            "\t.globl _main",
+            "_main:",
+            "\tcall _helper",
+            "\tcall _puts",
+            "_counter:",
+            "\t.const",
+            "Lhalf:",
+        ] {
+            assert!(
+                darwin.lines().any(|l| l == line),
+                "no `{line}` in\n{darwin}"
+            );
+        }
+        for directive in [".type", ".size", "@PLT", ".note.GNU-stack", ".Lhalf"] {
+            assert!(!darwin.contains(directive), "`{directive}` in\n{darwin}");
         }
     }
 }
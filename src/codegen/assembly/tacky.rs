@@ -1,7 +1,9 @@
 use super::Identifier;
 use super::InstructionSet;
+use crate::codegen::cfg::{Exit, Flow};
 use crate::lex::Constant;
 use crate::parse;
+use crate::parse::Arr;
 use crate::semantics::StaticInit;
 use parse::UnOp;
 use parse::VarType;
@@ -18,16 +20,24 @@ pub struct Program(pub Box<[TopLevel]>);
 pub enum TopLevel {
     Fn(FunctionDefinition),
     StaticVar(StaticVar),
+    StaticConstant(StaticConstant),
 }
 
 #[derive(Debug)]
 pub struct StaticVar {
     pub name: Identifier,
     pub global: bool,
-    pub init: StaticInit,
+    pub init: Arr<StaticInit>,
     pub typ: VarType,
 }
 
+#[derive(Debug)]
+pub struct StaticConstant {
+    pub name: Identifier,
+    pub typ: VarType,
+    pub init: StaticInit,
+}
+
 #[derive(Debug)]
 pub struct FunctionDefinition {
     pub name: Identifier,
@@ -43,12 +53,32 @@ pub enum Instruction {
         src: Value,
         dst: Value,
     },
+    ZeroExtend {
+        src: Value,
+        dst: Value,
+    },
 
     Truncate {
         src: Value,
         dst: Value,
     },
-    Return(Value),
+    DoubleToInt {
+        src: Value,
+        dst: Value,
+    },
+    DoubleToUInt {
+        src: Value,
+        dst: Value,
+    },
+    IntToDouble {
+        src: Value,
+        dst: Value,
+    },
+    UIntToDouble {
+        src: Value,
+        dst: Value,
+    },
+    Return(Option<Value>),
     Unary {
         op: UnOp,
         source: Value,
@@ -79,11 +109,65 @@ pub enum Instruction {
     FunCall {
         name: Identifier,
         args: Box<[Value]>,
+        // `None` for void functions
+        dst: Option<Value>,
+    },
+    // a call through a function pointer
+    IndirectCall {
+        ptr: Value,
+        args: Box<[Value]>,
+        dst: Option<Value>,
+        variadic: bool,
+    },
+    GetAddress {
+        src: Value,
         dst: Value,
     },
+    // dst = *src_ptr
+    Load {
+        src_ptr: Value,
+        dst: Value,
+    },
+    // *dst_ptr = src
+    Store {
+        src: Value,
+        dst_ptr: Value,
+    },
+    // dst = ptr + index * scale
+    AddPtr {
+        ptr: Value,
+        index: Value,
+        scale: i64,
+        dst: Value,
+    },
+    // write src `offset` bytes into the aggregate dst
+    CopyToOffset {
+        src: Value,
+        dst: Identifier,
+        offset: i64,
+    },
+    // read dst from `offset` bytes into the aggregate src
+    CopyFromOffset {
+        src: Identifier,
+        offset: i64,
+        dst: Value,
+    },
+    // fill in the va_list `ap` points at
+    VaStart(Value),
+    // the next variadic argument, as whatever type dst is
+    VaArg {
+        ap: Value,
+        dst: Value,
+    },
+    // only exists between `ssa::construct` and `ssa::destruct`, picks the source that goes with
+    // the label of the block control came from
+    Phi {
+        dst: Identifier,
+        sources: Vec<(Identifier, Value)>,
+    },
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Value {
     Constant(Constant),
     Var(Identifier),
@@ -108,3 +192,199 @@ pub enum TackyBinary {
     Leq,
     Geq,
 }
+
+impl Instruction {
+    // every value the instruction reads, `GetAddress` only needs to know where its source lives
+    pub fn sources(&self) -> Vec<&Value> {
+        match self {
+            Self::SignExtend { src, .. }
+            | Self::ZeroExtend { src, .. }
+            | Self::Truncate { src, .. }
+            | Self::DoubleToInt { src, .. }
+            | Self::DoubleToUInt { src, .. }
+            | Self::IntToDouble { src, .. }
+            | Self::UIntToDouble { src, .. }
+            | Self::Copy { src, .. }
+            | Self::CopyToOffset { src, .. }
+            | Self::Return(Some(src))
+            | Self::Unary { source: src, .. }
+            | Self::JumpIfZero { condition: src, .. }
+            | Self::JumpIfNotZero { condition: src, .. }
+            | Self::Load { src_ptr: src, .. }
+            | Self::VaStart(src)
+            | Self::VaArg { ap: src, .. } => vec![src],
+            Self::Binary {
+                source_1, source_2, ..
+            } => vec![source_1, source_2],
+            Self::Store { src, dst_ptr } => vec![src, dst_ptr],
+            Self::AddPtr { ptr, index, .. } => vec![ptr, index],
+            Self::FunCall { args, .. } => args.iter().collect(),
+            Self::IndirectCall { ptr, args, .. } => {
+                std::iter::once(ptr).chain(args.iter()).collect()
+            }
+            Self::Phi { sources, .. } => sources.iter().map(|(_, value)| value).collect(),
+            Self::Return(None)
+            | Self::Jump { .. }
+            | Self::Label(_)
+            | Self::GetAddress { .. }
+            | Self::CopyFromOffset { .. } => Vec::new(),
+        }
+    }
+
+    pub fn sources_mut(&mut self) -> Vec<&mut Value> {
+        match self {
+            Self::SignExtend { src, .. }
+            | Self::ZeroExtend { src, .. }
+            | Self::Truncate { src, .. }
+            | Self::DoubleToInt { src, .. }
+            | Self::DoubleToUInt { src, .. }
+            | Self::IntToDouble { src, .. }
+            | Self::UIntToDouble { src, .. }
+            | Self::Copy { src, .. }
+            | Self::CopyToOffset { src, .. }
+            | Self::Return(Some(src))
+            | Self::Unary { source: src, .. }
+            | Self::JumpIfZero { condition: src, .. }
+            | Self::JumpIfNotZero { condition: src, .. }
+            | Self::Load { src_ptr: src, .. }
+            | Self::VaStart(src)
+            | Self::VaArg { ap: src, .. } => vec![src],
+            Self::Binary {
+                source_1, source_2, ..
+            } => vec![source_1, source_2],
+            Self::Store { src, dst_ptr } => vec![src, dst_ptr],
+            Self::AddPtr { ptr, index, .. } => vec![ptr, index],
+            Self::FunCall { args, .. } => args.iter_mut().collect(),
+            Self::IndirectCall { ptr, args, .. } => {
+                std::iter::once(ptr).chain(args.iter_mut()).collect()
+            }
+            Self::Phi { sources, .. } => sources.iter_mut().map(|(_, value)| value).collect(),
+            Self::Return(None)
+            | Self::Jump { .. }
+            | Self::Label(_)
+            | Self::GetAddress { .. }
+            | Self::CopyFromOffset { .. } => Vec::new(),
+        }
+    }
+
+    // the variables whose values the instruction reads
+    pub fn reads(&self) -> Vec<&Identifier> {
+        let mut reads: Vec<_> = (self.sources().into_iter())
+            .filter_map(|value| match value {
+                Value::Var(name) => Some(name),
+                Value::Constant(_) => None,
+            })
+            .collect();
+        if let Self::CopyFromOffset { src, .. } = self {
+            reads.push(src);
+        }
+        reads
+    }
+
+    // the variable the instruction writes to, `CopyToOffset` only writes part of it
+    pub fn destination(&self) -> Option<&Identifier> {
+        match self {
+            Self::Unary { dst, .. } | Self::CopyToOffset { dst, .. } | Self::Phi { dst, .. } => {
+                Some(dst)
+            }
+            Self::SignExtend { dst, .. }
+            | Self::ZeroExtend { dst, .. }
+            | Self::Truncate { dst, .. }
+            | Self::DoubleToInt { dst, .. }
+            | Self::DoubleToUInt { dst, .. }
+            | Self::IntToDouble { dst, .. }
+            | Self::UIntToDouble { dst, .. }
+            | Self::Binary { dst, .. }
+            | Self::Copy { dst, .. }
+            | Self::GetAddress { dst, .. }
+            | Self::Load { dst, .. }
+            | Self::AddPtr { dst, .. }
+            | Self::CopyFromOffset { dst, .. }
+            | Self::VaArg { dst, .. }
+            | Self::FunCall { dst: Some(dst), .. }
+            | Self::IndirectCall { dst: Some(dst), .. } => match dst {
+                Value::Var(dst) => Some(dst),
+                Value::Constant(_) => None,
+            },
+            Self::Return(_)
+            | Self::Jump { .. }
+            | Self::JumpIfZero { .. }
+            | Self::JumpIfNotZero { .. }
+            | Self::Label(_)
+            | Self::FunCall { dst: None, .. }
+            | Self::IndirectCall { dst: None, .. }
+            | Self::Store { .. }
+            | Self::VaStart(_) => None,
+        }
+    }
+
+    pub fn destination_mut(&mut self) -> Option<&mut Identifier> {
+        match self {
+            Self::Unary { dst, .. } | Self::CopyToOffset { dst, .. } | Self::Phi { dst, .. } => {
+                Some(dst)
+            }
+            Self::SignExtend { dst, .. }
+            | Self::ZeroExtend { dst, .. }
+            | Self::Truncate { dst, .. }
+            | Self::DoubleToInt { dst, .. }
+            | Self::DoubleToUInt { dst, .. }
+            | Self::IntToDouble { dst, .. }
+            | Self::UIntToDouble { dst, .. }
+            | Self::Binary { dst, .. }
+            | Self::Copy { dst, .. }
+            | Self::GetAddress { dst, .. }
+            | Self::Load { dst, .. }
+            | Self::AddPtr { dst, .. }
+            | Self::CopyFromOffset { dst, .. }
+            | Self::VaArg { dst, .. }
+            | Self::FunCall { dst: Some(dst), .. }
+            | Self::IndirectCall { dst: Some(dst), .. } => match dst {
+                Value::Var(dst) => Some(dst),
+                Value::Constant(_) => None,
+            },
+            Self::Return(_)
+            | Self::Jump { .. }
+            | Self::JumpIfZero { .. }
+            | Self::JumpIfNotZero { .. }
+            | Self::Label(_)
+            | Self::FunCall { dst: None, .. }
+            | Self::IndirectCall { dst: None, .. }
+            | Self::Store { .. }
+            | Self::VaStart(_) => None,
+        }
+    }
+
+    // calls and anything going through a pointer can read or write any variable whose address
+    // got out
+    pub const fn touches_memory(&self) -> bool {
+        matches!(
+            self,
+            Self::FunCall { .. }
+                | Self::IndirectCall { .. }
+                | Self::Load { .. }
+                | Self::Store { .. }
+                | Self::VaStart(_)
+                | Self::VaArg { .. }
+        )
+    }
+}
+
+impl Flow for Instruction {
+    fn label(&self) -> Option<&Identifier> {
+        match self {
+            Self::Label(label) => Some(label),
+            _ => None,
+        }
+    }
+
+    fn exit(&self) -> Exit<'_> {
+        match self {
+            Self::Jump { target } => Exit::Jumps(target),
+            Self::JumpIfZero { target, .. } | Self::JumpIfNotZero { target, .. } => {
+                Exit::Branches(target)
+            }
+            Self::Return(_) => Exit::Returns,
+            _ => Exit::Falls,
+        }
+    }
+}
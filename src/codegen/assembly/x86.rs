@@ -1,16 +1,22 @@
-use super::tacky::Value;
 use super::Identifier;
 use super::InstructionSet;
 use super::Register;
+use super::{LocalLabel, Symbol};
+use crate::codegen::cfg::{Exit, Flow};
 use crate::parse::UnOp;
+use crate::Target;
 use std::fmt::{self, Display, Formatter};
 
 pub type Pseudo = BaseX86<PseudoOp>;
 pub type X86 = BaseX86<Op>;
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum AsmType {
+    Byte,
     Longword,
     Quadword,
+    Double,
+    // arrays, which only ever live in memory
+    ByteArray { size: usize, alignment: usize },
 }
 impl InstructionSet for X86 {}
 impl InstructionSet for Pseudo {}
@@ -22,7 +28,17 @@ pub enum BaseX86<T: Operand> {
         regs: OpPair<T>,
     },
     Movsx {
-        ty: AsmType,
+        src_ty: AsmType,
+        dst_ty: AsmType,
+        regs: OpPair<T>,
+    },
+    // the destination has to be a register
+    MovZeroExtend {
+        src_ty: AsmType,
+        dst_ty: AsmType,
+        regs: OpPair<T>,
+    },
+    Lea {
         regs: OpPair<T>,
     },
     Unary {
@@ -36,12 +52,32 @@ pub enum BaseX86<T: Operand> {
         ty: AsmType,
     },
     Push(T),
-    Call(Identifier),
+    // only for restoring callee saved registers
+    Pop(Register),
+    Call {
+        name: Identifier,
+        defined: bool,
+    },
+    // through a function pointer
+    CallIndirect(T),
     Ret,
     Idiv {
         divisor: T,
         ty: AsmType,
     },
+    Div {
+        divisor: T,
+        ty: AsmType,
+    },
+    // `ty` is the integer side of the conversion, the double side has to be a register
+    Cvtsi2sd {
+        ty: AsmType,
+        regs: OpPair<T>,
+    },
+    Cvttsd2si {
+        ty: AsmType,
+        regs: OpPair<T>,
+    },
     Cdq(AsmType),
     Cmp {
         ty: AsmType,
@@ -69,6 +105,13 @@ pub enum CondCode {
     GE,
     L,
     LE,
+    // unsigned
+    A,
+    AE,
+    B,
+    BE,
+    // comisd sets this when either side is NaN
+    P,
 }
 
 impl Display for CondCode {
@@ -83,6 +126,11 @@ impl Display for CondCode {
                 Self::GE => "ge",
                 Self::L => "l",
                 Self::LE => "le",
+                Self::A => "a",
+                Self::AE => "ae",
+                Self::B => "b",
+                Self::BE => "be",
+                Self::P => "p",
             }
         )
     }
@@ -152,21 +200,219 @@ impl<T: Operand> BaseX86<T> {
         Self::Idiv { divisor, ty }
     }
 
-    pub const fn movsx(src: T, dst: T) -> Self {
+    pub const fn div(divisor: T, ty: AsmType) -> Self {
+        Self::Div { divisor, ty }
+    }
+
+    pub const fn movsx(src: T, dst: T, src_ty: AsmType, dst_ty: AsmType) -> Self {
         Self::Movsx {
-            ty: AsmType::Quadword,
+            src_ty,
+            dst_ty,
+            regs: (src, dst),
+        }
+    }
+
+    pub const fn movzx(src: T, dst: T, src_ty: AsmType, dst_ty: AsmType) -> Self {
+        Self::MovZeroExtend {
+            src_ty,
+            dst_ty,
             regs: (src, dst),
         }
     }
 }
 
+impl<T: Operand> BaseX86<T> {
+    pub fn operands(&self) -> Vec<&T> {
+        match self {
+            Self::Mov { regs, .. }
+            | Self::Movsx { regs, .. }
+            | Self::MovZeroExtend { regs, .. }
+            | Self::Lea { regs }
+            | Self::Binary { regs, .. }
+            | Self::Cvtsi2sd { regs, .. }
+            | Self::Cvttsd2si { regs, .. }
+            | Self::Cmp { regs, .. } => vec![&regs.0, &regs.1],
+            Self::Unary { operand: op, .. }
+            | Self::Push(op)
+            | Self::CallIndirect(op)
+            | Self::Idiv { divisor: op, .. }
+            | Self::Div { divisor: op, .. }
+            | Self::SetCC { op, .. } => vec![op],
+            Self::Pop(_)
+            | Self::Call { .. }
+            | Self::Ret
+            | Self::Cdq(_)
+            | Self::Jmp(_)
+            | Self::JmpCC { .. }
+            | Self::Label(_) => Vec::new(),
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut T> {
+        match self {
+            Self::Mov { regs, .. }
+            | Self::Movsx { regs, .. }
+            | Self::MovZeroExtend { regs, .. }
+            | Self::Lea { regs }
+            | Self::Binary { regs, .. }
+            | Self::Cvtsi2sd { regs, .. }
+            | Self::Cvttsd2si { regs, .. }
+            | Self::Cmp { regs, .. } => vec![&mut regs.0, &mut regs.1],
+            Self::Unary { operand: op, .. }
+            | Self::Push(op)
+            | Self::CallIndirect(op)
+            | Self::Idiv { divisor: op, .. }
+            | Self::Div { divisor: op, .. }
+            | Self::SetCC { op, .. } => vec![op],
+            Self::Pop(_)
+            | Self::Call { .. }
+            | Self::Ret
+            | Self::Cdq(_)
+            | Self::Jmp(_)
+            | Self::JmpCC { .. }
+            | Self::Label(_) => Vec::new(),
+        }
+    }
+
+    // what the instruction reads and writes, the allocator and the peephole pass each work out
+    // which registers that comes to
+    pub fn accesses(&self) -> Vec<Access<'_, T>>
+    where
+        T: PartialEq,
+    {
+        match self {
+            Self::Mov {
+                ty: AsmType::Byte,
+                regs: (src, dst),
+            } => vec![Access::Read(src), Access::WriteByte(dst)],
+            Self::Mov {
+                regs: (src, dst), ..
+            }
+            | Self::Movsx {
+                regs: (src, dst), ..
+            }
+            | Self::MovZeroExtend {
+                regs: (src, dst), ..
+            }
+            | Self::Cvtsi2sd {
+                regs: (src, dst), ..
+            }
+            | Self::Cvttsd2si {
+                regs: (src, dst), ..
+            } => vec![Access::Read(src), Access::Write(dst)],
+            Self::Lea { regs: (src, dst) } => vec![Access::Address(src), Access::Write(dst)],
+            Self::Binary {
+                operator,
+                regs: (src, dst),
+                ty,
+            } => {
+                // zeroing a register doesn't care what was in it
+                let mut accesses = if *operator == Binary::Xor && src == dst {
+                    vec![Access::Write(dst)]
+                } else {
+                    vec![Access::Read(src), Access::Read(dst), Access::Write(dst)]
+                };
+                // shifting by zero leaves the flags alone, and sse arithmetic never touches them
+                if *ty != AsmType::Double
+                    && !matches!(
+                        operator,
+                        Binary::ShiftLeft | Binary::ShiftRight | Binary::ShiftRightLogical
+                    )
+                {
+                    accesses.push(Access::WriteFlags);
+                }
+                accesses
+            }
+            Self::Unary {
+                operator, operand, ..
+            } => {
+                let mut accesses = vec![Access::Read(operand), Access::Write(operand)];
+                if *operator == Unary::Neg {
+                    accesses.push(Access::WriteFlags);
+                }
+                accesses
+            }
+            // setcc only writes the low byte
+            Self::SetCC { op, .. } => vec![Access::ReadFlags, Access::Read(op), Access::Write(op)],
+            Self::Cmp {
+                regs: (left, right),
+                ..
+            } => vec![Access::Read(left), Access::Read(right), Access::WriteFlags],
+            Self::Push(op) => vec![Access::Read(op)],
+            Self::Pop(register) => vec![Access::WriteRegister(*register)],
+            Self::Idiv { divisor, .. } | Self::Div { divisor, .. } => vec![
+                Access::Read(divisor),
+                Access::ReadRegister(Register::Ax),
+                Access::ReadRegister(Register::Dx),
+                Access::WriteRegister(Register::Ax),
+                Access::WriteRegister(Register::Dx),
+                Access::WriteFlags,
+            ],
+            Self::Cdq(_) => vec![
+                Access::ReadRegister(Register::Ax),
+                Access::WriteRegister(Register::Dx),
+            ],
+            Self::Call { .. } => vec![Access::Arguments, Access::CallerSaved, Access::WriteFlags],
+            Self::CallIndirect(ptr) => vec![
+                Access::Read(ptr),
+                Access::Arguments,
+                Access::CallerSaved,
+                Access::WriteFlags,
+            ],
+            Self::Ret => vec![Access::Returns],
+            Self::JmpCC { .. } => vec![Access::ReadFlags],
+            Self::Jmp(_) | Self::Label(_) => Vec::new(),
+        }
+    }
+}
+
+pub enum Access<'a, T> {
+    Read(&'a T),
+    Write(&'a T),
+    // leaves the rest of a register as it was
+    WriteByte(&'a T),
+    // only the registers that find where the operand is in memory
+    Address(&'a T),
+    ReadRegister(Register),
+    WriteRegister(Register),
+    ReadFlags,
+    WriteFlags,
+    // the registers a call gets its arguments in
+    Arguments,
+    // and the ones a function returns in
+    Returns,
+    // what a call can leave anything in
+    CallerSaved,
+}
+
+impl<T: Operand> Flow for BaseX86<T> {
+    fn label(&self) -> Option<&Identifier> {
+        match self {
+            Self::Label(label) => Some(label),
+            _ => None,
+        }
+    }
+
+    fn exit(&self) -> Exit<'_> {
+        match self {
+            Self::Jmp(label) => Exit::Jumps(label),
+            Self::JmpCC { label, .. } => Exit::Branches(label),
+            Self::Ret => Exit::Returns,
+            _ => Exit::Falls,
+        }
+    }
+}
+
 pub trait Operand {}
 
 impl Display for AsmType {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         f.write_str(match self {
+            Self::Byte => "b",
             Self::Longword => "l",
             Self::Quadword => "q",
+            Self::Double => "sd",
+            Self::ByteArray { .. } => unreachable!("arrays aren't moved around as a whole"),
         })
     }
 }
@@ -174,10 +420,17 @@ impl Display for AsmType {
 impl Display for X86 {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match self {
-            Self::Call(fun) => write!(f, "call _{fun}"),
+            // anything we didn't define might live in a shared library
+            Self::Call {
+                name,
+                defined: false,
+            } if Target::current() == Target::Linux => write!(f, "call {}@PLT", Symbol(name)),
+            Self::Call { name, .. } => write!(f, "call {}", Symbol(name)),
+            Self::CallIndirect(op) => write!(f, "call *{}", op.sized_fmt(AsmType::Quadword)),
 
             Self::Push(Op::Register(r)) => write!(f, "pushq {}", r.eight_byte()),
             Self::Push(op) => write!(f, "pushq {op}"),
+            Self::Pop(r) => write!(f, "popq {}", r.eight_byte()),
 
             Self::Mov {
                 regs: (src, dst),
@@ -185,6 +438,9 @@ impl Display for X86 {
             } => {
                 write!(f, "mov{ty}  {}, {}", src.sized_fmt(*ty), dst.sized_fmt(*ty))
             }
+            Self::Lea { regs: (src, dst) } => {
+                write!(f, "leaq {src}, {}", dst.sized_fmt(AsmType::Quadword))
+            }
             Self::Ret => write!(f, "movq %rbp, %rsp\n\tpopq %rbp\n\tret"),
             Self::Unary {
                 operator,
@@ -192,12 +448,26 @@ impl Display for X86 {
                 ty,
             } => write!(f, "{operator}{ty} {}", operand.sized_fmt(*ty)),
             Self::Binary {
-                operator: operator @ (Binary::ShiftLeft | Binary::ShiftRight),
+                operator:
+                    operator @ (Binary::ShiftLeft | Binary::ShiftRight | Binary::ShiftRightLogical),
                 regs: (Op::Register(r), by),
                 ty,
             } => {
                 write!(f, "{operator}{ty} {}, {}", r.one_byte(), by.sized_fmt(*ty),)
             }
+            // sse doesn't follow the naming scheme all the way
+            Self::Binary {
+                operator: operator @ (Binary::Mult | Binary::Xor),
+                regs: (op, dst_op),
+                ty: AsmType::Double,
+            } => {
+                let name = if *operator == Binary::Mult {
+                    "mulsd"
+                } else {
+                    "xorpd"
+                };
+                write!(f, "{name} {op}, {dst_op}")
+            }
             Self::Binary {
                 operator,
                 regs: (op, dst_op),
@@ -213,12 +483,30 @@ impl Display for X86 {
             Self::Idiv { divisor, ty } => {
                 write!(f, "idiv{ty} {}", divisor.sized_fmt(*ty))
             }
+            Self::Div { divisor, ty } => {
+                write!(f, "div{ty} {}", divisor.sized_fmt(*ty))
+            }
             Self::Cdq(AsmType::Longword) => {
                 write!(f, "cdq")
             }
             Self::Cdq(AsmType::Quadword) => {
                 write!(f, "cqo")
             }
+            Self::Cdq(AsmType::Byte | AsmType::Double | AsmType::ByteArray { .. }) => {
+                unreachable!()
+            }
+            Self::Cvtsi2sd {
+                ty,
+                regs: (src, dst),
+            } => write!(f, "cvtsi2sd{ty} {}, {dst}", src.sized_fmt(*ty)),
+            Self::Cvttsd2si {
+                ty,
+                regs: (src, dst),
+            } => write!(f, "cvttsd2si{ty} {src}, {}", dst.sized_fmt(*ty)),
+            Self::Cmp {
+                regs: (left, right),
+                ty: AsmType::Double,
+            } => write!(f, "comisd {left}, {right}"),
             Self::Cmp {
                 regs: (left, right),
                 ty,
@@ -231,26 +519,52 @@ impl Display for X86 {
                 )
             }
             Self::Jmp(label) => {
-                write!(f, "jmp L{label}")
+                write!(f, "jmp {}", LocalLabel(label))
             }
             Self::JmpCC { label, condition } => {
-                write!(f, "j{condition} L{label}")
+                write!(f, "j{condition} {}", LocalLabel(label))
             }
             Self::SetCC { op, condition } => {
-                write!(f, "set{condition} {op}")
+                write!(f, "set{condition} {}", op.sized_fmt(AsmType::Byte))
             }
             Self::Label(label) => {
-                write!(f, "L{label}:")
+                write!(f, "{}:", LocalLabel(label))
             }
             Self::Movsx {
                 regs: (src, dst),
-                ty,
+                src_ty,
+                dst_ty,
+            } => {
+                write!(
+                    f,
+                    "movs{src_ty}{dst_ty} {}, {}",
+                    src.sized_fmt(*src_ty),
+                    dst.sized_fmt(*dst_ty)
+                )
+            }
+            // writing the low half of a register clears the high half
+            Self::MovZeroExtend {
+                regs: (src, dst),
+                src_ty: AsmType::Longword,
+                ..
             } => {
                 write!(
                     f,
-                    "movslq {}, {}",
+                    "movl {}, {}",
                     src.sized_fmt(AsmType::Longword),
-                    dst.sized_fmt(*ty)
+                    dst.sized_fmt(AsmType::Longword)
+                )
+            }
+            Self::MovZeroExtend {
+                regs: (src, dst),
+                src_ty,
+                dst_ty,
+            } => {
+                write!(
+                    f,
+                    "movz{src_ty}{dst_ty} {}, {}",
+                    src.sized_fmt(*src_ty),
+                    dst.sized_fmt(*dst_ty)
                 )
             }
         }
@@ -276,14 +590,31 @@ pub mod op_regs {
     pub const R10: Op = Op::Register(Register::R10);
     pub const R11: Op = Op::Register(Register::R11);
     pub const SP: Op = Op::Register(Register::Sp);
+    pub const XMM0: Op = Op::Register(Register::Xmm0);
+    pub const XMM13: Op = Op::Register(Register::Xmm13);
+    pub const XMM14: Op = Op::Register(Register::Xmm14);
+    pub const XMM15: Op = Op::Register(Register::Xmm15);
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Op {
     Imm(i64),
     Register(Register),
     Stack(isize),
-    Data(Identifier),
+    // a static variable, and how far into it
+    Data(Identifier, isize),
+    // a read only constant, see `literal`
+    Literal(Identifier),
+    // the global offset table entry holding a function's address
+    Got(Identifier),
+    // offset from an address held in a register
+    Memory(Register, isize),
+    // base + index * scale
+    Indexed {
+        base: Register,
+        index: Register,
+        scale: u8,
+    },
 }
 impl Operand for Op {}
 
@@ -311,6 +642,10 @@ pub enum Binary {
     Xor,
     ShiftLeft,
     ShiftRight,
+    // logical, for unsigned operands
+    ShiftRightLogical,
+    // there's no sse version of the integer division instructions
+    DivDouble,
 }
 
 impl Display for Unary {
@@ -337,6 +672,8 @@ impl Display for Binary {
             Self::Xor => "xor",
             Self::ShiftLeft => "sal",
             Self::ShiftRight => "sar",
+            Self::ShiftRightLogical => "shr",
+            Self::DivDouble => "div",
         })
     }
 }
@@ -357,7 +694,14 @@ impl Display for Op {
             Self::Imm(val) => write!(f, "${val}"),
             Self::Register(r) => write!(f, "{}", r.extended()),
             Self::Stack(n) => write!(f, "{n}(%rbp)"),
-            Self::Data(name) => write!(f, "_{name}(%rip)"),
+            Self::Data(name, 0) => write!(f, "{}(%rip)", Symbol(name)),
+            Self::Data(name, n) => write!(f, "{}+{n}(%rip)", Symbol(name)),
+            Self::Literal(name) => write!(f, "{}(%rip)", LocalLabel(name)),
+            Self::Got(name) => write!(f, "{}@GOTPCREL(%rip)", Symbol(name)),
+            Self::Memory(r, n) => write!(f, "{n}({})", r.eight_byte()),
+            Self::Indexed { base, index, scale } => {
+                write!(f, "({},{},{scale})", base.eight_byte(), index.eight_byte())
+            }
         }
     }
 }
@@ -366,8 +710,11 @@ impl Op {
     fn sized_fmt(&self, size: AsmType) -> String {
         if let Self::Register(r) = self {
             match size {
+                AsmType::Byte => r.one_byte().into(),
                 AsmType::Longword => r.extended().into(),
-                AsmType::Quadword => r.eight_byte().into(),
+                AsmType::Quadword | AsmType::Double | AsmType::ByteArray { .. } => {
+                    r.eight_byte().into()
+                }
             }
         } else {
             format!("{self}")
@@ -375,19 +722,12 @@ impl Op {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum PseudoOp {
     Normal(Op),
     PseudoRegister(Identifier),
-}
-impl From<Value> for PseudoOp {
-    fn from(val: Value) -> Self {
-        match val {
-            Value::Constant(c) => Self::imm(c.long()),
-
-            Value::Var(v) => Self::PseudoRegister(v),
-        }
-    }
+    // somewhere inside an aggregate, `offset` bytes from its start
+    PseudoMem(Identifier, isize),
 }
 
 impl From<Identifier> for PseudoOp {
@@ -403,7 +743,11 @@ impl<T: Into<Op>> From<T> for PseudoOp {
             Op::Imm(a) => Self::imm(a),
             Op::Register(a) => Self::register(a),
             Op::Stack(a) => Self::stack(a),
-            Op::Data(a) => Self::data(a),
+            op @ (Op::Data(..)
+            | Op::Memory(..)
+            | Op::Indexed { .. }
+            | Op::Literal(_)
+            | Op::Got(_)) => Self::Normal(op),
         }
     }
 }
@@ -422,6 +766,8 @@ pub mod pseudo_regs {
     pub const R10: Psu = Psu::Normal(regs::R10);
     pub const R11: Psu = Psu::Normal(regs::R11);
     pub const SP: Psu = Psu::Normal(regs::SP);
+    pub const XMM0: Psu = Psu::Normal(regs::XMM0);
+    pub const XMM13: Psu = Psu::Normal(regs::XMM13);
 }
 
 impl PseudoOp {
@@ -434,6 +780,17 @@ impl PseudoOp {
         Register::R9,
     ];
 
+    pub const SYSV_FLOAT_REGS: [Register; 8] = [
+        Register::Xmm0,
+        Register::Xmm1,
+        Register::Xmm2,
+        Register::Xmm3,
+        Register::Xmm4,
+        Register::Xmm5,
+        Register::Xmm6,
+        Register::Xmm7,
+    ];
+
     pub const fn imm(value: i64) -> Self {
         Self::Normal(Op::Imm(value))
     }
@@ -441,11 +798,66 @@ impl PseudoOp {
         Self::Normal(Op::Register(reg))
     }
 
-    pub const fn data(name: Identifier) -> Self {
-        Self::Normal(Op::Data(name))
+    // `by` bytes further into the same object
+    pub fn offset(&self, by: isize) -> Self {
+        match self {
+            Self::PseudoRegister(name) => Self::PseudoMem(name.clone(), by),
+            Self::PseudoMem(name, offset) => Self::PseudoMem(name.clone(), offset + by),
+            Self::Normal(Op::Memory(reg, offset)) => Self::Normal(Op::Memory(*reg, offset + by)),
+            Self::Normal(Op::Stack(offset)) => Self::Normal(Op::Stack(offset + by)),
+            op => unreachable!("{op:?} isn't an object in memory"),
+        }
     }
 
     pub const fn stack(offset: isize) -> Self {
         Self::Normal(Op::Stack(offset))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::op_regs::{AX, CX, DI};
+    use super::{AsmType, Binary, CondCode, Op, Register, X86};
+
+    #[test]
+    fn byte_operands() {
+        // registers go by the name of their low byte, memory and immediates look the same
+        let byte = AsmType::Byte;
+        for (instruction, text) in [
+            (X86::mov(CX, AX, byte), "movb  %cl, %al"),
+            (
+                X86::mov(Op::Imm(-1), Op::Memory(Register::Ax, 2), byte),
+                "movb  $-1, 2(%rax)",
+            ),
+            (
+                X86::binary(Binary::Add, Op::Imm(1), DI, byte),
+                "addb $1, %dil",
+            ),
+            (X86::cmp(CX, DI, byte), "cmpb %cl, %dil"),
+            (
+                X86::movsx(CX, AX, byte, AsmType::Longword),
+                "movsbl %cl, %eax",
+            ),
+            (
+                X86::movzx(CX, AX, byte, AsmType::Quadword),
+                "movzbq %cl, %rax",
+            ),
+            (
+                X86::SetCC {
+                    condition: CondCode::E,
+                    op: DI,
+                },
+                "sete %dil",
+            ),
+        ] {
+            assert_eq!(instruction.to_string(), text);
+        }
+    }
+
+    #[test]
+    fn indirect_calls() {
+        assert_eq!(X86::CallIndirect(AX).to_string(), "call *%rax");
+        let slot = Op::Memory(Register::Sp, 8);
+        assert_eq!(X86::CallIndirect(slot).to_string(), "call *8(%rsp)");
+    }
+}
@@ -0,0 +1,506 @@
+// basic blocks and a worklist solver for dataflow analyses over them, for any instruction set
+// that can say where its instructions jump to
+use super::Identifier;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+use std::ops::Range;
+
+pub trait Flow {
+    fn label(&self) -> Option<&Identifier>;
+    fn exit(&self) -> Exit<'_>;
+}
+
+// how control leaves an instruction
+pub enum Exit<'a> {
+    Falls,
+    Jumps(&'a Identifier),
+    // jumps or falls through
+    Branches(&'a Identifier),
+    Returns,
+}
+
+// a run of instructions that can only be entered at the top and only leaves at the bottom
+pub struct Block {
+    pub instructions: Range<usize>,
+    pub successors: Vec<usize>,
+    pub predecessors: Vec<usize>,
+}
+
+// the first block is where the function starts, the ones without successors return
+pub struct Graph {
+    pub blocks: Vec<Block>,
+}
+
+impl Graph {
+    pub fn new<T: Flow>(body: &[T]) -> Self {
+        let mut starts = vec![0];
+        for (i, instruction) in body.iter().enumerate() {
+            if instruction.label().is_some() {
+                starts.push(i);
+            } else if !matches!(instruction.exit(), Exit::Falls) {
+                starts.push(i + 1);
+            }
+        }
+        starts.push(body.len());
+        starts.dedup();
+
+        let mut blocks: Vec<Block> = starts
+            .windows(2)
+            .map(|bounds| Block {
+                instructions: bounds[0]..bounds[1],
+                successors: Vec::new(),
+                predecessors: Vec::new(),
+            })
+            .collect();
+        let labels: HashMap<&Identifier, usize> = (blocks.iter().enumerate())
+            .filter_map(|(i, block)| Some((body[block.instructions.start].label()?, i)))
+            .collect();
+
+        let mut edges = Vec::new();
+        for (i, block) in blocks.iter().enumerate() {
+            let fallthrough = (i + 1 < blocks.len()).then_some(i + 1);
+            match block
+                .instructions
+                .clone()
+                .last()
+                .map(|last| body[last].exit())
+            {
+                Some(Exit::Returns) => (),
+                Some(Exit::Jumps(target)) => edges.push((i, labels[target])),
+                Some(Exit::Branches(target)) => {
+                    edges.push((i, labels[target]));
+                    edges.extend(fallthrough.map(|next| (i, next)));
+                }
+                Some(Exit::Falls) | None => edges.extend(fallthrough.map(|next| (i, next))),
+            }
+        }
+        for (from, to) in edges {
+            if !blocks[from].successors.contains(&to) {
+                blocks[from].successors.push(to);
+                blocks[to].predecessors.push(from);
+            }
+        }
+        Self { blocks }
+    }
+
+    pub fn reachable(&self) -> Vec<bool> {
+        let mut reachable = vec![false; self.blocks.len()];
+        let mut worklist = vec![0];
+        while let Some(block) = worklist.pop() {
+            if block < self.blocks.len() && !std::mem::replace(&mut reachable[block], true) {
+                worklist.extend(&self.blocks[block].successors);
+            }
+        }
+        reachable
+    }
+
+    // each block's immediate dominator, the last block every path from the start goes through
+    // before it, `None` for the start and anything unreachable
+    pub fn dominators(&self) -> Vec<Option<usize>> {
+        // reverse postorder, so a block comes before everything it dominates
+        let mut order = Vec::new();
+        let mut visited = vec![false; self.blocks.len()];
+        let mut stack = vec![(0, 0)];
+        visited[0] = !self.blocks.is_empty();
+        while let Some((block, next)) = stack.pop() {
+            match self.blocks[block].successors.get(next) {
+                Some(&successor) => {
+                    stack.push((block, next + 1));
+                    if !std::mem::replace(&mut visited[successor], true) {
+                        stack.push((successor, 0));
+                    }
+                }
+                None => order.push(block),
+            }
+        }
+        order.reverse();
+        let mut position = vec![usize::MAX; self.blocks.len()];
+        for (i, block) in order.iter().enumerate() {
+            position[*block] = i;
+        }
+
+        let mut idom = vec![None; self.blocks.len()];
+        if let Some(entry) = idom.first_mut() {
+            *entry = Some(0);
+        }
+        let intersect = |idom: &[Option<usize>], mut a: usize, mut b: usize| {
+            while a != b {
+                while position[a] > position[b] {
+                    a = idom[a].unwrap();
+                }
+                while position[b] > position[a] {
+                    b = idom[b].unwrap();
+                }
+            }
+            a
+        };
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in order.iter().skip(1) {
+                let new = (self.blocks[block].predecessors.iter())
+                    .filter(|p| idom[**p].is_some())
+                    .fold(None, |new, &p| match new {
+                        None => Some(p),
+                        Some(new) => Some(intersect(&idom, p, new)),
+                    });
+                if new != idom[block] {
+                    idom[block] = new;
+                    changed = true;
+                }
+            }
+        }
+        if let Some(entry) = idom.first_mut() {
+            *entry = None;
+        }
+        idom
+    }
+
+    // where each block's dominance stops, the blocks it doesn't dominate that one of its
+    // successors or dominated blocks jumps into
+    pub fn frontiers(&self, idom: &[Option<usize>]) -> Vec<Vec<usize>> {
+        let mut frontiers = vec![Vec::new(); self.blocks.len()];
+        for (block, dominator) in idom.iter().enumerate() {
+            if block != 0 && dominator.is_none() {
+                continue;
+            }
+            let predecessors: Vec<_> = (self.blocks[block].predecessors.iter())
+                .filter(|p| **p == 0 || idom[**p].is_some())
+                .collect();
+            if predecessors.len() < 2 {
+                continue;
+            }
+            for &predecessor in predecessors {
+                let mut runner = Some(predecessor);
+                while let Some(r) = runner.filter(|r| Some(*r) != *dominator) {
+                    if !frontiers[r].contains(&block) {
+                        frontiers[r].push(block);
+                    }
+                    runner = idom[r];
+                }
+            }
+        }
+        frontiers
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum Direction {
+    Forward,
+    Backward,
+}
+
+pub trait Dataflow<T> {
+    type Fact: Clone + PartialEq;
+    const DIRECTION: Direction;
+
+    // what holds where the function starts, or where it returns going backward, blocks nothing
+    // flows into start out with it too
+    fn boundary(&self) -> Self::Fact;
+    // what meeting with changes nothing
+    fn initial(&self) -> Self::Fact;
+    fn meet(&self, fact: &mut Self::Fact, other: &Self::Fact);
+    fn transfer(&self, index: usize, instruction: &T, fact: &mut Self::Fact);
+}
+
+// the facts at the start and end of every block
+pub struct Solution<F> {
+    pub before: Vec<F>,
+    pub after: Vec<F>,
+}
+
+pub fn solve<T, D: Dataflow<T>>(graph: &Graph, body: &[T], analysis: &D) -> Solution<D::Fact> {
+    let blocks = &graph.blocks;
+    let forward = D::DIRECTION == Direction::Forward;
+    let mut inputs = vec![analysis.initial(); blocks.len()];
+    let mut outputs = vec![analysis.initial(); blocks.len()];
+    let mut worklist: VecDeque<usize> = if forward {
+        (0..blocks.len()).collect()
+    } else {
+        (0..blocks.len()).rev().collect()
+    };
+    let mut queued = vec![true; blocks.len()];
+
+    while let Some(b) = worklist.pop_front() {
+        queued[b] = false;
+        let block = &blocks[b];
+        let (sources, targets) = if forward {
+            (&block.predecessors, &block.successors)
+        } else {
+            (&block.successors, &block.predecessors)
+        };
+        let mut fact = if sources.is_empty() || (forward && b == 0) {
+            analysis.boundary()
+        } else {
+            analysis.initial()
+        };
+        for source in sources {
+            analysis.meet(&mut fact, &outputs[*source]);
+        }
+        inputs[b] = fact.clone();
+
+        let instructions = block.instructions.clone();
+        if forward {
+            for i in instructions {
+                analysis.transfer(i, &body[i], &mut fact);
+            }
+        } else {
+            for i in instructions.rev() {
+                analysis.transfer(i, &body[i], &mut fact);
+            }
+        }
+        if fact != outputs[b] {
+            outputs[b] = fact;
+            for &target in targets {
+                if !std::mem::replace(&mut queued[target], true) {
+                    worklist.push_back(target);
+                }
+            }
+        }
+    }
+
+    if forward {
+        Solution {
+            before: inputs,
+            after: outputs,
+        }
+    } else {
+        Solution {
+            before: outputs,
+            after: inputs,
+        }
+    }
+}
+
+// the variables an instruction reads, and the ones it overwrites completely, `index` is where it
+// is in the function
+pub trait Operands<T> {
+    type Var: Clone + Eq + Hash;
+
+    fn uses(&self, index: usize, instruction: &T) -> Vec<Self::Var>;
+    fn defs(&self, index: usize, instruction: &T) -> Vec<Self::Var>;
+}
+
+// backward, a variable is live wherever something might still read it before it's overwritten
+pub struct Liveness<O, V> {
+    pub operands: O,
+    // live once the function returns
+    exit: HashSet<V>,
+}
+
+impl<O, V> Liveness<O, V> {
+    pub const fn new(operands: O, exit: HashSet<V>) -> Self {
+        Self { operands, exit }
+    }
+}
+
+impl<T, V: Clone + Eq + Hash, O: Operands<T, Var = V>> Dataflow<T> for Liveness<O, V> {
+    type Fact = HashSet<V>;
+    const DIRECTION: Direction = Direction::Backward;
+
+    fn boundary(&self) -> Self::Fact {
+        self.exit.clone()
+    }
+
+    fn initial(&self) -> Self::Fact {
+        HashSet::new()
+    }
+
+    fn meet(&self, fact: &mut Self::Fact, other: &Self::Fact) {
+        fact.extend(other.iter().cloned());
+    }
+
+    fn transfer(&self, index: usize, instruction: &T, live: &mut Self::Fact) {
+        for def in self.operands.defs(index, instruction) {
+            live.remove(&def);
+        }
+        live.extend(self.operands.uses(index, instruction));
+    }
+}
+
+// forward, the writes that might still be what each variable holds, numbered in the order they
+// happen in, with one more for each variable at the start for whatever it held then
+pub struct ReachingDefinitions<O, V> {
+    pub operands: O,
+    // which instruction makes each write, and to what
+    writes: Vec<(Option<usize>, V)>,
+    by_var: HashMap<V, Vec<usize>>,
+    by_instruction: Vec<Vec<usize>>,
+}
+
+impl<V: Clone + Eq + Hash, O> ReachingDefinitions<O, V> {
+    pub fn new<T>(operands: O, body: &[T]) -> Self
+    where
+        O: Operands<T, Var = V>,
+    {
+        let mut writes = Vec::new();
+        let mut by_var: HashMap<V, Vec<usize>> = HashMap::new();
+        let mut by_instruction = Vec::with_capacity(body.len());
+        for (i, instruction) in body.iter().enumerate() {
+            let defs = operands.defs(i, instruction);
+            by_instruction.push((writes.len()..writes.len() + defs.len()).collect());
+            for def in defs {
+                by_var.entry(def.clone()).or_default().push(writes.len());
+                writes.push((Some(i), def));
+            }
+        }
+        for (var, defs) in &mut by_var {
+            defs.push(writes.len());
+            writes.push((None, var.clone()));
+        }
+        Self {
+            operands,
+            writes,
+            by_var,
+            by_instruction,
+        }
+    }
+
+    // every write to `var` in the function, and its start
+    pub fn writes(&self, var: &V) -> &[usize] {
+        self.by_var.get(var).map_or(&[], Vec::as_slice)
+    }
+
+    // the instruction making a write, `None` for the start
+    pub fn write(&self, write: usize) -> &(Option<usize>, V) {
+        &self.writes[write]
+    }
+}
+
+// the instructions are taken to still write what they did when `new` saw them
+impl<T, V: Clone + Eq + Hash, O> Dataflow<T> for ReachingDefinitions<O, V> {
+    type Fact = Vec<bool>;
+    const DIRECTION: Direction = Direction::Forward;
+
+    fn boundary(&self) -> Self::Fact {
+        (self.writes.iter())
+            .map(|(instruction, _)| instruction.is_none())
+            .collect()
+    }
+
+    fn initial(&self) -> Self::Fact {
+        vec![false; self.writes.len()]
+    }
+
+    fn meet(&self, fact: &mut Self::Fact, other: &Self::Fact) {
+        for (reaches, other) in fact.iter_mut().zip(other) {
+            *reaches |= other;
+        }
+    }
+
+    fn transfer(&self, index: usize, _: &T, reaching: &mut Self::Fact) {
+        for &write in &self.by_instruction[index] {
+            for &other in self.writes(&self.writes[write].1) {
+                reaching[other] = false;
+            }
+            reaching[write] = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{solve, Exit, Flow, Graph, Liveness, Operands, ReachingDefinitions};
+    use crate::codegen::Identifier;
+    use std::collections::HashSet;
+
+    enum Op {
+        Label(Identifier),
+        Jump(Identifier),
+        Branch(Identifier),
+        Return,
+        Def(u8),
+        Use(u8),
+    }
+
+    impl Flow for Op {
+        fn label(&self) -> Option<&Identifier> {
+            match self {
+                Self::Label(label) => Some(label),
+                _ => None,
+            }
+        }
+
+        fn exit(&self) -> Exit<'_> {
+            match self {
+                Self::Jump(target) => Exit::Jumps(target),
+                Self::Branch(target) => Exit::Branches(target),
+                Self::Return => Exit::Returns,
+                _ => Exit::Falls,
+            }
+        }
+    }
+
+    struct Vars;
+
+    impl Operands<Op> for Vars {
+        type Var = u8;
+
+        fn uses(&self, _: usize, op: &Op) -> Vec<u8> {
+            match op {
+                Op::Use(var) => vec![*var],
+                _ => Vec::new(),
+            }
+        }
+
+        fn defs(&self, _: usize, op: &Op) -> Vec<u8> {
+            match op {
+                Op::Def(var) => vec![*var],
+                _ => Vec::new(),
+            }
+        }
+    }
+
+    #[test]
+    fn loops() {
+        let (top, out) = (Identifier::from("top"), Identifier::from("out"));
+        let body = [
+            Op::Def(1),
+            Op::Label(top.clone()),
+            Op::Use(1),
+            Op::Def(2),
+            Op::Branch(out.clone()),
+            Op::Def(1),
+            Op::Jump(top),
+            Op::Label(out),
+            Op::Use(2),
+            Op::Return,
+        ];
+        let graph = Graph::new(&body);
+        let edges: Vec<_> = (graph.blocks.iter())
+            .map(|block| (block.instructions.clone(), block.successors.clone()))
+            .collect();
+        assert_eq!(
+            edges,
+            [
+                (0..1, vec![1]),
+                (1..5, vec![3, 2]),
+                (5..7, vec![1]),
+                (7..10, vec![])
+            ]
+        );
+        assert!(graph.reachable().into_iter().all(|r| r));
+
+        let live = solve(&graph, &body, &Liveness::new(Vars, HashSet::new()));
+        let set = |vars: &[u8]| vars.iter().copied().collect::<HashSet<_>>();
+        assert_eq!(live.before, [set(&[]), set(&[1]), set(&[]), set(&[2])]);
+        assert_eq!(live.after[1], set(&[2]));
+
+        let definitions = ReachingDefinitions::new(Vars, &body);
+        let reaching = solve(&graph, &body, &definitions);
+        let writes = |fact: &[bool]| {
+            (fact.iter().enumerate())
+                .filter(|(_, reaches)| **reaches)
+                .map(|(write, _)| *definitions.write(write))
+                .collect::<HashSet<_>>()
+        };
+        let set = |defs: &[(Option<usize>, u8)]| defs.iter().copied().collect::<HashSet<_>>();
+        let ones = [(Some(0), 1), (Some(5), 1)];
+        let two = (Some(3), 2);
+        // 2 hasn't been written the first time round the loop
+        let top = set(&[ones[0], ones[1], two, (None, 2)]);
+        assert_eq!(writes(&reaching.before[1]), top);
+        assert_eq!(writes(&reaching.after[2]), set(&[ones[1], two]));
+        assert_eq!(writes(&reaching.before[3]), set(&[ones[0], ones[1], two]));
+    }
+}
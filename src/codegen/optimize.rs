@@ -0,0 +1,195 @@
+// every pass opens up chances for the others, folding a condition makes a branch unreachable,
+// propagating a copy makes it dead, so they take turns until none of them changes anything
+mod copies;
+mod dead_stores;
+mod fold;
+mod ssa;
+mod unreachable;
+
+use super::assembly::tacky::{Instruction, Program, TopLevel, Value};
+use super::Identifier;
+use crate::lex::Constant;
+use crate::parse::VarType;
+use crate::semantics::{Attr, SymbolTable};
+use crate::Optimizations;
+use std::collections::HashSet;
+
+pub fn optimize(program: &mut Program, optimizations: Optimizations, table: &mut SymbolTable) {
+    for top_level in &mut program.0 {
+        if let TopLevel::Fn(f) = top_level {
+            let body = std::mem::take(&mut f.body).into_vec();
+            f.body = optimize_function(body, optimizations, table).into();
+        }
+    }
+}
+
+fn optimize_function(
+    mut body: Vec<Instruction>,
+    optimizations: Optimizations,
+    table: &mut SymbolTable,
+) -> Vec<Instruction> {
+    if optimizations.ssa {
+        // once every version is written exactly once, a copy of one holds for the rest of the
+        // function, the phis name their blocks by label though, so those have to stay put
+        let aliased = aliased(&body, table);
+        ssa::construct(&mut body, table, &aliased);
+        let in_ssa = Optimizations {
+            eliminate_unreachable_code: false,
+            ..optimizations
+        };
+        body = run_passes(body, in_ssa, table);
+        ssa::destruct(&mut body, table);
+    }
+    run_passes(body, optimizations, table)
+}
+
+fn run_passes(
+    mut body: Vec<Instruction>,
+    optimizations: Optimizations,
+    table: &SymbolTable,
+) -> Vec<Instruction> {
+    loop {
+        let mut changed = false;
+        if optimizations.fold_constants {
+            changed |= fold::fold(&mut body, table);
+        }
+        if optimizations.eliminate_unreachable_code {
+            changed |= unreachable::eliminate(&mut body);
+        }
+        let aliased = aliased(&body, table);
+        if optimizations.propagate_copies {
+            changed |= copies::propagate(&mut body, table, &aliased);
+        }
+        if optimizations.eliminate_dead_stores {
+            changed |= dead_stores::eliminate(&mut body, table, &aliased);
+        }
+        if !changed {
+            return body;
+        }
+    }
+}
+
+// statics, and anything whose address gets taken, can be changed or read behind our backs
+fn aliased(body: &[Instruction], table: &SymbolTable) -> HashSet<Identifier> {
+    let mut aliased = HashSet::new();
+    for instruction in body {
+        if let Instruction::GetAddress {
+            src: Value::Var(name),
+            ..
+        } = instruction
+        {
+            aliased.insert(name.clone());
+        }
+        let vars = instruction
+            .reads()
+            .into_iter()
+            .chain(instruction.destination());
+        aliased.extend(vars.filter(|name| is_static(name, table)).cloned());
+    }
+    aliased
+}
+
+fn is_static(name: &Identifier, table: &SymbolTable) -> bool {
+    matches!(
+        table.get(name),
+        Some(Attr::Static { .. } | Attr::Constant { .. })
+    )
+}
+
+fn var_type(name: &Identifier, table: &SymbolTable) -> VarType {
+    match table.get(name) {
+        Some(Attr::Automatic(typ) | Attr::Static { typ, .. } | Attr::Constant { typ, .. }) => *typ,
+        attr => unreachable!("{attr:?} isn't a variable"),
+    }
+}
+
+fn value_type(value: &Value, table: &SymbolTable) -> VarType {
+    match value {
+        Value::Constant(cnst) => constant_type(*cnst),
+        Value::Var(name) => var_type(name, table),
+    }
+}
+
+const fn constant_type(cnst: Constant) -> VarType {
+    match cnst {
+        Constant::Int(_) => VarType::Int,
+        Constant::Long(_) => VarType::Long,
+        Constant::UInt(_) => VarType::UInt,
+        Constant::ULong(_) => VarType::ULong,
+        Constant::Double(_) => VarType::Double,
+        Constant::Char(_) => VarType::Char,
+        Constant::UChar(_) => VarType::UChar,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::tacky_pass::lowered;
+    use crate::codegen::assembly::tacky::{Instruction, TackyBinary, Value};
+    use crate::lex::Constant;
+    use crate::Optimizations;
+
+    fn optimized(source: &[u8]) -> Vec<Instruction> {
+        let (mut functions, mut table) = lowered(source);
+        let body = functions.remove(0).body.into_vec();
+        super::optimize_function(body, Optimizations::ALL, &mut table)
+    }
+
+    #[test]
+    fn passes_feed_each_other() {
+        let body = optimized(b"int f(int x) { int y = 1 + 2 * 3; if (y < 5) x = 9; return y; }");
+        assert!(matches!(
+            body[..],
+            [Instruction::Return(Some(Value::Constant(Constant::Int(7))))]
+        ));
+        let body = optimized(b"int f(int x) { int y = x; while (0) y++; int z = y; return z; }");
+        assert!(matches!(
+            body[..],
+            [Instruction::Return(Some(Value::Var(_)))]
+        ));
+    }
+
+    #[test]
+    fn side_effects_stay() {
+        // the division traps, and every access to a volatile has to happen
+        let body =
+            optimized(b"int f(void) { int z = 1 / 0; volatile int v = 1; v = 2; return v; }");
+        assert!(body.iter().any(|instruction| matches!(
+            instruction,
+            Instruction::Binary {
+                operator: TackyBinary::Divide,
+                ..
+            }
+        )));
+        let writes = (body.iter())
+            .filter(|instruction| matches!(instruction, Instruction::Copy { .. }))
+            .count();
+        assert_eq!(writes, 2);
+        assert!(matches!(
+            body.last(),
+            Some(Instruction::Return(Some(Value::Var(_))))
+        ));
+
+        // a call can change anything whose address got out
+        let body = optimized(b"void g(int *p); int f(void) { int x = 1; g(&x); return x; }");
+        assert!(matches!(
+            body.last(),
+            Some(Instruction::Return(Some(Value::Var(_))))
+        ));
+    }
+
+    #[test]
+    fn overflow_wraps() {
+        // signed overflow isn't a trap, so it's folded to whatever the instruction would give
+        let body = optimized(b"int f(void) { int x = 2147483647; return x + 1; }");
+        assert!(matches!(
+            body[..],
+            [Instruction::Return(Some(Value::Constant(Constant::Int(i32::MIN))))]
+        ));
+        let body = optimized(b"long f(void) { long x = -9223372036854775807l - 1; return x * -1; }");
+        assert!(matches!(
+            body[..],
+            [Instruction::Return(Some(Value::Constant(Constant::Long(i64::MIN))))]
+        ));
+    }
+}
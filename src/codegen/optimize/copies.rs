@@ -0,0 +1,217 @@
+use super::{value_type, var_type};
+use crate::codegen::assembly::tacky::{Instruction, Value};
+use crate::codegen::cfg::{self, Dataflow, Graph, Operands, ReachingDefinitions};
+use crate::codegen::Identifier;
+use crate::semantics::SymbolTable;
+use std::collections::{HashMap, HashSet};
+
+// a copy reaches an instruction when every path there goes through it, and nothing on the way
+// writes to either side, then whatever it copied can be read instead of where it copied it to
+pub fn propagate(
+    body: &mut Vec<Instruction>,
+    table: &SymbolTable,
+    aliased: &HashSet<Identifier>,
+) -> bool {
+    let copies = Copies::new(body, table, aliased);
+    if copies.copies.is_empty() {
+        return false;
+    }
+    let graph = Graph::new(body);
+    let definitions = ReachingDefinitions::new(copies, body);
+    let solution = cfg::solve(&graph, body, &definitions);
+    let copies = &definitions.operands;
+
+    let mut changed = false;
+    let mut redundant = HashSet::new();
+    for (block, mut reaching) in graph.blocks.iter().zip(solution.before) {
+        for i in block.instructions.clone() {
+            let copy_of = |name: &Identifier| copies.copy_of(name, &definitions, &reaching);
+            changed |= replace(&mut body[i], copy_of);
+            // `x = y` when x already holds y doesn't change anything
+            if let Instruction::Copy {
+                src,
+                dst: Value::Var(dst),
+            } = &body[i]
+                && (*src == Value::Var(dst.clone()) || copy_of(dst) == Some(src))
+            {
+                redundant.insert(i);
+            }
+            definitions.transfer(i, &body[i], &mut reaching);
+        }
+    }
+    if !redundant.is_empty() {
+        let mut i = 0;
+        body.retain(|_| {
+            i += 1;
+            !redundant.contains(&(i - 1))
+        });
+    }
+    changed || !redundant.is_empty()
+}
+
+fn replace<'a>(
+    instruction: &mut Instruction,
+    copy_of: impl Fn(&Identifier) -> Option<&'a Value>,
+) -> bool {
+    let mut changed = false;
+    for value in instruction.sources_mut() {
+        if let Value::Var(name) = value
+            && let Some(src) = copy_of(name)
+        {
+            *value = src.clone();
+            changed = true;
+        }
+    }
+    if let Instruction::CopyFromOffset { src, .. } = instruction
+        && let Some(Value::Var(copied)) = copy_of(src)
+    {
+        *src = copied.clone();
+        changed = true;
+    }
+    changed
+}
+
+// a copy counts as a definition of where it copies to, and so does anything that writes where it
+// copied from, so a variable still holds a copy wherever the only definitions of it that reach
+// are copies of the same thing
+struct Copies<'a> {
+    // `dst = src` by where it is in the body
+    copies: HashMap<usize, (Identifier, Value)>,
+    // the variables that stop holding a copy once a variable is written
+    copied_from: HashMap<Identifier, Vec<Identifier>>,
+    aliased: &'a HashSet<Identifier>,
+}
+
+impl<'a> Copies<'a> {
+    // volatile variables have to be read every time, and copies that change the type, like
+    // between int and unsigned int, can't stand in for each other, pointers are all alike though
+    fn new(body: &[Instruction], table: &SymbolTable, aliased: &'a HashSet<Identifier>) -> Self {
+        let mut copies = HashMap::new();
+        let mut copied_from: HashMap<Identifier, Vec<Identifier>> = HashMap::new();
+        for (i, instruction) in body.iter().enumerate() {
+            let Instruction::Copy {
+                src,
+                dst: Value::Var(dst),
+            } = instruction
+            else {
+                continue;
+            };
+            let dst_ty = var_type(dst, table);
+            let src_ty = value_type(src, table);
+            let same_type = dst_ty.unqualified() == src_ty.unqualified()
+                || (dst_ty.is_pointer() && src_ty.is_pointer());
+            if dst_ty.is_volatile()
+                || src_ty.is_volatile()
+                || !same_type
+                || *src == Value::Var(dst.clone())
+            {
+                continue;
+            }
+            if let Value::Var(src) = src {
+                let copies = copied_from.entry(src.clone()).or_default();
+                if !copies.contains(dst) {
+                    copies.push(dst.clone());
+                }
+            }
+            copies.insert(i, (dst.clone(), src.clone()));
+        }
+        Self {
+            copies,
+            copied_from,
+            aliased,
+        }
+    }
+
+    // what `name` holds a copy of, going by the writes to it that reach
+    fn copy_of<'b>(
+        &'b self,
+        name: &Identifier,
+        definitions: &ReachingDefinitions<Self, Identifier>,
+        reaching: &[bool],
+    ) -> Option<&'b Value> {
+        // the start isn't a copy, and neither is writing what it was copied from
+        let mut srcs = (definitions.writes(name).iter())
+            .filter(|write| reaching[**write])
+            .map(|write| {
+                let (dst, src) = self.copies.get(&definitions.write(*write).0?)?;
+                (dst == name).then_some(src)
+            });
+        let src = srcs.next()??;
+        srcs.all(|other| other == Some(src)).then_some(src)
+    }
+
+    // `var` and every copy of it, which writing it changes
+    fn clobbered<'b>(&'b self, var: &'b Identifier) -> impl Iterator<Item = &'b Identifier> {
+        std::iter::once(var).chain(self.copied_from.get(var).into_iter().flatten())
+    }
+}
+
+// calls and pointers can write anything aliased
+impl Operands<Instruction> for Copies<'_> {
+    type Var = Identifier;
+
+    fn uses(&self, _: usize, instruction: &Instruction) -> Vec<Identifier> {
+        instruction.reads().into_iter().cloned().collect()
+    }
+
+    fn defs(&self, _: usize, instruction: &Instruction) -> Vec<Identifier> {
+        let mut defs: Vec<_> = (instruction.destination().into_iter())
+            .flat_map(|dst| self.clobbered(dst))
+            .cloned()
+            .collect();
+        if instruction.touches_memory() {
+            defs.extend(self.aliased.iter().flat_map(|var| self.clobbered(var)).cloned());
+        }
+        defs
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::aliased;
+    use super::propagate;
+    use crate::codegen::assembly::tacky::{Instruction, Value};
+    use crate::codegen::tacky_pass::lowered;
+    use crate::lex::Constant;
+
+    // what the first function in `source` returns once there's nothing left to propagate, and the
+    // parameters it might be returning
+    fn returned(source: &[u8]) -> (Value, Vec<Value>) {
+        let (mut functions, table) = lowered(source);
+        let f = functions.remove(0);
+        let mut body = f.body.into_vec();
+        let aliased = aliased(&body, &table);
+        while propagate(&mut body, &table, &aliased) {}
+        let value = body.into_iter().find_map(|instruction| match instruction {
+            Instruction::Return(value) => value,
+            _ => None,
+        });
+        let params = f.params.iter().cloned().map(Value::Var).collect();
+        (value.unwrap(), params)
+    }
+
+    #[test]
+    fn every_path() {
+        // the same copy down both sides of an if still holds where they meet
+        let (value, params) =
+            returned(b"int f(int c, int y) { int x; if (c) x = y; else x = y; return x; }");
+        assert_eq!(value, params[1]);
+        // but a parameter that only gets overwritten on one of them doesn't
+        let (value, params) = returned(b"int f(int x, int c) { if (c) x = c; return x; }");
+        assert_eq!(value, params[0]);
+    }
+
+    #[test]
+    fn writes_to_the_source() {
+        let (value, params) = returned(b"int f(int y) { int x = y; y = 5; return x; }");
+        assert!(value != params[0] && value != Value::Constant(Constant::Int(5)));
+        // and round a loop, where the copy can be reached again after the source changes
+        let source = b"int f(int y, int n) { int x = 0; for (int i = 0; i < n; i++) { x = y; y = i; } return x; }";
+        let (value, params) = returned(source);
+        assert!(!params.contains(&value) && matches!(value, Value::Var(_)));
+        // a call can change anything whose address got out
+        let source = b"void g(int *p); int f(int y) { int x = y; g(&y); return x; }";
+        let (value, params) = returned(source);
+        assert!(value != params[0]);
+    }
+}
@@ -0,0 +1,97 @@
+use super::{is_static, var_type};
+use crate::codegen::assembly::tacky::{Instruction, TackyBinary, Value};
+use crate::codegen::cfg::{self, Dataflow, Graph, Liveness, Operands};
+use crate::codegen::Identifier;
+use crate::semantics::{integer, SymbolTable};
+use std::collections::HashSet;
+
+// removes writes to variables nothing reads before they're written again or the function returns
+pub fn eliminate(
+    body: &mut Vec<Instruction>,
+    table: &SymbolTable,
+    aliased: &HashSet<Identifier>,
+) -> bool {
+    let graph = Graph::new(body);
+    // statics can still be read once the function returns
+    let statics = (aliased.iter())
+        .filter(|name| is_static(name, table))
+        .cloned()
+        .collect();
+    let liveness = Liveness::new(Variables { aliased }, statics);
+    let solution = cfg::solve(&graph, body, &liveness);
+
+    let mut dead = HashSet::new();
+    for (block, mut live) in graph.blocks.iter().zip(solution.after) {
+        for i in block.instructions.clone().rev() {
+            let instruction = &body[i];
+            match instruction.destination() {
+                Some(dst)
+                    if !live.contains(dst)
+                        && removable(instruction)
+                        && !var_type(dst, table).is_volatile() =>
+                {
+                    dead.insert(i);
+                }
+                _ => liveness.transfer(i, instruction, &mut live),
+            }
+        }
+    }
+    let mut i = 0;
+    body.retain(|_| {
+        i += 1;
+        !dead.contains(&(i - 1))
+    });
+    !dead.is_empty()
+}
+
+// everything besides calls and what goes through a pointer only changes its destination, unless
+// it's a division that could trap
+fn removable(instruction: &Instruction) -> bool {
+    match instruction {
+        Instruction::Binary {
+            operator: TackyBinary::Divide | TackyBinary::Remainder,
+            source_2,
+            ..
+        } => matches!(source_2, Value::Constant(c) if !matches!(integer(*c), 0 | -1)),
+        Instruction::SignExtend { .. }
+        | Instruction::ZeroExtend { .. }
+        | Instruction::Truncate { .. }
+        | Instruction::DoubleToInt { .. }
+        | Instruction::DoubleToUInt { .. }
+        | Instruction::IntToDouble { .. }
+        | Instruction::UIntToDouble { .. }
+        | Instruction::Unary { .. }
+        | Instruction::Binary { .. }
+        | Instruction::Copy { .. }
+        | Instruction::GetAddress { .. }
+        | Instruction::AddPtr { .. }
+        | Instruction::CopyToOffset { .. }
+        | Instruction::CopyFromOffset { .. } => true,
+        _ => false,
+    }
+}
+
+// calls and pointers can read anything aliased, and writing part of an aggregate leaves the rest
+// of it as it was
+struct Variables<'a> {
+    aliased: &'a HashSet<Identifier>,
+}
+
+impl Operands<Instruction> for Variables<'_> {
+    type Var = Identifier;
+
+    fn uses(&self, _: usize, instruction: &Instruction) -> Vec<Identifier> {
+        let mut uses: Vec<_> = instruction.reads().into_iter().cloned().collect();
+        if instruction.touches_memory() {
+            uses.extend(self.aliased.iter().cloned());
+        }
+        uses
+    }
+
+    fn defs(&self, _: usize, instruction: &Instruction) -> Vec<Identifier> {
+        match instruction {
+            Instruction::CopyToOffset { .. } => Vec::new(),
+            instruction => instruction.destination().into_iter().cloned().collect(),
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use super::{constant_type, value_type, var_type};
+use crate::codegen::assembly::tacky::{Instruction, TackyBinary, Value};
+use crate::lex::Constant;
+use crate::parse::{Bop, UnOp, VarType};
+use crate::semantics::{arithmetic, convert, integer, truthy, wrap, SymbolTable, Undefined};
+
+// anything whose operands are all constants becomes a copy of its result, except for what would
+// trap at runtime, which is left for the program to run into
+pub fn fold(body: &mut Vec<Instruction>, table: &SymbolTable) -> bool {
+    let mut changed = false;
+    body.retain_mut(|instruction| match fold_instruction(instruction, table) {
+        Folded::Unchanged => true,
+        Folded::Into(folded) => {
+            *instruction = folded;
+            changed = true;
+            true
+        }
+        Folded::Removed => {
+            changed = true;
+            false
+        }
+    });
+    changed
+}
+
+enum Folded {
+    Unchanged,
+    Into(Instruction),
+    Removed,
+}
+
+fn fold_instruction(instruction: &Instruction, table: &SymbolTable) -> Folded {
+    let copy = |cnst: Option<Constant>, dst: &Value| {
+        cnst.map_or(Folded::Unchanged, |cnst| {
+            Folded::Into(Instruction::Copy {
+                src: Value::Constant(cnst),
+                dst: dst.clone(),
+            })
+        })
+    };
+    match instruction {
+        Instruction::Unary {
+            op,
+            source: Value::Constant(cnst),
+            dst,
+        } => {
+            let result = unary(*op, *cnst, var_type(dst, table));
+            copy(Some(result), &Value::Var(dst.clone()))
+        }
+        Instruction::Binary {
+            operator,
+            source_1: Value::Constant(a),
+            source_2: Value::Constant(b),
+            dst,
+        } => {
+            // signed overflow wraps around at the operands' width, the same as `add` and `imul`
+            let result = match arithmetic(bop(*operator), *a, *b, constant_type(*a)) {
+                Ok(cnst) | Err(Undefined::Overflow(cnst)) => Some(cnst),
+                Err(Undefined::Trap) => None,
+            };
+            copy(result, dst)
+        }
+        Instruction::SignExtend {
+            src: Value::Constant(cnst),
+            dst,
+        }
+        | Instruction::ZeroExtend {
+            src: Value::Constant(cnst),
+            dst,
+        }
+        | Instruction::Truncate {
+            src: Value::Constant(cnst),
+            dst,
+        }
+        | Instruction::DoubleToInt {
+            src: Value::Constant(cnst),
+            dst,
+        }
+        | Instruction::DoubleToUInt {
+            src: Value::Constant(cnst),
+            dst,
+        }
+        | Instruction::IntToDouble {
+            src: Value::Constant(cnst),
+            dst,
+        }
+        | Instruction::UIntToDouble {
+            src: Value::Constant(cnst),
+            dst,
+        } => copy(Some(convert(*cnst, value_type(dst, table))), dst),
+        // casts between signed and unsigned are plain copies, the constant takes the new type so
+        // it can be propagated
+        Instruction::Copy {
+            src: Value::Constant(cnst),
+            dst,
+        } => {
+            let ty = value_type(dst, table);
+            let converted = convert(*cnst, ty);
+            if ty.arithmetic() && constant_type(converted) != constant_type(*cnst) {
+                copy(Some(converted), dst)
+            } else {
+                Folded::Unchanged
+            }
+        }
+        Instruction::JumpIfZero {
+            condition: Value::Constant(cnst),
+            target,
+        }
+        | Instruction::JumpIfNotZero {
+            condition: Value::Constant(cnst),
+            target,
+        } => {
+            let jumps_if = matches!(instruction, Instruction::JumpIfNotZero { .. });
+            if truthy(*cnst) == jumps_if {
+                Folded::Into(Instruction::Jump {
+                    target: target.clone(),
+                })
+            } else {
+                Folded::Removed
+            }
+        }
+        _ => Folded::Unchanged,
+    }
+}
+
+fn unary(op: UnOp, cnst: Constant, ty: VarType) -> Constant {
+    match (op, cnst) {
+        (UnOp::Not, cnst) => Constant::Int(!truthy(cnst) as i32),
+        (UnOp::Negate, Constant::Double(d)) => Constant::Double(-d),
+        (UnOp::Negate, cnst) => wrap(-integer(cnst), ty),
+        (UnOp::Complement, cnst) => wrap(!integer(cnst), ty),
+    }
+}
+
+// what the tacky operator was made from
+const fn bop(operator: TackyBinary) -> Bop {
+    match operator {
+        TackyBinary::Add => Bop::Add,
+        TackyBinary::Subtract => Bop::Subtract,
+        TackyBinary::Multiply => Bop::Multiply,
+        TackyBinary::Divide => Bop::Divide,
+        TackyBinary::Remainder => Bop::Remainder,
+        TackyBinary::BitAnd => Bop::BitAnd,
+        TackyBinary::BitOr => Bop::BitOr,
+        TackyBinary::Xor => Bop::Xor,
+        TackyBinary::LeftShift => Bop::LeftShift,
+        TackyBinary::RightShift => Bop::RightShift,
+        TackyBinary::EqualTo => Bop::EqualTo,
+        TackyBinary::NotEqual => Bop::NotEqual,
+        TackyBinary::LessThan => Bop::LessThan,
+        TackyBinary::GreaterThan => Bop::GreaterThan,
+        TackyBinary::Leq => Bop::Leq,
+        TackyBinary::Geq => Bop::Geq,
+    }
+}
@@ -0,0 +1,343 @@
+// every promotable variable gets written exactly once, with phis picking between the versions
+// where control flow meets, then the phis go back to copies on the edges coming in
+use super::var_type;
+use crate::codegen::assembly::tacky::{Instruction, Value};
+use crate::codegen::cfg::{self, Exit, Flow, Graph, Liveness, Operands};
+use crate::codegen::tacky_pass::{block_label, new_var};
+use crate::codegen::Identifier;
+use crate::semantics::{Attr, SymbolTable};
+use std::collections::{HashMap, HashSet};
+
+pub fn construct(
+    body: &mut Vec<Instruction>,
+    table: &mut SymbolTable,
+    aliased: &HashSet<Identifier>,
+) {
+    let vars = promotable(body, table, aliased);
+    if vars.is_empty() {
+        return;
+    }
+    label_blocks(body);
+    let graph = Graph::new(body);
+    let idom = graph.dominators();
+    let frontiers = graph.frontiers(&idom);
+    let promoted: HashSet<_> = vars.iter().cloned().collect();
+    let liveness = Liveness::new(Promoted { vars: &promoted }, HashSet::new());
+    let live = cfg::solve(&graph, body, &liveness);
+
+    // a phi goes wherever two definitions can meet, unless the variable is dead by then
+    let mut phis = vec![Vec::new(); graph.blocks.len()];
+    for var in &vars {
+        let mut worklist: Vec<_> = (graph.blocks.iter().enumerate())
+            .filter(|(_, block)| {
+                body[block.instructions.clone()]
+                    .iter()
+                    .any(|instruction| instruction.destination() == Some(var))
+            })
+            .map(|(i, _)| i)
+            .collect();
+        let mut placed = HashSet::new();
+        while let Some(block) = worklist.pop() {
+            for &frontier in &frontiers[block] {
+                if live.before[frontier].contains(var) && placed.insert(frontier) {
+                    phis[frontier].push(var.clone());
+                    worklist.push(frontier);
+                }
+            }
+        }
+    }
+
+    let mut blocks = split(body, &graph);
+    for (block, vars) in blocks.iter_mut().zip(&phis) {
+        let phis = vars.iter().map(|var| Instruction::Phi {
+            dst: var.clone(),
+            sources: Vec::new(),
+        });
+        block.splice(1..1, phis);
+    }
+    let labels: Vec<_> = (blocks.iter())
+        .map(|block| block[0].label().unwrap().clone())
+        .collect();
+    let mut children = vec![Vec::new(); blocks.len()];
+    for (block, dominator) in idom.iter().enumerate() {
+        if let Some(dominator) = dominator {
+            children[*dominator].push(block);
+        }
+    }
+
+    // down the dominator tree, so the version on top of each stack is the one that reaches here
+    enum Visit {
+        Enter(usize),
+        Leave(Vec<Identifier>),
+    }
+    let mut versions: HashMap<Identifier, Vec<Identifier>> = HashMap::new();
+    let mut stack = vec![Visit::Enter(0)];
+    while let Some(visit) = stack.pop() {
+        let block = match visit {
+            Visit::Enter(block) => block,
+            Visit::Leave(defined) => {
+                for var in defined {
+                    versions.get_mut(&var).unwrap().pop();
+                }
+                continue;
+            }
+        };
+        let mut defined = Vec::new();
+        for instruction in &mut blocks[block] {
+            if !matches!(instruction, Instruction::Phi { .. }) {
+                for value in instruction.sources_mut() {
+                    if let Value::Var(name) = value
+                        && let Some(version) = versions.get(name).and_then(|v| v.last())
+                    {
+                        *name = version.clone();
+                    }
+                }
+            }
+            if let Some(dst) = instruction.destination_mut()
+                && promoted.contains(dst)
+            {
+                let version = version(dst, table);
+                versions
+                    .entry(dst.clone())
+                    .or_default()
+                    .push(version.clone());
+                defined.push(std::mem::replace(dst, version));
+            }
+        }
+        for &successor in &graph.blocks[block].successors {
+            for (i, var) in phis[successor].iter().enumerate() {
+                let Instruction::Phi { sources, .. } = &mut blocks[successor][i + 1] else {
+                    unreachable!()
+                };
+                let version = versions.get(var).and_then(|v| v.last()).unwrap_or(var);
+                sources.push((labels[block].clone(), Value::Var(version.clone())));
+            }
+        }
+        stack.push(Visit::Leave(defined));
+        stack.extend(
+            children[block]
+                .iter()
+                .rev()
+                .map(|child| Visit::Enter(*child)),
+        );
+    }
+    *body = blocks.into_iter().flatten().collect();
+}
+
+pub fn destruct(body: &mut Vec<Instruction>, table: &mut SymbolTable) {
+    if !(body.iter()).any(|instruction| matches!(instruction, Instruction::Phi { .. })) {
+        return;
+    }
+    let graph = Graph::new(body);
+    let mut blocks = split(body, &graph);
+    let labels: Vec<_> = (blocks.iter())
+        .map(|block| block.first().and_then(Flow::label).cloned())
+        .collect();
+    let mut before: Vec<_> = blocks.iter().map(|_| Vec::new()).collect();
+    let mut after = Vec::new();
+    for (block, node) in graph.blocks.iter().enumerate() {
+        let mut phis = Vec::new();
+        blocks[block].retain(|instruction| match instruction {
+            Instruction::Phi { dst, sources } => {
+                phis.push((dst.clone(), sources.clone()));
+                false
+            }
+            _ => true,
+        });
+        for &predecessor in &node.predecessors {
+            // nothing's passed along from blocks that can't run
+            let copies: Vec<_> = (phis.iter())
+                .filter_map(|(dst, sources)| {
+                    let (_, src) = (sources.iter())
+                        .find(|(label, _)| Some(label) == labels[predecessor].as_ref())?;
+                    Some((dst.clone(), src.clone()))
+                })
+                .collect();
+            let copies = sequence(copies, table);
+            if copies.is_empty() {
+                continue;
+            }
+            let end = &mut blocks[predecessor];
+            if graph.blocks[predecessor].successors.len() == 1 {
+                let at = match end.last().map(Flow::exit) {
+                    Some(Exit::Falls) | None => end.len(),
+                    Some(_) => end.len() - 1,
+                };
+                end.splice(at..at, copies);
+                continue;
+            }
+            // the edge gets a block of its own, so the copies only happen going this way
+            let label = block_label();
+            let target = labels[block].clone().unwrap();
+            match end.last_mut() {
+                Some(
+                    Instruction::JumpIfZero { target: jump, .. }
+                    | Instruction::JumpIfNotZero { target: jump, .. },
+                ) if *jump == target => {
+                    *jump = label.clone();
+                    after.push(Instruction::Label(label));
+                    after.extend(copies);
+                    after.push(Instruction::Jump { target });
+                }
+                _ => {
+                    before[block].push(Instruction::Label(label));
+                    before[block].extend(copies);
+                }
+            }
+        }
+    }
+    *body = (before.into_iter().zip(blocks))
+        .flat_map(|(before, block)| before.into_iter().chain(block))
+        .chain(after)
+        .collect();
+
+    // construction labeled every block, most of them don't need it
+    let targets: HashSet<_> = (body.iter())
+        .filter_map(|instruction| match instruction.exit() {
+            Exit::Jumps(target) | Exit::Branches(target) => Some(target.clone()),
+            _ => None,
+        })
+        .collect();
+    body.retain(|instruction| match instruction {
+        Instruction::Label(label) => targets.contains(label),
+        _ => true,
+    });
+}
+
+// automatic scalars nothing takes the address of, in the order they're first written
+fn promotable(
+    body: &[Instruction],
+    table: &SymbolTable,
+    aliased: &HashSet<Identifier>,
+) -> Vec<Identifier> {
+    let mut vars = Vec::new();
+    for dst in body.iter().filter_map(Instruction::destination) {
+        let Some(Attr::Automatic(ty)) = table.get(dst) else {
+            continue;
+        };
+        if ty.scalar() && !ty.is_volatile() && !aliased.contains(dst) && !vars.contains(dst) {
+            vars.push(dst.clone());
+        }
+    }
+    vars
+}
+
+// phis name the blocks they come from by label, and the first block gets one nothing jumps to,
+// so it has no predecessors
+fn label_blocks(body: &mut Vec<Instruction>) {
+    let graph = Graph::new(body);
+    let mut labeled = Vec::with_capacity(body.len() + graph.blocks.len());
+    for (i, block) in split(body, &graph).into_iter().enumerate() {
+        if i == 0 || block[0].label().is_none() {
+            labeled.push(Instruction::Label(block_label()));
+        }
+        labeled.extend(block);
+    }
+    *body = labeled;
+}
+
+fn split(body: &mut Vec<Instruction>, graph: &Graph) -> Vec<Vec<Instruction>> {
+    let mut rest = std::mem::take(body).into_iter();
+    (graph.blocks.iter())
+        .map(|block| rest.by_ref().take(block.instructions.len()).collect())
+        .collect()
+}
+
+// the copies on an edge all happen at once, so one reading where another writes has to save
+// the old value first
+fn sequence(copies: Vec<(Identifier, Value)>, table: &mut SymbolTable) -> Vec<Instruction> {
+    let dsts: HashSet<_> = copies.iter().map(|(dst, _)| dst.clone()).collect();
+    let mut saved = Vec::new();
+    let mut moves = Vec::new();
+    for (dst, src) in copies {
+        let src = match src {
+            Value::Var(name) if name == dst => continue,
+            Value::Var(name) if dsts.contains(&name) => {
+                let temp = version(&name, table);
+                saved.push(Instruction::Copy {
+                    src: Value::Var(name),
+                    dst: Value::Var(temp.clone()),
+                });
+                Value::Var(temp)
+            }
+            src => src,
+        };
+        moves.push(Instruction::Copy {
+            src,
+            dst: Value::Var(dst),
+        });
+    }
+    saved.extend(moves);
+    saved
+}
+
+fn version(var: &Identifier, table: &mut SymbolTable) -> Identifier {
+    new_var(var_type(var, table), table)
+}
+
+struct Promoted<'a> {
+    vars: &'a HashSet<Identifier>,
+}
+
+impl Operands<Instruction> for Promoted<'_> {
+    type Var = Identifier;
+
+    fn uses(&self, _: usize, instruction: &Instruction) -> Vec<Identifier> {
+        (instruction.reads().into_iter())
+            .filter(|name| self.vars.contains(*name))
+            .cloned()
+            .collect()
+    }
+
+    fn defs(&self, _: usize, instruction: &Instruction) -> Vec<Identifier> {
+        (instruction.destination().into_iter())
+            .filter(|name| self.vars.contains(*name))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::aliased;
+    use crate::codegen::assembly::tacky::Instruction;
+    use crate::codegen::tacky_pass::lowered;
+    use std::collections::HashSet;
+
+    #[test]
+    fn loop_round_trip() {
+        let source = b"int f(int n) { int i = 0; int s = 0; while (i < n) { s = s + i; i = i + 1; } return s; }";
+        let (mut functions, mut table) = lowered(source);
+        let mut body = functions.remove(0).body.into_vec();
+        let aliased = aliased(&body, &table);
+        super::construct(&mut body, &mut table, &aliased);
+
+        // i and s meet at the top of the loop, and both come in from before it and the back edge
+        let phis: Vec<_> = (body.iter())
+            .filter_map(|instruction| match instruction {
+                Instruction::Phi { sources, .. } => Some(sources),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(phis.len(), 2);
+        assert!(phis.iter().all(|sources| sources.len() == 2));
+        let mut written = HashSet::new();
+        for dst in body.iter().filter_map(Instruction::destination) {
+            assert!(written.insert(dst.clone()), "{dst} written twice");
+        }
+
+        super::destruct(&mut body, &mut table);
+        assert!(!(body.iter()).any(|instruction| matches!(instruction, Instruction::Phi { .. })));
+        // the s that's returned gets copied to on the way in and at the end of every iteration
+        let returned = (body.iter())
+            .find_map(|instruction| match instruction {
+                Instruction::Return(Some(value)) => Some(value),
+                _ => None,
+            })
+            .unwrap();
+        let copies = (body.iter())
+            .filter(|instruction| matches!(instruction, Instruction::Copy { dst, .. } if dst == returned))
+            .count();
+        assert_eq!(copies, 2);
+    }
+}
@@ -0,0 +1,55 @@
+use crate::codegen::assembly::tacky::Instruction;
+use crate::codegen::cfg::Graph;
+use std::collections::HashSet;
+
+// drops the blocks nothing can get to from the start of the function, then the jumps that only go
+// to the next instruction and the labels nothing jumps to anymore
+pub fn eliminate(body: &mut Vec<Instruction>) -> bool {
+    let before = body.len();
+    let graph = Graph::new(body);
+    let mut keep = vec![false; body.len()];
+    for (block, _) in graph
+        .blocks
+        .iter()
+        .zip(graph.reachable())
+        .filter(|(_, r)| *r)
+    {
+        keep[block.instructions.clone()].fill(true);
+    }
+    let mut kept = keep.into_iter();
+    body.retain(|_| kept.next().unwrap());
+
+    // the condition of a jump is just a value, dropping it doesn't lose anything
+    let mut i = 0;
+    while i < body.len() {
+        let redundant = match (&body[i], body.get(i + 1)) {
+            (
+                Instruction::Jump { target }
+                | Instruction::JumpIfZero { target, .. }
+                | Instruction::JumpIfNotZero { target, .. },
+                Some(Instruction::Label(next)),
+            ) => target == next,
+            _ => false,
+        };
+        if redundant {
+            body.remove(i);
+        } else {
+            i += 1;
+        }
+    }
+
+    let targets: HashSet<_> = body
+        .iter()
+        .filter_map(|instruction| match instruction {
+            Instruction::Jump { target }
+            | Instruction::JumpIfZero { target, .. }
+            | Instruction::JumpIfNotZero { target, .. } => Some(target.clone()),
+            _ => None,
+        })
+        .collect();
+    body.retain(|instruction| match instruction {
+        Instruction::Label(label) => targets.contains(label),
+        _ => true,
+    });
+    body.len() != before
+}
@@ -0,0 +1,336 @@
+// rewrites short runs of the final instructions into cheaper ones: every rule looks at a window of
+// a few instructions in a row, along with what's still needed after each of them, and either
+// leaves it alone or hands back what goes there instead, over and over until none of them apply
+use super::assembly::x86::{Access, AsmType, CondCode};
+use super::assembly::{Binary, Op, Program, Register, TopLevel, X86};
+use super::cfg::{self, Dataflow, Graph, Liveness, Operands};
+use super::regalloc::{ARGUMENTS, RETURNS};
+use std::collections::HashSet;
+
+// what's still needed after each instruction in the window
+type Live = HashSet<Location>;
+
+struct Rule {
+    // how many instructions in a row it looks at
+    window: usize,
+    rewrite: fn(&[X86], &[Live]) -> Option<Vec<X86>>,
+}
+
+// tried in order at each instruction, the first that matches wins
+const RULES: [Rule; 5] = [
+    Rule {
+        window: 1,
+        rewrite: self_move,
+    },
+    Rule {
+        window: 4,
+        rewrite: compare_and_branch,
+    },
+    Rule {
+        window: 1,
+        rewrite: dead_write,
+    },
+    Rule {
+        window: 1,
+        rewrite: zero_with_xor,
+    },
+    Rule {
+        window: 1,
+        rewrite: multiply_by_shift,
+    },
+];
+
+pub fn optimize(program: &mut Program<X86>) {
+    for top_level in program.0.iter_mut() {
+        if let TopLevel::Fn(function) = top_level {
+            let mut body = std::mem::take(&mut function.body).into_vec();
+            while rewrite(&mut body) {}
+            function.body = body.into();
+        }
+    }
+}
+
+fn rewrite(body: &mut Vec<X86>) -> bool {
+    let live = live_after(body);
+    let mut rewritten = Vec::with_capacity(body.len());
+    let mut changed = false;
+    let mut i = 0;
+    'instructions: while i < body.len() {
+        for rule in &RULES {
+            let end = i + rule.window;
+            if end > body.len() {
+                continue;
+            }
+            if let Some(replacement) = (rule.rewrite)(&body[i..end], &live[i..end]) {
+                rewritten.extend(replacement);
+                changed = true;
+                i = end;
+                continue 'instructions;
+            }
+        }
+        rewritten.push(body[i].clone());
+        i += 1;
+    }
+    *body = rewritten;
+    changed
+}
+
+// `mov %eax, %eax`, which the allocator leaves behind when both sides get the same register
+fn self_move(window: &[X86], _: &[Live]) -> Option<Vec<X86>> {
+    match window {
+        [X86::Mov {
+            regs: (Op::Register(src), Op::Register(dst)),
+            ..
+        }] if src == dst => Some(Vec::new()),
+        _ => None,
+    }
+}
+
+// a comparison's result gets set into a register only to be tested against zero right away,
+// but mov and setcc leave the flags alone, so the jump can use the first comparison directly
+fn compare_and_branch(window: &[X86], _: &[Live]) -> Option<Vec<X86>> {
+    let [zero @ X86::Mov {
+        ty,
+        regs: (Op::Imm(0), Op::Register(dst)),
+    }, set @ X86::SetCC {
+        condition,
+        op: Op::Register(set_dst),
+    }, X86::Cmp {
+        ty: tested_ty,
+        regs: (Op::Imm(0), Op::Register(tested)),
+    }, X86::JmpCC {
+        condition: jump @ (CondCode::E | CondCode::NE),
+        label,
+    }] = window
+    else {
+        return None;
+    };
+    if dst != set_dst || dst != tested || ty != tested_ty {
+        return None;
+    }
+    let condition = match jump {
+        CondCode::NE => condition.clone(),
+        _ => negate(condition)?,
+    };
+    // the register's usually dead by now, and `dead_write` gets rid of it next time around
+    Some(vec![
+        zero.clone(),
+        set.clone(),
+        X86::JmpCC {
+            condition,
+            label: label.clone(),
+        },
+    ])
+}
+
+// the value goes nowhere, reads from memory stay since they might be volatile
+fn dead_write(window: &[X86], live: &[Live]) -> Option<Vec<X86>> {
+    let dst = match window {
+        [X86::Mov {
+            regs: (Op::Imm(_) | Op::Register(_), Op::Register(dst)),
+            ..
+        }
+        | X86::SetCC {
+            op: Op::Register(dst),
+            ..
+        }] => dst,
+        _ => return None,
+    };
+    (*dst != Register::Sp && !live[0].contains(&Location::Register(*dst))).then(Vec::new)
+}
+
+// shorter, but it sets the flags where mov doesn't, writing the low half clears the rest
+fn zero_with_xor(window: &[X86], live: &[Live]) -> Option<Vec<X86>> {
+    match window {
+        [X86::Mov {
+            ty: AsmType::Longword | AsmType::Quadword,
+            regs: (Op::Imm(0), dst @ Op::Register(_)),
+        }] if !live[0].contains(&Location::Flags) => Some(vec![X86::binary(
+            Binary::Xor,
+            dst.clone(),
+            dst.clone(),
+            AsmType::Longword,
+        )]),
+        _ => None,
+    }
+}
+
+// imul leaves most of the flags undefined, so nothing can be reading them after
+fn multiply_by_shift(window: &[X86], _: &[Live]) -> Option<Vec<X86>> {
+    match window {
+        [X86::Binary {
+            operator: Binary::Mult,
+            regs: (Op::Imm(by), dst),
+            ty: ty @ (AsmType::Longword | AsmType::Quadword),
+        }] if *by > 0 && by.count_ones() == 1 => Some(match by.trailing_zeros() {
+            0 => Vec::new(),
+            shift => vec![X86::binary(
+                Binary::ShiftLeft,
+                Op::Imm(shift.into()),
+                dst.clone(),
+                *ty,
+            )],
+        }),
+        _ => None,
+    }
+}
+
+// comisd sets every flag it uses when either side is NaN, so these are still opposites for doubles
+const fn negate(condition: &CondCode) -> Option<CondCode> {
+    Some(match condition {
+        CondCode::E => CondCode::NE,
+        CondCode::NE => CondCode::E,
+        CondCode::G => CondCode::LE,
+        CondCode::GE => CondCode::L,
+        CondCode::L => CondCode::GE,
+        CondCode::LE => CondCode::G,
+        CondCode::A => CondCode::BE,
+        CondCode::AE => CondCode::B,
+        CondCode::B => CondCode::AE,
+        CondCode::BE => CondCode::A,
+        CondCode::P => return None,
+    })
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Location {
+    Register(Register),
+    Flags,
+}
+
+fn live_after(body: &[X86]) -> Vec<Live> {
+    let graph = Graph::new(body);
+    let liveness = Liveness::new(Locations, HashSet::new());
+    let solution = cfg::solve(&graph, body, &liveness);
+    let mut after = vec![HashSet::new(); body.len()];
+    for (block, mut live) in graph.blocks.iter().zip(solution.after) {
+        for i in block.instructions.clone().rev() {
+            after[i] = live.clone();
+            liveness.transfer(i, &body[i], &mut live);
+        }
+    }
+    after
+}
+
+// what each instruction reads and writes, where calls only clobber the flags, keeping the
+// registers live through them is never wrong
+struct Locations;
+
+impl Locations {
+    fn access(instruction: &X86) -> (Vec<Location>, Vec<Location>) {
+        let mut uses = Vec::new();
+        let mut defs = Vec::new();
+        for access in instruction.accesses() {
+            match access {
+                Access::Read(op) => read(op, &mut uses),
+                Access::Write(op) => write(op, &mut uses, &mut defs),
+                Access::WriteByte(op) => {
+                    read(op, &mut uses);
+                    write(op, &mut uses, &mut defs);
+                }
+                Access::Address(op) => address(op, &mut uses),
+                Access::ReadRegister(register) => uses.push(Location::Register(register)),
+                Access::WriteRegister(register) => defs.push(Location::Register(register)),
+                Access::ReadFlags => uses.push(Location::Flags),
+                Access::WriteFlags => defs.push(Location::Flags),
+                Access::Arguments => uses.extend(ARGUMENTS.map(Location::Register)),
+                Access::Returns => uses.extend(RETURNS.map(Location::Register)),
+                Access::CallerSaved => (),
+            }
+        }
+        (uses, defs)
+    }
+}
+
+fn read(op: &Op, uses: &mut Vec<Location>) {
+    match op {
+        Op::Register(register) => uses.push(Location::Register(*register)),
+        op => address(op, uses),
+    }
+}
+
+fn write(op: &Op, uses: &mut Vec<Location>, defs: &mut Vec<Location>) {
+    match op {
+        Op::Register(register) => defs.push(Location::Register(*register)),
+        op => address(op, uses),
+    }
+}
+
+// the registers an operand in memory reads to find its address
+fn address(op: &Op, uses: &mut Vec<Location>) {
+    match op {
+        Op::Memory(base, _) => uses.push(Location::Register(*base)),
+        Op::Indexed { base, index, .. } => {
+            uses.extend([*base, *index].map(Location::Register));
+        }
+        _ => (),
+    }
+}
+
+impl Operands<X86> for Locations {
+    type Var = Location;
+
+    fn uses(&self, _: usize, instruction: &X86) -> Vec<Location> {
+        Self::access(instruction).0
+    }
+
+    fn defs(&self, _: usize, instruction: &X86) -> Vec<Location> {
+        Self::access(instruction).1
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::assembly::x86::{AsmType, CondCode};
+    use super::super::assembly::{Binary, Op, Register, X86};
+    use super::super::Identifier;
+
+    #[test]
+    fn rules_feed_each_other() {
+        let long = AsmType::Longword;
+        let (ax, di) = (Op::Register(Register::Ax), Op::Register(Register::Di));
+        let skip = Identifier::from("skip");
+        let mut body = vec![
+            X86::cmp(Op::Register(Register::Si), di.clone(), long),
+            X86::mov(Op::Imm(0), ax.clone(), long),
+            X86::SetCC {
+                condition: CondCode::L,
+                op: ax.clone(),
+            },
+            X86::cmp(Op::Imm(0), ax.clone(), long),
+            X86::JmpCC {
+                condition: CondCode::E,
+                label: skip.clone(),
+            },
+            X86::binary(Binary::Mult, Op::Imm(8), di.clone(), long),
+            X86::mov(di.clone(), di.clone(), long),
+            X86::Label(skip),
+            X86::mov(Op::Imm(0), ax.clone(), long),
+            X86::Ret,
+        ];
+        while super::rewrite(&mut body) {}
+
+        // the jump goes by the first comparison, and the result it was set from was never needed
+        assert!(matches!(
+            body[..],
+            [
+                X86::Cmp { .. },
+                X86::JmpCC {
+                    condition: CondCode::GE,
+                    ..
+                },
+                X86::Binary {
+                    operator: Binary::ShiftLeft,
+                    regs: (Op::Imm(3), _),
+                    ..
+                },
+                X86::Label(_),
+                X86::Binary {
+                    operator: Binary::Xor,
+                    ..
+                },
+                X86::Ret,
+            ]
+        ));
+    }
+}
@@ -16,24 +16,33 @@ use assembly::tacky::Program as TackyProgram;
 use assembly::tacky::FunctionDefinition as TackyFD;
 use assembly::tacky::TopLevel as TackyTL;
 
+use assembly::tacky::StaticConstant as TackySC;
 use assembly::tacky::StaticVar as TackySV;
 use assembly::CondCode;
 use assembly::Pseudo;
 
 use assembly::x86::pseudo_regs as pseudop;
 use assembly::PseudoOp;
-use assembly::StaticVar;
 use assembly::TackyInstruction;
 use assembly::TopLevel;
 use assembly::{FunctionDefinition, Program, Register};
+use assembly::{StaticConstant, StaticInit, StaticVar};
 
+use abi::Class;
 use assembly::SymbolTable as BackendTable;
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-pub fn emit(program: TackyProgram, table: SymbolTable) -> (Program<Pseudo>, BackendTable) {
+mod abi;
+
+static LABEL_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+pub fn emit(program: TackyProgram, mut table: SymbolTable) -> (Program<Pseudo>, BackendTable) {
     let mut decs = Vec::with_capacity(program.0.len());
+    let mut literals = Literals::default();
     for dec in program.0 {
         decs.push(match dec {
-            TackyTL::Fn(f) => TopLevel::Fn(convert_function(f, &table)),
+            TackyTL::Fn(f) => TopLevel::Fn(convert_function(f, &mut table, &mut literals)),
             TackyTL::StaticVar(TackySV {
                 name,
                 global,
@@ -45,44 +54,100 @@ pub fn emit(program: TackyProgram, table: SymbolTable) -> (Program<Pseudo>, Back
                 init,
                 alignment: typ.alignment(),
             }),
+            TackyTL::StaticConstant(TackySC { name, typ, init }) => {
+                TopLevel::StaticConstant(StaticConstant {
+                    name,
+                    alignment: typ.alignment(),
+                    init,
+                })
+            }
         })
     }
+    decs.extend(literals.into_constants().map(TopLevel::StaticConstant));
     (Program(decs.into()), assembly::update_table(table))
 }
 
-const fn get_type(ty: &VarType) -> AsmType {
-    match ty {
-        VarType::Int => AsmType::Longword,
-        VarType::Long => AsmType::Quadword,
-    }
+fn get_type(ty: &VarType) -> AsmType {
+    assembly::asm_type(*ty)
 }
 
 fn var_type(var: &Identifier, table: &SymbolTable) -> AsmType {
-    let Some(Attr::Automatic(typ) | Attr::Static { typ, .. }) = table.get(var) else {
+    get_type(&source_type(var, table))
+}
+
+fn source_type(var: &Identifier, table: &SymbolTable) -> VarType {
+    let Some(Attr::Automatic(typ) | Attr::Static { typ, .. } | Attr::Constant { typ, .. }) =
+        table.get(var)
+    else {
         panic!(
             "unexpected symbol result: {:?} (expected automatic)",
             table.get(var)
         )
     };
-    get_type(typ)
+    *typ
 }
 
-fn convert_val(val: &Value) -> PseudoOp {
-    match val {
-        Value::Constant(c) => Op::Imm(c.long()).pseudo(),
-        Value::Var(v) => PseudoOp::PseudoRegister(v.clone()),
+// doubles can't be immediates, so every one the program uses gets a slot in .rodata, named after
+// its bits
+#[derive(Default)]
+struct Literals(BTreeSet<u64>);
+
+impl Literals {
+    fn double(&mut self, d: f64) -> PseudoOp {
+        let bits = d.to_bits();
+        self.0.insert(bits);
+        Op::Literal(literal_name(bits)).into()
+    }
+
+    fn operand(&mut self, val: &Value) -> PseudoOp {
+        match val {
+            Value::Constant(Constant::Double(d)) => self.double(*d),
+            Value::Constant(c) => PseudoOp::imm(c.long()),
+            Value::Var(v) => PseudoOp::PseudoRegister(v.clone()),
+        }
+    }
+
+    fn into_constants(self) -> impl Iterator<Item = StaticConstant> {
+        self.0.into_iter().map(|bits| StaticConstant {
+            name: literal_name(bits),
+            // xorpd reads 16 bytes at a time
+            alignment: 16,
+            init: StaticInit::Double(f64::from_bits(bits)),
+        })
     }
 }
 
+fn literal_name(bits: u64) -> Identifier {
+    Identifier::from(format!("dbl{bits:x}"))
+}
+
+fn new_label(name: &str) -> Identifier {
+    let number = LABEL_COUNT.fetch_add(1, Ordering::SeqCst);
+    Identifier::from(format!("{name}{number}"))
+}
+
 use crate::lex::Constant;
 fn val_type(val: &Value, table: &SymbolTable) -> AsmType {
     match val {
-        Value::Constant(Constant::Int(_)) => AsmType::Longword,
-        Value::Constant(Constant::Long(_)) => AsmType::Quadword,
+        Value::Constant(Constant::Int(_) | Constant::UInt(_)) => AsmType::Longword,
+        Value::Constant(Constant::Long(_) | Constant::ULong(_)) => AsmType::Quadword,
+        Value::Constant(Constant::Double(_)) => AsmType::Double,
+        Value::Constant(Constant::Char(_) | Constant::UChar(_)) => AsmType::Byte,
         Value::Var(v) => var_type(v, table),
     }
 }
 
+// decides between signed and unsigned division, comparison and shifts
+fn val_signed(val: &Value, table: &SymbolTable) -> bool {
+    match val {
+        Value::Constant(Constant::Int(_) | Constant::Long(_) | Constant::Char(_)) => true,
+        Value::Constant(
+            Constant::UInt(_) | Constant::ULong(_) | Constant::UChar(_) | Constant::Double(_),
+        ) => false,
+        Value::Var(v) => source_type(v, table).signed(),
+    }
+}
+
 fn convert_function(
     TackyFD {
         name,
@@ -90,34 +155,80 @@ fn convert_function(
         params,
         global,
     }: TackyFD,
-    table: &SymbolTable,
+    table: &mut SymbolTable,
+    literals: &mut Literals,
 ) -> FunctionDefinition<Pseudo> {
     let mut instructions = OpVec::new();
 
-    for (dst, src) in params
+    // the caller passes a pointer to wherever big structs should be returned in rdi, which we
+    // need to hang on to until the end
+    let return_ptr = returns_in_memory(&name, table).then(|| {
+        let ptr = Identifier::from(format!("{name}.return"));
+        table.insert(ptr.clone(), Attr::Automatic(VarType::ULong));
+        instructions.push_one(Pseudo::mov(
+            pseudop::DI,
+            PseudoOp::PseudoRegister(ptr.clone()),
+            AsmType::Quadword,
+        ));
+        ptr
+    });
+    let save_area = variadic(&name, table).then(|| {
+        let area = Identifier::from(format!("{name}.va_save"));
+        table.insert(
+            area.clone(),
+            Attr::Automatic(VarType::Char.array_of(REG_SAVE_AREA as usize)),
+        );
+        save_registers(&area, &mut instructions);
+        area
+    });
+    let table = &*table;
+
+    let (registers, stack) = classify(
+        params.iter(),
+        |param| eightbytes(&Value::Var((*param).clone()), table),
+        return_ptr.is_some(),
+    );
+    let sse = registers
         .iter()
-        .zip(PseudoOp::SYSV_ARG_REGS.map(PseudoOp::register))
-    {
-        instructions.push([Pseudo::Mov {
-            regs: (src, PseudoOp::PseudoRegister(dst.clone())),
-            ty: var_type(dst, table),
-        }])
+        .filter(|(_, _, ty, _)| *ty == AsmType::Double)
+        .count();
+    let ints = registers.len() - sse + usize::from(return_ptr.is_some());
+    for (param, offset, ty, register) in registers {
+        let dst = piece(
+            PseudoOp::PseudoRegister(param.clone()),
+            offset,
+            var_type(param, table),
+        );
+        store_eightbyte(register, dst, ty, &mut instructions);
     }
 
     let mut start = 16;
-    for param_n in params.iter().skip(6) {
-        instructions.push([Pseudo::Mov {
-            regs: (
-                Op::Stack(start).into(),
-                PseudoOp::PseudoRegister(param_n.clone()),
-            ),
-            ty: var_type(param_n, table),
-        }]);
-        start += 8;
+    for param in stack {
+        let ty = var_type(param, table);
+        copy(
+            Op::Stack(start).into(),
+            PseudoOp::PseudoRegister(param.clone()),
+            ty,
+            &mut instructions,
+        );
+        start += stack_size(ty) as isize;
     }
 
+    let va = save_area.map(|area| VaFrame {
+        area,
+        gp_offset: 8 * ints as i64,
+        fp_offset: FP_START + 16 * sse as i64,
+        overflow: start,
+    });
     for op in body {
-        convert_instruction(op, &mut instructions, table);
+        convert_instruction(
+            op,
+            &mut instructions,
+            table,
+            return_ptr.as_ref(),
+            va.as_ref(),
+            literals,
+        );
     }
 
     FunctionDefinition {
@@ -134,37 +245,69 @@ fn convert_instruction(
     instruction: TackyInstruction,
     instructions: &mut OpVec<Pseudo>,
     table: &SymbolTable,
+    return_ptr: Option<&Identifier>,
+    va: Option<&VaFrame>,
+    literals: &mut Literals,
 ) {
     use TackyInstruction as TackyOp;
     match instruction {
         TackyOp::FunCall { name, args, dst } => {
-            convert_funcall(name, args, dst, instructions, table)
+            let defined = matches!(table.get(&name), Some(Attr::Fn { defined: true, .. }));
+            let variadic = variadic(&name, table);
+            let call = Pseudo::Call { name, defined };
+            convert_funcall(call, variadic, args, dst, instructions, table, literals);
         }
+        TackyOp::IndirectCall {
+            ptr,
+            args,
+            dst,
+            variadic,
+        } => {
+            let call = Pseudo::CallIndirect(literals.operand(&ptr));
+            convert_funcall(call, variadic, args, dst, instructions, table, literals);
+        }
+        TackyOp::VaStart(ap) => {
+            convert_va_start(ap, va.expect("typecheck checked"), instructions, literals)
+        }
+        TackyOp::VaArg { ap, dst } => convert_va_arg(ap, dst, instructions, table, literals),
         TackyOp::Binary {
             operator,
             source_1,
             source_2,
             dst,
         } => {
-            convert_binary(operator, source_1, source_2, dst, instructions, table);
-        }
-        TackyOp::Return(var) => {
-            instructions.push([
-                Pseudo::Mov {
-                    ty: val_type(&var, table),
-                    regs: (var.into(), pseudop::AX),
-                },
-                Pseudo::Ret,
-            ]);
+            convert_binary(
+                operator,
+                source_1,
+                source_2,
+                dst,
+                instructions,
+                table,
+                literals,
+            );
         }
+        TackyOp::Return(val) => convert_return(val, return_ptr, instructions, table, literals),
         TackyOp::Unary { op, source, dst } => {
-            convert_unary(instructions, op, source, dst, table);
+            convert_unary(instructions, op, source, dst, table, literals);
         }
         TackyOp::Copy { src, dst } => {
-            instructions.push_one(Pseudo::Mov {
-                ty: val_type(&src, table),
-                regs: (src.into(), dst.into()),
-            });
+            let ty = val_type(&src, table);
+            copy(
+                literals.operand(&src),
+                literals.operand(&dst),
+                ty,
+                instructions,
+            );
+        }
+        TackyOp::JumpIfZero { condition, target }
+            if val_type(&condition, table) == AsmType::Double =>
+        {
+            convert_double_jump(condition, target, true, instructions, literals)
+        }
+        TackyOp::JumpIfNotZero { condition, target }
+            if val_type(&condition, table) == AsmType::Double =>
+        {
+            convert_double_jump(condition, target, false, instructions, literals)
         }
         TackyOp::JumpIfZero { condition, target } => {
             let condition_code = CondCode::E;
@@ -175,6 +318,7 @@ fn convert_instruction(
                 target,
                 condition_code,
                 ty,
+                literals,
             ));
         }
         TackyOp::JumpIfNotZero { condition, target } => {
@@ -185,114 +329,910 @@ fn convert_instruction(
                 target,
                 condition_code,
                 ty,
+                literals,
             ));
         }
         TackyOp::Jump { target } => instructions.push_one(Pseudo::Jmp(target)),
         TackyOp::Label(label) => instructions.push_one(Pseudo::Label(label)),
-        TackyOp::SignExtend { src, dst } => {
-            instructions.push_one(Pseudo::movsx(src.into(), dst.into()))
+        TackyOp::SignExtend { src, dst } => instructions.push_one(Pseudo::movsx(
+            literals.operand(&src),
+            literals.operand(&dst),
+            val_type(&src, table),
+            val_type(&dst, table),
+        )),
+        TackyOp::ZeroExtend { src, dst } => instructions.push_one(Pseudo::movzx(
+            literals.operand(&src),
+            literals.operand(&dst),
+            val_type(&src, table),
+            val_type(&dst, table),
+        )),
+        TackyOp::GetAddress {
+            src: Value::Var(name),
+            dst,
+        } if matches!(table.get(&name), Some(Attr::Fn { .. })) => {
+            instructions.push_one(function_address(name, literals.operand(&dst), table));
         }
+        TackyOp::GetAddress { src, dst } => instructions.push_one(Pseudo::Lea {
+            regs: (literals.operand(&src), literals.operand(&dst)),
+        }),
+        TackyOp::Phi { .. } => unreachable!("ssa::destruct turns phis into copies"),
+        TackyOp::Load { src_ptr, dst } => {
+            instructions.push_one(Pseudo::mov(
+                literals.operand(&src_ptr),
+                pseudop::AX,
+                AsmType::Quadword,
+            ));
+            let ty = val_type(&dst, table);
+            copy(
+                Op::Memory(Register::Ax, 0).into(),
+                literals.operand(&dst),
+                ty,
+                instructions,
+            );
+        }
+        TackyOp::Store { src, dst_ptr } => {
+            let ty = val_type(&src, table);
+            instructions.push_one(Pseudo::mov(
+                literals.operand(&dst_ptr),
+                pseudop::AX,
+                AsmType::Quadword,
+            ));
+            copy(
+                literals.operand(&src),
+                Op::Memory(Register::Ax, 0).into(),
+                ty,
+                instructions,
+            );
+        }
+        TackyOp::AddPtr {
+            ptr,
+            index,
+            scale,
+            dst,
+        } => convert_add_ptr(ptr, index, scale, dst, instructions, literals),
+        TackyOp::CopyToOffset { src, dst, offset } => copy(
+            literals.operand(&src),
+            PseudoOp::PseudoMem(dst, offset as isize),
+            val_type(&src, table),
+            instructions,
+        ),
+        TackyOp::CopyFromOffset { src, offset, dst } => copy(
+            PseudoOp::PseudoMem(src, offset as isize),
+            literals.operand(&dst),
+            val_type(&dst, table),
+            instructions,
+        ),
+        // the low bytes are already where they need to be, constants just have to fit
         TackyOp::Truncate { src, dst } => {
+            let ty = val_type(&dst, table);
+            let src = match src {
+                Value::Constant(c) if ty == AsmType::Byte => PseudoOp::imm(c.long() as i8 as i64),
+                Value::Constant(c) => PseudoOp::imm(c.long() as i32 as i64),
+                src => literals.operand(&src),
+            };
             instructions.push_one(Pseudo::Mov {
-                regs: (src.into(), dst.into()),
-                ty: AsmType::Longword,
+                regs: (src, literals.operand(&dst)),
+                ty,
             });
         }
+        // cvtsi2sd doesn't take bytes, so chars get widened to ints first
+        TackyOp::IntToDouble { src, dst } if val_type(&src, table) == AsmType::Byte => instructions
+            .push([
+                Pseudo::movsx(
+                    literals.operand(&src),
+                    pseudop::AX,
+                    AsmType::Byte,
+                    AsmType::Longword,
+                ),
+                Pseudo::Cvtsi2sd {
+                    ty: AsmType::Longword,
+                    regs: (pseudop::AX, literals.operand(&dst)),
+                },
+            ]),
+        TackyOp::UIntToDouble { src, dst } if val_type(&src, table) == AsmType::Byte => {
+            instructions.push([
+                Pseudo::movzx(
+                    literals.operand(&src),
+                    pseudop::AX,
+                    AsmType::Byte,
+                    AsmType::Longword,
+                ),
+                Pseudo::Cvtsi2sd {
+                    ty: AsmType::Longword,
+                    regs: (pseudop::AX, literals.operand(&dst)),
+                },
+            ])
+        }
+        // and go through an int on the way back, which every char fits in
+        TackyOp::DoubleToInt { src, dst } | TackyOp::DoubleToUInt { src, dst }
+            if val_type(&dst, table) == AsmType::Byte =>
+        {
+            instructions.push([
+                Pseudo::Cvttsd2si {
+                    ty: AsmType::Longword,
+                    regs: (literals.operand(&src), pseudop::AX),
+                },
+                Pseudo::mov(pseudop::AX, literals.operand(&dst), AsmType::Byte),
+            ])
+        }
+        TackyOp::IntToDouble { src, dst } => instructions.push_one(Pseudo::Cvtsi2sd {
+            ty: val_type(&src, table),
+            regs: (literals.operand(&src), literals.operand(&dst)),
+        }),
+        TackyOp::DoubleToInt { src, dst } => instructions.push_one(Pseudo::Cvttsd2si {
+            ty: val_type(&dst, table),
+            regs: (literals.operand(&src), literals.operand(&dst)),
+        }),
+        // an unsigned int fits in a long, so convert that instead
+        TackyOp::UIntToDouble { src, dst } if val_type(&src, table) == AsmType::Longword => {
+            instructions.push([
+                Pseudo::movzx(
+                    literals.operand(&src),
+                    pseudop::AX,
+                    AsmType::Longword,
+                    AsmType::Quadword,
+                ),
+                Pseudo::Cvtsi2sd {
+                    ty: AsmType::Quadword,
+                    regs: (pseudop::AX, literals.operand(&dst)),
+                },
+            ])
+        }
+        TackyOp::UIntToDouble { src, dst } => {
+            convert_ulong_to_double(src, dst, instructions, literals)
+        }
+        TackyOp::DoubleToUInt { src, dst } if val_type(&dst, table) == AsmType::Longword => {
+            instructions.push([
+                Pseudo::Cvttsd2si {
+                    ty: AsmType::Quadword,
+                    regs: (literals.operand(&src), pseudop::AX),
+                },
+                Pseudo::mov(pseudop::AX, literals.operand(&dst), AsmType::Longword),
+            ])
+        }
+        TackyOp::DoubleToUInt { src, dst } => {
+            convert_double_to_ulong(src, dst, instructions, literals)
+        }
     };
 }
 
-fn should_pad(args: &[Value]) -> bool {
-    if args.len() <= 6 {
-        false
+fn convert_return(
+    val: Option<Value>,
+    return_ptr: Option<&Identifier>,
+    instructions: &mut OpVec<Pseudo>,
+    table: &SymbolTable,
+    literals: &mut Literals,
+) {
+    let Some(val) = val else {
+        instructions.push_one(Pseudo::Ret);
+        return;
+    };
+    let whole = val_type(&val, table);
+    match return_ptr {
+        // rax has to hold the pointer again on the way out
+        Some(ptr) => {
+            instructions.push_one(Pseudo::mov(
+                PseudoOp::PseudoRegister(ptr.clone()),
+                pseudop::AX,
+                AsmType::Quadword,
+            ));
+            copy(
+                literals.operand(&val),
+                Op::Memory(Register::Ax, 0).into(),
+                whole,
+                instructions,
+            );
+        }
+        None => {
+            let registers = return_registers(&val, table).expect("returned in a register");
+            for (offset, ty, register) in registers {
+                let src = piece(literals.operand(&val), offset, whole);
+                load_eightbyte(src, register, ty, instructions);
+            }
+        }
+    }
+    instructions.push_one(Pseudo::Ret);
+}
+
+// where a value comes back from a call, `None` for structs that get written through rdi instead
+fn return_registers(val: &Value, table: &SymbolTable) -> Option<Vec<(isize, AsmType, Register)>> {
+    let mut ints = [Register::Ax, Register::Dx].into_iter();
+    let mut doubles = [Register::Xmm0, Register::Xmm1].into_iter();
+    eightbytes(val, table)
+        .into_iter()
+        .zip((0..).step_by(8))
+        .map(|((class, ty), offset)| {
+            let register = match class {
+                Class::Integer => ints.next(),
+                Class::Sse => doubles.next(),
+                Class::Memory => None,
+            };
+            register.map(|register| (offset, ty, register))
+        })
+        .collect()
+}
+
+fn returns_in_memory(name: &Identifier, table: &SymbolTable) -> bool {
+    let Some(Attr::Fn { typ, .. }) = table.get(name) else {
+        unreachable!("{name} is a function")
+    };
+    typ.ret.is_struct() && abi::classify(typ.ret).first() == Some(&Class::Memory)
+}
+
+// the register save area holds rdi through r9 followed by xmm0 through xmm7, and the va_list
+// offsets count how far into it va_arg has got
+const REG_SAVE_AREA: i64 = 176;
+const FP_START: i64 = 48;
+
+// where a variadic function's unnamed arguments are
+struct VaFrame {
+    area: Identifier,
+    // the first register the named parameters didn't use, as an offset into `area`
+    gp_offset: i64,
+    fp_offset: i64,
+    // the first stack argument that isn't a named parameter
+    overflow: isize,
+}
+
+fn variadic(name: &Identifier, table: &SymbolTable) -> bool {
+    matches!(table.get(name), Some(Attr::Fn { typ, .. }) if typ.variadic)
+}
+
+// before anything else can clobber them, the callee doesn't know which ones hold arguments
+fn save_registers(area: &Identifier, instructions: &mut OpVec<Pseudo>) {
+    for (i, register) in PseudoOp::SYSV_ARG_REGS.into_iter().enumerate() {
+        instructions.push_one(Pseudo::mov(
+            PseudoOp::register(register),
+            PseudoOp::PseudoMem(area.clone(), 8 * i as isize),
+            AsmType::Quadword,
+        ));
+    }
+    for (i, register) in PseudoOp::SYSV_FLOAT_REGS.into_iter().enumerate() {
+        instructions.push_one(Pseudo::mov(
+            PseudoOp::register(register),
+            PseudoOp::PseudoMem(area.clone(), (FP_START + 16 * i as i64) as isize),
+            AsmType::Double,
+        ));
+    }
+}
+
+// va_list is { unsigned gp_offset, unsigned fp_offset, void *overflow_arg_area,
+// void *reg_save_area }
+fn convert_va_start(
+    ap: Value,
+    va: &VaFrame,
+    instructions: &mut OpVec<Pseudo>,
+    literals: &mut Literals,
+) {
+    let field = |offset| PseudoOp::from(Op::Memory(Register::Ax, offset));
+    instructions.push([
+        Pseudo::mov(literals.operand(&ap), pseudop::AX, AsmType::Quadword),
+        Pseudo::mov(PseudoOp::imm(va.gp_offset), field(0), AsmType::Longword),
+        Pseudo::mov(PseudoOp::imm(va.fp_offset), field(4), AsmType::Longword),
+        Pseudo::Lea {
+            regs: (PseudoOp::stack(va.overflow), pseudop::DX),
+        },
+        Pseudo::mov(pseudop::DX, field(8), AsmType::Quadword),
+        Pseudo::Lea {
+            regs: (PseudoOp::PseudoMem(va.area.clone(), 0), pseudop::DX),
+        },
+        Pseudo::mov(pseudop::DX, field(16), AsmType::Quadword),
+    ]);
+}
+
+// the argument comes out of the register save area if everything it needs is still left in
+// there, the same way it would have been passed in registers, otherwise off the stack
+fn convert_va_arg(
+    ap: Value,
+    dst: Value,
+    instructions: &mut OpVec<Pseudo>,
+    table: &SymbolTable,
+    literals: &mut Literals,
+) {
+    let field = |offset| PseudoOp::from(Op::Memory(Register::Ax, offset));
+    let whole = val_type(&dst, table);
+    let parts = eightbytes(&dst, table);
+    let stack = new_label("va_stack");
+    let end = new_label("va_end");
+
+    instructions.push_one(Pseudo::mov(
+        literals.operand(&ap),
+        pseudop::AX,
+        AsmType::Quadword,
+    ));
+    if !parts.iter().any(|(class, _)| *class == Class::Memory) {
+        let sse = parts
+            .iter()
+            .filter(|(class, _)| *class == Class::Sse)
+            .count() as i64;
+        let ints = parts.len() as i64 - sse;
+        // the offsets are unsigned, and there has to be room for all of it
+        for (needed, field_offset, limit, step) in
+            [(ints, 0, FP_START, 8), (sse, 4, REG_SAVE_AREA, 16)]
+        {
+            if needed == 0 {
+                continue;
+            }
+            instructions.push([
+                Pseudo::mov(field(field_offset), pseudop::CX, AsmType::Longword),
+                Pseudo::Cmp {
+                    ty: AsmType::Longword,
+                    regs: (PseudoOp::imm(limit - needed * step), pseudop::CX),
+                },
+                Pseudo::JmpCC {
+                    condition: CondCode::A,
+                    label: stack.clone(),
+                },
+            ]);
+        }
+        for ((class, ty), offset) in parts.into_iter().zip((0..).step_by(8)) {
+            let (field_offset, step) = if class == Class::Sse { (4, 16) } else { (0, 8) };
+            instructions.push([
+                Pseudo::mov(field(16), pseudop::DX, AsmType::Quadword),
+                Pseudo::mov(field(field_offset), pseudop::CX, AsmType::Longword),
+                Pseudo::binary(Binary::Add, pseudop::CX, pseudop::DX, AsmType::Quadword),
+            ]);
+            copy(
+                Op::Memory(Register::Dx, 0).into(),
+                piece(literals.operand(&dst), offset, whole),
+                ty,
+                instructions,
+            );
+            instructions.push_one(Pseudo::binary(
+                Binary::Add,
+                PseudoOp::imm(step),
+                field(field_offset),
+                AsmType::Longword,
+            ));
+        }
+        instructions.push_one(Pseudo::Jmp(end.clone()));
+    }
+
+    instructions.push_one(Pseudo::Label(stack));
+    instructions.push_one(Pseudo::mov(field(8), pseudop::DX, AsmType::Quadword));
+    copy(
+        Op::Memory(Register::Dx, 0).into(),
+        literals.operand(&dst),
+        whole,
+        instructions,
+    );
+    instructions.push([
+        Pseudo::binary(
+            Binary::Add,
+            PseudoOp::imm(stack_size(whole) as i64),
+            field(8),
+            AsmType::Quadword,
+        ),
+        Pseudo::Label(end),
+    ]);
+}
+
+// cvtsi2sd only knows about signed numbers, so anything too big for a long gets halved first,
+// keeping the low bit around so it still rounds the right way, and doubled again afterwards
+fn convert_ulong_to_double(
+    src: Value,
+    dst: Value,
+    instructions: &mut OpVec<Pseudo>,
+    literals: &mut Literals,
+) {
+    let out_of_range = new_label("ulong_big");
+    let end = new_label("ulong_end");
+    let src = literals.operand(&src);
+    let dst = literals.operand(&dst);
+    instructions.push([
+        Pseudo::cmp(PseudoOp::imm(0), src.clone(), AsmType::Quadword),
+        Pseudo::JmpCC {
+            condition: CondCode::L,
+            label: out_of_range.clone(),
+        },
+        Pseudo::Cvtsi2sd {
+            ty: AsmType::Quadword,
+            regs: (src.clone(), dst.clone()),
+        },
+        Pseudo::Jmp(end.clone()),
+        Pseudo::Label(out_of_range),
+        Pseudo::mov(src, pseudop::AX, AsmType::Quadword),
+        Pseudo::mov(pseudop::AX, pseudop::DX, AsmType::Quadword),
+        Pseudo::binary(
+            Binary::ShiftRightLogical,
+            PseudoOp::imm(1),
+            pseudop::DX,
+            AsmType::Quadword,
+        ),
+        Pseudo::binary(
+            Binary::And,
+            PseudoOp::imm(1),
+            pseudop::AX,
+            AsmType::Quadword,
+        ),
+        Pseudo::binary(Binary::Or, pseudop::AX, pseudop::DX, AsmType::Quadword),
+        Pseudo::Cvtsi2sd {
+            ty: AsmType::Quadword,
+            regs: (pseudop::DX, dst.clone()),
+        },
+        Pseudo::binary(Binary::Add, dst.clone(), dst, AsmType::Double),
+        Pseudo::Label(end),
+    ]);
+}
+
+// cvttsd2si only knows about signed numbers too, so anything past i64::MAX gets 2^63 taken off
+// before converting and added back on after
+fn convert_double_to_ulong(
+    src: Value,
+    dst: Value,
+    instructions: &mut OpVec<Pseudo>,
+    literals: &mut Literals,
+) {
+    let out_of_range = new_label("double_big");
+    let end = new_label("double_end");
+    let upper_bound = literals.double(9223372036854775808.0);
+    let src = literals.operand(&src);
+    let dst = literals.operand(&dst);
+    instructions.push([
+        Pseudo::cmp(upper_bound.clone(), src.clone(), AsmType::Double),
+        Pseudo::JmpCC {
+            condition: CondCode::AE,
+            label: out_of_range.clone(),
+        },
+        Pseudo::Cvttsd2si {
+            ty: AsmType::Quadword,
+            regs: (src.clone(), dst.clone()),
+        },
+        Pseudo::Jmp(end.clone()),
+        Pseudo::Label(out_of_range),
+        Pseudo::mov(src, pseudop::XMM13, AsmType::Double),
+        Pseudo::binary(Binary::Sub, upper_bound, pseudop::XMM13, AsmType::Double),
+        Pseudo::Cvttsd2si {
+            ty: AsmType::Quadword,
+            regs: (pseudop::XMM13, dst.clone()),
+        },
+        Pseudo::binary(Binary::Add, PseudoOp::imm(i64::MIN), dst, AsmType::Quadword),
+        Pseudo::Label(end),
+    ]);
+}
+
+fn zero_xmm13() -> Pseudo {
+    Pseudo::binary(Binary::Xor, pseudop::XMM13, pseudop::XMM13, AsmType::Double)
+}
+
+// NaN compares unordered with everything, which sets the parity flag, and counts as true
+fn convert_double_jump(
+    condition: Value,
+    label: Identifier,
+    if_zero: bool,
+    instructions: &mut OpVec<Pseudo>,
+    literals: &mut Literals,
+) {
+    instructions.push([
+        zero_xmm13(),
+        Pseudo::cmp(
+            literals.operand(&condition),
+            pseudop::XMM13,
+            AsmType::Double,
+        ),
+    ]);
+    if if_zero {
+        let nan = new_label("nan");
+        instructions.push([
+            Pseudo::JmpCC {
+                condition: CondCode::P,
+                label: nan.clone(),
+            },
+            Pseudo::JmpCC {
+                condition: CondCode::E,
+                label,
+            },
+            Pseudo::Label(nan),
+        ]);
     } else {
-        // each arg from here is 8 more bytes, but we want to be 16 byte
-        // aligned, so we should align the stack
-        //
-        // even number of non stack args, so the whole thing will be odd if the stack args are odd,
-        // in which case we should pad
-        args.len() % 2 != 0
+        instructions.push([
+            Pseudo::JmpCC {
+                condition: CondCode::NE,
+                label: label.clone(),
+            },
+            Pseudo::JmpCC {
+                condition: CondCode::P,
+                label,
+            },
+        ]);
     }
 }
 
+// `==` and `!=`, where NaN makes `dst` whatever `unordered` says
+fn double_equality(
+    condition: CondCode,
+    unordered: i64,
+    (left, right): (PseudoOp, PseudoOp),
+    dst: PseudoOp,
+    dst_ty: AsmType,
+    instructions: &mut OpVec<Pseudo>,
+) {
+    let nan = new_label("nan");
+    instructions.push([
+        Pseudo::cmp(left, right, AsmType::Double),
+        Pseudo::mov(PseudoOp::imm(unordered), dst.clone(), dst_ty),
+        Pseudo::JmpCC {
+            condition: CondCode::P,
+            label: nan.clone(),
+        },
+        Pseudo::SetCC { condition, op: dst },
+        Pseudo::Label(nan),
+    ]);
+}
+
+// a mov, or a run of them for structs, which don't fit in a register
+fn copy(src: PseudoOp, dst: PseudoOp, ty: AsmType, instructions: &mut OpVec<Pseudo>) {
+    let AsmType::ByteArray { size, .. } = ty else {
+        instructions.push_one(Pseudo::mov(src, dst, ty));
+        return;
+    };
+    let mut offset = 0;
+    while offset < size {
+        let (ty, len) = match size - offset {
+            8.. => (AsmType::Quadword, 8),
+            4.. => (AsmType::Longword, 4),
+            _ => (AsmType::Byte, 1),
+        };
+        let at = offset as isize;
+        instructions.push_one(Pseudo::mov(src.offset(at), dst.offset(at), ty));
+        offset += len;
+    }
+}
+
+// lea can scale by 1, 2, 4 or 8 itself, anything else gets multiplied first
+fn convert_add_ptr(
+    ptr: Value,
+    index: Value,
+    scale: i64,
+    dst: Value,
+    instructions: &mut OpVec<Pseudo>,
+    literals: &mut Literals,
+) {
+    instructions.push_one(Pseudo::mov(
+        literals.operand(&ptr),
+        pseudop::AX,
+        AsmType::Quadword,
+    ));
+    let address = match index {
+        Value::Constant(c) => Op::Memory(Register::Ax, (c.long() * scale) as isize),
+        index => {
+            instructions.push_one(Pseudo::mov(
+                literals.operand(&index),
+                pseudop::DX,
+                AsmType::Quadword,
+            ));
+            let scale = match scale {
+                1 | 2 | 4 | 8 => scale as u8,
+                _ => {
+                    instructions.push_one(Pseudo::binary(
+                        Binary::Mult,
+                        PseudoOp::imm(scale),
+                        pseudop::DX,
+                        AsmType::Quadword,
+                    ));
+                    1
+                }
+            };
+            Op::Indexed {
+                base: Register::Ax,
+                index: Register::Dx,
+                scale,
+            }
+        }
+    };
+    instructions.push_one(Pseudo::Lea {
+        regs: (address.into(), literals.operand(&dst)),
+    });
+}
+
+fn should_pad(stack_bytes: usize) -> bool {
+    // everything on the stack takes up a multiple of 8 bytes, but we want to be 16 byte aligned,
+    // so we should align the stack if we're 8 bytes off
+    !stack_bytes.is_multiple_of(16)
+}
+
+// structs on the stack get padded out to a multiple of 8 bytes like everything else
+fn stack_size(ty: AsmType) -> usize {
+    match ty {
+        AsmType::ByteArray { size, .. } => size.next_multiple_of(8),
+        _ => 8,
+    }
+}
+
+// a value, how far into it the eightbyte starts, its type, and where it goes
+type InRegister<T> = (T, isize, AsmType, Register);
+
+// system v puts the first six integers in general purpose registers and the first eight doubles
+// in xmm registers, whatever's left over goes on the stack in order
+// structs go in as many registers as they have eightbytes, but only if there's room for all of
+// them, otherwise the whole thing goes on the stack
+fn classify<T: Clone>(
+    values: impl IntoIterator<Item = T>,
+    eightbytes: impl Fn(&T) -> Vec<(Class, AsmType)>,
+    return_in_memory: bool,
+) -> (Vec<InRegister<T>>, Vec<T>) {
+    // the hidden return pointer takes up rdi
+    let mut ints = PseudoOp::SYSV_ARG_REGS
+        .into_iter()
+        .skip(usize::from(return_in_memory));
+    let mut doubles = PseudoOp::SYSV_FLOAT_REGS.into_iter();
+    let mut registers = Vec::new();
+    let mut stack = Vec::new();
+    for value in values {
+        let parts = eightbytes(&value);
+        let sse = parts
+            .iter()
+            .filter(|(class, _)| *class == Class::Sse)
+            .count();
+        let fits = !parts.iter().any(|(class, _)| *class == Class::Memory)
+            && sse <= doubles.len()
+            && parts.len() - sse <= ints.len();
+        if !fits {
+            stack.push(value);
+            continue;
+        }
+        for ((class, ty), offset) in parts.into_iter().zip((0..).step_by(8)) {
+            let register = if class == Class::Sse {
+                doubles.next()
+            } else {
+                ints.next()
+            };
+            registers.push((value.clone(), offset, ty, register.unwrap()));
+        }
+    }
+    (registers, stack)
+}
+
+// a scalar is a single eightbyte of its own type, structs get split up, with the last one
+// covering whatever's left over
+fn eightbytes(val: &Value, table: &SymbolTable) -> Vec<(Class, AsmType)> {
+    match val_type(val, table) {
+        AsmType::Double => vec![(Class::Sse, AsmType::Double)],
+        AsmType::ByteArray { size, .. } => {
+            let Value::Var(var) = val else {
+                unreachable!("structs aren't constants")
+            };
+            abi::classify(source_type(var, table))
+                .into_iter()
+                .zip((0..size).step_by(8))
+                .map(|(class, offset)| {
+                    let ty = match (class, size - offset) {
+                        (Class::Sse, _) => AsmType::Double,
+                        (_, 8..) => AsmType::Quadword,
+                        (_, 4) => AsmType::Longword,
+                        (_, 1) => AsmType::Byte,
+                        (_, size) => AsmType::ByteArray { size, alignment: 1 },
+                    };
+                    (class, ty)
+                })
+                .collect()
+        }
+        ty => vec![(Class::Integer, ty)],
+    }
+}
+
+// the eightbyte `offset` bytes into a struct, scalars only have the one
+fn piece(op: PseudoOp, offset: isize, whole: AsmType) -> PseudoOp {
+    match whole {
+        AsmType::ByteArray { .. } => op.offset(offset),
+        _ => op,
+    }
+}
+
+// there's no mov for 3, 5, 6 or 7 bytes, so those get shifted in a byte at a time, starting
+// from the top
+fn load_eightbyte(src: PseudoOp, dst: Register, ty: AsmType, instructions: &mut OpVec<Pseudo>) {
+    let dst = PseudoOp::register(dst);
+    let AsmType::ByteArray { size, .. } = ty else {
+        instructions.push_one(Pseudo::mov(src, dst, ty));
+        return;
+    };
+    for i in (0..size).rev() {
+        if i + 1 != size {
+            instructions.push_one(Pseudo::binary(
+                Binary::ShiftLeft,
+                PseudoOp::imm(8),
+                dst.clone(),
+                AsmType::Quadword,
+            ));
+        }
+        instructions.push_one(Pseudo::mov(
+            src.offset(i as isize),
+            dst.clone(),
+            AsmType::Byte,
+        ));
+    }
+}
+
+// the other way around, writing only the bytes that belong to the struct
+fn store_eightbyte(src: Register, dst: PseudoOp, ty: AsmType, instructions: &mut OpVec<Pseudo>) {
+    let src = PseudoOp::register(src);
+    let AsmType::ByteArray { size, .. } = ty else {
+        instructions.push_one(Pseudo::mov(src, dst, ty));
+        return;
+    };
+    for i in 0..size {
+        instructions.push_one(Pseudo::mov(
+            src.clone(),
+            dst.offset(i as isize),
+            AsmType::Byte,
+        ));
+        if i + 1 != size {
+            instructions.push_one(Pseudo::binary(
+                Binary::ShiftRightLogical,
+                PseudoOp::imm(8),
+                src.clone(),
+                AsmType::Quadword,
+            ));
+        }
+    }
+}
+
+// how many bytes to pop off the stack after the call, if any
 fn push_args(
     args: &[Value],
+    return_in_memory: bool,
+    variadic: bool,
     instructions: &mut OpVec<Pseudo>,
     table: &SymbolTable,
+    literals: &mut Literals,
 ) -> Option<usize> {
-    const TABLE: [Register; 6] = PseudoOp::SYSV_ARG_REGS;
+    let (registers, stack) = classify(args, |arg| eightbytes(arg, table), return_in_memory);
+    let sse = registers
+        .iter()
+        .filter(|(_, _, ty, _)| *ty == AsmType::Double)
+        .count();
+
+    let stack_bytes = stack
+        .iter()
+        .map(|arg| stack_size(val_type(arg, table)))
+        .sum();
+    let padding = if should_pad(stack_bytes) {
+        instructions.push_one(Pseudo::allocate_stack(8));
+        8
+    } else {
+        0
+    };
+
+    for (arg, offset, ty, register) in registers {
+        if matches!(val_type(arg, table), AsmType::ByteArray { .. }) {
+            load_eightbyte(
+                literals.operand(arg).offset(offset),
+                register,
+                ty,
+                instructions,
+            );
+        } else {
+            instructions.push_one(move_arg(arg, PseudoOp::register(register), table, literals));
+        }
+    }
 
-    for (i, arg) in args.iter().take(6).enumerate() {
+    let cleanup = push_stack_args(&stack, instructions, table, literals) + padding;
+    // variadic functions find out how many xmm registers to save from al, pushing goes through
+    // rax so this has to come last
+    if variadic {
         instructions.push_one(Pseudo::mov(
-            arg.clone().into(),
-            PseudoOp::register(TABLE[i]),
-            val_type(arg, table),
-        ))
+            PseudoOp::imm(sse as i64),
+            pseudop::AX,
+            AsmType::Longword,
+        ));
     }
+    (cleanup != 0).then_some(cleanup)
+}
 
-    if args.len() > 6 {
-        Some(push_stack_args(&args[6..], instructions, table))
-    } else {
-        None
+// chars get widened to ints on their way into a call, which is what gcc and clang expect
+fn move_arg(arg: &Value, dst: PseudoOp, table: &SymbolTable, literals: &mut Literals) -> Pseudo {
+    match val_type(arg, table) {
+        AsmType::Byte if val_signed(arg, table) => {
+            Pseudo::movsx(literals.operand(arg), dst, AsmType::Byte, AsmType::Longword)
+        }
+        AsmType::Byte => {
+            Pseudo::movzx(literals.operand(arg), dst, AsmType::Byte, AsmType::Longword)
+        }
+        ty => Pseudo::mov(literals.operand(arg), dst, ty),
     }
 }
 
-fn push_val(val: &Value, instructions: &mut OpVec<Pseudo>, table: &SymbolTable) {
-    if val_type(val, table) == AsmType::Quadword {
-        let val = match val {
-            Value::Constant(c) => Op::Imm(c.long()).pseudo(),
-            Value::Var(v) => PseudoOp::PseudoRegister(v.clone()),
-        };
-        instructions.push([Pseudo::Push(val)])
+fn push_val(
+    val: &Value,
+    instructions: &mut OpVec<Pseudo>,
+    table: &SymbolTable,
+    literals: &mut Literals,
+) {
+    if matches!(val_type(val, table), AsmType::Quadword | AsmType::Double) {
+        instructions.push([Pseudo::Push(literals.operand(val))])
     } else {
         instructions.push([
-            Pseudo::Mov {
-                ty: AsmType::Longword,
-                regs: (convert_val(val), pseudop::AX),
-            },
+            move_arg(val, pseudop::AX, table, literals),
             Pseudo::Push(Register::Ax.into()),
         ])
     }
 }
 
 fn push_stack_args(
-    stack_args: &[Value],
+    stack_args: &[&Value],
     instructions: &mut OpVec<Pseudo>,
     table: &SymbolTable,
+    literals: &mut Literals,
 ) -> usize {
     let mut byte_count = 0;
     for arg in stack_args.iter().rev() {
-        push_val(arg, instructions, table);
-        byte_count += 8;
+        match val_type(arg, table) {
+            // too big to push, so make room and copy it in
+            ty @ AsmType::ByteArray { .. } => {
+                let size = stack_size(ty);
+                instructions.push_one(Pseudo::allocate_stack(size as i64));
+                copy(
+                    literals.operand(arg),
+                    Op::Memory(Register::Sp, 0).into(),
+                    ty,
+                    instructions,
+                );
+                byte_count += size;
+            }
+            _ => {
+                push_val(arg, instructions, table, literals);
+                byte_count += 8;
+            }
+        }
     }
     byte_count
 }
 
+// anything we didn't define might live in a shared library, so its address comes from the GOT
+fn function_address(name: Identifier, dst: PseudoOp, table: &SymbolTable) -> Pseudo {
+    if matches!(table.get(&name), Some(Attr::Fn { defined: true, .. })) {
+        Pseudo::Lea {
+            regs: (Op::Data(name, 0).into(), dst),
+        }
+    } else {
+        Pseudo::mov(Op::Got(name).into(), dst, AsmType::Quadword)
+    }
+}
+
+// `call` is either a direct call or one through a function pointer
 fn convert_funcall(
-    name: Identifier,
+    call: Pseudo,
+    variadic: bool,
     args: Box<[Value]>,
-    dst: Value,
+    dst: Option<Value>,
     instructions: &mut OpVec<Pseudo>,
     table: &SymbolTable,
+    literals: &mut Literals,
 ) {
-    let padding = if should_pad(&args) {
-        instructions.push_one(Pseudo::allocate_stack(8));
-        8
-    } else {
-        0
+    let return_registers = match &dst {
+        Some(dst) => return_registers(dst, table),
+        None => Some(Vec::new()),
     };
+    if let (Some(dst), None) = (&dst, &return_registers) {
+        // the callee writes big structs straight into dst
+        instructions.push_one(Pseudo::Lea {
+            regs: (literals.operand(dst), pseudop::DI),
+        });
+    }
+    let cleanup_bytes = push_args(
+        &args,
+        return_registers.is_none(),
+        variadic,
+        instructions,
+        table,
+        literals,
+    );
 
-    let cleanup_bytes = push_args(&args, instructions, table);
-
-    instructions.push_one(Pseudo::Call(name.clone()));
+    instructions.push_one(call);
 
     if let Some(cleanup) = cleanup_bytes {
-        instructions.push_one(Pseudo::deallocate_stack((cleanup + padding) as i64));
+        instructions.push_one(Pseudo::deallocate_stack(cleanup as i64));
     }
 
-    instructions.push_one(Pseudo::Mov {
-        ty: val_type(&dst, table),
-        regs: (Register::Ax.into(), dst.into()),
-    });
+    let Some(dst) = dst else {
+        return;
+    };
+    let whole = val_type(&dst, table);
+    for (offset, ty, register) in return_registers.into_iter().flatten() {
+        let dst = piece(literals.operand(&dst), offset, whole);
+        store_eightbyte(register, dst, ty, instructions);
+    }
 }
 
 fn convert_unary(
@@ -301,10 +1241,33 @@ fn convert_unary(
     src: Value,
     dst: Identifier,
     table: &SymbolTable,
+    literals: &mut Literals,
 ) {
     let ty = val_type(&src, table);
-    let src = src.into();
-    if op == UnOp::Not {
+    let src = literals.operand(&src);
+    if ty == AsmType::Double {
+        let dst_ty = var_type(&dst, table);
+        let dst = PseudoOp::from(dst);
+        match op {
+            UnOp::Not => {
+                instructions.push_one(zero_xmm13());
+                double_equality(
+                    CondCode::E,
+                    0,
+                    (src, pseudop::XMM13),
+                    dst,
+                    dst_ty,
+                    instructions,
+                );
+            }
+            // flip the sign bit
+            UnOp::Negate => instructions.push([
+                Pseudo::mov(src, dst.clone(), ty),
+                Pseudo::binary(Binary::Xor, literals.double(-0.0), dst, ty),
+            ]),
+            UnOp::Complement => unreachable!("typecheck doesn't allow ~ on doubles"),
+        }
+    } else if op == UnOp::Not {
         let dst_ty = var_type(&dst, table);
         let dst = PseudoOp::from(dst);
         instructions.push([
@@ -342,11 +1305,12 @@ fn convert_conditional_jump(
     label: TackyIdent,
     code: CondCode,
     ty: AsmType,
+    literals: &mut Literals,
 ) -> [Pseudo; 2] {
     [
         Pseudo::Cmp {
             ty,
-            regs: (Op::Imm(0).into(), condition.into()),
+            regs: (Op::Imm(0).into(), literals.operand(&condition)),
         },
         Pseudo::JmpCC {
             condition: code,
@@ -362,15 +1326,30 @@ fn convert_binary(
     dst: Value,
     instructions: &mut OpVec<Pseudo>,
     table: &SymbolTable,
+    literals: &mut Literals,
 ) {
     let src_ty = val_type(&source_1, table);
-    let source_1 = PseudoOp::from(source_1);
-    let source_2 = PseudoOp::from(source_2);
+    let count_ty = val_type(&source_2, table);
+    let signed = val_signed(&source_1, table);
+    let source_1 = literals.operand(&source_1);
+    let source_2 = literals.operand(&source_2);
 
-    match process_binop(op) {
+    if src_ty == AsmType::Double {
+        let dst_ty = val_type(&dst, table);
+        return convert_double_binary(
+            op,
+            source_1,
+            source_2,
+            literals.operand(&dst),
+            dst_ty,
+            instructions,
+        );
+    }
+
+    match process_binop(op, signed) {
         Binop::Relational(condition) => {
             let dst_ty = val_type(&dst, table);
-            let dst = PseudoOp::from(dst);
+            let dst = literals.operand(&dst);
 
             instructions.push([
                 Pseudo::cmp(source_2, source_1, src_ty),
@@ -378,15 +1357,26 @@ fn convert_binary(
                 Pseudo::SetCC { condition, op: dst },
             ]);
         }
+        // a shift count that isn't a constant has to be in cl
+        Binop::Normal(
+            operator @ (Binary::ShiftLeft | Binary::ShiftRight | Binary::ShiftRightLogical),
+        ) if !matches!(source_2, PseudoOp::Normal(Op::Imm(_))) => {
+            let dst = literals.operand(&dst);
+            instructions.push([
+                Pseudo::mov(source_2, pseudop::CX, count_ty),
+                Pseudo::mov(source_1, dst.clone(), src_ty),
+                Pseudo::binary(operator, pseudop::CX, dst, src_ty),
+            ]);
+        }
         Binop::Normal(operator) => {
-            let dst = PseudoOp::from(dst);
+            let dst = literals.operand(&dst);
             instructions.push([
                 Pseudo::mov(source_1, dst.clone(), src_ty),
                 Pseudo::binary(operator, source_2, dst, src_ty),
             ]);
         }
         Binop::Div(result_register) => {
-            let dst = PseudoOp::from(dst);
+            let dst = literals.operand(&dst);
 
             instructions.push([
                 Pseudo::mov(source_1, Register::Ax.into(), src_ty),
@@ -395,11 +1385,75 @@ fn convert_binary(
                 Pseudo::mov(result_register.into(), dst, src_ty),
             ]);
         }
+        // unsigned division takes a zeroed high half instead of a sign extended one
+        Binop::UDiv(result_register) => {
+            let dst = literals.operand(&dst);
+
+            instructions.push([
+                Pseudo::mov(source_1, Register::Ax.into(), src_ty),
+                Pseudo::mov(Op::Imm(0).into(), Register::Dx.into(), src_ty),
+                Pseudo::div(source_2, src_ty),
+                Pseudo::mov(result_register.into(), dst, src_ty),
+            ]);
+        }
+    };
+}
+
+// comisd sets the flags like an unsigned comparison, and on NaN like `<` being true, so `<` and
+// `<=` are flipped around into `>` and `>=`, which NaN makes false
+fn convert_double_binary(
+    op: TackyBinary,
+    source_1: PseudoOp,
+    source_2: PseudoOp,
+    dst: PseudoOp,
+    dst_ty: AsmType,
+    instructions: &mut OpVec<Pseudo>,
+) {
+    let (left, right, condition) = match op {
+        TackyBinary::EqualTo | TackyBinary::NotEqual => {
+            let (condition, unordered) = if op == TackyBinary::EqualTo {
+                (CondCode::E, 0)
+            } else {
+                (CondCode::NE, 1)
+            };
+            let operands = (source_2, source_1);
+            return double_equality(condition, unordered, operands, dst, dst_ty, instructions);
+        }
+        TackyBinary::GreaterThan => (source_2, source_1, CondCode::A),
+        TackyBinary::Geq => (source_2, source_1, CondCode::AE),
+        TackyBinary::LessThan => (source_1, source_2, CondCode::A),
+        TackyBinary::Leq => (source_1, source_2, CondCode::AE),
+        _ => {
+            let operator = match op {
+                TackyBinary::Add => Binary::Add,
+                TackyBinary::Subtract => Binary::Sub,
+                TackyBinary::Multiply => Binary::Mult,
+                TackyBinary::Divide => Binary::DivDouble,
+                _ => unreachable!("typecheck only allows arithmetic on doubles"),
+            };
+            instructions.push([
+                Pseudo::mov(source_1, dst.clone(), AsmType::Double),
+                Pseudo::binary(operator, source_2, dst, AsmType::Double),
+            ]);
+            return;
+        }
     };
+    instructions.push([
+        Pseudo::cmp(left, right, AsmType::Double),
+        Pseudo::mov(PseudoOp::imm(0), dst.clone(), dst_ty),
+        Pseudo::SetCC { condition, op: dst },
+    ]);
 }
 
-const fn process_binop(op: TackyBinary) -> Binop {
+const fn process_binop(op: TackyBinary, signed: bool) -> Binop {
     match op {
+        TackyBinary::RightShift if !signed => Binop::Normal(Binary::ShiftRightLogical),
+        TackyBinary::LessThan if !signed => Binop::Relational(CondCode::B),
+        TackyBinary::GreaterThan if !signed => Binop::Relational(CondCode::A),
+        TackyBinary::Leq if !signed => Binop::Relational(CondCode::BE),
+        TackyBinary::Geq if !signed => Binop::Relational(CondCode::AE),
+        TackyBinary::Divide if !signed => Binop::UDiv(Register::Ax),
+        TackyBinary::Remainder if !signed => Binop::UDiv(Register::Dx),
         TackyBinary::Add => Binop::Normal(Binary::Add),
         TackyBinary::Subtract => Binop::Normal(Binary::Sub),
         TackyBinary::Multiply => Binop::Normal(Binary::Mult),
@@ -424,4 +1478,302 @@ enum Binop {
     Relational(CondCode),
     Normal(Binary),
     Div(Register),
+    UDiv(Register),
+}
+
+#[cfg(test)]
+mod test {
+    use super::emit;
+    use crate::codegen::assembly::tacky::{Program as TackyProgram, TopLevel as TackyTL};
+    use crate::codegen::assembly::x86::AsmType;
+    use crate::codegen::assembly::{Binary, Op, Program, Pseudo, PseudoOp, Register, TopLevel};
+    use crate::codegen::assembly::{StaticConstant, StaticInit};
+    use crate::codegen::tacky_pass::lowered;
+
+    // the first function in `source`, before its registers get allocated
+    fn pseudo(source: &[u8]) -> Vec<Pseudo> {
+        let (functions, table) = lowered(source);
+        let program = TackyProgram(functions.into_iter().map(TackyTL::Fn).collect());
+        let (Program(decs), _) = emit(program, table);
+        let Some(TopLevel::Fn(f)) = decs.into_vec().into_iter().next() else {
+            unreachable!()
+        };
+        f.body.into_vec()
+    }
+
+    // the condition codes it sets or jumps on, in order
+    fn conditions(body: &[Pseudo]) -> Vec<String> {
+        (body.iter())
+            .filter_map(|instruction| match instruction {
+                Pseudo::SetCC { condition, .. } | Pseudo::JmpCC { condition, .. } => {
+                    Some(condition.to_string())
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pointers() {
+        let body = pseudo(b"int f(int *p) { int x = *p; *p = x; int *q = &x; return *q; }");
+        // the pointer goes in rax, then whatever it points at is read or written through it
+        let pointed = PseudoOp::Normal(Op::Memory(Register::Ax, 0));
+        let movs: Vec<_> = (body.iter())
+            .filter_map(|instruction| match instruction {
+                Pseudo::Mov { regs, .. } => Some(regs),
+                _ => None,
+            })
+            .collect();
+        let reads = movs.iter().filter(|(src, _)| *src == pointed).count();
+        let writes = movs.iter().filter(|(_, dst)| *dst == pointed).count();
+        assert_eq!((reads, writes), (2, 1));
+        assert!(body.iter().any(|instruction| matches!(
+            instruction,
+            Pseudo::Lea {
+                regs: (PseudoOp::PseudoRegister(_), PseudoOp::PseudoRegister(_))
+            }
+        )));
+    }
+
+    #[test]
+    fn subscripts() {
+        // lea only scales by 1, 2, 4 and 8, and an index that's already a constant long is just a
+        // displacement
+        let body = pseudo(b"int f(int i, int j) { int a[3][5]; return a[i][j] + a[2l][1l]; }");
+        let addresses: Vec<_> = (body.iter())
+            .filter_map(|instruction| match instruction {
+                Pseudo::Lea {
+                    regs: (PseudoOp::Normal(address), _),
+                } => Some(address.clone()),
+                _ => None,
+            })
+            .collect();
+        let indexed = |scale| Op::Indexed {
+            base: Register::Ax,
+            index: Register::Dx,
+            scale,
+        };
+        assert_eq!(
+            addresses,
+            [
+                indexed(1),
+                indexed(4),
+                Op::Memory(Register::Ax, 40),
+                Op::Memory(Register::Ax, 4)
+            ]
+        );
+        assert!(body.iter().any(|instruction| matches!(
+            instruction,
+            Pseudo::Binary {
+                operator: Binary::Mult,
+                regs: (PseudoOp::Normal(Op::Imm(20)), _),
+                ..
+            }
+        )));
+    }
+
+    #[test]
+    fn unsigned() {
+        let source = b"unsigned long f(unsigned a, unsigned b) { return a / b + (a >> 1) + (a < b) + (a >= b); }";
+        let body = pseudo(source);
+        assert!(body
+            .iter()
+            .any(|instruction| matches!(instruction, Pseudo::Div { .. })));
+        assert!(!body
+            .iter()
+            .any(|instruction| matches!(instruction, Pseudo::Idiv { .. })));
+        assert!(body.iter().any(|instruction| matches!(
+            instruction,
+            Pseudo::Binary {
+                operator: Binary::ShiftRightLogical,
+                ..
+            }
+        )));
+        assert_eq!(conditions(&body), ["b", "ae"]);
+        assert!(body.iter().any(|instruction| matches!(
+            instruction,
+            Pseudo::MovZeroExtend {
+                src_ty: AsmType::Longword,
+                dst_ty: AsmType::Quadword,
+                ..
+            }
+        )));
+    }
+
+    #[test]
+    fn double_comparisons() {
+        // comisd says NaN is below everything, so `<` and `<=` get turned around into `>` and
+        // `>=`, which NaN makes false, and the equalities skip setting anything on the parity flag
+        let source =
+            b"int f(double a, double b) { return (a < b) + (a >= b) + (a == b) + (a != b); }";
+        assert_eq!(
+            conditions(&pseudo(source)),
+            ["a", "ae", "p", "e", "p", "ne"]
+        );
+        // NaN is true as a condition, so it skips over the jump for zero
+        let body = pseudo(b"int f(double d) { if (d) return 1; return 0; }");
+        assert_eq!(conditions(&body), ["p", "e"]);
+    }
+
+    #[test]
+    fn double_constants() {
+        // each program gets the constants it uses and no others, however many came before it
+        let constants = |source: &[u8]| {
+            let (functions, table) = lowered(source);
+            let program = TackyProgram(functions.into_iter().map(TackyTL::Fn).collect());
+            let (Program(decs), _) = emit(program, table);
+            (decs.into_vec().into_iter())
+                .filter_map(|dec| match dec {
+                    TopLevel::StaticConstant(StaticConstant {
+                        init: StaticInit::Double(d),
+                        alignment,
+                        ..
+                    }) => Some((d.to_bits(), alignment)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        // even with other programs being emitted alongside it
+        let start = std::sync::Arc::new(std::sync::Barrier::new(8));
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let start = start.clone();
+                std::thread::spawn(move || {
+                    start.wait();
+                    for _ in 0..200 {
+                        let source = format!("double f(void) {{ return {i}.5; }}");
+                        let expected = (f64::from(i) + 0.5).to_bits();
+                        assert_eq!(constants(source.as_bytes()), [(expected, 16)]);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        let negated = constants(b"double g(double x) { return -x; }");
+        assert_eq!(negated, [((-0.0f64).to_bits(), 16)]);
+    }
+
+    #[test]
+    fn double_arguments() {
+        // doubles and integers go in the first of their own kind of register that's left
+        let source = b"double g(double, int, double); double f(double x, long l, double y) { return g(y, 2, x); }";
+        let body = pseudo(source);
+        let call = (body.iter())
+            .position(|instruction| matches!(instruction, Pseudo::Call { .. }))
+            .unwrap();
+        let register = |op: &PseudoOp| match op {
+            PseudoOp::Normal(Op::Register(register)) => Some(*register),
+            _ => None,
+        };
+        let movs = || {
+            body[..call]
+                .iter()
+                .filter_map(|instruction| match instruction {
+                    Pseudo::Mov { regs, .. } => Some(regs),
+                    _ => None,
+                })
+        };
+        let params: Vec<_> = movs().filter_map(|(src, _)| register(src)).collect();
+        let args: Vec<_> = movs().filter_map(|(_, dst)| register(dst)).collect();
+        let expected = [Register::Xmm0, Register::Di, Register::Xmm1];
+        assert_eq!((params, args), (expected.to_vec(), expected.to_vec()));
+    }
+
+    #[test]
+    fn char_arguments() {
+        // chars get widened to ints on the way into a call, however they're signed
+        let body = pseudo(
+            b"int g(char, unsigned char); int f(char c, unsigned char u) { return g(c, u); }",
+        );
+        let widened: Vec<_> = (body.iter())
+            .filter_map(|instruction| match instruction {
+                Pseudo::Movsx {
+                    src_ty: AsmType::Byte,
+                    dst_ty: AsmType::Longword,
+                    regs: (_, PseudoOp::Normal(Op::Register(register))),
+                } => Some(("sign", *register)),
+                Pseudo::MovZeroExtend {
+                    src_ty: AsmType::Byte,
+                    dst_ty: AsmType::Longword,
+                    regs: (_, PseudoOp::Normal(Op::Register(register))),
+                } => Some(("zero", *register)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(widened, [("sign", Register::Di), ("zero", Register::Si)]);
+    }
+
+    #[test]
+    fn variadic_calls() {
+        // al says how many vector registers a variadic function has to save, right before the call
+        let al_before_call = |source: &[u8]| {
+            let body = pseudo(source);
+            (body.windows(2)).find_map(|pair| match pair {
+                [Pseudo::Mov {
+                    regs: (PseudoOp::Normal(Op::Imm(sse)), ax),
+                    ty: AsmType::Longword,
+                }, Pseudo::Call { .. }]
+                    if *ax == PseudoOp::from(Register::Ax) =>
+                {
+                    Some(*sse)
+                }
+                _ => None,
+            })
+        };
+        assert_eq!(
+            al_before_call(b"int g(int, ...); int f(void) { return g(1, 2.0, 3, 4.0); }"),
+            Some(2)
+        );
+        assert_eq!(
+            al_before_call(b"int g(int, ...); int f(void) { return g(1); }"),
+            Some(0)
+        );
+        assert_eq!(
+            al_before_call(b"int g(int, double); int f(void) { return g(1, 2.0); }"),
+            None
+        );
+    }
+
+    #[test]
+    fn va_arg() {
+        // an offset past 40 means the six general purpose registers in the save area are used up,
+        // past 160 the eight vector ones are, and the argument comes off the stack instead
+        let source = b"double f(int n, ...) { __builtin_va_list ap; __builtin_va_start(ap, n); \
+                       return __builtin_va_arg(ap, int) + __builtin_va_arg(ap, double); }";
+        let body = pseudo(source);
+        let limits: Vec<_> = (body.windows(2))
+            .filter_map(|pair| match pair {
+                [Pseudo::Cmp {
+                    regs: (PseudoOp::Normal(Op::Imm(limit)), _),
+                    ..
+                }, Pseudo::JmpCC { .. }] => Some(*limit),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(limits, [40, 160]);
+    }
+
+    #[test]
+    fn function_pointers() {
+        // functions defined in this file are at a fixed offset from rip, anything else has to come
+        // out of the global offset table
+        let source = b"int g(int x); int h(int x); \
+                       int f(int i) { int (*p)(int) = i ? g : h; return p(i); } \
+                       int g(int x) { return x; }";
+        let body = pseudo(source);
+        assert!(body.iter().any(|instruction| matches!(
+            instruction,
+            Pseudo::Lea { regs: (PseudoOp::Normal(Op::Data(name, 0)), _) } if name.as_ref() == b"g"
+        )));
+        assert!(body.iter().any(|instruction| matches!(
+            instruction,
+            Pseudo::Mov { regs: (PseudoOp::Normal(Op::Got(name)), _), .. } if name.as_ref() == b"h"
+        )));
+        assert!(body.iter().any(|instruction| matches!(
+            instruction,
+            Pseudo::CallIndirect(PseudoOp::PseudoRegister(_))
+        )));
+    }
 }
@@ -0,0 +1,85 @@
+use crate::parse::VarType;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Class {
+    Integer,
+    Sse,
+    Memory,
+}
+
+// system v splits a struct into eightbytes, an eightbyte holding nothing but doubles goes in an
+// xmm register and anything else in a general purpose one. structs bigger than two eightbytes
+// are passed in memory instead
+pub fn classify(ty: VarType) -> Vec<Class> {
+    let size = ty.size();
+    let eightbytes = size.div_ceil(8);
+    if size > 16 {
+        return vec![Class::Memory; eightbytes];
+    }
+    let mut classes = vec![Class::Sse; eightbytes];
+    scalars(ty, 0, &mut |offset, scalar| {
+        if scalar != VarType::Double {
+            classes[offset / 8] = Class::Integer;
+        }
+    });
+    classes
+}
+
+// every scalar in a type along with where it starts, nothing naturally aligned straddles two
+// eightbytes so that's all we need to know
+fn scalars(ty: VarType, offset: usize, f: &mut impl FnMut(usize, VarType)) {
+    let ty = ty.unqualified();
+    match ty {
+        VarType::Array(elem, len) => {
+            for i in 0..len {
+                scalars(*elem, offset + i * elem.size(), f);
+            }
+        }
+        VarType::Struct(_) | VarType::Union(_) => {
+            let layout = ty
+                .layout()
+                .expect("only complete structs get passed around");
+            for member in &layout.members {
+                scalars(member.typ, offset + member.offset, f);
+            }
+        }
+        scalar => f(offset, scalar),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lex::Span;
+    use crate::parse::Layout;
+
+    fn define(tag: &'static str, members: &[VarType]) -> VarType {
+        let members = members
+            .iter()
+            .enumerate()
+            .map(|(i, typ)| (i.to_string().into_bytes().into(), *typ));
+        Layout::define(tag, Layout::new(members, false, Span::default()));
+        VarType::Struct(tag)
+    }
+
+    #[test]
+    fn eightbytes() {
+        let mixed = define(
+            "abi_mixed.0",
+            &[VarType::Double, VarType::Int, VarType::Char],
+        );
+        assert_eq!(classify(mixed), [Class::Sse, Class::Integer]);
+
+        let doubles = define("abi_doubles.0", &[VarType::Double, VarType::Double]);
+        assert_eq!(classify(doubles), [Class::Sse, Class::Sse]);
+
+        let packed = define(
+            "abi_packed.0",
+            &[VarType::Int, VarType::Char, VarType::Char],
+        );
+        assert_eq!(classify(packed), [Class::Integer]);
+
+        let big = define("abi_big.0", &[VarType::Long, VarType::Long, VarType::Char]);
+        assert_eq!(classify(big), [Class::Memory; 3]);
+    }
+}
@@ -0,0 +1,548 @@
+// graph coloring register allocation, with conservative coalescing of moves: every scalar
+// pseudoregister that never has its address taken gets a hardware register unless there aren't
+// enough to go around, then x86_pass gives it a stack slot like before
+use super::assembly::x86::{Access, AsmType};
+use super::assembly::TopLevel;
+use super::assembly::{BackendSymbol, Op, Program, Pseudo, PseudoOp, Register, SymbolTable};
+use super::cfg::{self, Exit, Flow, Graph, Liveness, Operands};
+use super::Identifier;
+use std::collections::{HashMap, HashSet};
+
+// r10, r11, xmm14 and xmm15 stay free for x86_pass to fix up operands with, and the callee saved
+// registers come last so they only get saved when they're needed
+const GENERAL: [Register; 12] = [
+    Register::Ax,
+    Register::Cx,
+    Register::Dx,
+    Register::Si,
+    Register::Di,
+    Register::R8,
+    Register::R9,
+    Register::Bx,
+    Register::R12,
+    Register::R13,
+    Register::R14,
+    Register::R15,
+];
+
+const SSE: [Register; 14] = [
+    Register::Xmm0,
+    Register::Xmm1,
+    Register::Xmm2,
+    Register::Xmm3,
+    Register::Xmm4,
+    Register::Xmm5,
+    Register::Xmm6,
+    Register::Xmm7,
+    Register::Xmm8,
+    Register::Xmm9,
+    Register::Xmm10,
+    Register::Xmm11,
+    Register::Xmm12,
+    Register::Xmm13,
+];
+
+// al tells a variadic function how many xmm registers it got
+pub const ARGUMENTS: [Register; 15] = [
+    Register::Di,
+    Register::Si,
+    Register::Dx,
+    Register::Cx,
+    Register::R8,
+    Register::R9,
+    Register::Ax,
+    Register::Xmm0,
+    Register::Xmm1,
+    Register::Xmm2,
+    Register::Xmm3,
+    Register::Xmm4,
+    Register::Xmm5,
+    Register::Xmm6,
+    Register::Xmm7,
+];
+
+pub const RETURNS: [Register; 4] = [Register::Ax, Register::Dx, Register::Xmm0, Register::Xmm1];
+
+pub fn allocate(
+    program: &mut Program<Pseudo>,
+    table: &SymbolTable,
+    volatile: &HashSet<Identifier>,
+) {
+    for top_level in program.0.iter_mut() {
+        if let TopLevel::Fn(function) = top_level {
+            let mut body = std::mem::take(&mut function.body).into_vec();
+            allocate_function(&mut body, table, volatile);
+            function.body = body.into();
+        }
+    }
+}
+
+fn allocate_function(body: &mut Vec<Pseudo>, table: &SymbolTable, volatile: &HashSet<Identifier>) {
+    let (mut order, mut types) = candidates(body, table, volatile);
+    loop {
+        let mut graph = Interference::new(body, &types);
+        let merged = coalesce(body, &types, &mut graph);
+        if merged.is_empty() {
+            let assigned = color(&order, &types, &graph);
+            replace(body, |name| {
+                assigned.get(name).map(|r| PseudoOp::register(*r))
+            });
+            body.retain(|instruction| !matches!(instruction, Pseudo::Mov { regs: (src, dst), .. } if src == dst));
+            return;
+        }
+        replace(body, |name| {
+            match find(&merged, Node::Pseudo(name.clone())) {
+                Node::Hard(register) => Some(PseudoOp::register(register)),
+                Node::Pseudo(other) => Some(PseudoOp::PseudoRegister(other)),
+            }
+        });
+        // coalesced moves stay until the end, they're how calls and returns show what registers
+        // they read
+        order.retain(|name| !merged.contains_key(name));
+        types.retain(|name, _| !merged.contains_key(name));
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Node {
+    Hard(Register),
+    Pseudo(Identifier),
+}
+
+fn allocatable(register: Register) -> bool {
+    GENERAL.contains(&register) || SSE.contains(&register)
+}
+
+fn node(op: &PseudoOp, types: &HashMap<Identifier, AsmType>) -> Option<Node> {
+    match op {
+        PseudoOp::PseudoRegister(name) if types.contains_key(name) => {
+            Some(Node::Pseudo(name.clone()))
+        }
+        PseudoOp::Normal(Op::Register(r)) if allocatable(*r) => Some(Node::Hard(*r)),
+        _ => None,
+    }
+}
+
+fn is_sse(node: &Node, types: &HashMap<Identifier, AsmType>) -> bool {
+    match node {
+        Node::Hard(register) => register.is_xmm(),
+        Node::Pseudo(name) => types[name] == AsmType::Double,
+    }
+}
+
+const fn colors(sse: bool) -> &'static [Register] {
+    if sse {
+        &SSE
+    } else {
+        &GENERAL
+    }
+}
+
+// scalars local to the function whose address nothing takes, in the order they first show up so
+// the same program always gets the same registers
+fn candidates(
+    body: &[Pseudo],
+    table: &SymbolTable,
+    volatile: &HashSet<Identifier>,
+) -> (Vec<Identifier>, HashMap<Identifier, AsmType>) {
+    let mut aliased = HashSet::new();
+    for instruction in body {
+        if let Pseudo::Lea {
+            regs: (PseudoOp::PseudoRegister(name), _),
+        } = instruction
+        {
+            aliased.insert(name);
+        }
+    }
+    let mut order = Vec::new();
+    let mut types = HashMap::new();
+    for op in body.iter().flat_map(Pseudo::operands) {
+        let PseudoOp::PseudoRegister(name) = op else {
+            continue;
+        };
+        let Some(BackendSymbol::Obj {
+            ty,
+            is_static: false,
+        }) = table.get(name)
+        else {
+            continue;
+        };
+        if !matches!(ty, AsmType::ByteArray { .. })
+            && !aliased.contains(name)
+            && !volatile.contains(name)
+            && !types.contains_key(name)
+        {
+            order.push(name.clone());
+            types.insert(name.clone(), *ty);
+        }
+    }
+    (order, types)
+}
+
+// what each instruction reads and writes, besides its operands that's whatever registers it uses
+// implicitly, calls write every register they don't have to save and read the argument registers
+// set up for them
+struct Locations<'a> {
+    types: &'a HashMap<Identifier, AsmType>,
+    // the argument or return registers written since the start of the block, by call or return
+    passed: HashMap<usize, Vec<Register>>,
+}
+
+impl<'a> Locations<'a> {
+    fn new(body: &[Pseudo], types: &'a HashMap<Identifier, AsmType>) -> Self {
+        let mut locations = Self {
+            types,
+            passed: HashMap::new(),
+        };
+        let mut written = Vec::new();
+        for (i, instruction) in body.iter().enumerate() {
+            let reads: &[Register] = match instruction {
+                Pseudo::Call { .. } | Pseudo::CallIndirect(_) => &ARGUMENTS,
+                Pseudo::Ret => &RETURNS,
+                _ if instruction.label().is_some()
+                    || !matches!(instruction.exit(), Exit::Falls) =>
+                {
+                    written.clear();
+                    continue;
+                }
+                _ => {
+                    let (_, defs) = locations.access(i, instruction);
+                    written.extend(defs.into_iter().filter_map(|def| match def {
+                        Node::Hard(register) => Some(register),
+                        Node::Pseudo(_) => None,
+                    }));
+                    continue;
+                }
+            };
+            let passed = (written.drain(..))
+                .filter(|register| reads.contains(register))
+                .collect();
+            locations.passed.insert(i, passed);
+        }
+        locations
+    }
+
+    fn access(&self, index: usize, instruction: &Pseudo) -> (Vec<Node>, Vec<Node>) {
+        let mut uses = Vec::new();
+        let mut defs = Vec::new();
+        for access in instruction.accesses() {
+            match access {
+                Access::Read(op) => self.read(op, &mut uses),
+                // a byte pseudoregister doesn't have anything else in it
+                Access::Write(op) | Access::WriteByte(op) => self.write(op, &mut uses, &mut defs),
+                Access::Address(op) => self.address(op, &mut uses),
+                Access::ReadRegister(register) => uses.push(Node::Hard(register)),
+                Access::WriteRegister(register) => defs.push(Node::Hard(register)),
+                Access::Arguments | Access::Returns => uses.extend(self.passed(index)),
+                Access::CallerSaved => defs.extend(
+                    (GENERAL.iter().chain(&SSE))
+                        .filter(|register| !Register::CALLEE_SAVED.contains(register))
+                        .map(|register| Node::Hard(*register)),
+                ),
+                Access::ReadFlags | Access::WriteFlags => (),
+            }
+        }
+        (uses, defs)
+    }
+
+    fn passed(&self, index: usize) -> impl Iterator<Item = Node> + '_ {
+        (self.passed.get(&index).into_iter().flatten()).map(|register| Node::Hard(*register))
+    }
+
+    fn read(&self, op: &PseudoOp, uses: &mut Vec<Node>) {
+        match node(op, self.types) {
+            Some(node) => uses.push(node),
+            None => self.address(op, uses),
+        }
+    }
+
+    fn write(&self, op: &PseudoOp, uses: &mut Vec<Node>, defs: &mut Vec<Node>) {
+        match node(op, self.types) {
+            Some(node) => defs.push(node),
+            None => self.address(op, uses),
+        }
+    }
+
+    // the registers an operand in memory reads to find its address
+    fn address(&self, op: &PseudoOp, uses: &mut Vec<Node>) {
+        let registers = match op {
+            PseudoOp::Normal(Op::Memory(base, _)) => vec![*base],
+            PseudoOp::Normal(Op::Indexed { base, index, .. }) => vec![*base, *index],
+            _ => Vec::new(),
+        };
+        uses.extend(
+            (registers.into_iter())
+                .filter(|register| allocatable(*register))
+                .map(Node::Hard),
+        );
+    }
+}
+
+impl Operands<Pseudo> for Locations<'_> {
+    type Var = Node;
+
+    fn uses(&self, index: usize, instruction: &Pseudo) -> Vec<Node> {
+        self.access(index, instruction).0
+    }
+
+    fn defs(&self, index: usize, instruction: &Pseudo) -> Vec<Node> {
+        self.access(index, instruction).1
+    }
+}
+
+// which nodes can't share a register, along with how often each candidate shows up, since
+// that's what spilling it costs
+struct Interference {
+    neighbors: HashMap<Node, HashSet<Node>>,
+    uses: HashMap<Identifier, usize>,
+}
+
+impl Interference {
+    // anything an instruction writes interferes with everything live after it, except the source
+    // of a move, which holds the same value
+    fn new(body: &[Pseudo], types: &HashMap<Identifier, AsmType>) -> Self {
+        let liveness = Liveness::new(Locations::new(body, types), HashSet::new());
+        let graph = Graph::new(body);
+        let solution = cfg::solve(&graph, body, &liveness);
+        let locations = &liveness.operands;
+
+        let mut neighbors: HashMap<Node, HashSet<Node>> = (GENERAL.iter().chain(&SSE))
+            .map(|register| Node::Hard(*register))
+            .chain(types.keys().map(|name| Node::Pseudo(name.clone())))
+            .map(|node| (node, HashSet::new()))
+            .collect();
+        let mut uses = HashMap::new();
+        for (block, mut live) in graph.blocks.iter().zip(solution.after) {
+            for i in block.instructions.clone().rev() {
+                let (reads, writes) = locations.access(i, &body[i]);
+                let moved = match &body[i] {
+                    Pseudo::Mov { regs: (src, _), .. } => node(src, types),
+                    _ => None,
+                };
+                for def in &writes {
+                    for other in &live {
+                        if other != def
+                            && Some(other) != moved.as_ref()
+                            && is_sse(other, types) == is_sse(def, types)
+                        {
+                            neighbors.get_mut(def).unwrap().insert(other.clone());
+                            neighbors.get_mut(other).unwrap().insert(def.clone());
+                        }
+                    }
+                }
+                for node in reads.iter().chain(&writes) {
+                    if let Node::Pseudo(name) = node {
+                        *uses.entry(name.clone()).or_insert(0) += 1;
+                    }
+                }
+                for def in &writes {
+                    live.remove(def);
+                }
+                live.extend(reads);
+            }
+        }
+        Self { neighbors, uses }
+    }
+
+    // george's test when one side is a register: everything next to the candidate already
+    // interferes with the register or is easy to color anyway, and briggs' test otherwise: the
+    // merged node has fewer than k neighbors that are hard to color
+    fn conservative(&self, keep: &Node, gone: &Node, k: usize) -> bool {
+        let significant = |node: &Node| match node {
+            Node::Hard(_) => true,
+            Node::Pseudo(_) => self.neighbors[node].len() >= k,
+        };
+        match keep {
+            Node::Hard(_) => self.neighbors[gone]
+                .iter()
+                .all(|node| self.neighbors[keep].contains(node) || !significant(node)),
+            Node::Pseudo(_) => {
+                let merged: HashSet<_> =
+                    self.neighbors[keep].union(&self.neighbors[gone]).collect();
+                merged.into_iter().filter(|node| significant(node)).count() < k
+            }
+        }
+    }
+
+    fn merge(&mut self, keep: &Node, gone: &Node) {
+        for neighbor in self.neighbors.remove(gone).unwrap() {
+            let others = self.neighbors.get_mut(&neighbor).unwrap();
+            others.remove(gone);
+            others.insert(keep.clone());
+            self.neighbors.get_mut(keep).unwrap().insert(neighbor);
+        }
+    }
+}
+
+fn find(merged: &HashMap<Identifier, Node>, mut node: Node) -> Node {
+    while let Node::Pseudo(name) = &node {
+        match merged.get(name) {
+            Some(next) => node = next.clone(),
+            None => break,
+        }
+    }
+    node
+}
+
+// a move whose sides don't interfere can use the same register for both and disappear, as long
+// as that can't make coloring any harder, a candidate merged into a register just becomes it
+fn coalesce(
+    body: &[Pseudo],
+    types: &HashMap<Identifier, AsmType>,
+    graph: &mut Interference,
+) -> HashMap<Identifier, Node> {
+    let mut merged = HashMap::new();
+    for instruction in body {
+        let Pseudo::Mov {
+            ty,
+            regs: (src, dst),
+        } = instruction
+        else {
+            continue;
+        };
+        let (Some(src), Some(dst)) = (node(src, types), node(dst, types)) else {
+            continue;
+        };
+        // a move of part of a candidate leaves the other side holding something else
+        let resized = |node: &Node| matches!(node, Node::Pseudo(name) if types[name] != *ty);
+        if resized(&src) || resized(&dst) || is_sse(&src, types) != is_sse(&dst, types) {
+            continue;
+        }
+        let (keep, gone) = match (find(&merged, src), find(&merged, dst)) {
+            (a, b) if a == b => continue,
+            (Node::Hard(_), Node::Hard(_)) => continue,
+            (hard @ Node::Hard(_), Node::Pseudo(name))
+            | (Node::Pseudo(name), hard @ Node::Hard(_)) => (hard, name),
+            (keep @ Node::Pseudo(_), Node::Pseudo(name)) => (keep, name),
+        };
+        let gone_node = Node::Pseudo(gone.clone());
+        let k = colors(is_sse(&keep, types)).len();
+        if graph.neighbors[&keep].contains(&gone_node) || !graph.conservative(&keep, &gone_node, k)
+        {
+            continue;
+        }
+        graph.merge(&keep, &gone_node);
+        merged.insert(gone, keep);
+    }
+    merged
+}
+
+// simplify then select: anything with fewer neighbors left than there are registers can always
+// get one, so it goes on the stack to color last, and when nothing's left like that the cheapest
+// candidate to spill goes on anyway, in case its neighbors end up sharing registers
+fn color(
+    order: &[Identifier],
+    types: &HashMap<Identifier, AsmType>,
+    graph: &Interference,
+) -> HashMap<Identifier, Register> {
+    let neighbors = |name: &Identifier| &graph.neighbors[&Node::Pseudo(name.clone())];
+    let k = |name: &Identifier| colors(types[name] == AsmType::Double).len();
+    let degree = |name: &Identifier, left: &HashSet<&Identifier>| {
+        (neighbors(name).iter())
+            .filter(|node| match node {
+                Node::Hard(_) => true,
+                Node::Pseudo(other) => left.contains(other),
+            })
+            .count()
+    };
+    let cost = |name: &Identifier| graph.uses.get(name).copied().unwrap_or(0);
+
+    let mut remaining: Vec<&Identifier> = order.iter().collect();
+    let mut left: HashSet<&Identifier> = order.iter().collect();
+    let mut stack = Vec::with_capacity(order.len());
+    while !remaining.is_empty() {
+        let next = (remaining.iter())
+            .position(|name| degree(name, &left) < k(name))
+            .unwrap_or_else(|| {
+                (0..remaining.len())
+                    .min_by(|&a, &b| {
+                        let (a, b) = (remaining[a], remaining[b]);
+                        (cost(a) * degree(b, &left)).cmp(&(cost(b) * degree(a, &left)))
+                    })
+                    .unwrap()
+            });
+        let name = remaining.remove(next);
+        left.remove(name);
+        stack.push(name);
+    }
+
+    let mut assigned = HashMap::new();
+    while let Some(name) = stack.pop() {
+        let taken: HashSet<Register> = (neighbors(name).iter())
+            .filter_map(|node| match node {
+                Node::Hard(register) => Some(*register),
+                Node::Pseudo(other) => assigned.get(other).copied(),
+            })
+            .collect();
+        let free = colors(types[name] == AsmType::Double)
+            .iter()
+            .find(|register| !taken.contains(register));
+        if let Some(register) = free {
+            assigned.insert(name.clone(), *register);
+        }
+    }
+    assigned
+}
+
+fn replace(body: &mut [Pseudo], with: impl Fn(&Identifier) -> Option<PseudoOp>) {
+    for op in body.iter_mut().flat_map(Pseudo::operands_mut) {
+        if let PseudoOp::PseudoRegister(name) = op
+            && let Some(new) = with(name)
+        {
+            *op = new;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::allocate_function;
+    use crate::codegen::assembly::x86::{pseudo_regs as reg, AsmType};
+    use crate::codegen::assembly::SymbolTable;
+    use crate::codegen::assembly::{BackendSymbol, Binary, Pseudo, PseudoOp, Register};
+    use crate::codegen::Identifier;
+    use std::collections::HashSet;
+
+    #[test]
+    fn coalesce_around_call() {
+        let var = |name: &str| PseudoOp::PseudoRegister(Identifier::from(name));
+        let table: SymbolTable = ["a", "b", "c"]
+            .into_iter()
+            .map(|name| {
+                let symbol = BackendSymbol::Obj {
+                    ty: AsmType::Longword,
+                    is_static: false,
+                };
+                (Identifier::from(name), symbol)
+            })
+            .collect();
+        let ty = AsmType::Longword;
+        let call = || Pseudo::Call {
+            name: Identifier::from("f"),
+            defined: true,
+        };
+        let mut body = vec![
+            Pseudo::mov(reg::DI, var("a"), ty),
+            Pseudo::mov(var("a"), var("b"), ty),
+            Pseudo::binary(Binary::Add, PseudoOp::imm(1), var("b"), ty),
+            call(),
+            Pseudo::mov(reg::AX, var("c"), ty),
+            Pseudo::binary(Binary::Add, var("b"), var("c"), ty),
+            Pseudo::mov(var("c"), reg::AX, ty),
+            Pseudo::Ret,
+        ];
+        allocate_function(&mut body, &table, &HashSet::new());
+
+        // a and c become the registers they're moved from and to, b lives across the call so it
+        // needs one the callee saves
+        let bx = PseudoOp::register(Register::Bx);
+        let expected = [
+            Pseudo::mov(reg::DI, bx.clone(), ty),
+            Pseudo::binary(Binary::Add, PseudoOp::imm(1), bx.clone(), ty),
+            call(),
+            Pseudo::binary(Binary::Add, bx, reg::AX, ty),
+            Pseudo::Ret,
+        ];
+        assert_eq!(format!("{body:?}"), format!("{expected:?}"));
+    }
+}
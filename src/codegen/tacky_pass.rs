@@ -11,11 +11,11 @@ use assembly::tacky::Value;
 use assembly::OpVec;
 
 use crate::semantics;
-use assembly::tacky::StaticVar;
+use assembly::tacky::{StaticConstant, StaticVar};
 use parse::inc_dec::*;
 use parse::VarType;
 use semantics::typed::{
-    self, Block, BlockItem, Dec, Expr, Fix, FnDec, ForInit, Label, Stmnt, VarDec,
+    self, Block, BlockItem, Dec, Expr, Fix, FnDec, ForInit, Initializer, Label, Stmnt, VarDec,
 };
 use semantics::Attr;
 
@@ -30,32 +30,43 @@ static TEMP_COUNT: AtomicUsize = AtomicUsize::new(0);
 
 static LABEL_COUNT: AtomicUsize = AtomicUsize::new(0);
 
+// what void expressions evaluate to, typecheck makes sure nothing ever reads it
+const VOID: Value = Value::Constant(Constant::Int(0));
+
 pub fn emit(program: typed::Program, symbol_table: &mut SymbolTable) -> Program {
     let mut tlvs = Vec::with_capacity(program.len());
     for dec in program {
-        if let Dec::Fn(f) = dec {
-            if let Some(f) = convert_function(f, symbol_table) {
-                tlvs.push(TopLevel::Fn(f));
-            }
+        if let Dec::Fn(f) = dec
+            && let Some(f) = convert_function(f, symbol_table)
+        {
+            tlvs.push(TopLevel::Fn(f));
         }
     }
 
     tlvs.reverse();
 
     for (name, attr) in symbol_table {
-        if let Attr::Static {
-            init: Some(init),
-            global: g,
-            typ: t,
-        } = attr
-        {
-            let init = init.get_static(*t);
-            tlvs.push(TopLevel::StaticVar(StaticVar {
+        match attr {
+            Attr::Static {
+                init: Some(init),
+                global: g,
+                typ: t,
+                ..
+            } => {
+                let init = init.get_static(*t);
+                tlvs.push(TopLevel::StaticVar(StaticVar {
+                    name: name.clone(),
+                    global: *g,
+                    init,
+                    typ: *t,
+                }))
+            }
+            Attr::Constant { typ, init } => tlvs.push(TopLevel::StaticConstant(StaticConstant {
                 name: name.clone(),
-                global: *g,
-                init,
-                typ: *t,
-            }))
+                typ: *typ,
+                init: *init,
+            })),
+            _ => (),
         }
     }
 
@@ -70,12 +81,15 @@ fn convert_function(
         body,
         typ: _,
         sc,
+        ..
     }: FnDec,
     table: &mut SymbolTable,
 ) -> Option<FunctionDefinition> {
     let mut body_ops = OpVec::new();
     convert_block(body?, &mut body_ops, &name, table);
-    body_ops.push_one(Instruction::Return(Value::Constant(Constant::Long(0))));
+    // falling off the end of main returns 0, anywhere else there's no value to return
+    let ret = (name.as_ref() == b"main").then_some(Value::Constant(Constant::Int(0)));
+    body_ops.push_one(Instruction::Return(ret));
 
     Some(FunctionDefinition {
         name: name.clone(),
@@ -194,12 +208,12 @@ fn convert_statement(
                 Instruction::Label(r#break),
             ])
         }
-        Stmnt::Break(label) => {
+        Stmnt::Break(label, _) => {
             instructions.push_one(Instruction::Jump {
                 target: label.r#break(),
             });
         }
-        Stmnt::Continue(label) => {
+        Stmnt::Continue(label, _) => {
             instructions.push_one(Instruction::Jump {
                 target: label.r#continue(),
             });
@@ -207,8 +221,8 @@ fn convert_statement(
 
         Stmnt::Compound(block) => convert_block(block, instructions, fn_name, table),
 
-        Stmnt::Ret(e) => {
-            let result = convert_expression(e, instructions, table);
+        Stmnt::Ret(e, _) => {
+            let result = e.map(|e| convert_expression(e, instructions, table));
             instructions.push_one(Instruction::Return(result));
         }
         Stmnt::Null => {}
@@ -255,20 +269,23 @@ fn convert_statement(
         Stmnt::Label {
             name: Label::Named(name),
             body,
+            ..
         } => {
             instructions.push_one(Instruction::Label(named_label(&name, fn_name)));
             convert_statement(*body, instructions, fn_name, table);
         }
         Stmnt::Label {
-            name: Label::Case { c, id },
+            name: Label::Case { index, id },
             body,
+            ..
         } => {
-            instructions.push_one(Instruction::Label(id.case(c)));
+            instructions.push_one(Instruction::Label(id.case(index)));
             convert_statement(*body, instructions, fn_name, table);
         }
         Stmnt::Label {
             name: Label::Default(id),
             body,
+            ..
         } => {
             instructions.push_one(Instruction::Label(id.default()));
             convert_statement(*body, instructions, fn_name, table);
@@ -282,7 +299,7 @@ fn convert_statement(
         } => {
             let end_label = label.labels().r#break;
             let switch_val = convert_expression(val, instructions, table);
-            for case in cases {
+            for (index, case) in cases.into_iter().enumerate() {
                 let target_var = new_var(VarType::Int, table);
                 instructions.push([
                     // if val == case
@@ -295,7 +312,7 @@ fn convert_statement(
                     // jump to its label
                     Instruction::JumpIfNotZero {
                         condition: Value::Var(target_var),
-                        target: label.case(case),
+                        target: label.case(index),
                     },
                 ]);
             }
@@ -309,7 +326,7 @@ fn convert_statement(
             convert_statement(*body, instructions, fn_name, table);
             instructions.push_one(Instruction::Label(end_label));
         }
-        Stmnt::Goto(label) => instructions.push_one(Instruction::Jump {
+        Stmnt::Goto(label, _) => instructions.push_one(Instruction::Jump {
             target: named_label(&label, fn_name),
         }),
     }
@@ -317,14 +334,24 @@ fn convert_statement(
 
 fn convert_cast_op(
     ty: VarType,
+    from: VarType,
     src: Value,
     d: Value,
     instructions: &mut OpVec<Instruction>,
 ) -> Value {
+    use std::cmp::Ordering;
     let dst = d.clone();
-    let op = match ty {
-        VarType::Int => Instruction::Truncate { src, dst },
-        VarType::Long => Instruction::SignExtend { src, dst },
+    let op = match (from, ty) {
+        (VarType::Double, ty) if ty.signed() => Instruction::DoubleToInt { src, dst },
+        (VarType::Double, _) => Instruction::DoubleToUInt { src, dst },
+        (from, VarType::Double) if from.signed() => Instruction::IntToDouble { src, dst },
+        (_, VarType::Double) => Instruction::UIntToDouble { src, dst },
+        _ => match ty.size().cmp(&from.size()) {
+            Ordering::Equal => Instruction::Copy { src, dst },
+            Ordering::Greater if from.signed() => Instruction::SignExtend { src, dst },
+            Ordering::Greater => Instruction::ZeroExtend { src, dst },
+            Ordering::Less => Instruction::Truncate { src, dst },
+        },
     };
     instructions.push_one(op);
     d
@@ -337,12 +364,17 @@ fn convert_cast(
 ) -> Value {
     match (target, exp, ty) {
         (target, exp, _) if target == exp.ty() => convert_expression(exp, instructions, table),
+        (VarType::Void, exp, _) => {
+            convert_expression(exp, instructions, table);
+            VOID
+        }
         (_, exp, ty) => {
+            let from = exp.ty();
             let src = convert_expression(exp, instructions, table);
             let dst = Value::Var(new_var(ty, r#table));
             // add dst to symbol table
             //
-            convert_cast_op(ty, src, dst, instructions)
+            convert_cast_op(ty, from, src, dst, instructions)
         }
     }
 }
@@ -352,14 +384,252 @@ fn convert_vardec(dec: VarDec, instructions: &mut OpVec<Instruction>, table: &mu
         name,
         init: Some(init),
         sc: None | Some(StorageClass::Extern),
-        typ: _,
+        typ,
+        ..
     } = dec
     {
-        let result = convert_expression(init, instructions, table);
-        instructions.push_one(Instruction::Copy {
-            src: result,
-            dst: Value::Var(name),
-        })
+        convert_initializer(name, init, typ, instructions, table);
+    }
+}
+
+fn convert_initializer(
+    name: Identifier,
+    init: Initializer,
+    typ: VarType,
+    instructions: &mut OpVec<Instruction>,
+    table: &mut SymbolTable,
+) {
+    match init {
+        Initializer::Single(exp) if !typ.is_array() => {
+            let result = convert_expression(exp, instructions, table);
+            instructions.push_one(Instruction::Copy {
+                src: result,
+                dst: Value::Var(name),
+            })
+        }
+        init => convert_compound_init(&name, init, typ, 0, instructions, table),
+    }
+}
+
+// arrays get initialized one element at a time, structs one member at a time
+fn convert_compound_init(
+    name: &Identifier,
+    init: Initializer,
+    typ: VarType,
+    offset: i64,
+    instructions: &mut OpVec<Instruction>,
+    table: &mut SymbolTable,
+) {
+    match init {
+        Initializer::Single(Expr::String { value, ty, .. }) => {
+            convert_string_init(name, &value, ty.size(), offset, instructions)
+        }
+        Initializer::Single(exp) => {
+            let src = convert_expression(exp, instructions, table);
+            instructions.push_one(Instruction::CopyToOffset {
+                src,
+                dst: name.clone(),
+                offset,
+            });
+        }
+        Initializer::Compound(inits, _) if typ.is_struct() => {
+            let layout = typ.layout().expect("declarations are complete");
+            for (init, member) in inits.into_iter().zip(&layout.members) {
+                let offset = offset + member.offset as i64;
+                convert_compound_init(name, init, member.typ, offset, instructions, table);
+            }
+        }
+        Initializer::Compound(inits, _) => {
+            let elem = typ
+                .element()
+                .expect("typecheck only allows braces for aggregates");
+            let size = elem.size() as i64;
+            for (i, init) in inits.into_iter().enumerate() {
+                let offset = offset + i as i64 * size;
+                convert_compound_init(name, init, elem, offset, instructions, table);
+            }
+        }
+        Initializer::Designated(..) => unreachable!("typecheck puts initializers in order"),
+    }
+}
+
+// a char array initialized from a string, copied in as many bytes at a time as we can, with
+// zeroes for whatever the string doesn't cover
+fn convert_string_init(
+    name: &Identifier,
+    value: &[u8],
+    len: usize,
+    offset: i64,
+    instructions: &mut OpVec<Instruction>,
+) {
+    let mut bytes = value.to_vec();
+    bytes.resize(len, 0);
+    let mut i = 0;
+    while i < len {
+        let rest = &bytes[i..];
+        let (constant, size) = match rest.len() {
+            8.. => (
+                Constant::Long(i64::from_le_bytes(rest[..8].try_into().unwrap())),
+                8,
+            ),
+            4.. => (
+                Constant::Int(i32::from_le_bytes(rest[..4].try_into().unwrap())),
+                4,
+            ),
+            _ => (Constant::Char(rest[0] as i8), 1),
+        };
+        instructions.push_one(Instruction::CopyToOffset {
+            src: Value::Constant(constant),
+            dst: name.clone(),
+            offset: offset + i as i64,
+        });
+        i += size;
+    }
+}
+
+// `ptr + index`, where index is counted in whatever `ptr` points at
+fn convert_add_ptr(
+    ptr: Value,
+    index: Value,
+    ptr_ty: VarType,
+    dst: Value,
+    instructions: &mut OpVec<Instruction>,
+) -> Value {
+    let scale = ptr_ty.element().expect("only pointers are offset").size() as i64;
+    instructions.push_one(Instruction::AddPtr {
+        ptr,
+        index,
+        scale,
+        dst: dst.clone(),
+    });
+    dst
+}
+
+fn negate(index: Value, instructions: &mut OpVec<Instruction>, table: &mut SymbolTable) -> Value {
+    match index {
+        Value::Constant(c) => Value::Constant(Constant::Long(-c.long())),
+        index => {
+            let dst = new_var(VarType::Long, table);
+            instructions.push_one(Instruction::Unary {
+                op: parse::UnOp::Negate,
+                source: index,
+                dst: dst.clone(),
+            });
+            Value::Var(dst)
+        }
+    }
+}
+
+// an lvalue is either a plain variable, whatever a pointer points at, or a member somewhere
+// inside a struct variable
+enum ExpResult {
+    Plain(Value),
+    Deref(Value),
+    SubObject(Identifier, i64),
+}
+
+impl ExpResult {
+    // the current value, loaded into a temporary if it's behind a pointer
+    fn read(
+        &self,
+        ty: VarType,
+        instructions: &mut OpVec<Instruction>,
+        table: &mut SymbolTable,
+    ) -> Value {
+        match self {
+            Self::Plain(v) => v.clone(),
+            Self::Deref(ptr) => {
+                let dst = Value::Var(new_var(ty, table));
+                instructions.push_one(Instruction::Load {
+                    src_ptr: ptr.clone(),
+                    dst: dst.clone(),
+                });
+                dst
+            }
+            Self::SubObject(base, offset) => {
+                let dst = Value::Var(new_var(ty, table));
+                instructions.push_one(Instruction::CopyFromOffset {
+                    src: base.clone(),
+                    offset: *offset,
+                    dst: dst.clone(),
+                });
+                dst
+            }
+        }
+    }
+
+    // store a value we got from `read` back, plain variables were updated in place
+    fn write_back(&self, value: Value, instructions: &mut OpVec<Instruction>) {
+        match self {
+            Self::Plain(_) => {}
+            Self::Deref(ptr) => instructions.push_one(Instruction::Store {
+                src: value,
+                dst_ptr: ptr.clone(),
+            }),
+            Self::SubObject(base, offset) => instructions.push_one(Instruction::CopyToOffset {
+                src: value,
+                dst: base.clone(),
+                offset: *offset,
+            }),
+        }
+    }
+}
+
+// `ptr` moved forward by `offset` bytes
+fn offset_ptr(
+    ptr: Value,
+    offset: usize,
+    ptr_ty: VarType,
+    instructions: &mut OpVec<Instruction>,
+    table: &mut SymbolTable,
+) -> Value {
+    if offset == 0 {
+        return ptr;
+    }
+    let dst = Value::Var(new_var(ptr_ty, table));
+    instructions.push_one(Instruction::AddPtr {
+        ptr,
+        index: Value::Constant(Constant::Long(offset as i64)),
+        scale: 1,
+        dst: dst.clone(),
+    });
+    dst
+}
+
+fn convert_lvalue(
+    exp: Expr,
+    instructions: &mut OpVec<Instruction>,
+    table: &mut SymbolTable,
+) -> ExpResult {
+    match exp {
+        Expr::Deref { exp, .. } => ExpResult::Deref(convert_expression(*exp, instructions, table)),
+        Expr::Subscript { array, index, .. } => {
+            let ptr_ty = array.ty();
+            let ptr = convert_expression(*array, instructions, table);
+            let index = convert_expression(*index, instructions, table);
+            let dst = Value::Var(new_var(ptr_ty, table));
+            ExpResult::Deref(convert_add_ptr(ptr, index, ptr_ty, dst, instructions))
+        }
+        Expr::Dot { exp, offset, .. } => {
+            let ptr_ty = exp.ty().pointer_to();
+            match convert_lvalue(*exp, instructions, table) {
+                ExpResult::Plain(Value::Var(base)) => ExpResult::SubObject(base, offset as i64),
+                ExpResult::Plain(Value::Constant(_)) => unreachable!("structs aren't constants"),
+                ExpResult::SubObject(base, inner) => {
+                    ExpResult::SubObject(base, inner + offset as i64)
+                }
+                ExpResult::Deref(ptr) => {
+                    ExpResult::Deref(offset_ptr(ptr, offset, ptr_ty, instructions, table))
+                }
+            }
+        }
+        Expr::Arrow { exp, offset, .. } => {
+            let ptr_ty = exp.ty();
+            let ptr = convert_expression(*exp, instructions, table);
+            ExpResult::Deref(offset_ptr(ptr, offset, ptr_ty, instructions, table))
+        }
+        Expr::Nested { inner, .. } => convert_lvalue(*inner, instructions, table),
+        exp => ExpResult::Plain(convert_expression(exp, instructions, table)),
     }
 }
 
@@ -369,40 +639,95 @@ fn convert_expression(
     table: &mut SymbolTable,
 ) -> Value {
     match exp {
-        Expr::Cast { target, exp, ty } => convert_cast((target, *exp, ty), instructions, table),
+        Expr::Cast {
+            target, exp, ty, ..
+        } => convert_cast((target, *exp, ty), instructions, table),
 
-        Expr::Const { cnst: c, ty: _ } => Value::Constant(c),
-        Expr::FunctionCall { name, args, ty } => {
+        Expr::Const { cnst: c, .. } => Value::Constant(c),
+        Expr::OffsetOf { target, member, .. } => {
+            Value::Constant(semantics::offset_of(target, &member))
+        }
+        Expr::String { .. } => unreachable!("strings only initialize char arrays"),
+        Expr::FunctionCall { func, args, ty, .. } => {
+            let VarType::Pointer(&VarType::Function(fn_type)) = func.ty() else {
+                unreachable!("typecheck only calls function pointers")
+            };
+            // calling a function by name doesn't need its address
+            let callee = match *func {
+                Expr::AddrOf { exp, ty, span } => match *exp {
+                    Expr::Var { name, .. } => Ok(name),
+                    exp => Err(Expr::AddrOf {
+                        exp: Box::new(exp),
+                        ty,
+                        span,
+                    }),
+                },
+                func => Err(func),
+            }
+            .map_err(|func| convert_expression(func, instructions, table));
             let mut args_vec = Vec::new();
             for arg in args {
                 args_vec.push(convert_expression(arg, instructions, table));
             }
-            let result = Value::Var(new_var(ty, table));
-            instructions.push_one(Instruction::FunCall {
-                name,
-                args: args_vec.into(),
-                dst: result.clone(),
+            let args = args_vec.into();
+            let result = (ty != VarType::Void).then(|| Value::Var(new_var(ty, table)));
+            instructions.push_one(match callee {
+                Ok(name) => Instruction::FunCall {
+                    name,
+                    args,
+                    dst: result.clone(),
+                },
+                Err(ptr) => Instruction::IndirectCall {
+                    ptr,
+                    args,
+                    dst: result.clone(),
+                    variadic: fn_type.variadic,
+                },
             });
-            result
+            result.unwrap_or(VOID)
         }
-        Expr::Assignment { dst, src, ty: _ } => {
-            let Expr::Var { name: v, .. } = *dst else {
-                unreachable!()
-            };
-            let result = convert_expression(*src, instructions, table);
-            let var = Value::Var(v);
-            instructions.push_one(Instruction::Copy {
-                src: result,
-                dst: var.clone(),
+        Expr::VaStart { ap, .. } => {
+            let ap = convert_expression(*ap, instructions, table);
+            instructions.push_one(Instruction::VaStart(ap));
+            VOID
+        }
+        Expr::VaArg { ap, ty, .. } => {
+            let ap = convert_expression(*ap, instructions, table);
+            let dst = Value::Var(new_var(ty, table));
+            instructions.push_one(Instruction::VaArg {
+                ap,
+                dst: dst.clone(),
             });
-            var
+            dst
+        }
+        Expr::Assignment { dst, src, .. } => {
+            let lvalue = convert_lvalue(*dst, instructions, table);
+            let result = convert_expression(*src, instructions, table);
+            match lvalue {
+                ExpResult::Plain(var) => {
+                    instructions.push_one(Instruction::Copy {
+                        src: result,
+                        dst: var.clone(),
+                    });
+                    var
+                }
+                lvalue => {
+                    lvalue.write_back(result.clone(), instructions);
+                    result
+                }
+            }
         }
         Expr::Binary {
             operator,
             left,
             right,
             ty,
+            ..
         } => match process_binop(operator) {
+            ProcessedBinop::Comma => {
+                convert_expression(*left, instructions, table);
+                convert_expression(*right, instructions, table)
+            }
             ProcessedBinop::LogAnd => {
                 let source_1 = convert_expression(*left, instructions, table);
                 let false_label = and_label();
@@ -467,6 +792,41 @@ fn convert_expression(
                 result
             }
 
+            // pointer arithmetic, typecheck put the pointer on the left
+            ProcessedBinop::Normal(operator @ (TackyBinary::Add | TackyBinary::Subtract))
+                if ty.is_pointer() =>
+            {
+                let ptr = convert_expression(*left, instructions, table);
+                let mut index = convert_expression(*right, instructions, table);
+                if operator == TackyBinary::Subtract {
+                    index = negate(index, instructions, table);
+                }
+                let dst = Value::Var(new_var(ty, table));
+                convert_add_ptr(ptr, index, ty, dst, instructions)
+            }
+            // the difference between two pointers is in elements, not bytes
+            ProcessedBinop::Normal(TackyBinary::Subtract) if left.ty().is_pointer() => {
+                let scale = left.ty().element().unwrap().size() as i64;
+                let source_1 = convert_expression(*left, instructions, table);
+                let source_2 = convert_expression(*right, instructions, table);
+                let bytes = Value::Var(new_var(ty, table));
+                let dst = Value::Var(new_var(ty, table));
+                instructions.push([
+                    Instruction::Binary {
+                        operator: TackyBinary::Subtract,
+                        source_1,
+                        source_2,
+                        dst: bytes.clone(),
+                    },
+                    Instruction::Binary {
+                        operator: TackyBinary::Divide,
+                        source_1: bytes,
+                        source_2: Value::Constant(Constant::Long(scale)),
+                        dst: dst.clone(),
+                    },
+                ]);
+                dst
+            }
             ProcessedBinop::Normal(operator) => {
                 let source_1 = convert_expression(*left, instructions, table);
                 let source_2 = convert_expression(*right, instructions, table);
@@ -481,15 +841,38 @@ fn convert_expression(
                 dst
             }
             ProcessedBinop::Compound(op) => {
-                let dst = convert_expression(*left, instructions, table);
+                let common = right.ty();
+                let lvalue = convert_lvalue(*left, instructions, table);
                 let modifier = convert_expression(*right, instructions, table);
-                let binary = Instruction::Binary {
-                    operator: op.into(),
-                    source_1: dst.clone(),
-                    source_2: modifier,
-                    dst: dst.clone(),
-                };
-                instructions.push_one(binary);
+                let dst = lvalue.read(ty, instructions, table);
+                let operator = op.into();
+                if ty.is_pointer() {
+                    let index = if operator == TackyBinary::Subtract {
+                        negate(modifier, instructions, table)
+                    } else {
+                        modifier
+                    };
+                    convert_add_ptr(dst.clone(), index, ty, dst.clone(), instructions);
+                } else if common == ty {
+                    instructions.push_one(Instruction::Binary {
+                        operator,
+                        source_1: dst.clone(),
+                        source_2: modifier,
+                        dst: dst.clone(),
+                    });
+                } else {
+                    // the arithmetic happens in the common type, like it would for `a = a op b`
+                    let widened = Value::Var(new_var(common, table));
+                    convert_cast_op(common, ty, dst.clone(), widened.clone(), instructions);
+                    instructions.push_one(Instruction::Binary {
+                        operator,
+                        source_1: widened.clone(),
+                        source_2: modifier,
+                        dst: widened.clone(),
+                    });
+                    convert_cast_op(ty, common, widened, dst.clone(), instructions);
+                }
+                lvalue.write_back(dst.clone(), instructions);
                 dst
             }
         },
@@ -499,9 +882,14 @@ fn convert_expression(
             r#true,
             r#false,
             ty,
+            ..
         } => {
             let c = convert_expression(*condition, instructions, table);
-            let result = Value::Var(new_var(ty, table));
+            let result = if ty == VarType::Void {
+                VOID
+            } else {
+                Value::Var(new_var(ty, table))
+            };
             let false_label = conditional_label();
 
             let end = conditional_label();
@@ -510,30 +898,33 @@ fn convert_expression(
                 target: false_label.clone(),
             });
             let true_res = convert_expression(*r#true, instructions, table);
-            instructions.push([
-                Instruction::Copy {
+            if ty != VarType::Void {
+                instructions.push_one(Instruction::Copy {
                     src: true_res,
                     dst: result.clone(),
-                },
+                });
+            }
+            instructions.push([
                 Instruction::Jump {
                     target: end.clone(),
                 },
                 Instruction::Label(false_label),
             ]);
             let false_res = convert_expression(*r#false, instructions, table);
-            instructions.push([
-                Instruction::Copy {
+            if ty != VarType::Void {
+                instructions.push_one(Instruction::Copy {
                     src: false_res,
                     dst: result.clone(),
-                },
-                Instruction::Label(end),
-            ]);
+                });
+            }
+            instructions.push_one(Instruction::Label(end));
             result
         }
         Expr::Unary {
             operator,
             operand,
             ty,
+            ..
         } => {
             let tmp = new_var(ty, table);
             let factor_result = convert_expression(*operand, instructions, table);
@@ -547,24 +938,55 @@ fn convert_expression(
         }
         Expr::Nested { inner: e, .. } => convert_expression(*e, instructions, table),
         Expr::Var { name: v, .. } => Value::Var(v),
+        // initialized again every time it's evaluated, like a declaration in a loop would be
+        Expr::CompoundLiteral { name, init, ty, .. } => {
+            convert_initializer(name.clone(), *init, ty, instructions, table);
+            Value::Var(name)
+        }
+        Expr::Subscript { ty, .. } | Expr::Dot { ty, .. } | Expr::Arrow { ty, .. } => {
+            convert_lvalue(exp, instructions, table).read(ty, instructions, table)
+        }
+        Expr::Deref { exp, ty, .. } => {
+            let src_ptr = convert_expression(*exp, instructions, table);
+            let dst = Value::Var(new_var(ty, table));
+            instructions.push_one(Instruction::Load {
+                src_ptr,
+                dst: dst.clone(),
+            });
+            dst
+        }
+        Expr::AddrOf { exp, ty, .. } => match convert_lvalue(*exp, instructions, table) {
+            ExpResult::Plain(src) => {
+                let dst = Value::Var(new_var(ty, table));
+                instructions.push_one(Instruction::GetAddress {
+                    src,
+                    dst: dst.clone(),
+                });
+                dst
+            }
+            // &*p is just p
+            ExpResult::Deref(ptr) => ptr,
+            ExpResult::SubObject(base, offset) => {
+                let dst = Value::Var(new_var(ty, table));
+                instructions.push_one(Instruction::GetAddress {
+                    src: Value::Var(base),
+                    dst: dst.clone(),
+                });
+                offset_ptr(dst, offset as usize, ty, instructions, table)
+            }
+        },
         Expr::IncDec {
             op: IncDec { inc, fix: Fix::Pre },
-            ty: _,
             exp,
+            ty,
+            ..
         } => {
-            let expression_result = convert_expression(*exp, instructions, table);
-            let op = match inc {
-                IncOp::Inc => TackyBinary::Add,
-                IncOp::Dec => TackyBinary::Subtract,
-            };
+            let lvalue = convert_lvalue(*exp, instructions, table);
+            let expression_result = lvalue.read(ty, instructions, table);
 
             //prefix
-            instructions.push_one(Instruction::Binary {
-                operator: op,
-                source_1: expression_result.clone(),
-                source_2: Value::Constant(Constant::Int(1)),
-                dst: expression_result.clone(),
-            });
+            instructions.push_one(increment(expression_result.clone(), inc, ty));
+            lvalue.write_back(expression_result.clone(), instructions);
             expression_result
         }
         Expr::IncDec {
@@ -574,12 +996,10 @@ fn convert_expression(
             },
             ty,
             exp,
+            ..
         } => {
-            let res = convert_expression(*exp, instructions, table);
-            let op = match inc {
-                IncOp::Inc => TackyBinary::Add,
-                IncOp::Dec => TackyBinary::Subtract,
-            };
+            let lvalue = convert_lvalue(*exp, instructions, table);
+            let res = lvalue.read(ty, instructions, table);
 
             let old_val = Value::Var(new_var(ty, table));
             //prefix
@@ -588,19 +1008,47 @@ fn convert_expression(
                     src: res.clone(),
                     dst: old_val.clone(),
                 },
-                Instruction::Binary {
-                    operator: op,
-                    source_1: res.clone(),
-                    source_2: Value::Constant(Constant::Int(1)),
-                    dst: res.clone(),
-                },
+                increment(res.clone(), inc, ty),
             ]);
+            lvalue.write_back(res, instructions);
             old_val
         }
     }
 }
 
+// `value += 1` or `value -= 1` in place, pointers move by a whole element
+fn increment(value: Value, inc: IncOp, ty: VarType) -> Instruction {
+    if let Some(elem) = ty.element() {
+        let index = match inc {
+            IncOp::Inc => 1,
+            IncOp::Dec => -1,
+        };
+        return Instruction::AddPtr {
+            ptr: value.clone(),
+            index: Value::Constant(Constant::Long(index)),
+            scale: elem.size() as i64,
+            dst: value,
+        };
+    }
+    let operator = match inc {
+        IncOp::Inc => TackyBinary::Add,
+        IncOp::Dec => TackyBinary::Subtract,
+    };
+    let one = if ty == VarType::Double {
+        Constant::Double(1.0)
+    } else {
+        Constant::Int(1)
+    };
+    Instruction::Binary {
+        operator,
+        source_1: value.clone(),
+        source_2: Value::Constant(one),
+        dst: value,
+    }
+}
+
 enum ProcessedBinop {
+    Comma,
     LogAnd,
     LogOr,
     Normal(TackyBinary),
@@ -643,6 +1091,7 @@ const fn process_binop(binop: parse::Bop) -> ProcessedBinop {
     match binop {
         Pre::Equals => unreachable!(),
         Pre::Ternary => unreachable!(),
+        Pre::Comma => Post::Comma,
         Pre::LogAnd => Post::LogAnd,
         Pre::LogOr => Post::LogOr,
         Pre::Add => Post::Normal(TackyBinary::Add),
@@ -675,7 +1124,7 @@ const fn process_binop(binop: parse::Bop) -> ProcessedBinop {
     }
 }
 
-fn new_var(typ: VarType, symbols: &mut SymbolTable) -> Identifier {
+pub(super) fn new_var(typ: VarType, symbols: &mut SymbolTable) -> Identifier {
     let number = TEMP_COUNT.fetch_add(1, Ordering::SeqCst);
     let var_name: Box<[u8]> = format!("tmp_{number}").into_bytes().into();
     let ident = Identifier(var_name.into());
@@ -723,3 +1172,210 @@ fn conditional_label() -> Identifier {
     let var_name: Box<[u8]> = format!("c{number}").into_bytes().into();
     Identifier(var_name.into())
 }
+
+pub(super) fn block_label() -> Identifier {
+    let number = LABEL_COUNT.fetch_add(1, Ordering::SeqCst);
+    let var_name: Box<[u8]> = format!("block{number}").into_bytes().into();
+    Identifier(var_name.into())
+}
+
+// the functions `source` defines, in order, for the tests of the passes over tacky
+#[cfg(test)]
+pub(super) fn lowered(source: &[u8]) -> (Vec<FunctionDefinition>, SymbolTable) {
+    let (program, mut table) = semantics::checked(source).unwrap();
+    let program = emit(program, &mut table);
+    let functions = (program.0.into_vec().into_iter())
+        .filter_map(|top_level| match top_level {
+            TopLevel::Fn(f) => Some(f),
+            _ => None,
+        })
+        .collect();
+    (functions, table)
+}
+
+#[cfg(test)]
+mod test {
+    use super::lowered;
+    use crate::codegen::assembly::tacky::{Instruction, Value};
+    use crate::lex::Constant;
+
+    #[test]
+    fn pointers() {
+        let (functions, _) =
+            lowered(b"int f(int *p) { int x = *p; *p = x; int *q = &x; return *q; } int *g(int *p) { return &*p; }");
+        let through: Vec<_> = (functions[0].body.iter())
+            .filter_map(|instruction| match instruction {
+                Instruction::Load { .. } => Some("load"),
+                Instruction::Store { .. } => Some("store"),
+                Instruction::GetAddress { .. } => Some("address"),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(through, ["load", "store", "address", "load"]);
+        // `&*p` is just `p`, nothing gets read
+        assert!(!(functions[1].body.iter())
+            .any(|instruction| matches!(instruction, Instruction::Load { .. })));
+    }
+
+    #[test]
+    fn subscripts() {
+        // each subscript steps over a whole element of the array it's in
+        let (functions, _) = lowered(b"int f(int i, int j) { int a[3][5]; return a[i][j]; }");
+        let scales: Vec<_> = (functions[0].body.iter())
+            .filter_map(|instruction| match instruction {
+                Instruction::AddPtr { scale, .. } => Some(*scale),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(scales, [20, 4]);
+    }
+
+    #[test]
+    fn unsigned_conversions() {
+        // an int added to an unsigned int becomes unsigned too, but a long can hold every unsigned
+        // int, so that's what the sum is then
+        let extensions = |source: &[u8]| {
+            let (functions, _) = lowered(source);
+            (functions[0].body.iter())
+                .filter_map(|instruction| match instruction {
+                    Instruction::SignExtend { .. } => Some("sign"),
+                    Instruction::ZeroExtend { .. } => Some("zero"),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            extensions(b"long f(int i, unsigned u) { return i + u; }"),
+            ["zero"]
+        );
+        assert_eq!(
+            extensions(b"long f(long l, unsigned u) { return l + u; }"),
+            ["zero"]
+        );
+        assert_eq!(
+            extensions(b"unsigned long f(long l, int i) { return l + i; }"),
+            ["sign"]
+        );
+    }
+
+    #[test]
+    fn members() {
+        // members of a struct that's right there are read and written in place, through a pointer
+        // their address is worked out first
+        let source = b"struct s { char c; int i; };
+            int f(struct s *p) { struct s x; x.i = 3; p->i = x.i; return 0; }";
+        let (functions, _) = lowered(source);
+        assert!(matches!(
+            functions[0].body[..],
+            [
+                Instruction::CopyToOffset { offset: 4, .. },
+                Instruction::AddPtr {
+                    index: Value::Constant(Constant::Long(4)),
+                    scale: 1,
+                    ..
+                },
+                Instruction::CopyFromOffset { offset: 4, .. },
+                Instruction::Store { .. },
+                ..
+            ]
+        ));
+    }
+
+    #[test]
+    fn implicit_returns() {
+        // only main returns 0 when it runs off the end, calls to void functions don't get anywhere
+        // to put a result
+        let (functions, _) =
+            lowered(b"void g(void); int main(void) { g(); } void f(void) {} int h(void) {}");
+        let ends: Vec<_> = (functions.iter()).map(|f| f.body.last().unwrap()).collect();
+        assert!(matches!(
+            ends[..],
+            [
+                Instruction::Return(Some(Value::Constant(Constant::Int(0)))),
+                Instruction::Return(None),
+                Instruction::Return(None)
+            ]
+        ));
+        assert!(matches!(
+            functions[0].body[0],
+            Instruction::FunCall { dst: None, .. }
+        ));
+    }
+
+    #[test]
+    fn default_promotions() {
+        // whatever goes where the `...` is gets promoted, like chars to ints
+        let (functions, _) = lowered(b"int g(char c, ...); int f(char c) { return g(c, c); }");
+        let Some(Instruction::FunCall { args, .. }) = (functions[0].body.iter())
+            .find(|instruction| matches!(instruction, Instruction::FunCall { .. }))
+        else {
+            unreachable!()
+        };
+        let promoted = (functions[0].body.iter())
+            .find_map(|instruction| match instruction {
+                Instruction::SignExtend { dst, .. } => Some(dst),
+                _ => None,
+            })
+            .unwrap();
+        assert_ne!(args[0], *promoted);
+        assert_eq!(args[1], *promoted);
+    }
+
+    #[test]
+    fn indirect_calls() {
+        // calling a function by name stays direct, anything else goes through its address
+        let source = b"int g(int x); int (*t[2])(int); \
+                       int f(int i) { int (*p)(int) = g; return g(i) + t[i](i) + (*p)(i); }";
+        let (functions, _) = lowered(source);
+        let calls: Vec<_> = (functions[0].body.iter())
+            .filter_map(|instruction| match instruction {
+                Instruction::FunCall { .. } => Some("direct"),
+                Instruction::IndirectCall { .. } => Some("indirect"),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(calls, ["direct", "indirect", "indirect"]);
+    }
+
+    #[test]
+    fn compound_literals() {
+        // a compound literal is initialized in place every time it's evaluated, and then it's
+        // used like any other variable
+        let (functions, _) = lowered(b"int f(void) { int *p = (int[]){1, 2}; return p[1]; }");
+        assert!(matches!(
+            functions[0].body[..],
+            [
+                Instruction::CopyToOffset {
+                    src: Value::Constant(Constant::Int(1)),
+                    offset: 0,
+                    ..
+                },
+                Instruction::CopyToOffset {
+                    src: Value::Constant(Constant::Int(2)),
+                    offset: 4,
+                    ..
+                },
+                Instruction::GetAddress { .. },
+                ..
+            ]
+        ));
+    }
+
+    #[test]
+    fn commas_and_sizes() {
+        // both sides of a comma are evaluated, but the operand of `sizeof` never is
+        let (functions, _) = lowered(b"int g(void); long f(void) { return g(), sizeof g(); }");
+        assert!(matches!(
+            functions[0].body[..],
+            [
+                Instruction::FunCall { .. },
+                Instruction::Copy {
+                    src: Value::Constant(Constant::ULong(4)),
+                    ..
+                },
+                Instruction::Return(_),
+                ..
+            ]
+        ));
+    }
+}
@@ -22,6 +22,7 @@ pub fn fix_ast(program: Program<Pseudo>, table: &SymbolTable) -> Program<X86> {
         decs.push(match dec {
             TopLevel::Fn(f) => TopLevel::Fn(convert_function(f, table)),
             TopLevel::StaticVar(s) => TopLevel::StaticVar(s),
+            TopLevel::StaticConstant(c) => TopLevel::StaticConstant(c),
         })
     }
     Program(decs.into())
@@ -36,10 +37,18 @@ fn convert_function(
     }: FunctionDefinition<Pseudo>,
     table: &SymbolTable,
 ) -> FunctionDefinition<X86> {
-    let mut stack_frame = StackFrame::new(table);
+    // whatever callee saved registers the allocator handed out get pushed under the locals
+    let saved: Box<[Register]> = Register::CALLEE_SAVED
+        .into_iter()
+        .filter(|r| {
+            (body.iter().flat_map(Pseudo::operands)).any(|op| *op == PseudoOp::register(*r))
+        })
+        .collect();
+    let mut stack_frame = StackFrame::new(table, saved.clone());
     let body_vec: Vec<X86> = Vec::with_capacity(body.len() + 1);
     let mut body_vec: OpVec<X86> = body_vec.into();
     body_vec.push_one(X86::allocate_stack(0));
+    body_vec.push(saved.iter().map(|r| X86::Push(Op::Register(*r))));
 
     for op in body.into_iter() {
         fix_instruction(op, &mut stack_frame, &mut body_vec);
@@ -47,11 +56,16 @@ fn convert_function(
 
     let mut body: Vec<X86> = body_vec.0;
 
-    if stack_frame.size == 0 {
+    // the pushes have to leave the stack 16 byte aligned too
+    let size = match stack_frame.size {
+        0 => 0,
+        _ => stack_frame.rounded_size(),
+    } + 8 * (saved.len() % 2) as i64;
+    if size == 0 {
         // eww eww eww sorry sorry sorry
         body.remove(0);
     } else {
-        body[0] = X86::allocate_stack(stack_frame.rounded_size());
+        body[0] = X86::allocate_stack(size);
     }
 
     FunctionDefinition {
@@ -66,26 +80,19 @@ struct StackFrame<'a> {
     map: HashMap<Identifier, isize>,
     size: usize,
     table: &'a SymbolTable,
-}
-
-const fn align_quadword(size: usize) -> usize {
-    let remainder = (size + 8) % 16;
-    if remainder == 0 {
-        size
-    } else {
-        // should align to 16
-        size + (16 - remainder)
-    }
+    // popped in reverse before every return
+    saved: Box<[Register]>,
 }
 
 type RuleRes = std::result::Result<Op, Op>;
 
 impl<'a> StackFrame<'a> {
-    fn new(table: &'a SymbolTable) -> Self {
+    fn new(table: &'a SymbolTable, saved: Box<[Register]>) -> Self {
         Self {
             map: HashMap::new(),
             size: 0,
             table,
+            saved,
         }
     }
 
@@ -94,7 +101,7 @@ impl<'a> StackFrame<'a> {
         self.table
             .get(ident)
             .and_then(|input| match input {
-                BackendSymbol::Obj { ty, .. } => Some(ty),
+                BackendSymbol::Obj { ty, .. } | BackendSymbol::Constant { ty } => Some(ty),
                 BackendSymbol::Fn { .. } => None,
             })
             .copied()
@@ -106,20 +113,24 @@ impl<'a> StackFrame<'a> {
         } else {
             match self.table.get(ident).unwrap() {
                 BackendSymbol::Fn { .. } => panic!("expected var {ident}, got fn"),
+                BackendSymbol::Constant { .. } => Op::Literal(ident.clone()),
                 BackendSymbol::Obj {
                     ty: _,
                     is_static: true,
-                } => Op::Data(ident.clone()),
+                } => Op::Data(ident.clone(), 0),
                 BackendSymbol::Obj {
                     ty,
                     is_static: false,
                 } => {
-                    let size = match ty {
-                        AsmType::Longword => 4,
-                        AsmType::Quadword => align_quadword(self.size),
+                    let (size, alignment) = match *ty {
+                        AsmType::Byte => (1, 1),
+                        AsmType::Longword => (4, 4),
+                        AsmType::Quadword | AsmType::Double => (8, 8),
+                        AsmType::ByteArray { size, alignment } => (size, alignment),
                     };
 
-                    self.size += size;
+                    // the variable starts at the bottom, so padding goes above it
+                    self.size = (self.size + size).next_multiple_of(alignment);
 
                     let offset = -(self.size as isize);
 
@@ -140,6 +151,8 @@ impl<'a> StackFrame<'a> {
         match op {
             op if rules.none() => Ok(self.fix_operand(op)),
             PseudoOp::PseudoRegister(name) if rules.no_mem() => Err(self.fix_by_name(&name)),
+            op @ PseudoOp::PseudoMem(..) if rules.no_mem() => Err(self.fix_operand(op)),
+            PseudoOp::Normal(op @ Op::Literal(_)) if rules.no_mem() => Err(op),
             PseudoOp::Normal(op @ Op::Imm(i)) if rules.imm_not_allowed(i) => Err(op),
             op => Ok(self.fix_operand(op)),
         }
@@ -149,10 +162,11 @@ impl<'a> StackFrame<'a> {
         let left = self.check(pair.0, rules.left());
         let right = self.check(pair.1.clone(), rules.right());
         match (left, right) {
-            (
-                Ok(left @ (Op::Stack(_) | Op::Data(_))) | Err(left @ (Op::Stack(_) | Op::Data(_))),
-                right @ Ok(Op::Stack(_) | Op::Data(_)),
-            ) if rules.max_1_stack() => (Err(left), right),
+            (Ok(left) | Err(left), Ok(right))
+                if rules.max_1_stack() && in_memory(&left) && in_memory(&right) =>
+            {
+                (Err(left), Ok(right))
+            }
             other => other,
         }
     }
@@ -161,6 +175,11 @@ impl<'a> StackFrame<'a> {
         match operand {
             PseudoOp::Normal(o) => o,
             PseudoOp::PseudoRegister(name) => self.fix_by_name(&name),
+            PseudoOp::PseudoMem(name, offset) => match self.fix_by_name(&name) {
+                Op::Stack(base) => Op::Stack(base + offset),
+                Op::Data(name, base) => Op::Data(name, base + offset),
+                op => unreachable!("only variables are written piecewise, got {op}"),
+            },
         }
     }
 
@@ -179,6 +198,26 @@ impl<'a> StackFrame<'a> {
     }
 }
 
+const fn in_memory(op: &Op) -> bool {
+    matches!(
+        op,
+        Op::Stack(_)
+            | Op::Data(..)
+            | Op::Literal(_)
+            | Op::Got(_)
+            | Op::Memory(..)
+            | Op::Indexed { .. }
+    )
+}
+
+// registers for fixing up a source and a destination, sse instructions need xmm ones
+const fn scratch(ty: AsmType) -> (Op, Op) {
+    match ty {
+        AsmType::Double => (op::XMM14, op::XMM15),
+        _ => (op::R10, op::R11),
+    }
+}
+
 pub mod rule;
 
 use rule::{PairSet, RuleSet};
@@ -186,39 +225,101 @@ use rule::{PairSet, RuleSet};
 fn fix_instruction(op: Pseudo, sf: &mut StackFrame, vec: &mut OpVec<X86>) {
     use rule::RULES;
     match op {
+        // there's no pushing an xmm register, so make room and move it there instead
+        Pseudo::Push(PseudoOp::Normal(Op::Register(r))) if r.is_xmm() => vec.push([
+            X86::allocate_stack(8),
+            X86::mov(
+                Op::Register(r),
+                Op::Memory(Register::Sp, 0),
+                AsmType::Double,
+            ),
+        ]),
         // push rule: needs dword/NoQuadImm
         Pseudo::Push(op) => match sf.check(op, RULES.push) {
             Ok(op) => vec.push_one(X86::Push(op)),
             Err(op) => vec.push([X86::mov(op, op::R10, AsmType::Quadword), X86::Push(op::R10)]),
         },
-        Pseudo::Call(fun) => vec.push_one(X86::Call(fun)),
+        Pseudo::Call { name, defined } => vec.push_one(X86::Call { name, defined }),
+        Pseudo::CallIndirect(ptr) => match sf.fix_operand(ptr) {
+            ptr @ Op::Imm(_) => vec.push([
+                X86::mov(ptr, op::R11, AsmType::Quadword),
+                X86::CallIndirect(op::R11),
+            ]),
+            ptr => vec.push_one(X86::CallIndirect(ptr)),
+        },
         Pseudo::Mov {
             ty: AsmType::Quadword,
             // problem i have right now, I want
             regs:
                 (
                     PseudoOp::Normal(src @ Op::Imm(i)),
-                    PseudoOp::PseudoRegister(name) | PseudoOp::Normal(Op::Data(name)),
+                    dst @ (PseudoOp::PseudoRegister(_)
+                    | PseudoOp::PseudoMem(..)
+                    | PseudoOp::Normal(Op::Data(..))),
                 ),
-        } if i > i32::MAX as i64 => vec.push([
+        } if i32::try_from(i).is_err() => vec.push([
             X86::mov(src, op::R10, AsmType::Quadword),
-            X86::mov(op::R10, sf.fix_by_name(&name), AsmType::Quadword),
+            X86::mov(op::R10, sf.fix_operand(dst), AsmType::Quadword),
         ]),
         Pseudo::Mov { ty, regs } => {
             let (src, dst) = sf.check_pair(regs, RULES.mov);
+            let (src_temp, dst_temp) = scratch(ty);
 
             let src = match src {
                 Ok(src) => src,
                 Err(src) => {
-                    vec.push_one(X86::mov(src, op::R10, ty));
-                    Op::Register(Register::R10)
+                    vec.push_one(X86::mov(src, src_temp.clone(), ty));
+                    src_temp
                 }
             };
             match dst {
                 Ok(dst) => vec.push_one(X86::mov(src.clone(), dst, ty)),
-                Err(dst) => vec.push([X86::mov(src, op::R11, ty), X86::mov(op::R11, dst, ty)]),
+                Err(dst) => vec.push([
+                    X86::mov(src, dst_temp.clone(), ty),
+                    X86::mov(dst_temp, dst, ty),
+                ]),
             }
         }
+        // the destination has to be a register, and movzb can't take an immediate
+        Pseudo::MovZeroExtend {
+            src_ty,
+            dst_ty,
+            regs,
+        } => {
+            let (src, dst) = sf.check_pair(regs, RULES.movzx);
+            let src = match src {
+                Ok(src) => src,
+                Err(src) => {
+                    vec.push_one(X86::mov(src, op::R10, src_ty));
+                    op::R10
+                }
+            };
+            match dst {
+                Ok(dst) => vec.push_one(X86::MovZeroExtend {
+                    src_ty,
+                    dst_ty,
+                    regs: (src, dst),
+                }),
+                Err(dst) => vec.push([
+                    X86::MovZeroExtend {
+                        src_ty,
+                        dst_ty,
+                        regs: (src, op::R11),
+                    },
+                    X86::mov(op::R11, dst, dst_ty),
+                ]),
+            }
+        }
+        // the destination has to be a register
+        Pseudo::Lea { regs } => match sf.check_pair(regs, RULES.lea) {
+            (Ok(src) | Err(src), Ok(dst)) => vec.push_one(X86::Lea { regs: (src, dst) }),
+            (Ok(src) | Err(src), Err(dst)) => vec.push([
+                X86::Lea {
+                    regs: (src, op::R11),
+                },
+                X86::mov(op::R11, dst, AsmType::Quadword),
+            ]),
+        },
         Pseudo::Unary {
             operator,
             operand: o,
@@ -245,18 +346,23 @@ fn fix_instruction(op: Pseudo, sf: &mut StackFrame, vec: &mut OpVec<X86>) {
             let (src, dst) = sf.check_pair(
                 regs,
                 match operator {
+                    _ if ty == AsmType::Double => RULES.sse,
                     Binary::And | Binary::Xor | Binary::Or => RULES.bitwise,
                     Binary::Add => RULES.add,
                     Binary::Sub => RULES.sub,
                     Binary::Mult => RULES.mul,
-                    Binary::ShiftLeft | Binary::ShiftRight => RULES.shift,
+                    Binary::ShiftLeft | Binary::ShiftRight | Binary::ShiftRightLogical => {
+                        RULES.shift
+                    }
+                    Binary::DivDouble => unreachable!("divsd only works on doubles"),
                 },
             );
 
-            let temp_op = Op::from(match operator {
-                Binary::ShiftLeft | Binary::ShiftRight => Register::Cx,
-                _ => Register::R10,
-            });
+            let (src_temp, dst_temp) = scratch(ty);
+            let temp_op = match operator {
+                Binary::ShiftLeft | Binary::ShiftRight | Binary::ShiftRightLogical => op::CX,
+                _ => src_temp,
+            };
 
             let src = match src {
                 Ok(src) => src,
@@ -269,49 +375,81 @@ fn fix_instruction(op: Pseudo, sf: &mut StackFrame, vec: &mut OpVec<X86>) {
             match dst {
                 Ok(dst) => vec.push_one(X86::binary(operator, src, dst, ty)),
                 Err(dst) => vec.push([
-                    X86::mov(dst.clone(), Register::R11.into(), ty),
-                    X86::binary(operator, src, Register::R11.into(), ty),
-                    X86::mov(Register::R11.into(), dst, ty),
+                    X86::mov(dst.clone(), dst_temp.clone(), ty),
+                    X86::binary(operator, src, dst_temp.clone(), ty),
+                    X86::mov(dst_temp, dst, ty),
                 ]),
             }
         }
 
         // can't be an immediate
-        Pseudo::Idiv { divisor, ty } => match sf.check(divisor, RULES.div) {
-            Ok(divisor) => {
-                vec.push_one(X86::Idiv { divisor, ty });
-            }
-            Err(divisor) => {
-                let temp_register = Op::Register(Register::R10);
-                vec.push([
-                    X86::mov(divisor, temp_register.clone(), ty),
-                    X86::Idiv {
-                        divisor: temp_register,
-                        ty,
-                    },
-                ]);
-            }
-        },
-        Pseudo::Ret => vec.push_one(X86::Ret),
+        Pseudo::Idiv { divisor, ty } => fix_divisor(divisor, ty, X86::idiv, sf, vec),
+        Pseudo::Div { divisor, ty } => fix_divisor(divisor, ty, X86::div, sf, vec),
+        Pseudo::Pop(r) => vec.push_one(X86::Pop(r)),
+        Pseudo::Ret => {
+            vec.push(sf.saved.iter().rev().map(|r| X86::Pop(*r)));
+            vec.push_one(X86::Ret);
+        }
         Pseudo::Cdq(ty) => vec.push_one(X86::Cdq(ty)),
         // cmp is no quad, one mem, need some kinda bitfield or smth
         Pseudo::Cmp { regs, ty } => {
-            let (l, r) = sf.check_pair(regs, RULES.cmp);
+            let rules = if ty == AsmType::Double {
+                RULES.sse
+            } else {
+                RULES.cmp
+            };
+            let (l, r) = sf.check_pair(regs, rules);
+            let (l_temp, r_temp) = scratch(ty);
             let l = match l {
                 Ok(l) => l,
                 Err(l) => {
-                    vec.push_one(X86::mov(l, Register::R10.into(), ty));
-                    Register::R10.into()
+                    vec.push_one(X86::mov(l, l_temp.clone(), ty));
+                    l_temp
                 }
             };
             match r {
                 Ok(r) => vec.push_one(X86::cmp(l, r, ty)),
-                Err(r) => vec.push([
-                    X86::mov(r, Register::R11.into(), ty),
-                    X86::cmp(l, Register::R11.into(), ty),
+                Err(r) => vec.push([X86::mov(r, r_temp.clone(), ty), X86::cmp(l, r_temp, ty)]),
+            }
+        }
+        // the destination has to be an xmm register, and the source can't be an immediate
+        Pseudo::Cvtsi2sd { ty, regs } => {
+            let (src, dst) = sf.check_pair(regs, RULES.cvtsi2sd);
+            let src = match src {
+                Ok(src) => src,
+                Err(src) => {
+                    vec.push_one(X86::mov(src, op::R10, ty));
+                    op::R10
+                }
+            };
+            match dst {
+                Ok(dst) => vec.push_one(X86::Cvtsi2sd {
+                    ty,
+                    regs: (src, dst),
+                }),
+                Err(dst) => vec.push([
+                    X86::Cvtsi2sd {
+                        ty,
+                        regs: (src, op::XMM15),
+                    },
+                    X86::mov(op::XMM15, dst, AsmType::Double),
                 ]),
             }
         }
+        // the destination has to be a register
+        Pseudo::Cvttsd2si { ty, regs } => match sf.check_pair(regs, RULES.cvttsd2si) {
+            (Ok(src) | Err(src), Ok(dst)) => vec.push_one(X86::Cvttsd2si {
+                ty,
+                regs: (src, dst),
+            }),
+            (Ok(src) | Err(src), Err(dst)) => vec.push([
+                X86::Cvttsd2si {
+                    ty,
+                    regs: (src, op::R11),
+                },
+                X86::mov(op::R11, dst, ty),
+            ]),
+        },
         Pseudo::Jmp(label) => vec.push_one(X86::Jmp(label)),
         Pseudo::Label(name) => vec.push_one(X86::Label(name)),
         Pseudo::JmpCC { condition, label } => vec.push_one(X86::JmpCC { condition, label }),
@@ -322,7 +460,8 @@ fn fix_instruction(op: Pseudo, sf: &mut StackFrame, vec: &mut OpVec<X86>) {
         // no stack dst
         // no imm src
         Pseudo::Movsx {
-            ty,
+            src_ty,
+            dst_ty,
             regs, //src,
                   //dst: PseudoOp::PseudoRegister(p),
         } => {
@@ -331,15 +470,15 @@ fn fix_instruction(op: Pseudo, sf: &mut StackFrame, vec: &mut OpVec<X86>) {
             let src = match src {
                 Ok(src) => src,
                 Err(src) => {
-                    vec.push_one(X86::mov(src, Register::R10.into(), ty));
+                    vec.push_one(X86::mov(src, Register::R10.into(), src_ty));
                     Register::R10.into()
                 }
             };
             match dst {
-                Ok(dst) => vec.push_one(X86::movsx(src, dst)),
+                Ok(dst) => vec.push_one(X86::movsx(src, dst, src_ty, dst_ty)),
                 Err(dst) => vec.push([
-                    X86::movsx(src, Register::R11.into()),
-                    X86::mov(Register::R11.into(), dst, AsmType::Quadword),
+                    X86::movsx(src, Register::R11.into(), src_ty, dst_ty),
+                    X86::mov(Register::R11.into(), dst, dst_ty),
                 ]),
             }
         }
@@ -348,3 +487,22 @@ fn fix_instruction(op: Pseudo, sf: &mut StackFrame, vec: &mut OpVec<X86>) {
 
 // no mem dst
 // no quad immediate
+
+fn fix_divisor(
+    divisor: PseudoOp,
+    ty: AsmType,
+    div: fn(Op, AsmType) -> X86,
+    sf: &mut StackFrame,
+    vec: &mut OpVec<X86>,
+) {
+    match sf.check(divisor, rule::RULES.div) {
+        Ok(divisor) => vec.push_one(div(divisor, ty)),
+        Err(divisor) => {
+            let temp_register = Op::Register(Register::R10);
+            vec.push([
+                X86::mov(divisor, temp_register.clone(), ty),
+                div(temp_register, ty),
+            ]);
+        }
+    }
+}
@@ -16,6 +16,8 @@ const NORULE: RuleSet = RuleSet(0);
 pub struct RuleTable {
     pub mov: PairSet,
     pub movsx: PairSet,
+    pub movzx: PairSet,
+    pub lea: PairSet,
     pub add: PairSet,
     pub unop: RuleSet,
     pub sub: PairSet,
@@ -25,6 +27,9 @@ pub struct RuleTable {
     pub push: RuleSet,
     pub cmp: PairSet,
     pub mul: PairSet,
+    pub sse: PairSet,
+    pub cvtsi2sd: PairSet,
+    pub cvttsd2si: PairSet,
 }
 
 impl RuleTable {
@@ -34,6 +39,8 @@ impl RuleTable {
 pub const RULES: RuleTable = RuleTable {
     mov: pair_set(MemRule::One, NO_MEM, NO_IMM),
     movsx: pair_set(MemRule::Two, NO_IMM, NO_IMM.or(NO_MEM)),
+    movzx: pair_set(MemRule::Two, NO_IMM, NO_MEM),
+    lea: pair_set(MemRule::Two, NORULE, NO_MEM),
     add: RuleTable::ADD_SUB_CMP,
     sub: RuleTable::ADD_SUB_CMP,
     mul: pair_set(MemRule::One, MAX_DWORD, NO_MEM),
@@ -43,6 +50,10 @@ pub const RULES: RuleTable = RuleTable {
     div: NO_IMM,
     shift: pair_set(MemRule::One, NO_MEM, NO_IMM),
     push: MAX_DWORD,
+    // sse arithmetic and comisd write to, or compare against, a register
+    sse: pair_set(MemRule::Two, NORULE, NO_MEM),
+    cvtsi2sd: pair_set(MemRule::Two, NO_IMM, NO_MEM),
+    cvttsd2si: pair_set(MemRule::Two, NORULE, NO_MEM),
 };
 
 // ideally we'd have some nice elegant matching stuff but it's pretty involved
@@ -105,7 +116,8 @@ impl Display for RuleSet {
 
 impl RuleSet {
     pub const fn imm_not_allowed(&self, val: i64) -> bool {
-        self.no_imm() || (self.max_dword() && val > i32::MAX as i64)
+        // unsigned longs above i64::MAX show up as negative
+        self.no_imm() || (self.max_dword() && (val > i32::MAX as i64 || val < i32::MIN as i64))
     }
 
     pub const fn validate(self) -> RuleSet {
@@ -202,15 +214,15 @@ mod test {
     #[test]
     fn test_pair() {
         let rule = super::RULES.mov;
-        assert!(!rule.max_1_stack());
+        assert!(rule.max_1_stack());
         assert!(!rule.left().none());
-        assert!(rule.left().max_dword());
+        assert!(!rule.left().max_dword());
         assert!(rule.left().no_mem());
         assert!(!rule.left().no_imm());
 
-        assert!(rule.right().none());
+        assert!(!rule.right().none());
         assert!(!rule.right().no_mem());
         assert!(!rule.right().max_dword());
-        assert!(!rule.right().no_imm());
+        assert!(rule.right().no_imm());
     }
 }
@@ -0,0 +1,907 @@
+// turns everything that can go wrong into clang/rustc style messages
+//
+// codes are grouped by stage: E00xx preprocessing and the driver, E01xx lexing, E02xx parsing,
+// E03xx name resolution, E04xx loops, switches and labels, E05xx type checking. warnings count
+// up from W0001
+use crate::lex::{self, Span};
+#[cfg(feature = "parse")]
+use crate::parse;
+use crate::preprocess;
+#[cfg(feature = "semantics")]
+use crate::semantics::{self, typecheck, LabelError, LoopError, ResolveError};
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter, Write};
+use std::fs;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+    Help,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+            Self::Note => "note",
+            Self::Help => "help",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub span: Option<Span>,
+    // printed next to the underline
+    pub label: Option<String>,
+    pub notes: Vec<Note>,
+    pub fix: Option<FixIt>,
+}
+
+// "previous declaration was here", without a span it's just a line at the bottom
+#[derive(Debug, Clone)]
+pub struct Note {
+    pub message: String,
+    pub span: Option<Span>,
+}
+
+// replace `span` with `replacement`, a zero length span is an insertion
+#[derive(Debug, Clone)]
+pub struct FixIt {
+    pub message: String,
+    pub span: Span,
+    pub replacement: String,
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            code,
+            message: message.into(),
+            span: None,
+            label: None,
+            notes: Vec::new(),
+            fix: None,
+        }
+    }
+
+    pub fn warning(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::error(code, message)
+        }
+    }
+
+    pub const fn at(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    pub fn note(mut self, message: impl Into<String>) -> Self {
+        self.notes.push(Note {
+            message: message.into(),
+            span: None,
+        });
+        self
+    }
+
+    pub fn note_at(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.notes.push(Note {
+            message: message.into(),
+            span: Some(span),
+        });
+        self
+    }
+
+    pub fn insert(mut self, span: Span, text: impl Into<String>) -> Self {
+        let replacement = text.into();
+        self.fix = Some(FixIt {
+            message: format!("insert `{replacement}`"),
+            span: Span { len: 0, ..span },
+            replacement,
+        });
+        self
+    }
+
+    pub fn render(&self, sources: &mut Sources) -> String {
+        let mut out = String::new();
+        let _ = Renderer::new(self, &mut out, sources).diagnostic(self);
+        out
+    }
+
+    // one object per diagnostic, `rendered` is what we would have printed otherwise
+    pub fn json(&self, sources: &mut Sources) -> String {
+        let mut out = String::from("{");
+        let _ = write!(
+            out,
+            "\"severity\":{},\"code\":{},\"message\":{},\"span\":{},\"label\":{},",
+            json_str(&self.severity.to_string()),
+            json_str(self.code),
+            json_str(&self.message),
+            json_span(self.span),
+            self.label.as_deref().map_or("null".into(), json_str),
+        );
+        let notes: Vec<String> = self
+            .notes
+            .iter()
+            .map(|note| {
+                format!(
+                    "{{\"message\":{},\"span\":{}}}",
+                    json_str(&note.message),
+                    json_span(note.span)
+                )
+            })
+            .collect();
+        let _ = write!(out, "\"notes\":[{}],", notes.join(","));
+        let fixes = self.fix.as_ref().map_or(String::new(), |fix| {
+            format!(
+                "{{\"message\":{},\"span\":{},\"replacement\":{}}}",
+                json_str(&fix.message),
+                json_span(Some(fix.span)),
+                json_str(&fix.replacement)
+            )
+        });
+        let _ = write!(
+            out,
+            "\"fixes\":[{fixes}],\"rendered\":{}}}",
+            json_str(&self.render(sources))
+        );
+        out
+    }
+}
+
+// render a whole batch, ending with a count like clang does
+pub fn render(diagnostics: &[Diagnostic]) -> String {
+    let mut sources = Sources::default();
+    let mut out: String = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.render(&mut sources) + "\n")
+        .collect();
+    let errors = diagnostics
+        .iter()
+        .filter(|d| d.severity == Severity::Error)
+        .count();
+    match errors {
+        0 => {}
+        1 => out.push_str("1 error generated.\n"),
+        n => {
+            let _ = writeln!(out, "{n} errors generated.");
+        }
+    }
+    out
+}
+
+// source files get read the first time a diagnostic points into them
+#[derive(Default)]
+pub struct Sources {
+    files: HashMap<&'static str, Option<Vec<String>>>,
+}
+
+impl Sources {
+    fn line(&mut self, span: Span) -> Option<&str> {
+        if span.file.is_empty() || span.line == 0 {
+            return None;
+        }
+        let lines = self.files.entry(span.file).or_insert_with(|| {
+            let bytes = fs::read(span.file).ok()?;
+            Some(
+                String::from_utf8_lossy(&bytes)
+                    .lines()
+                    .map(String::from)
+                    .collect(),
+            )
+        });
+        lines.as_ref()?.get(span.line - 1).map(String::as_str)
+    }
+}
+
+struct Renderer<'a> {
+    out: &'a mut String,
+    sources: &'a mut Sources,
+    // width of the line number column
+    gutter: usize,
+}
+
+impl<'a> Renderer<'a> {
+    fn new(diagnostic: &Diagnostic, out: &'a mut String, sources: &'a mut Sources) -> Self {
+        let widest = diagnostic
+            .span
+            .iter()
+            .chain(
+                diagnostic
+                    .notes
+                    .iter()
+                    .filter_map(|note| note.span.as_ref()),
+            )
+            .chain(diagnostic.fix.as_ref().map(|fix| &fix.span))
+            .map(|span| span.line)
+            .max()
+            .unwrap_or(0);
+        Self {
+            out,
+            sources,
+            gutter: widest.to_string().len(),
+        }
+    }
+
+    fn diagnostic(&mut self, diagnostic: &Diagnostic) -> fmt::Result {
+        writeln!(
+            self.out,
+            "{}[{}]: {}",
+            diagnostic.severity, diagnostic.code, diagnostic.message
+        )?;
+        if let Some(span) = diagnostic.span {
+            self.snippet(span, '^', diagnostic.label.as_deref())?;
+        }
+        let (located, loose): (Vec<_>, Vec<_>) = diagnostic
+            .notes
+            .iter()
+            .partition(|note| note.span.is_some());
+        for note in loose {
+            writeln!(
+                self.out,
+                "{:w$} = note: {}",
+                "",
+                note.message,
+                w = self.gutter
+            )?;
+        }
+        for note in located {
+            writeln!(self.out, "note: {}", note.message)?;
+            self.snippet(note.span.unwrap(), '-', None)?;
+        }
+        if let Some(fix) = &diagnostic.fix {
+            writeln!(self.out, "help: {}", fix.message)?;
+            self.fixed(fix)?;
+        }
+        Ok(())
+    }
+
+    fn snippet(&mut self, span: Span, mark: char, label: Option<&str>) -> fmt::Result {
+        let w = self.gutter;
+        writeln!(self.out, "{:w$}--> {span}", "")?;
+        let Some(line) = self.sources.line(span).map(String::from) else {
+            return Ok(());
+        };
+        writeln!(self.out, "{:w$} |", "")?;
+        writeln!(self.out, "{:>w$} | {line}", span.line)?;
+        let start = span.column.saturating_sub(1);
+        // underline up to the end of the line, but always at least one character
+        let len = span.len.min(line.len().saturating_sub(start)).max(1);
+        let mut underline = indent(&line, start);
+        underline.extend(std::iter::repeat_n(mark, len));
+        if let Some(label) = label {
+            underline.push(' ');
+            underline.push_str(label);
+        }
+        writeln!(self.out, "{:w$} | {underline}", "")
+    }
+
+    // the line as it would look with the fix applied, with the new part marked
+    fn fixed(&mut self, fix: &FixIt) -> fmt::Result {
+        let w = self.gutter;
+        let Some(line) = self.sources.line(fix.span).map(String::from) else {
+            return Ok(());
+        };
+        let start = (fix.span.column.saturating_sub(1)).min(line.len());
+        let end = (start + fix.span.len).min(line.len());
+        if !line.is_char_boundary(start) || !line.is_char_boundary(end) {
+            return Ok(());
+        }
+        let patched = format!("{}{}{}", &line[..start], fix.replacement, &line[end..]);
+        let mut marks = indent(&patched, start);
+        marks.extend(std::iter::repeat_n('+', fix.replacement.len().max(1)));
+        writeln!(self.out, "{:w$} |", "")?;
+        writeln!(self.out, "{:>w$} | {patched}", fix.span.line)?;
+        writeln!(self.out, "{:w$} | {marks}", "")
+    }
+}
+
+// whitespace that lines up with the first `column` bytes of `line`, keeping tabs as tabs
+fn indent(line: &str, column: usize) -> String {
+    line.bytes()
+        .chain(std::iter::repeat(b' '))
+        .take(column)
+        .map(|b| if b == b'\t' { '\t' } else { ' ' })
+        .collect()
+}
+
+fn json_str(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_span(span: Option<Span>) -> String {
+    span.map_or("null".into(), |span| {
+        format!(
+            "{{\"file\":{},\"line\":{},\"column\":{},\"len\":{}}}",
+            json_str(span.file),
+            span.line,
+            span.column,
+            span.len
+        )
+    })
+}
+
+impl crate::Error {
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        vec![match self {
+            Self::Todo => Diagnostic::error("E0001", "not implemented yet"),
+            Self::InvalidInput => Diagnostic::error("E0002", "couldn't read the input file"),
+            Self::Io(e) => Diagnostic::error("E0003", format!("couldn't write the output: {e}")),
+            Self::Lexing(e) => e.into(),
+            #[cfg(feature = "parse")]
+            Self::Parsing(errors) => return errors.iter().map(Diagnostic::from).collect(),
+            #[cfg(feature = "semantics")]
+            Self::Semantics(e) => e.into(),
+        }]
+    }
+}
+
+// the preprocessor only knows the line or macro an error came from, not the exact token
+impl From<&preprocess::Error> for Diagnostic {
+    fn from(e: &preprocess::Error) -> Self {
+        use preprocess::Error;
+        match e {
+            Error::Io(e) => Self::error("E0010", format!("couldn't read source file: {e}")),
+            Error::IncludeNotFound(name) => {
+                Self::error("E0011", format!("'{name}' file not found"))
+            }
+            Error::IncludeDepth => Self::error("E0012", "#include nested too deeply"),
+            Error::UnterminatedComment => Self::error("E0013", "unterminated /* comment"),
+            Error::UnterminatedLiteral => {
+                Self::error("E0014", "missing terminating quote character")
+            }
+            Error::InvalidDirective(name) => {
+                Self::error("E0015", format!("invalid preprocessing directive `{name}`"))
+            }
+            Error::InvalidMacro(why) => Self::error("E0016", format!("invalid macro: {why}")),
+            Error::MacroArgs(name) => Self::error(
+                "E0017",
+                format!("wrong number of arguments to macro `{name}`"),
+            ),
+            Error::UnterminatedCall(name) => Self::error(
+                "E0018",
+                format!("unterminated argument list invoking macro `{name}`"),
+            ),
+            Error::InvalidExpression(why) => {
+                Self::error("E0019", format!("invalid #if expression: {why}"))
+            }
+            Error::DivideByZero => Self::error("E0020", "division by zero in #if expression"),
+            Error::Unbalanced(why) => Self::error("E0021", *why),
+            Error::MissingEndif => Self::error("E0022", "unterminated conditional directive")
+                .note("every #if needs a matching #endif"),
+            Error::User(message) => Self::error("E0023", format!("#error {message}")),
+            Error::At(span, e) => Self::from(&**e).at(*span),
+        }
+    }
+}
+
+impl From<&lex::Error> for Diagnostic {
+    fn from(e: &lex::Error) -> Self {
+        use lex::Error;
+        let span = e.span();
+        match e {
+            Error::InvalidConstant(_) => Self::error("E0101", "invalid numeric constant")
+                .at(span)
+                .label("constants can't run into identifiers"),
+            Error::InvalidLiteral(_) => Self::error("E0102", "invalid token").at(span),
+            Error::InvalidIdentifier(_) => Self::error("E0103", "invalid identifier").at(span),
+            Error::InvalidLineMarker(_) => Self::error("E0104", "malformed line marker").at(span),
+            Error::NotAscii(_) => Self::error("E0105", "non-ascii character in source")
+                .at(span)
+                .label("only ascii is accepted"),
+            Error::ConstantTooLarge(_) => {
+                Self::error("E0106", "integer constant is too large for its type").at(span)
+            }
+            Error::UnterminatedLiteral(_) => Self::error("E0107", "missing terminating quote")
+                .at(span)
+                .label("literal starts here"),
+            Error::InvalidEscape(_) => Self::error("E0108", "invalid escape sequence").at(span),
+            Error::InvalidCharacter(_) => Self::error(
+                "E0109",
+                "character constant must hold exactly one character",
+            )
+            .at(span),
+            Error::Other(message, _) => Self::error("E0100", message.as_str()).at(span),
+        }
+    }
+}
+
+#[cfg(feature = "parse")]
+impl From<&parse::Error> for Diagnostic {
+    fn from(e: &parse::Error) -> Self {
+        use parse::Error;
+        let span = e.span();
+        match e {
+            Error::UnexpectedEof(_) => Self::error("E0201", "unexpected end of file")
+                .at(span)
+                .label("expected more input here"),
+            Error::Expected(token, _, missing) => {
+                let diagnostic = Self::error("E0202", format!("expected `{token}`")).at(span);
+                if *missing {
+                    diagnostic.insert(span, token.to_string())
+                } else {
+                    diagnostic
+                }
+            }
+            Error::ExpectedIdentifier(_) => Self::error("E0203", "expected identifier")
+                .at(span)
+                .label("expected an identifier here"),
+            Error::ExpectedConstant(_) => Self::error("E0204", "expected constant")
+                .at(span)
+                .label("expected an integer constant here"),
+            Error::ExpectedExpr(_) => Self::error("E0205", "expected expression")
+                .at(span)
+                .label("expected an expression here"),
+            Error::ExpectedAnyKeyword(_) => Self::error("E0206", "expected a keyword").at(span),
+            Error::Catchall(message, _) => Self::error("E0200", *message).at(span),
+            Error::DoubleDef(_) => Self::error("E0208", "duplicate definition").at(span),
+            Error::NoType(_) => Self::error("E0209", "type specifier missing")
+                .at(span)
+                .note("C99 and later don't allow implicit int"),
+            Error::ConflictingLinkage(_) => {
+                Self::error("E0210", "conflicting storage class specifiers")
+                    .at(span)
+                    .label("`static` and `extern` can't be used together")
+            }
+            Error::InvalidSpecifiers(_) => {
+                Self::error("E0211", "invalid declaration specifiers").at(span)
+            }
+            Error::InvalidType(_, _) => {
+                Self::error("E0212", "invalid combination of type specifiers").at(span)
+            }
+            Error::NoStorageClass(_) => Self::error("E0213", "storage class not allowed here")
+                .at(span)
+                .label("parameters and loop variables can't have a storage class"),
+            Error::NamelessParam(_) => {
+                Self::error("E0215", "parameter name omitted in function definition").at(span)
+            }
+            Error::StructDefinition(_) => {
+                Self::error("E0216", "struct or enum defined in a type name")
+                    .at(span)
+                    .label("define it in a declaration of its own")
+            }
+            Error::InvalidDeclarator(message, _) => Self::error("E0217", "invalid declarator")
+                .at(span)
+                .label(*message),
+            Error::TypedefInit(_) => Self::error("E0218", "typedef can't be initialized")
+                .at(span)
+                .label("a typedef names a type, not an object"),
+        }
+    }
+}
+
+#[cfg(feature = "semantics")]
+impl From<&semantics::Error> for Diagnostic {
+    fn from(e: &semantics::Error) -> Self {
+        match e {
+            semantics::Error::Resolve(e) => e.into(),
+            semantics::Error::Loops(e) => e.into(),
+            semantics::Error::Label(e) => e.into(),
+            semantics::Error::TypeCheck(e) => e.into(),
+        }
+    }
+}
+
+#[cfg(feature = "semantics")]
+impl From<&ResolveError> for Diagnostic {
+    fn from(e: &ResolveError) -> Self {
+        use ResolveError as Error;
+        let span = e.span();
+        match e {
+            Error::DuplicateDeclaration { previous, .. } => {
+                Self::error("E0301", "redefinition of parameter")
+                    .at(span)
+                    .note_at(*previous, "previous declaration was here")
+            }
+            Error::InvalidLval(_) => Self::error("E0302", "expression is not assignable")
+                .at(span)
+                .label("cannot assign to this"),
+            Error::UndeclaredVar(_) => Self::error("E0303", "use of undeclared identifier")
+                .at(span)
+                .label("not found in this scope"),
+            Error::UndeclaredFn(_) => Self::error("E0304", "call to undeclared function")
+                .at(span)
+                .note("functions have to be declared before they're called"),
+            Error::LocalFnDecBody(_) => {
+                Self::error("E0305", "function definition is not allowed here")
+                    .at(span)
+                    .note("functions can only be defined at file scope")
+            }
+            Error::ConflictingDec { previous, .. } => {
+                Self::error("E0306", "redeclaration in the same scope")
+                    .at(span)
+                    .note_at(*previous, "previous declaration was here")
+            }
+            Error::StaticBlockScopeFn(_) => Self::error(
+                "E0307",
+                "function declared in block scope can't be `static`",
+            )
+            .at(span),
+            Error::AddrOfRvalue(_) => Self::error("E0308", "cannot take the address of an rvalue")
+                .at(span)
+                .label("this isn't an object in memory"),
+            Error::WrongTagKind { previous, .. } => Self::error(
+                "E0309",
+                "use of a tag that doesn't match its previous declaration",
+            )
+            .at(span)
+            .note_at(*previous, "previous declaration was here"),
+        }
+    }
+}
+
+#[cfg(feature = "semantics")]
+impl From<&LoopError> for Diagnostic {
+    fn from(e: &LoopError) -> Self {
+        use LoopError as Error;
+        let span = e.span();
+        match e {
+            Error::Break(_) => {
+                Self::error("E0401", "`break` statement not in loop or switch statement").at(span)
+            }
+            Error::Continue(_) => {
+                Self::error("E0402", "`continue` statement not in loop statement").at(span)
+            }
+            Error::Switch(_) => {
+                Self::error("E0403", "`case` or `default` label not in switch statement").at(span)
+            }
+            Error::DoubleDefault { previous, .. } => {
+                Self::error("E0405", "multiple default labels in one switch")
+                    .at(span)
+                    .note_at(*previous, "previous default label was here")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "semantics")]
+impl From<&LabelError> for Diagnostic {
+    fn from(e: &LabelError) -> Self {
+        use LabelError as Error;
+        let span = e.span();
+        match e {
+            Error::RedefinedLabel { previous, .. } => Self::error("E0407", "redefinition of label")
+                .at(span)
+                .note_at(*previous, "previous definition was here"),
+            Error::ClashedLabel(_) => {
+                Self::error("E0408", "label has the same name as a function or variable").at(span)
+            }
+            Error::UndefinedLabel(_) => Self::error("E0409", "use of undeclared label")
+                .at(span)
+                .label("no label with this name in the function"),
+        }
+    }
+}
+
+#[cfg(feature = "semantics")]
+impl From<&typecheck::Error> for Diagnostic {
+    fn from(e: &typecheck::Error) -> Self {
+        use typecheck::Error;
+        let span = e.span();
+        match e {
+            Error::DuplicateDefinition { previous, .. } => {
+                Self::error("E0501", "redefinition of function")
+                    .at(span)
+                    .note_at(*previous, "previous definition was here")
+            }
+            Error::ConflictingDeclaration { previous, .. } => {
+                Self::error("E0502", "redefinition of variable")
+                    .at(span)
+                    .note_at(*previous, "previous definition was here")
+            }
+            Error::UndefinedVar(_) => Self::error("E0503", "use of undeclared variable").at(span),
+            Error::UndefinedFn(_) => Self::error("E0504", "call to undeclared function").at(span),
+            Error::WrongParams { previous, .. } => {
+                Self::error("E0505", "parameters don't match the previous declaration")
+                    .at(span)
+                    .note_at(*previous, "previous declaration was here")
+            }
+            Error::FnAsVar { previous, .. } => {
+                Self::error("E0506", "redeclaration of a function as a variable")
+                    .at(span)
+                    .note_at(*previous, "previous declaration was here")
+            }
+            Error::VarAsFn(_) => Self::error("E0507", "variable used as a function").at(span),
+            Error::WrongArgs { declaration, .. } => {
+                let diagnostic =
+                    Self::error("E0508", "wrong number of arguments to function call").at(span);
+                match declaration {
+                    Some(declaration) => diagnostic.note_at(*declaration, "function declared here"),
+                    None => diagnostic,
+                }
+            }
+            Error::ConflictingType { previous, .. } => {
+                let diagnostic = Self::error("E0509", "conflicting types").at(span);
+                match previous {
+                    Some(previous) => {
+                        diagnostic.note_at(*previous, "previous declaration was here")
+                    }
+                    None => diagnostic,
+                }
+            }
+            Error::StaticGlobal(_) => {
+                Self::error("E0510", "invalid static declaration at file scope").at(span)
+            }
+            Error::NotConstInitialized(_) => Self::error(
+                "E0511",
+                "initializer element is not a compile-time constant",
+            )
+            .at(span)
+            .note("variables with static storage need constant initializers"),
+            Error::ConflictingLinkage { previous, .. } => {
+                Self::error("E0512", "conflicting linkage")
+                    .at(span)
+                    .note_at(*previous, "previous declaration was here")
+            }
+            Error::DeclaredExtern(_) => {
+                Self::error("E0513", "`extern` variable can't have an initializer")
+                    .at(span)
+                    .note("block scope `extern` declarations only refer to other variables")
+            }
+            Error::StaticRedec { previous, .. } => {
+                Self::error("E0514", "static declaration follows non-static declaration")
+                    .at(span)
+                    .note_at(*previous, "previous declaration was here")
+            }
+            Error::InvalidCast(_) => {
+                Self::error("E0515", "operands don't have a common type").at(span)
+            }
+            Error::ExpectedVarType(_) => {
+                Self::error("E0516", "function used as a variable").at(span)
+            }
+            Error::ExpectedFnType(_) => Self::error("E0517", "called object is not a function")
+                .at(span)
+                .label("this isn't a function"),
+            Error::NotAPointer { ty, .. } => {
+                Self::error("E0518", "indirection requires a pointer operand")
+                    .at(span)
+                    .label(format!("this has type `{ty}`"))
+            }
+            Error::IncompatibleTypes { from, to, .. } => Self::error("E0519", "incompatible types")
+                .at(span)
+                .label(format!("expected `{to}`, found `{from}`")),
+            Error::MismatchedOperands { left, right, .. } => Self::error(
+                "E0520",
+                format!("operands have mismatched types `{left}` and `{right}`"),
+            )
+            .at(span),
+            Error::InvalidOperand { ty, .. } => {
+                Self::error("E0521", format!("invalid operand of type `{ty}`")).at(span)
+            }
+            Error::ArrayInitializer { ty, .. } => Self::error(
+                "E0522",
+                format!("array of type `{ty}` needs a braced initializer"),
+            )
+            .at(span),
+            Error::ExcessElements { len, ty, .. } => match ty {
+                parse::VarType::Array(..) => {
+                    Self::error("E0523", "excess elements in array initializer")
+                        .at(span)
+                        .note(format!("the array only has {len} elements"))
+                }
+                parse::VarType::Union(_) => {
+                    Self::error("E0523", "excess elements in union initializer")
+                        .at(span)
+                        .note("a union only holds one member at a time")
+                }
+                _ => Self::error("E0523", "excess elements in struct initializer")
+                    .at(span)
+                    .note(format!("`{ty}` only has {len} members")),
+            },
+            Error::BracedScalar { ty, .. } => Self::error(
+                "E0524",
+                format!("braced initializer for scalar type `{ty}`"),
+            )
+            .at(span),
+            Error::ArrayAssignment { ty, .. } => {
+                Self::error("E0525", "arrays can't be assigned to")
+                    .at(span)
+                    .label(format!("this has type `{ty}`"))
+            }
+            Error::NotSubscriptable { ty, .. } => {
+                Self::error("E0526", "subscripted value is not an array or pointer")
+                    .at(span)
+                    .label(format!("this has type `{ty}`"))
+            }
+            Error::StringTooLong { ty, .. } => Self::error(
+                "E0527",
+                format!("initializer string is too long for `{ty}`"),
+            )
+            .at(span),
+            Error::IncompleteType { ty, .. } => {
+                let diagnostic = Self::error("E0528", format!("incomplete type `{ty}`")).at(span);
+                if *ty == parse::VarType::Void {
+                    diagnostic
+                } else {
+                    diagnostic.note("a struct can't be used until it's been defined")
+                }
+            }
+            Error::StructRedefinition { previous, ty, .. } => {
+                Self::error("E0529", format!("redefinition of `{ty}`"))
+                    .at(span)
+                    .note_at(*previous, "previous definition was here")
+            }
+            Error::DuplicateMember { previous, .. } => Self::error("E0530", "duplicate member")
+                .at(span)
+                .note_at(*previous, "previous declaration was here"),
+            Error::NotAStruct { ty, .. } => Self::error(
+                "E0531",
+                format!("member reference base type `{ty}` is not a struct or union"),
+            )
+            .at(span),
+            Error::NoMember { ty, member, .. } => {
+                Self::error("E0532", format!("no member named `{member}` in `{ty}`")).at(span)
+            }
+            Error::VoidValue(_) => {
+                Self::error("E0533", "void value not ignored as it ought to be").at(span)
+            }
+            Error::ReturnInVoid(_) => {
+                Self::error("E0534", "void function should not return a value").at(span)
+            }
+            Error::MissingReturnValue { ty, .. } => Self::error(
+                "E0535",
+                format!("function returning `{ty}` should return a value"),
+            )
+            .at(span),
+            Error::VaStartNotVariadic(_) => {
+                Self::error("E0536", "`va_start` used in function with fixed arguments").at(span)
+            }
+            Error::ConstAssignment { ty, .. } => Self::error(
+                "E0537",
+                format!("cannot assign to an object of type `{ty}`"),
+            )
+            .at(span)
+            .label("this is read-only"),
+            Error::FnAssignment { ty, .. } => {
+                Self::error("E0538", "functions can't be assigned to")
+                    .at(span)
+                    .label(format!("this has type `{ty}`"))
+            }
+            // these used to be caught while labelling loops, and keep their codes from then
+            Error::InvalidCase(_) => Self::error("E0404", "invalid case label")
+                .at(span)
+                .label("expected an integer constant expression"),
+            Error::DuplicateCase { previous, .. } => Self::error("E0406", "duplicate case value")
+                .at(span)
+                .note_at(*previous, "previous case was here"),
+            Error::ConstantOverflow { ty, .. } => Self::error(
+                "E0539",
+                format!("overflow in constant expression of type `{ty}`"),
+            )
+            .at(span),
+            Error::DesignatorRange { len, .. } => {
+                Self::error("E0540", "array index in initializer exceeds array bounds")
+                    .at(span)
+                    .note(format!("the array only has {len} elements"))
+            }
+            Error::WrongDesignator { ty, .. } => Self::error(
+                "E0541",
+                format!("designator doesn't name a member or element of `{ty}`"),
+            )
+            .at(span),
+            // these used to be caught by the parser, and keep their codes from then
+            Error::ExpectedConstant(_) => Self::error("E0204", "expected constant")
+                .at(span)
+                .label("expected an integer constant here"),
+            Error::InvalidArraySize(_) => {
+                Self::error("E0214", "array size must be a positive integer constant").at(span)
+            }
+            Error::EnumeratorRange(_) => {
+                Self::error("E0219", "enumerator value doesn't fit in an `int`").at(span)
+            }
+            Error::InvalidDesignator(_) => Self::error("E0220", "invalid array designator")
+                .at(span)
+                .label("expected a non-negative integer constant expression"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Diagnostic, Sources};
+    use crate::lex::Span;
+    use std::fs;
+
+    fn span(file: &'static str, line: usize, column: usize, len: usize) -> Span {
+        Span {
+            file,
+            line,
+            column,
+            len,
+        }
+    }
+
+    #[test]
+    fn snippets() {
+        let path = std::env::temp_dir().join("mcc_diagnostics_test.c");
+        fs::write(&path, "int main(void) {\n\tint x = 1\n\treturn x;\n}\n").unwrap();
+        let file: &'static str = path.to_str().unwrap().to_owned().leak();
+
+        let missing = span(file, 2, 11, 1);
+        let diagnostic = Diagnostic::error("E0202", "expected `;`")
+            .at(missing)
+            .note_at(span(file, 2, 6, 1), "declared here")
+            .insert(missing, ";");
+        let rendered = diagnostic.render(&mut Sources::default());
+        let expected = format!(
+            "error[E0202]: expected `;`
+ --> {file}:2:11
+  |
+2 | \tint x = 1
+  | \t         ^
+note: declared here
+ --> {file}:2:6
+  |
+2 | \tint x = 1
+  | \t    -
+help: insert `;`
+  |
+2 | \tint x = 1;
+  | \t         +
+"
+        );
+        let _ = fs::remove_file(&path);
+        assert_eq!(rendered, expected);
+    }
+
+    #[test]
+    fn json() {
+        let diagnostic = Diagnostic::error("E0100", "bad \"quote\"").note("a note");
+        assert_eq!(
+            diagnostic.json(&mut Sources::default()),
+            "{\"severity\":\"error\",\"code\":\"E0100\",\"message\":\"bad \\\"quote\\\"\",\
+             \"span\":null,\"label\":null,\"notes\":[{\"message\":\"a note\",\"span\":null}],\
+             \"fixes\":[],\"rendered\":\"error[E0100]: bad \\\"quote\\\"\\n  = note: a note\\n\"}"
+        );
+    }
+
+    #[cfg(feature = "parse")]
+    #[test]
+    fn fix_its() {
+        use crate::lex::tokenize;
+        use crate::parse::parse;
+        let fixes = |source: &[u8]| -> Vec<bool> {
+            let (_, errors) = parse(tokenize(source).unwrap());
+            (errors.iter())
+                .map(|error| Diagnostic::from(error).fix.is_some())
+                .collect()
+        };
+        assert_eq!(
+            fixes(b"int f(void) {\n  int x = 1\n  return x }\nint g(int a) { if (a {} }"),
+            [true, true, true]
+        );
+        // a `;` in front of the `,` or the `)` wouldn't fix anything
+        assert_eq!(
+            fixes(b"int f(void) {\n  for (int i = 0, j = 5; i < j; i++, j--) ;\n}\n"),
+            [false, false]
+        );
+    }
+}
@@ -1,22 +1,70 @@
 use super::slice_iter::SliceIter;
 
+use std::cmp::Ordering;
 use std::fmt::{self, Display, Formatter};
 use std::rc::Rc;
+use std::sync::Mutex;
 
 #[derive(Debug, Clone)]
 pub struct DebugToken {
     pub token: Token,
-    pub line: usize,
+    pub span: Span,
 }
 
 impl DebugToken {
-    pub fn into_inner(self) -> (Token, usize) {
-        (self.token, self.line)
+    pub fn into_inner(self) -> (Token, Span) {
+        (self.token, self.span)
     }
 
     pub const fn line(&self) -> usize {
-        self.line
+        self.span.line
+    }
+}
+
+// where something came from in the original source, lines and columns start at 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    pub file: &'static str,
+    pub line: usize,
+    pub column: usize,
+    // in bytes, how much of the line to underline
+    pub len: usize,
+}
+
+impl Span {
+    // from the start of self to the end of `end`, only spans a single line
+    pub fn to(self, end: Self) -> Self {
+        if self.file != end.file || self.line != end.line || end.column < self.column {
+            return self;
+        }
+        Self {
+            len: self.len.max(end.column + end.len - self.column),
+            ..self
+        }
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let file = if self.file.is_empty() {
+            "<stdin>"
+        } else {
+            self.file
+        };
+        write!(f, "{file}:{}:{}", self.line, self.column)
+    }
+}
+
+// file names and struct tags live as long as the compiler does, so spans and types can stay Copy
+pub fn intern(name: &str) -> &'static str {
+    static FILES: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+    let mut files = FILES.lock().unwrap();
+    if let Some(file) = files.iter().find(|file| **file == name) {
+        return file;
     }
+    let file: &'static str = Box::leak(name.into());
+    files.push(file);
+    file
 }
 
 impl std::ops::Deref for DebugToken {
@@ -73,14 +121,38 @@ pub enum Token {
     Static,
     Extern,
     Long,
+    Signed,
+    Unsigned,
+    Double,
+    Char,
+    Struct,
+    Union,
+    Enum,
+    Typedef,
+    Const,
+    Volatile,
+    Sizeof,
+    Alignof,
+    // `offsetof` from `stddef.h`
+    Offsetof,
+    // the `stdarg.h` builtins
+    VaList,
+    VaStart,
+    VaArg,
+    VaEnd,
+    VaCopy,
 
     Constant(Constant),
     Identifier(Identifier),
+    // escapes already applied, no null terminator
+    StringLiteral(Box<[u8]>),
     OpenParen,
     CloseParen,
     OpenBrace,
     Semicolon,
     CloseBrace,
+    OpenBracket,
+    CloseBracket,
     Tilde,
     Decrement,
     Minus,
@@ -120,23 +192,169 @@ pub enum Token {
 
     QuestionMark,
     Colon,
+    Dot,
+    Arrow,
+    Ellipsis,
+}
+
+// how the token is spelled in the source, for diagnostics
+impl Display for Token {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let spelling = match self {
+            Self::Constant(c) => return c.fmt(f),
+            Self::Identifier(i) => return i.fmt(f),
+            Self::StringLiteral(s) => return write!(f, "\"{}\"", s.escape_ascii()),
+            Self::Int => "int",
+            Self::Void => "void",
+            Self::Return => "return",
+            Self::If => "if",
+            Self::Else => "else",
+            Self::Goto => "goto",
+            Self::Do => "do",
+            Self::While => "while",
+            Self::For => "for",
+            Self::Break => "break",
+            Self::Continue => "continue",
+            Self::Switch => "switch",
+            Self::Default => "default",
+            Self::Case => "case",
+            Self::Static => "static",
+            Self::Extern => "extern",
+            Self::Long => "long",
+            Self::Signed => "signed",
+            Self::Unsigned => "unsigned",
+            Self::Double => "double",
+            Self::Char => "char",
+            Self::Struct => "struct",
+            Self::Union => "union",
+            Self::Enum => "enum",
+            Self::Typedef => "typedef",
+            Self::Const => "const",
+            Self::Volatile => "volatile",
+            Self::Sizeof => "sizeof",
+            Self::Alignof => "_Alignof",
+            Self::Offsetof => "__builtin_offsetof",
+            Self::VaList => "__builtin_va_list",
+            Self::VaStart => "__builtin_va_start",
+            Self::VaArg => "__builtin_va_arg",
+            Self::VaEnd => "__builtin_va_end",
+            Self::VaCopy => "__builtin_va_copy",
+            Self::OpenParen => "(",
+            Self::CloseParen => ")",
+            Self::OpenBrace => "{",
+            Self::Semicolon => ";",
+            Self::CloseBrace => "}",
+            Self::OpenBracket => "[",
+            Self::CloseBracket => "]",
+            Self::Tilde => "~",
+            Self::Decrement => "--",
+            Self::Minus => "-",
+            Self::Plus => "+",
+            Self::PlusEqual => "+=",
+            Self::MinusEqual => "-=",
+            Self::TimesEqual => "*=",
+            Self::DivEqual => "/=",
+            Self::PercentEqual => "%=",
+            Self::BitAndEqual => "&=",
+            Self::BitOrEqual => "|=",
+            Self::BitXorEqual => "^=",
+            Self::Asterisk => "*",
+            Self::Slash => "/",
+            Self::Percent => "%",
+            Self::Ampersand => "&",
+            Self::Bar => "|",
+            Self::Caret => "^",
+            Self::Increment => "++",
+            Self::LeftShift => "<<",
+            Self::LeftShiftEqual => "<<=",
+            Self::RightShift => ">>",
+            Self::RightShiftEqual => ">>=",
+            Self::Not => "!",
+            Self::LogicalAnd => "&&",
+            Self::LogicalOr => "||",
+            Self::EqualTo => "==",
+            Self::NotEqual => "!=",
+            Self::LessThan => "<",
+            Self::GreaterThan => ">",
+            Self::Leq => "<=",
+            Self::Geq => ">=",
+            Self::Equals => "=",
+            Self::Comma => ",",
+            Self::QuestionMark => "?",
+            Self::Colon => ":",
+            Self::Dot => ".",
+            Self::Arrow => "->",
+            Self::Ellipsis => "...",
+        };
+        f.write_str(spelling)
+    }
 }
 
 pub fn tokenize(bytes: &[u8]) -> Result<Box<[DebugToken]>, Error> {
     let mut iter = SliceIter::new(bytes);
+    let mut position = Position {
+        file: "",
+        line: 1,
+        line_start: 0,
+        end: bytes.len(),
+    };
 
     let mut tokens = Vec::new();
-    let mut cur_line = 0;
-    while let Some(token) = lex_slice(&mut iter, &mut cur_line)? {
-        tokens.push(DebugToken {
-            token,
-            line: cur_line,
-        });
+    loop {
+        skip_whitespace(&mut iter, &mut position)?;
+        let start = position.offset(&iter);
+        let mut span = position.span(start);
+        let Some(token) = lex_slice(&mut iter, span)? else {
+            break;
+        };
+        span.len = position.offset(&iter) - start;
+        tokens.push(DebugToken { token, span });
     }
     Ok(tokens.into())
 }
 
-fn lex_slice(iter: &mut SliceIter<u8>, cur_line: &mut usize) -> Result<Option<Token>, Error> {
+struct Position {
+    file: &'static str,
+    line: usize,
+    // offset of the first byte on the current line
+    line_start: usize,
+    end: usize,
+}
+
+impl Position {
+    fn offset(&self, iter: &SliceIter<u8>) -> usize {
+        self.end - iter.as_slice().len()
+    }
+
+    const fn span(&self, offset: usize) -> Span {
+        Span {
+            file: self.file,
+            line: self.line,
+            column: offset - self.line_start + 1,
+            len: 1,
+        }
+    }
+}
+
+fn skip_whitespace(iter: &mut SliceIter<u8>, position: &mut Position) -> Result<(), Error> {
+    loop {
+        match iter.peek() {
+            Some(b'\n') => {
+                iter.next();
+                position.line += 1;
+                position.line_start = position.offset(iter);
+            }
+            Some(a) if a.is_ascii_whitespace() => {
+                iter.next();
+            }
+            // line marker from the preprocessor, `# 12 "file.c"`
+            Some(b'#') => line_marker(iter, position)?,
+            _ => return Ok(()),
+        }
+    }
+}
+
+fn lex_slice(iter: &mut SliceIter<u8>, span: Span) -> Result<Option<Token>, Error> {
     match iter.as_slice() {
         [b'<', b'<', b'=', ..] => {
             iter.next();
@@ -155,6 +373,11 @@ fn lex_slice(iter: &mut SliceIter<u8>, cur_line: &mut usize) -> Result<Option<To
             iter.next();
             Ok(Some(Token::Decrement))
         }
+        [b'-', b'>', ..] => {
+            iter.next();
+            iter.next();
+            Ok(Some(Token::Arrow))
+        }
         [b'<', b'<', ..] => {
             iter.next();
             iter.next();
@@ -241,14 +464,35 @@ fn lex_slice(iter: &mut SliceIter<u8>, cur_line: &mut usize) -> Result<Option<To
             Ok(Some(Token::BitXorEqual))
         }
 
-        [a, ..] if !a.is_ascii() => error("Invalid Character (I Only Accept Ascii :[)"),
-        [a, ..] if a.is_ascii_whitespace() => {
-            if *a == b'\n' {
-                *cur_line += 1;
+        [b'\'', ..] => {
+            iter.next();
+            Ok(Some(Token::Constant(char_constant(iter, span)?)))
+        }
+        [b'"', ..] => {
+            iter.next();
+            let mut bytes = Vec::new();
+            while let Some(byte) = character(iter, b'"', span)? {
+                bytes.push(byte);
             }
+            Ok(Some(Token::StringLiteral(bytes.into())))
+        }
+
+        [b'.', b'.', b'.', ..] => {
             iter.next();
-            lex_slice(iter, cur_line)
+            iter.next();
+            iter.next();
+            Ok(Some(Token::Ellipsis))
         }
+        [b'.', b'0'..=b'9', ..] => {
+            iter.next();
+            Ok(Some(Token::Constant(float_constant(
+                String::from("."),
+                iter,
+                span,
+            )?)))
+        }
+
+        [a, ..] if !a.is_ascii() => error("Invalid Character (I Only Accept Ascii :[)", span),
         [a, ..] => {
             iter.next();
             Ok(Some(match a {
@@ -257,10 +501,12 @@ fn lex_slice(iter: &mut SliceIter<u8>, cur_line: &mut usize) -> Result<Option<To
                 b'{' => Token::OpenBrace,
                 b';' => Token::Semicolon,
                 b'}' => Token::CloseBrace,
+                b'[' => Token::OpenBracket,
+                b']' => Token::CloseBracket,
                 b'~' => Token::Tilde,
                 b'0'..=b'9' => {
                     let byte = AsciiDigit::from_int(*a).unwrap();
-                    Token::Constant(constant_number(byte, iter)?)
+                    Token::Constant(constant_number(byte, iter, span)?)
                 }
                 b'-' => Token::Minus,
                 b'+' => Token::Plus,
@@ -277,13 +523,45 @@ fn lex_slice(iter: &mut SliceIter<u8>, cur_line: &mut usize) -> Result<Option<To
                 b',' => Token::Comma,
                 b'?' => Token::QuestionMark,
                 b':' => Token::Colon,
-                a => literal(*a, iter)?,
+                b'.' => Token::Dot,
+                a => literal(*a, iter, span)?,
             }))
         }
         [] => Ok(None),
     }
 }
 
+fn line_marker(iter: &mut SliceIter<u8>, position: &mut Position) -> Result<(), Error> {
+    let span = position.span(position.offset(iter));
+    iter.next();
+    while iter.next_if(|b| b == b' ' || b == b'\t').is_some() {}
+    let mut digits = Vec::new();
+    while let Some(digit) = next_if_number(iter) {
+        digits.push(digit);
+    }
+    if digits.is_empty() {
+        return Err(Error::InvalidLineMarker(span));
+    }
+    while iter.next_if(|b| b == b' ' || b == b'\t').is_some() {}
+    if iter.next_if(|b| b == b'"').is_some() {
+        let mut name = Vec::new();
+        while let Some(byte) = iter.next_if(|b| b != b'"' && b != b'\n') {
+            if byte == b'\\' {
+                name.extend(iter.next());
+            } else {
+                name.push(byte);
+            }
+        }
+        position.file = intern(&String::from_utf8_lossy(&name));
+    }
+    // skip the flags, the next line is the one the marker names
+    while iter.next_if(|b| b != b'\n').is_some() {}
+    iter.next();
+    position.line = parse_u64(&digits).unwrap_or_default() as usize;
+    position.line_start = position.offset(iter);
+    Ok(())
+}
+
 impl AsciiDigit {
     const fn from_int(int: u8) -> Option<Self> {
         match int {
@@ -302,28 +580,134 @@ impl AsciiDigit {
     }
 }
 
-fn constant_number(start: AsciiDigit, iter: &mut SliceIter<u8>) -> Result<Constant, Error> {
+fn constant_number(
+    start: AsciiDigit,
+    iter: &mut SliceIter<u8>,
+    span: Span,
+) -> Result<Constant, Error> {
     let mut bytes = vec![start];
     while let Some(constant) = next_if_number(iter) {
         bytes.push(constant);
     }
+    if iter.peek().is_some_and(|b| matches!(b, b'.' | b'e' | b'E')) {
+        let digits = bytes.iter().map(|&digit| char::from(b'0' + digit as u8));
+        return float_constant(digits.collect(), iter, span);
+    }
 
-    match iter.peek() {
-        Some(b'l') => {
-            iter.next();
-            Ok(Constant::from(parse_long(&bytes)))
+    let mut suffix = Vec::new();
+    while let Some(byte) = next_if_word(iter) {
+        suffix.push(byte.to_ascii_lowercase());
+    }
+    let value = parse_u64(&bytes).ok_or(Error::ConstantTooLarge(span))?;
+    // the first type the value fits in, starting from the one the suffix asks for
+    let constant = match suffix.as_slice() {
+        b"" => i32::try_from(value)
+            .map(Constant::Int)
+            .or(i64::try_from(value).map(Constant::Long)),
+        b"l" => i64::try_from(value).map(Constant::Long),
+        b"u" => Ok(u32::try_from(value).map_or(Constant::ULong(value), Constant::UInt)),
+        b"ul" | b"lu" => Ok(Constant::ULong(value)),
+        _ => return Err(Error::InvalidConstant(span)),
+    };
+    constant.map_err(|_| Error::ConstantTooLarge(span))
+}
+
+// `text` is everything before the fraction or exponent, rust agrees with c on how to round
+fn float_constant(
+    mut text: String,
+    iter: &mut SliceIter<u8>,
+    span: Span,
+) -> Result<Constant, Error> {
+    // false if there weren't any
+    fn digits(iter: &mut SliceIter<u8>, text: &mut String) -> bool {
+        let start = text.len();
+        while let Some(digit) = iter.next_if(|b| b.is_ascii_digit()) {
+            text.push(char::from(digit));
+        }
+        text.len() > start
+    }
+    if !text.starts_with('.') && iter.next_if(|b| b == b'.').is_some() {
+        text.push('.');
+    }
+    digits(iter, &mut text);
+    if iter.next_if(|b| b == b'e' || b == b'E').is_some() {
+        text.push('e');
+        if let Some(sign) = iter.next_if(|b| b == b'+' || b == b'-') {
+            text.push(char::from(sign));
         }
-        Some(x) if !word_character(x) => {
-            let long = parse_long(&bytes);
-            i32::try_from(long)
-                .map(Constant::Int)
-                .or(Ok(Constant::Long(long)))
+        if !digits(iter, &mut text) {
+            return Err(Error::InvalidConstant(span));
         }
-        _ => Err(Error::InvalidConstant),
     }
+    if iter.peek().is_some_and(|b| word_character(b) || b == b'.') {
+        return Err(Error::InvalidConstant(span));
+    }
+    text.parse()
+        .map(Constant::Double)
+        .map_err(|_| Error::InvalidConstant(span))
+}
+
+// chars are signed, so '\xff' is -1 just like it is with gcc
+fn char_constant(iter: &mut SliceIter<u8>, span: Span) -> Result<Constant, Error> {
+    let Some(byte) = character(iter, b'\'', span)? else {
+        return Err(Error::InvalidCharacter(span));
+    };
+    if character(iter, b'\'', span)?.is_some() {
+        return Err(Error::InvalidCharacter(span));
+    }
+    Ok(Constant::Int(byte as i8 as i32))
 }
 
-fn literal(byte: u8, iter: &mut SliceIter<u8>) -> Result<Token, Error> {
+// one character of a char or string literal, `None` once we hit the closing quote
+fn character(iter: &mut SliceIter<u8>, quote: u8, span: Span) -> Result<Option<u8>, Error> {
+    match iter.next() {
+        None | Some(b'\n') => Err(Error::UnterminatedLiteral(span)),
+        Some(b'\\') => escape(iter, span).map(Some),
+        Some(byte) if byte == quote => Ok(None),
+        Some(byte) => Ok(Some(byte)),
+    }
+}
+
+fn escape(iter: &mut SliceIter<u8>, span: Span) -> Result<u8, Error> {
+    let byte = match iter.next() {
+        Some(b'n') => b'\n',
+        Some(b't') => b'\t',
+        Some(b'r') => b'\r',
+        Some(b'a') => 0x07,
+        Some(b'b') => 0x08,
+        Some(b'f') => 0x0c,
+        Some(b'v') => 0x0b,
+        Some(byte @ (b'\\' | b'\'' | b'"' | b'?')) => byte,
+        Some(digit @ b'0'..=b'7') => {
+            // at most three octal digits
+            let mut value = u32::from(digit - b'0');
+            for _ in 0..2 {
+                let Some(digit) = iter.next_if(|b| matches!(b, b'0'..=b'7')) else {
+                    break;
+                };
+                value = value * 8 + u32::from(digit - b'0');
+            }
+            u8::try_from(value).map_err(|_| Error::InvalidEscape(span))?
+        }
+        Some(b'x') => {
+            let mut value: Option<u8> = None;
+            while let Some(digit) = iter.next_if(|b| b.is_ascii_hexdigit()) {
+                let digit = (digit as char).to_digit(16).unwrap() as u8;
+                let shifted = value.unwrap_or(0).checked_mul(16);
+                value = Some(
+                    shifted
+                        .and_then(|v| v.checked_add(digit))
+                        .ok_or(Error::InvalidEscape(span))?,
+                );
+            }
+            value.ok_or(Error::InvalidEscape(span))?
+        }
+        _ => return Err(Error::InvalidEscape(span)),
+    };
+    Ok(byte)
+}
+
+fn literal(byte: u8, iter: &mut SliceIter<u8>, span: Span) -> Result<Token, Error> {
     let mut bytes = vec![byte];
     while let Some(character) = next_if_word(iter) {
         bytes.push(character);
@@ -347,18 +731,36 @@ fn literal(byte: u8, iter: &mut SliceIter<u8>) -> Result<Token, Error> {
             b"static" => Token::Static,
             b"extern" => Token::Extern,
             b"long" => Token::Long,
-            _ => identifier(bytes.into())?.into(),
+            b"signed" => Token::Signed,
+            b"unsigned" => Token::Unsigned,
+            b"double" => Token::Double,
+            b"char" => Token::Char,
+            b"struct" => Token::Struct,
+            b"union" => Token::Union,
+            b"enum" => Token::Enum,
+            b"typedef" => Token::Typedef,
+            b"const" => Token::Const,
+            b"volatile" => Token::Volatile,
+            b"sizeof" => Token::Sizeof,
+            b"_Alignof" => Token::Alignof,
+            b"__builtin_offsetof" => Token::Offsetof,
+            b"__builtin_va_list" => Token::VaList,
+            b"__builtin_va_start" => Token::VaStart,
+            b"__builtin_va_arg" => Token::VaArg,
+            b"__builtin_va_end" => Token::VaEnd,
+            b"__builtin_va_copy" => Token::VaCopy,
+            _ => identifier(bytes.into(), span)?.into(),
         })
     } else {
-        Err(Error::InvalidLiteral)
+        Err(Error::InvalidLiteral(span))
     }
 }
 
-fn identifier(bytes: Box<[u8]>) -> Result<Identifier, Error> {
+fn identifier(bytes: Box<[u8]>, span: Span) -> Result<Identifier, Error> {
     if word_start(bytes[0]) && bytes[1..].iter().all(|&x| word_character(x)) {
         Ok(Identifier(bytes.into()))
     } else {
-        Err(Error::InvalidIdentifier)
+        Err(Error::InvalidIdentifier(span))
     }
 }
 
@@ -400,17 +802,38 @@ enum AsciiDigit {
     Nine = 9,
 }
 
-fn parse_long(slice: &[AsciiDigit]) -> i64 {
-    let mut cur = 0i64;
-    for (place, digit) in slice.iter().map(|&x| i64::from(x as u8)).rev().enumerate() {
-        cur += 10i64.pow(place as u32) * digit;
-    }
-    cur
+// `None` if it doesn't fit
+fn parse_u64(slice: &[AsciiDigit]) -> Option<u64> {
+    slice.iter().try_fold(0u64, |cur, &digit| {
+        cur.checked_mul(10)?.checked_add(digit as u64)
+    })
 }
 impl Token {
     pub const fn identifier(&self) -> bool {
         matches!(self, Self::Identifier(_))
     }
+    pub const fn type_specifier(&self) -> bool {
+        matches!(
+            self,
+            Self::Int
+                | Self::Long
+                | Self::Signed
+                | Self::Unsigned
+                | Self::Double
+                | Self::Char
+                | Self::Struct
+                | Self::Union
+                | Self::Enum
+                | Self::Void
+                | Self::VaList
+                | Self::Const
+                | Self::Volatile
+        )
+    }
+    // anything that can start a declaration, typedef names aside
+    pub const fn specifier(&self) -> bool {
+        self.type_specifier() || matches!(self, Self::Static | Self::Extern | Self::Typedef)
+    }
     pub const fn constant(&self) -> bool {
         matches!(self, Self::Constant(_))
     }
@@ -495,28 +918,77 @@ impl From<Constant> for Token {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone, Ord, PartialOrd)]
+#[derive(Debug, Copy, Clone)]
 pub enum Constant {
     Int(i32),
     Long(i64),
+    UInt(u32),
+    ULong(u64),
+    Double(f64),
+    // never lexed, character constants are ints, these only come out of typecheck
+    Char(i8),
+    UChar(u8),
 }
 
 impl Constant {
     pub fn int(&self) -> i32 {
-        match self {
-            Self::Int(i) => *i,
-            Self::Long(l) => *l as i32,
-        }
+        self.long() as i32
     }
 
+    // unsigned values keep their bits, which is all codegen cares about
     pub fn long(&self) -> i64 {
         match self {
             Self::Int(i) => *i as i64,
             Self::Long(l) => *l,
+            Self::UInt(u) => *u as i64,
+            Self::ULong(u) => *u as i64,
+            Self::Double(d) => *d as i64,
+            Self::Char(c) => *c as i64,
+            Self::UChar(c) => *c as i64,
+        }
+    }
+
+    pub const fn is_double(&self) -> bool {
+        matches!(self, Self::Double(_))
+    }
+
+    const fn rank(&self) -> u8 {
+        match self {
+            Self::Int(_) => 0,
+            Self::Long(_) => 1,
+            Self::UInt(_) => 2,
+            Self::ULong(_) => 3,
+            Self::Double(_) => 4,
+            Self::Char(_) => 5,
+            Self::UChar(_) => 6,
+        }
+    }
+}
+
+// switch cases get sorted, doubles are compared bit for bit so the order stays total
+impl Ord for Constant {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::Double(a), Self::Double(b)) => a.total_cmp(b),
+            _ => (self.rank(), self.long()).cmp(&(other.rank(), other.long())),
         }
     }
 }
 
+impl PartialOrd for Constant {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Constant {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Constant {}
+
 impl From<i32> for Constant {
     fn from(i: i32) -> Self {
         Self::Int(i)
@@ -534,21 +1006,49 @@ impl fmt::Display for Constant {
         match self {
             Self::Int(i) => i.fmt(f),
             Self::Long(l) => l.fmt(f),
+            Self::UInt(u) => u.fmt(f),
+            Self::ULong(u) => u.fmt(f),
+            Self::Double(d) => write!(f, "{d:?}"),
+            Self::Char(c) => c.fmt(f),
+            Self::UChar(c) => c.fmt(f),
         }
     }
 }
 
 #[derive(Debug)]
 pub enum Error {
-    InvalidConstant,
-    InvalidLiteral,
-    InvalidIdentifier,
-    NotAscii,
-    Other(String),
+    InvalidConstant(Span),
+    InvalidLiteral(Span),
+    InvalidIdentifier(Span),
+    InvalidLineMarker(Span),
+    NotAscii(Span),
+    ConstantTooLarge(Span),
+    UnterminatedLiteral(Span),
+    InvalidEscape(Span),
+    // empty or more than one character
+    InvalidCharacter(Span),
+    Other(String, Span),
+}
+
+impl Error {
+    pub const fn span(&self) -> Span {
+        match self {
+            Self::InvalidConstant(span)
+            | Self::InvalidLiteral(span)
+            | Self::InvalidIdentifier(span)
+            | Self::InvalidLineMarker(span)
+            | Self::NotAscii(span)
+            | Self::ConstantTooLarge(span)
+            | Self::UnterminatedLiteral(span)
+            | Self::InvalidEscape(span)
+            | Self::InvalidCharacter(span)
+            | Self::Other(_, span) => *span,
+        }
+    }
 }
 
-fn error<T>(message: &str) -> Result<T, Error> {
-    Err(Error::Other(message.into()))
+fn error<T>(message: &str, span: Span) -> Result<T, Error> {
+    Err(Error::Other(message.into(), span))
 }
 
 impl Display for Identifier {
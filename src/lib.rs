@@ -4,9 +4,11 @@ use std::fs;
 use std::io;
 use std::path::PathBuf;
 
+pub mod diagnostics;
 pub mod lex;
 pub use lex::DebugToken;
 pub use lex::Token;
+pub mod preprocess;
 pub mod slice_iter;
 
 #[cfg(feature = "codegen")]
@@ -22,6 +24,8 @@ pub static CONFIG: OnceLock<Config> = OnceLock::new();
 pub struct Config {
     pub stage: Option<CompileStage>,
     pub version: CVersion,
+    pub target: Target,
+    pub optimizations: Optimizations,
 }
 
 pub fn compile(mut path: PathBuf) -> Result<PathBuf, Error> {
@@ -53,7 +57,8 @@ pub fn compile(mut path: PathBuf) -> Result<PathBuf, Error> {
                     return Ok("".into());
                 } else {
                     let code = codegen::generate(program, should_emit(&stage), map);
-                    path.set_extension("S");
+                    // not `.S`, gcc would run the preprocessor over it and `linux` is a macro there
+                    path.set_extension("s");
                     fs::write(&path, &code)?;
                 }
             }
@@ -85,9 +90,12 @@ const fn should_codegen(s: &Option<CompileStage>) -> bool {
     }
 }
 
+// what did parse is only partial, the later stages would just trip over whatever didn't
 fn parse(tokens: Box<[DebugToken]>) -> Result<parse::ast::Program, Error> {
-    let tokens = tokens.into_iter().map(|x| x.token).collect();
-    Ok(parse::parse(tokens)?)
+    match parse::parse(tokens) {
+        (program, errors) if errors.is_empty() => Ok(program),
+        (_, errors) => Err(Error::Parsing(errors.into())),
+    }
 }
 
 #[derive(Copy, Clone, Eq, PartialEq)]
@@ -96,6 +104,82 @@ pub enum CVersion {
     C23,
 }
 
+// decides symbol mangling and which directives the assembler wants
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Target {
+    // mach-o, every symbol gets a leading underscore
+    Darwin,
+    // elf/system v, undefined functions get called through the plt
+    Linux,
+}
+
+impl Target {
+    pub const fn host() -> Self {
+        if cfg!(target_os = "macos") {
+            Self::Darwin
+        } else {
+            Self::Linux
+        }
+    }
+
+    pub fn current() -> Self {
+        #[cfg(test)]
+        if let Some(target) = TEST_TARGET.get() {
+            return target;
+        }
+        CONFIG.get().map_or(Self::host(), |config| config.target)
+    }
+
+    // `CONFIG` can only be set once per process, every test runs on its own thread
+    #[cfg(test)]
+    pub fn set_for_test(target: Self) {
+        TEST_TARGET.set(Some(target));
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    static TEST_TARGET: std::cell::Cell<Option<Target>> = const { std::cell::Cell::new(None) };
+}
+
+// which of the optimization passes run, `-O` turns on all of them
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct Optimizations {
+    pub fold_constants: bool,
+    pub eliminate_unreachable_code: bool,
+    pub propagate_copies: bool,
+    pub eliminate_dead_stores: bool,
+    // the passes above see every variable written once, then the phis go back to copies
+    pub ssa: bool,
+    // over the final assembly rather than tacky
+    pub peephole: bool,
+}
+
+impl Optimizations {
+    pub const ALL: Self = Self {
+        fold_constants: true,
+        eliminate_unreachable_code: true,
+        propagate_copies: true,
+        eliminate_dead_stores: true,
+        ssa: true,
+        peephole: true,
+    };
+
+    pub fn current() -> Self {
+        CONFIG
+            .get()
+            .map_or(Self::default(), |config| config.optimizations)
+    }
+
+    // whether any of the tacky passes run
+    pub fn any(&self) -> bool {
+        Self {
+            peephole: false,
+            ..*self
+        } != Self::default()
+    }
+}
+
 #[derive(PartialEq, Eq, Copy, Clone, Debug)]
 pub enum CompileStage {
     Lex,
@@ -118,7 +202,7 @@ pub enum Error {
     Lexing(lex::Error),
 
     #[cfg(feature = "parse")]
-    Parsing(parse::Error),
+    Parsing(Box<[parse::Error]>),
 
     #[cfg(feature = "semantics")]
     Semantics(semantics::Error),
@@ -146,6 +230,39 @@ impl From<lex::Error> for Error {
 #[cfg(feature = "parse")]
 impl From<parse::Error> for Error {
     fn from(e: parse::Error) -> Self {
-        Error::Parsing(e)
+        Error::Parsing(Box::new([e]))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{compile, CVersion, Config, Optimizations, Target, CONFIG};
+    use std::fs;
+    use std::process::Command;
+
+    #[test]
+    fn assembly_is_left_alone() {
+        let _ = CONFIG.set(Config {
+            stage: None,
+            version: CVersion::C23,
+            target: Target::host(),
+            optimizations: Optimizations::default(),
+        });
+        let dir = std::env::temp_dir().join(format!("mcc-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("names.i");
+        fs::write(&source, "int linux = 3; int unix(void) { return linux; }").unwrap();
+
+        let assembly = compile(source).unwrap();
+        assert_eq!(assembly.extension().unwrap(), "s");
+        let status = Command::new("gcc")
+            .arg("-c")
+            .arg(&assembly)
+            .arg("-o")
+            .arg(dir.join("names.o"))
+            .status()
+            .unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(status.success());
     }
 }
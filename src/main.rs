@@ -1,28 +1,59 @@
+use mcc::diagnostics::{self, Diagnostic, Sources};
 use mcc::CompileStage;
 use std::fmt;
 use std::io;
 
+use mcc::preprocess::{self, Preprocessor};
 use mcc::CVersion;
 use mcc::Config;
+use mcc::Optimizations;
+use mcc::Target;
 use mcc::CONFIG;
+use std::fs;
 use std::path::Path;
 use std::path::PathBuf;
 use std::process::Command;
+use std::process::ExitCode;
 
-fn main() -> Result<(), MCCError> {
+fn main() -> ExitCode {
     let Some(args) = Args::parse() else {
-        return Err(MCCError::Usage);
+        eprintln!("{}", MCCError::Usage);
+        return ExitCode::FAILURE;
     };
 
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            report(&e.diagnostics(), args.error_format);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn report(diagnostics: &[Diagnostic], format: ErrorFormat) {
+    match format {
+        ErrorFormat::Human => eprint!("{}", diagnostics::render(diagnostics)),
+        ErrorFormat::Json => {
+            let mut sources = Sources::default();
+            for diagnostic in diagnostics {
+                eprintln!("{}", diagnostic.json(&mut sources));
+            }
+        }
+    }
+}
+
+fn run(args: &Args) -> Result<(), MCCError> {
     let _ = CONFIG.set(Config {
         stage: args.stage,
         version: CVersion::C23,
+        target: args.target,
+        optimizations: args.optimizations,
     });
     let output = args.file.with_extension("i");
-    let preprocessed_file = preprocess(&args.file, output).map_err(MCCError::Preprocess)?;
+    let preprocessed_file = preprocess(args, output).map_err(MCCError::Preprocess)?;
     let object_file = mcc::compile(preprocessed_file).map_err(MCCError::Compile)?;
     if CONFIG.get().unwrap().stage.is_none() {
-        assemble(&object_file, &args).map_err(MCCError::Assemble)
+        assemble(&object_file, args).map_err(MCCError::Assemble)
     } else {
         Ok(())
     }
@@ -32,6 +63,18 @@ struct Args {
     file: PathBuf,
     stage: Option<CompileStage>,
     compile: bool,
+    target: Target,
+    optimizations: Optimizations,
+    include_paths: Vec<PathBuf>,
+    defines: Vec<String>,
+    error_format: ErrorFormat,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum ErrorFormat {
+    Human,
+    // one json object per line on stderr, for editors
+    Json,
 }
 
 impl Args {
@@ -40,6 +83,11 @@ impl Args {
         let mut stage: Option<CompileStage> = None;
         let mut keep_asm = false;
         let mut compile: bool = false;
+        let mut target: Option<Target> = None;
+        let mut optimizations = Optimizations::default();
+        let mut include_paths = Vec::new();
+        let mut defines = Vec::new();
+        let mut error_format: Option<ErrorFormat> = None;
 
         let mut args = std::env::args();
         args.next();
@@ -72,6 +120,14 @@ impl Args {
                     }
                 }
 
+                // these can be repeated, turning a pass on twice doesn't hurt
+                "-O" | "--optimize" => optimizations = Optimizations::ALL,
+                "--fold-constants" => optimizations.fold_constants = true,
+                "--eliminate-unreachable-code" => optimizations.eliminate_unreachable_code = true,
+                "--propagate-copies" => optimizations.propagate_copies = true,
+                "--eliminate-dead-stores" => optimizations.eliminate_dead_stores = true,
+                "--ssa" => optimizations.ssa = true,
+                "--peephole" => optimizations.peephole = true,
                 "-S" => {
                     if keep_asm {
                         return None;
@@ -84,6 +140,32 @@ impl Args {
                     }
                     compile = true;
                 }
+                "--target=linux" => {
+                    if !Self::try_update(&mut target, Target::Linux) {
+                        return None;
+                    }
+                }
+                "--target=darwin" | "--target=macos" => {
+                    if !Self::try_update(&mut target, Target::Darwin) {
+                        return None;
+                    }
+                }
+                "--error-format=human" => {
+                    if !Self::try_update(&mut error_format, ErrorFormat::Human) {
+                        return None;
+                    }
+                }
+                "--error-format=json" => {
+                    if !Self::try_update(&mut error_format, ErrorFormat::Json) {
+                        return None;
+                    }
+                }
+                include if include.starts_with("-I") && include.len() > 2 => {
+                    include_paths.push(PathBuf::from(&include[2..]));
+                }
+                define if define.starts_with("-D") && define.len() > 2 => {
+                    defines.push(define[2..].into());
+                }
                 new_path => {
                     if !Self::try_update(&mut path, new_path.into()) {
                         return None;
@@ -95,6 +177,11 @@ impl Args {
             file,
             stage,
             compile,
+            target: target.unwrap_or(Target::host()),
+            optimizations,
+            include_paths,
+            defines,
+            error_format: error_format.unwrap_or(ErrorFormat::Human),
         })
     }
 
@@ -108,14 +195,22 @@ impl Args {
     }
 }
 
-fn preprocess(input: &Path, mut output: PathBuf) -> Result<PathBuf, io::Error> {
-    output.set_extension("i");
-    Command::new("gcc")
-        .args(["-E", "-P"])
-        .arg(input)
-        .arg("-o")
-        .arg(&output)
-        .status()?;
+fn preprocess(args: &Args, output: PathBuf) -> Result<PathBuf, preprocess::Error> {
+    let mut system_paths = vec![PathBuf::from("/usr/local/include")];
+    // debian and ubuntu keep the headers that depend on the architecture, like bits/, apart
+    if args.target == Target::Linux {
+        system_paths.push(PathBuf::from("/usr/include/x86_64-linux-gnu"));
+    }
+    system_paths.push(PathBuf::from("/usr/include"));
+
+    let mut preprocessor = Preprocessor::new(args.include_paths.clone(), system_paths);
+    for define in &args.defines {
+        preprocessor.define(define)?;
+    }
+    let bytes = preprocessor.run(&args.file);
+    report(&preprocessor.take_warnings(), args.error_format);
+    let bytes = bytes?;
+    fs::write(&output, bytes).map_err(preprocess::Error::Io)?;
     Ok(output)
 }
 
@@ -127,22 +222,39 @@ fn assemble(input: &Path, args: &Args) -> Result<(), io::Error> {
         command.arg("-c");
     }
 
-    command.status().map(|_| ())
+    match command.status()? {
+        status if status.success() => Ok(()),
+        status => Err(io::Error::other(format!("gcc {status}"))),
+    }
 }
 
 #[derive(Debug)]
 pub enum MCCError {
     Usage,
-    Preprocess(io::Error),
+    Preprocess(preprocess::Error),
     Compile(mcc::Error),
     Assemble(io::Error),
 }
 
+impl MCCError {
+    fn diagnostics(&self) -> Vec<Diagnostic> {
+        match self {
+            Self::Usage => vec![Diagnostic::error("E0000", "invalid arguments")],
+            Self::Preprocess(e) => vec![e.into()],
+            Self::Compile(e) => e.diagnostics(),
+            Self::Assemble(e) => vec![Diagnostic::error(
+                "E0004",
+                format!("couldn't run the assembler: {e}"),
+            )],
+        }
+    }
+}
+
 impl fmt::Display for MCCError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Self::Usage => write!(f, "Usage: mcc [input] [output]\n Arguments: \n\t[input]:The File to Compile\n\t[output] Optional output, if [input] ends in .c, compiles to output with .c"),
-            _ => todo!()
+            _ => f.write_str(&diagnostics::render(&self.diagnostics())),
         }
     }
 }
@@ -1,96 +1,314 @@
 pub mod ast;
+pub mod layout;
+mod length;
 mod specifier_list;
 
 pub use ast::{
-    Arr, Binary, Block, BlockItem, Bop, Dec, Expr, FnDec, FnType, ForInit, Label, Param, ParamList,
-    Program, Stmnt, StorageClass, UnOp, Unary, VarDec, VarType,
+    Arr, Binary, Block, BlockItem, Bop, Dec, Designator, EnumDec, Enumerator, Expr, FnDec, FnType,
+    ForInit, Initializer, Label, MemberDec, Param, ParamList, Program, Qualifiers, Stmnt,
+    StorageClass, StructDec, TypedefDec, UnOp, Unary, VarDec, VarType,
 };
+pub use layout::{va_list, Layout, VA_LIST_TAG};
+pub use length::Length;
 
 pub use ast::inc_dec::{self, *};
 
-use super::lex::Identifier;
+use super::lex::{intern, DebugToken, Identifier, Span};
 use super::slice_iter::TokenIter;
 
 use super::Token;
-use std::fmt::{self, Display, Formatter};
+use std::fmt::{self, Display, Formatter, Write};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
 
-pub fn parse(tokens: Box<[Token]>) -> Result<Program, Error> {
+// keeps going after a syntax error so we can report all of them, whatever didn't parse is left
+// out of the program
+pub fn parse(tokens: Box<[DebugToken]>) -> (Program, Vec<Error>) {
     let mut tokens = TokenIter::new(tokens);
-    let program = program(&mut tokens)?;
-    if tokens.is_empty() {
-        Ok(program)
-    } else {
-        Err(Error::ExtraStuff)
-    }
+    let program = program(&mut tokens);
+    (program, tokens.take_errors())
 }
 
-fn program(tokens: &mut TokenIter) -> Result<Program, Error> {
+fn program(tokens: &mut TokenIter) -> Program {
     let mut functions = Vec::new();
     while !tokens.is_empty() {
-        functions.push(declaration(tokens)?);
+        let before = tokens.as_slice().len();
+        match declaration(tokens) {
+            Ok(decs) => functions.extend(decs),
+            Err(e) => {
+                tokens.report(e);
+                synchronize(tokens, before, 0);
+            }
+        }
     }
-    Ok(Program(functions.into()))
+    Program(functions.into())
 }
 
-fn declaration(tokens: &mut TokenIter) -> Result<Dec, Error> {
-    let spec_list @ SpecifierList { sc, typ } = specifiers(tokens)?;
+// panic mode, throw tokens away until a `;`, the end of a block, or something that starts the
+// next declaration (or statement, inside a block). `outer` is how many braces deep the
+// declaration or statement that failed started, the error itself can be further in
+fn synchronize(tokens: &mut TokenIter, before: usize, outer: usize) {
+    // if the error was on the very first token, skip it so we can't get stuck
+    if tokens.as_slice().len() == before {
+        tokens.next();
+    }
+    let top_level = outer == 0;
+    while let Some(token) = tokens.peek() {
+        let depth = tokens.depth();
+        match token {
+            Token::Semicolon if depth == outer => {
+                tokens.next();
+                return;
+            }
+            // the enclosing block gets to consume it
+            Token::CloseBrace if depth == outer && !top_level => return,
+            // the end of a body we were in, `struct s { ... };` has one more `;` to go
+            Token::CloseBrace if depth == outer + 1 => {
+                tokens.next();
+                tokens.next_if(|t| t == &Token::Semicolon);
+                return;
+            }
+            token if token.specifier() && depth == outer => return,
+            Token::Return
+            | Token::If
+            | Token::Switch
+            | Token::Goto
+            | Token::Do
+            | Token::While
+            | Token::For
+            | Token::Break
+            | Token::Continue
+            | Token::Case
+            | Token::Default
+                if depth == outer && !top_level =>
+            {
+                return
+            }
+            _ => {}
+        }
+        tokens.next();
+    }
+}
 
-    let name = tokens.consume_identifier()?;
+// a declaration can declare any number of things, and define the structs and enums it uses on
+// the way
+fn declaration(tokens: &mut TokenIter) -> Result<Vec<Dec>, Error> {
+    // `struct s;` declares the tag in this scope, even if there's one further out
+    if let [Token::Struct | Token::Union, Token::Identifier(_), Token::Semicolon, ..] =
+        tokens.as_slice()
+    {
+        let union = tokens.next() == Some(Token::Union);
+        let span = tokens.span();
+        let tag = intern(&tokens.consume_identifier()?.to_string());
+        tokens.next();
+        return Ok(vec![Dec::Struct(StructDec {
+            tag,
+            union,
+            members: None,
+            span,
+        })]);
+    }
+    let mut decs = Vec::new();
+    let specifiers = specifiers(tokens, &mut decs)?;
+    if tokens.next_if(|t| t == &Token::Semicolon).is_some() {
+        return Ok(decs);
+    }
 
-    match tokens.consume_any()? {
-        Token::Equals => top_level_vardec(tokens, spec_list, name).map(Dec::from),
-        Token::OpenParen => top_level_fndec(tokens, spec_list, name).map(Dec::from),
-        // Variable Declaration no Initializer
-        Token::Semicolon => Ok(Dec::Var(VarDec {
-            name,
-            init: None,
-            sc,
-            typ,
-        })),
-        _ => Err(Error::Catchall("expected initializer or semicolon")),
+    loop {
+        let span = tokens.span();
+        let (name, typ, params) = process(declarator(tokens)?, specifiers.typ, span)?;
+        let Some(name) = name else {
+            return Err(Error::ExpectedIdentifier(span));
+        };
+        if specifiers.typedef {
+            if tokens.peek() == Some(&Token::Equals) {
+                return Err(Error::TypedefInit(tokens.span()));
+            }
+            tokens.define_type(&name, typ);
+            if typ.has_length() {
+                decs.push(Dec::Typedef(TypedefDec { name, typ, span }));
+            }
+        } else if let VarType::Function(fn_type) = typ {
+            tokens.declare(&name);
+            let first = decs
+                .iter()
+                .all(|dec| matches!(dec, Dec::Struct(_) | Dec::Enum(_)));
+            let body = match params {
+                Some(ref params) if first && tokens.peek() == Some(&Token::OpenBrace) => {
+                    Some(function_body(tokens, params)?)
+                }
+                _ => None,
+            };
+            let defined = body.is_some();
+            decs.push(Dec::Fn(FnDec {
+                name,
+                params: params.unwrap_or_else(|| unnamed_params(fn_type)),
+                body,
+                sc: specifiers.sc,
+                typ: fn_type.clone(),
+                span,
+            }));
+            if defined {
+                return Ok(decs);
+            }
+        } else {
+            tokens.declare(&name);
+            let init = if tokens.next_if(|t| t == &Token::Equals).is_some() {
+                Some(initializer(tokens)?)
+            } else {
+                None
+            };
+            // the `;` was most likely forgotten after an initializer
+            if init.is_some() && !matches!(tokens.peek(), Some(Token::Comma | Token::Semicolon)) {
+                tokens.consume(Token::Semicolon)?;
+            }
+            decs.push(Dec::Var(VarDec {
+                name,
+                init,
+                sc: specifiers.sc,
+                typ,
+                span,
+            }));
+        }
+        match tokens.consume_any()? {
+            Token::Comma => {}
+            Token::Semicolon => return Ok(decs),
+            _ => {
+                return Err(Error::Catchall(
+                    "expected initializer or semicolon",
+                    tokens.prev_span(),
+                ))
+            }
+        }
     }
 }
 
-fn top_level_fndec(
-    tokens: &mut TokenIter,
-    SpecifierList { sc, typ }: SpecifierList,
-    name: Identifier,
-) -> Result<FnDec, Error> {
-    let params = param_list(tokens)?;
-
-    let fn_type = FnType {
-        ret: Some(typ),
-        params: params.iter().map(|p| p.typ).collect(),
-    };
+// the parameters are in scope for the whole body, and every one of them needs a name
+fn function_body(tokens: &mut TokenIter, params: &ParamList) -> Result<Block, Error> {
+    if let Some(param) = params.iter().find(|param| param.unnamed()) {
+        return Err(Error::NamelessParam(param.span));
+    }
+    tokens.push_scope();
+    for param in params {
+        tokens.declare(&param.name);
+    }
+    let body = block(tokens);
+    tokens.pop_scope();
+    body
+}
 
-    let body = if tokens.next_if(|x| x == &Token::Semicolon).is_some() {
-        None
-    } else {
-        Some(block(tokens)?)
-    };
+// for a function declared through a typedef, or with a declarator too complicated to have
+// given us the parameters directly
+fn unnamed_params(typ: &FnType) -> ParamList {
+    typ.params
+        .iter()
+        .enumerate()
+        .map(|(i, &typ)| Param {
+            typ,
+            name: Identifier::from(i.to_string()),
+            span: Span::default(),
+        })
+        .collect()
+}
 
-    Ok(FnDec {
-        name,
-        params,
-        body,
-        typ: fn_type,
-        sc,
+// `struct s`, `struct s { ... }` or `struct { ... }`, the definitions are pushed onto `defs`
+// with any nested ones ahead of the struct they're nested in
+fn struct_specifier(tokens: &mut TokenIter, defs: &mut Vec<Dec>) -> Result<VarType, Error> {
+    static ANONYMOUS: AtomicU32 = AtomicU32::new(0);
+    let union = tokens.next() == Some(Token::Union);
+    let span = tokens.span();
+    let tag = match tokens.peek() {
+        Some(Token::Identifier(tag)) => {
+            let tag = intern(&tag.to_string());
+            tokens.next();
+            tag
+        }
+        // `tag_name` strips the number back off for diagnostics
+        Some(Token::OpenBrace) => intern(&format!(
+            "(anonymous).{}",
+            ANONYMOUS.fetch_add(1, Ordering::Relaxed)
+        )),
+        _ => return Err(Error::ExpectedIdentifier(span)),
+    };
+    if tokens.next_if(|t| t == &Token::OpenBrace).is_some() {
+        let mut members = member_declaration(tokens, defs)?;
+        while tokens.next_if(|t| t == &Token::CloseBrace).is_none() {
+            members.extend(member_declaration(tokens, defs)?);
+        }
+        defs.push(Dec::Struct(StructDec {
+            tag,
+            union,
+            members: Some(members.into()),
+            span,
+        }));
+    }
+    Ok(if union {
+        VarType::Union(tag)
+    } else {
+        VarType::Struct(tag)
     })
 }
 
-fn top_level_vardec(
-    iter: &mut TokenIter,
-    SpecifierList { sc, typ }: SpecifierList,
-    name: Identifier,
-) -> Result<VarDec, Error> {
-    let exp = expression(iter, None)?;
-    iter.consume(Token::Semicolon)?;
-    Ok(VarDec {
-        name,
-        init: Some(exp),
-        sc,
-        typ,
-    })
+// `enum e`, `enum e { A, B = 2 }` or `enum { ... }`, the enumerators are in scope as soon as
+// they're declared so later values can be written in terms of earlier ones, typecheck works out
+// what those are
+fn enum_specifier(tokens: &mut TokenIter, defs: &mut Vec<Dec>) -> Result<VarType, Error> {
+    tokens.next();
+    let span = tokens.span();
+    let tagged = tokens
+        .next_if(|t| matches!(t, Token::Identifier(_)))
+        .is_some();
+    if !tagged && tokens.peek() != Some(&Token::OpenBrace) {
+        return Err(Error::ExpectedIdentifier(span));
+    }
+    if tokens.next_if(|t| t == &Token::OpenBrace).is_some() {
+        let mut enumerators = Vec::new();
+        loop {
+            let span = tokens.span();
+            let name = tokens.consume_identifier()?;
+            let value = if tokens.next_if(|t| t == &Token::Equals).is_some() {
+                Some(assignment_expression(tokens)?)
+            } else {
+                None
+            };
+            tokens.declare(&name);
+            enumerators.push(Enumerator { name, value, span });
+            // the last one can have a trailing comma
+            if tokens.next_if(|t| t == &Token::Comma).is_none() {
+                tokens.consume(Token::CloseBrace)?;
+                break;
+            }
+            if tokens.next_if(|t| t == &Token::CloseBrace).is_some() {
+                break;
+            }
+        }
+        defs.push(Dec::Enum(EnumDec {
+            enumerators: enumerators.into(),
+            span,
+        }));
+    }
+    Ok(VarType::Int)
+}
+
+fn member_declaration(
+    tokens: &mut TokenIter,
+    defs: &mut Vec<Dec>,
+) -> Result<Vec<MemberDec>, Error> {
+    let start = tokens.span();
+    let typ = specifier_list::get_specifiers(tokens, defs)?.type_specifier(start)?;
+    let mut members = Vec::new();
+    loop {
+        let span = tokens.span();
+        let (name, typ, _) = process(declarator(tokens)?, typ, span)?;
+        let Some(name) = name else {
+            return Err(Error::ExpectedIdentifier(span));
+        };
+        members.push(MemberDec { name, typ, span });
+        if tokens.next_if(|t| t == &Token::Comma).is_none() {
+            tokens.consume(Token::Semicolon)?;
+            return Ok(members);
+        }
+    }
 }
 
 // type specifier
@@ -98,96 +316,341 @@ fn top_level_vardec(
 pub struct SpecifierList {
     sc: Option<StorageClass>,
     typ: VarType,
+    typedef: bool,
 }
 
-fn specifiers(tokens: &mut TokenIter) -> Result<SpecifierList, Error> {
-    let builder = specifier_list::get_specifiers(tokens)?;
-    builder.done()
+fn specifiers(tokens: &mut TokenIter, defs: &mut Vec<Dec>) -> Result<SpecifierList, Error> {
+    let span = tokens.span();
+    let builder = specifier_list::get_specifiers(tokens, defs)?;
+    builder.done(span)
 }
 
+// what goes in a cast, `va_arg` or a parameter list, where there's no room to define a struct
+// or an enum
 fn type_specifier(tokens: &mut TokenIter) -> Result<VarType, Error> {
-    let (res, num) = match tokens.as_slice() {
-        [Token::Long | Token::Int, Token::Int | Token::Long, ..] => (VarType::Long, 2),
-        [Token::Int, ..] => (VarType::Int, 1),
-        [Token::Long, ..] => (VarType::Long, 1),
-        _ => return Err(Error::InvalidSpecifiers),
+    let span = tokens.span();
+    let mut defs = Vec::new();
+    let builder = specifier_list::get_specifiers(tokens, &mut defs)?;
+    match defs.first() {
+        Some(Dec::Struct(StructDec { span, .. }) | Dec::Enum(EnumDec { span, .. })) => {
+            Err(Error::StructDefinition(*span))
+        }
+        _ => builder.type_specifier(span),
+    }
+}
+
+// `int`, `char *`, `int (*)(long)`
+fn type_name(tokens: &mut TokenIter) -> Result<VarType, Error> {
+    let typ = type_specifier(tokens)?;
+    let span = tokens.span();
+    match process(declarator(tokens)?, typ, span)? {
+        (None, typ, _) => Ok(typ),
+        (Some(_), ..) => Err(Error::InvalidDeclarator(
+            "type names can't have a name",
+            span,
+        )),
+    }
+}
+
+// whether the next token can start a type name, for telling casts apart from parentheses
+fn at_type_name(tokens: &TokenIter) -> bool {
+    starts_type_name(tokens, tokens.peek())
+}
+
+fn starts_type_name(tokens: &TokenIter, token: Option<&Token>) -> bool {
+    match token {
+        Some(Token::Identifier(name)) => tokens.typedef(name).is_some(),
+        Some(token) => token.type_specifier(),
+        None => false,
+    }
+}
+
+// what wraps the base type, read inside out: `*const p`, `(*fp)(long)`, `a[2][3]`, or just
+// nothing in a type name
+#[derive(Debug)]
+enum Declarator {
+    Name(Identifier),
+    Abstract,
+    Pointer(Box<Declarator>, Qualifiers),
+    // no length for `[]`
+    Array(Box<Declarator>, Option<Expr>),
+    Function(Box<Declarator>, ParamList, bool),
+}
+
+fn declarator(tokens: &mut TokenIter) -> Result<Declarator, Error> {
+    if tokens.next_if(|t| t == &Token::Asterisk).is_some() {
+        let mut qualifiers = Qualifiers::NONE;
+        while let Some(token) = tokens.next_if(|t| matches!(t, Token::Const | Token::Volatile)) {
+            qualifiers = qualifiers.union(if token == Token::Const {
+                Qualifiers::CONST
+            } else {
+                Qualifiers::VOLATILE
+            });
+        }
+        return Ok(Declarator::Pointer(
+            Box::new(declarator(tokens)?),
+            qualifiers,
+        ));
+    }
+    let mut declarator = match tokens.peek() {
+        Some(Token::Identifier(name)) => {
+            let name = Declarator::Name(name.clone());
+            tokens.next();
+            name
+        }
+        // `(` is either a nested declarator or, in an abstract one, the parameter list
+        Some(Token::OpenParen) if nested_declarator(tokens) => {
+            tokens.next();
+            let inner = self::declarator(tokens)?;
+            tokens.consume(Token::CloseParen)?;
+            inner
+        }
+        _ => Declarator::Abstract,
     };
-    let _ = tokens.nth(num - 1);
-    Ok(res)
+    loop {
+        declarator = match tokens.peek() {
+            Some(Token::OpenBracket) => Declarator::Array(declarator.into(), array_size(tokens)?),
+            Some(Token::OpenParen) => {
+                tokens.next();
+                tokens.push_scope();
+                let params = param_list(tokens);
+                tokens.pop_scope();
+                let (params, variadic) = params?;
+                Declarator::Function(declarator.into(), params, variadic)
+            }
+            _ => return Ok(declarator),
+        }
+    }
 }
 
-fn param_list(tokens: &mut TokenIter) -> Result<ParamList, Error> {
-    if tokens.consume(Token::Void).is_ok() {
-        tokens.consume(Token::CloseParen)?;
-        return Ok(Box::new([]));
+fn nested_declarator(tokens: &TokenIter) -> bool {
+    match tokens.peek_peek() {
+        Some(Token::Asterisk | Token::OpenParen | Token::OpenBracket) => true,
+        Some(Token::Identifier(name)) => tokens.typedef(name).is_none(),
+        _ => false,
+    }
+}
+
+// `[2]` or `[]`
+fn array_size(tokens: &mut TokenIter) -> Result<Option<Expr>, Error> {
+    tokens.consume(Token::OpenBracket)?;
+    if tokens.next_if(|t| *t == Token::CloseBracket).is_some() {
+        return Ok(None);
+    }
+    let len = assignment_expression(tokens)?;
+    tokens.consume(Token::CloseBracket)?;
+    Ok(Some(len))
+}
+
+// a plain number can be read off right away, anything else waits for typecheck
+fn array_type(elem: VarType, len: Option<Expr>) -> VarType {
+    match len {
+        Some(Expr::Const(c, _)) if !c.is_double() && c.long() > 0 => {
+            elem.array_of(c.long() as usize)
+        }
+        len => elem.incomplete_array(len.map(Length::new)),
+    }
+}
+
+// turns the declarator inside out into the declared name and its type, and the parameters if
+// it declares a function
+fn process(
+    declarator: Declarator,
+    base: VarType,
+    span: Span,
+) -> Result<(Option<Identifier>, VarType, Option<ParamList>), Error> {
+    match declarator {
+        Declarator::Name(name) => Ok((Some(name), base, None)),
+        Declarator::Abstract => Ok((None, base, None)),
+        Declarator::Pointer(inner, qualifiers) => {
+            process(*inner, base.pointer_to().qualified(qualifiers), span)
+        }
+        Declarator::Array(_, _) if base.is_function() => Err(Error::InvalidDeclarator(
+            "arrays of functions aren't allowed",
+            span,
+        )),
+        Declarator::Array(inner, len) => process(*inner, array_type(base, len), span),
+        Declarator::Function(..) if base.is_function() || base.is_array() => Err(
+            Error::InvalidDeclarator("functions can't return arrays or functions", span),
+        ),
+        Declarator::Function(inner, params, variadic) => {
+            let typ = VarType::function(FnType {
+                // qualifiers on a return value don't mean anything
+                ret: base.unqualified(),
+                params: params.iter().map(|param| param.typ.unqualified()).collect(),
+                variadic,
+            });
+            match *inner {
+                Declarator::Name(name) => Ok((Some(name), typ, Some(params))),
+                inner => process(inner, typ, span),
+            }
+        }
+    }
+}
+
+fn initializer(tokens: &mut TokenIter) -> Result<Initializer<Expr>, Error> {
+    let start = tokens.span();
+    if tokens.next_if(|t| t == &Token::OpenBrace).is_none() {
+        return assignment_expression(tokens).map(Initializer::Single);
+    }
+    braced_initializer(tokens, start)
+}
+
+// the rest of `{1, .x = 2, [3] = {4}}` after the `{`
+fn braced_initializer(tokens: &mut TokenIter, start: Span) -> Result<Initializer<Expr>, Error> {
+    let mut inits = vec![designated_initializer(tokens)?];
+    while tokens.next_if(|t| t == &Token::Comma).is_some() {
+        // a trailing comma is allowed
+        if tokens.peek() == Some(&Token::CloseBrace) {
+            break;
+        }
+        inits.push(designated_initializer(tokens)?);
+    }
+    tokens.consume(Token::CloseBrace)?;
+    Ok(Initializer::Compound(inits.into(), tokens.since(start)))
+}
+
+fn designated_initializer(tokens: &mut TokenIter) -> Result<Initializer<Expr>, Error> {
+    let mut designators = Vec::new();
+    loop {
+        let start = tokens.span();
+        if tokens.next_if(|t| t == &Token::Dot).is_some() {
+            let member = tokens.consume_identifier()?;
+            designators.push(Designator::Member(member, tokens.since(start)));
+        } else if tokens.next_if(|t| t == &Token::OpenBracket).is_some() {
+            let index = assignment_expression(tokens)?;
+            tokens.consume(Token::CloseBracket)?;
+            designators.push(Designator::Index(index, tokens.since(start)));
+        } else {
+            break;
+        }
+    }
+    if designators.is_empty() {
+        return initializer(tokens);
+    }
+    tokens.consume(Token::Equals)?;
+    Ok(Initializer::Designated(
+        designators.into(),
+        Box::new(initializer(tokens)?),
+    ))
+}
+
+// also whether it ended in `, ...`
+fn param_list(tokens: &mut TokenIter) -> Result<(ParamList, bool), Error> {
+    // `(void)` means no parameters, but `(void *p)` is a parameter, and we don't do K&R so `()`
+    // means none either
+    if let [Token::Void, Token::CloseParen, ..] = tokens.as_slice() {
+        tokens.nth(1);
+        return Ok((Box::new([]), false));
+    }
+    if tokens.next_if(|t| t == &Token::CloseParen).is_some() {
+        return Ok((Box::new([]), false));
     }
 
     let mut params = Vec::new();
 
-    let (mut p, mut last) = param(tokens)?;
+    let (mut p, mut last) = param(tokens, 0)?;
 
     while !last {
         params.push(p);
-        (p, last) = param(tokens)?;
+        if tokens.consume(Token::Ellipsis).is_ok() {
+            tokens.consume(Token::CloseParen)?;
+            return Ok((params.into(), true));
+        }
+        (p, last) = param(tokens, params.len())?;
     }
 
     params.push(p);
-    Ok(params.into())
+    Ok((params.into(), false))
 }
 
-fn param(tokens: &mut TokenIter) -> Result<(Param, bool), Error> {
+fn param(tokens: &mut TokenIter, index: usize) -> Result<(Param, bool), Error> {
     let typ = type_specifier(tokens)?;
 
-    let name = tokens.consume_identifier()?;
+    let span = tokens.span();
+    let (name, typ, _) = process(declarator(tokens)?, typ, span)?;
+    // array parameters are really pointers, and so are function parameters
+    let typ = match typ.unqualified() {
+        VarType::Array(elem, _) | VarType::IncompleteArray(elem, _) => elem.pointer_to(),
+        VarType::Function(_) => typ.pointer_to(),
+        _ => typ,
+    };
+    // nothing else can start with a digit, see `Param::unnamed`
+    let name = name.unwrap_or_else(|| Identifier::from(index.to_string()));
+    tokens.declare(&name);
     let last = match tokens.consume_any()? {
         Token::Comma => Ok(false),
         Token::CloseParen => Ok(true),
-        other => {
-            eprintln!("got {other:?}");
-            Err(Error::Catchall("expected ',' or ')'."))
-        }
+        _ => Err(Error::Catchall("expected ',' or ')'.", tokens.prev_span())),
     }?;
-    Ok((Param { typ, name }, last))
+    Ok((Param { typ, name, span }, last))
 }
 
 fn block(tokens: &mut TokenIter) -> Result<Block, Error> {
     tokens.consume(Token::OpenBrace)?;
+    tokens.push_scope();
+    let depth = tokens.depth();
     let mut body = Vec::new();
-    while let Some(item) = block_item(tokens)? {
-        body.push(item);
+    while tokens.peek().is_some_and(|t| t != &Token::CloseBrace) {
+        let before = tokens.as_slice().len();
+        match block_item(tokens) {
+            Ok(items) => body.extend(items),
+            Err(e) => {
+                tokens.report(e);
+                synchronize(tokens, before, depth);
+            }
+        }
     }
+    tokens.pop_scope();
     tokens.consume(Token::CloseBrace)?;
     Ok(body.into())
 }
 
-fn block_item(tokens: &mut TokenIter) -> Result<Option<BlockItem>, Error> {
-    match tokens.peek_any()? {
-        Token::Int | Token::Static | Token::Extern | Token::Long => {
-            Ok(Some(BlockItem::D(declaration(tokens)?)))
-        }
-        Token::CloseBrace => Ok(None),
-        _ => Ok(Some(BlockItem::S(statement(tokens)?))),
+fn block_item(tokens: &mut TokenIter) -> Result<Vec<BlockItem>, Error> {
+    // a typedef name can still be a label
+    if tokens.peek_peek() != Some(&Token::Colon) && tokens.at_declaration() {
+        let decs = declaration(tokens)?;
+        Ok(decs.into_iter().map(BlockItem::D).collect())
+    } else {
+        Ok(vec![BlockItem::S(statement(tokens)?)])
     }
 }
 
-fn var_declaration(tokens: &mut TokenIter, sc: Option<StorageClass>) -> Result<VarDec, Error> {
+// just the one variable, which can't be static or extern
+fn var_declaration(tokens: &mut TokenIter) -> Result<VarDec, Error> {
+    let start = tokens.span();
     let typ = type_specifier(tokens)?;
-    let name = tokens.consume_identifier()?;
+    let span = tokens.span();
+    let (name, typ, _) = process(declarator(tokens)?, typ, span)?;
+    let Some(name) = name else {
+        return Err(Error::ExpectedIdentifier(span));
+    };
+    if typ.is_function() {
+        return Err(Error::InvalidDeclarator(
+            "functions can't be declared here",
+            tokens.since(start),
+        ));
+    }
+    tokens.declare(&name);
     let init = match tokens.consume_any()? {
         Token::Equals => {
-            let exp = expression(tokens, None)?;
+            let exp = initializer(tokens)?;
 
             tokens.consume(Token::Semicolon)?;
             Ok(Some(exp))
         }
         Token::Semicolon => Ok(None),
-        _ => Err(Error::Catchall("expected initializer or semicolon")),
+        _ => Err(Error::Catchall(
+            "expected initializer or semicolon",
+            tokens.prev_span(),
+        )),
     }?;
     Ok(VarDec {
         name,
         init,
         typ,
-        sc,
+        sc: None,
+        span,
     })
 }
 
@@ -198,19 +661,329 @@ pub enum Type {
 }
 
 impl VarType {
-    pub const fn common_type(&self, other: &Self) -> Option<Self> {
-        match (self, other) {
-            (Self::Long, Self::Long) | (Self::Long, Self::Int) | (Self::Int, Self::Long) => {
-                Some(Self::Long)
+    // types live as long as the compiler does, so they can stay Copy
+    fn intern(self) -> &'static Self {
+        static TYPES: Mutex<Vec<&'static VarType>> = Mutex::new(Vec::new());
+        let mut types = TYPES.lock().unwrap();
+        match types.iter().find(|&&t| *t == self) {
+            Some(t) => t,
+            None => {
+                let t: &'static Self = Box::leak(Box::new(self));
+                types.push(t);
+                t
+            }
+        }
+    }
+
+    pub fn pointer_to(self) -> Self {
+        Self::Pointer(self.intern())
+    }
+
+    pub fn array_of(self, len: usize) -> Self {
+        Self::Array(self.intern(), len)
+    }
+
+    pub fn incomplete_array(self, len: Option<Length>) -> Self {
+        Self::IncompleteArray(self.intern(), len)
+    }
+
+    pub fn function(typ: FnType) -> Self {
+        static FUNCTIONS: Mutex<Vec<&'static FnType>> = Mutex::new(Vec::new());
+        let mut functions = FUNCTIONS.lock().unwrap();
+        match functions.iter().find(|&&t| *t == typ) {
+            Some(t) => Self::Function(t),
+            None => {
+                let t: &'static FnType = Box::leak(Box::new(typ));
+                functions.push(t);
+                Self::Function(t)
+            }
+        }
+    }
+
+    // qualifying an array qualifies its elements
+    pub fn qualified(self, qualifiers: Qualifiers) -> Self {
+        match self {
+            _ if qualifiers.is_empty() => self,
+            Self::Array(elem, len) => elem.qualified(qualifiers).array_of(len),
+            Self::IncompleteArray(elem, len) => elem.qualified(qualifiers).incomplete_array(len),
+            Self::Qualified(t, old) => Self::Qualified(t, old.union(qualifiers)),
+            t => Self::Qualified(t.intern(), qualifiers),
+        }
+    }
+
+    // what's left once the value has been read out of an object
+    pub const fn unqualified(self) -> Self {
+        match self {
+            Self::Qualified(t, _) => *t,
+            t => t,
+        }
+    }
+
+    pub const fn qualifiers(&self) -> Qualifiers {
+        match self {
+            Self::Qualified(_, qualifiers) => *qualifiers,
+            _ => Qualifiers::NONE,
+        }
+    }
+
+    // whether any part of an object of this type is volatile, so its reads and writes all stay
+    pub fn is_volatile(&self) -> bool {
+        self.qualifiers().volatile
+            || match self.unqualified() {
+                Self::Array(elem, _) | Self::IncompleteArray(elem, _) => elem.is_volatile(),
+                Self::Struct(_) | Self::Union(_) => self
+                    .layout()
+                    .is_some_and(|layout| layout.members.iter().any(|m| m.typ.is_volatile())),
+                _ => false,
             }
-            (Self::Int, Self::Int) => Some(Self::Int),
+    }
+
+    pub const fn is_pointer(&self) -> bool {
+        matches!(self.unqualified(), Self::Pointer(_))
+    }
+
+    pub const fn is_array(&self) -> bool {
+        matches!(
+            self.unqualified(),
+            Self::Array(..) | Self::IncompleteArray(..)
+        )
+    }
+
+    // whether typecheck has an array length to work out somewhere in here
+    pub fn has_length(&self) -> bool {
+        match self {
+            Self::IncompleteArray(_, Some(_)) => true,
+            Self::Pointer(t) | Self::Array(t, _) | Self::IncompleteArray(t, _) => t.has_length(),
+            Self::Qualified(t, _) => t.has_length(),
+            Self::Function(f) => f.ret.has_length() || f.params.iter().any(Self::has_length),
+            _ => false,
+        }
+    }
+
+    // or a union
+    pub const fn is_struct(&self) -> bool {
+        matches!(self.unqualified(), Self::Struct(_) | Self::Union(_))
+    }
+
+    pub const fn is_function(&self) -> bool {
+        matches!(self.unqualified(), Self::Function(_))
+    }
+
+    // anything that can be compared against zero
+    pub const fn scalar(&self) -> bool {
+        self.arithmetic() || self.is_pointer()
+    }
+
+    // a struct we've only seen `struct s;` for can't be used for anything but pointers, and
+    // neither can void or a function
+    pub fn complete(&self) -> bool {
+        match self {
+            Self::Void | Self::Function(_) | Self::IncompleteArray(..) => false,
+            Self::Struct(_) | Self::Union(_) => self.layout().is_some(),
+            Self::Array(t, _) | Self::Qualified(t, _) => t.complete(),
+            _ => true,
+        }
+    }
+
+    pub fn layout(&self) -> Option<&'static Layout> {
+        match self {
+            Self::Struct(tag) | Self::Union(tag) => Layout::get(tag),
+            Self::Qualified(t, _) => t.layout(),
+            _ => None,
+        }
+    }
+
+    // what a pointer points at, or what an array holds
+    pub const fn element(&self) -> Option<Self> {
+        match self {
+            Self::Pointer(t) | Self::Array(t, _) | Self::IncompleteArray(t, _) => Some(**t),
+            Self::Qualified(t, _) => t.element(),
+            Self::Char
+            | Self::SChar
+            | Self::UChar
+            | Self::Int
+            | Self::Long
+            | Self::UInt
+            | Self::ULong
+            | Self::Double
+            | Self::Void
+            | Self::Struct(_)
+            | Self::Union(_)
+            | Self::Function(_) => None,
+        }
+    }
+
+    pub const fn arithmetic(&self) -> bool {
+        self.integer() || matches!(self.unqualified(), Self::Double)
+    }
+
+    pub const fn integer(&self) -> bool {
+        self.is_character()
+            || matches!(
+                self.unqualified(),
+                Self::Int | Self::Long | Self::UInt | Self::ULong
+            )
+    }
+
+    pub const fn is_character(&self) -> bool {
+        matches!(self.unqualified(), Self::Char | Self::SChar | Self::UChar)
+    }
+
+    // pointers compare like unsigned numbers, and plain chars are signed like they are with gcc
+    pub const fn signed(&self) -> bool {
+        matches!(
+            self.unqualified(),
+            Self::Char | Self::SChar | Self::Int | Self::Long
+        )
+    }
+
+    // nothing smaller than an int takes part in arithmetic
+    pub const fn promoted(self) -> Self {
+        if self.is_character() {
+            Self::Int
+        } else {
+            self.unqualified()
+        }
+    }
+
+    // the usual arithmetic conversions: double beats everything, otherwise the bigger type wins,
+    // and unsigned wins a tie
+    // pointers only have a common type with themselves, see `typecheck`
+    pub fn common_type(&self, other: &Self) -> Option<Self> {
+        match (&self.promoted(), &other.promoted()) {
+            (a, b) if a == b => Some(*a),
+            (Self::Double, b) | (b, Self::Double) if b.arithmetic() => Some(Self::Double),
+            (a, b) if a.arithmetic() && b.arithmetic() => Some(match a.size().cmp(&b.size()) {
+                std::cmp::Ordering::Greater => *a,
+                std::cmp::Ordering::Less => *b,
+                std::cmp::Ordering::Equal if a.signed() => *b,
+                std::cmp::Ordering::Equal => *a,
+            }),
+            _ => None,
         }
     }
 
-    pub const fn alignment(&self) -> u32 {
+    // incomplete types don't have a size, typecheck makes sure nothing asks for one
+    pub fn size(&self) -> usize {
         match self {
-            Self::Int => 4,
-            Self::Long => 8,
+            Self::Char | Self::SChar | Self::UChar => 1,
+            Self::Int | Self::UInt => 4,
+            Self::Long | Self::ULong | Self::Double | Self::Pointer(_) => 8,
+            Self::Void | Self::Function(_) | Self::IncompleteArray(..) => 0,
+            Self::Array(t, len) => t.size() * *len,
+            Self::Struct(_) | Self::Union(_) => self.layout().map_or(0, |layout| layout.size),
+            Self::Qualified(t, _) => t.size(),
+        }
+    }
+
+    // the system v abi wants arrays of 16 bytes or more to be 16 byte aligned
+    pub fn alignment(&self) -> usize {
+        match self {
+            Self::Array(..) if self.size() >= 16 => 16,
+            Self::Array(t, _) | Self::IncompleteArray(t, _) | Self::Qualified(t, _) => {
+                t.alignment()
+            }
+            Self::Struct(_) | Self::Union(_) => self.layout().map_or(1, |layout| layout.alignment),
+            Self::Void | Self::Function(_) => 1,
+            _ => self.size(),
+        }
+    }
+
+    // what `_Alignof` says, big arrays only get more than this as variables
+    pub fn type_alignment(&self) -> usize {
+        match self.unqualified() {
+            Self::Array(elem, _) | Self::IncompleteArray(elem, _) => elem.type_alignment(),
+            typ => typ.alignment(),
+        }
+    }
+}
+
+// the tag as it was written, without what `resolve` added to make it unique
+fn tag_name(tag: &'static str) -> &'static str {
+    tag.split('.').next().unwrap_or(tag)
+}
+
+// spelled the way C would, for diagnostics
+impl Display for VarType {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        // C types read inside out, so build up the declarator around an empty name
+        let mut declarator = String::new();
+        let mut keyword = "";
+        let mut qualifiers = Qualifiers::NONE;
+        let mut ty = *self;
+        let base = loop {
+            match ty {
+                Self::Char => break "char",
+                Self::SChar => break "signed char",
+                Self::UChar => break "unsigned char",
+                Self::Int => break "int",
+                Self::Long => break "long",
+                Self::UInt => break "unsigned int",
+                Self::ULong => break "unsigned long",
+                Self::Double => break "double",
+                Self::Void => break "void",
+                Self::Struct(tag) => {
+                    keyword = "struct ";
+                    break tag_name(tag);
+                }
+                Self::Union(tag) => {
+                    keyword = "union ";
+                    break tag_name(tag);
+                }
+                // `int *const`, but `const int`
+                Self::Qualified(Self::Pointer(t), q) => {
+                    let space = if declarator.is_empty() { "" } else { " " };
+                    declarator = format!("*{q}{space}{declarator}");
+                    ty = **t;
+                }
+                Self::Qualified(t, q) => {
+                    qualifiers = q;
+                    ty = *t;
+                }
+                Self::Pointer(t) => {
+                    declarator.insert(0, '*');
+                    ty = *t;
+                }
+                Self::Array(t, len) => {
+                    if declarator.starts_with('*') {
+                        declarator = format!("({declarator})");
+                    }
+                    write!(declarator, "[{len}]")?;
+                    ty = *t;
+                }
+                Self::IncompleteArray(t, _) => {
+                    if declarator.starts_with('*') {
+                        declarator = format!("({declarator})");
+                    }
+                    declarator.push_str("[]");
+                    ty = *t;
+                }
+                Self::Function(t) => {
+                    if declarator.starts_with('*') {
+                        declarator = format!("({declarator})");
+                    }
+                    declarator.push('(');
+                    for (i, param) in t.params.iter().enumerate() {
+                        let comma = if i == 0 { "" } else { ", " };
+                        write!(declarator, "{comma}{param}")?;
+                    }
+                    match (t.params.is_empty(), t.variadic) {
+                        (true, false) => declarator.push_str("void"),
+                        (false, true) => declarator.push_str(", ..."),
+                        _ => {}
+                    }
+                    declarator.push(')');
+                    ty = t.ret;
+                }
+            }
+        };
+        if !qualifiers.is_empty() {
+            write!(f, "{qualifiers} ")?;
+        }
+        if declarator.is_empty() {
+            write!(f, "{keyword}{base}")
+        } else {
+            write!(f, "{keyword}{base} {declarator}")
         }
     }
 }
@@ -237,12 +1010,17 @@ impl Display for Function {
 }
 
 fn statement(tokens: &mut TokenIter) -> Result<Stmnt, Error> {
+    let start = tokens.span();
     Ok(match tokens.peek_any()? {
         Token::Return => {
             tokens.next();
-            let expression = expression(tokens, None)?;
+            let expression = if tokens.peek() == Some(&Token::Semicolon) {
+                None
+            } else {
+                Some(expression(tokens, None)?)
+            };
             tokens.consume(Token::Semicolon)?;
-            Stmnt::Ret(expression)
+            Stmnt::Ret(expression, start)
         }
         Token::Semicolon => {
             tokens.next();
@@ -275,9 +1053,10 @@ fn statement(tokens: &mut TokenIter) -> Result<Stmnt, Error> {
         }
         Token::Goto => {
             tokens.next();
+            let span = tokens.span();
             let identifier = tokens.consume_identifier()?;
             tokens.consume(Token::Semicolon)?;
-            Stmnt::Goto(identifier)
+            Stmnt::Goto(identifier, span)
         }
         // LABEL
         Token::Identifier(_) if tokens.peek_peek().is_some_and(|x| x == &Token::Colon) => {
@@ -286,7 +1065,11 @@ fn statement(tokens: &mut TokenIter) -> Result<Stmnt, Error> {
             tokens.next();
             let body = statement(tokens)?.into();
 
-            Stmnt::Label { label, body }
+            Stmnt::Label {
+                label,
+                body,
+                span: start,
+            }
         }
 
         Token::Default => {
@@ -296,6 +1079,7 @@ fn statement(tokens: &mut TokenIter) -> Result<Stmnt, Error> {
             Stmnt::Label {
                 label: Label::Default,
                 body,
+                span: start,
             }
         }
         Token::OpenBrace => {
@@ -306,23 +1090,22 @@ fn statement(tokens: &mut TokenIter) -> Result<Stmnt, Error> {
             tokens.next();
             tokens.consume(Token::Semicolon)?;
 
-            Stmnt::Break
+            Stmnt::Break(start)
         }
         Token::Case => {
             tokens.next();
-            let Token::Constant(con) = tokens.consume_any()? else {
-                return Err(Error::Catchall("expected constant"));
-            };
+            let value = assignment_expression(tokens)?;
+            let span = tokens.since(start);
 
             tokens.consume(Token::Colon)?;
-            let label = Label::Case(con);
+            let label = Label::Case(value);
             let body = statement(tokens)?.into();
-            Ok(Stmnt::Label { label, body })?
+            Ok(Stmnt::Label { label, body, span })?
         }
         Token::Continue => {
             tokens.next();
             tokens.consume(Token::Semicolon)?;
-            Stmnt::Continue
+            Stmnt::Continue(start)
         }
         Token::While => {
             tokens.next();
@@ -345,21 +1128,10 @@ fn statement(tokens: &mut TokenIter) -> Result<Stmnt, Error> {
         }
         Token::For => {
             tokens.next();
-            tokens.consume(Token::OpenParen)?;
-            let init = for_init(tokens)?;
-
-            let condition = optional_expr(tokens, Token::Semicolon)?;
-
-            let post = optional_expr(tokens, Token::CloseParen)?;
-
-            let body = Box::new(statement(tokens)?);
-
-            Stmnt::For {
-                init,
-                condition,
-                post,
-                body,
-            }
+            tokens.push_scope();
+            let stmnt = for_stmnt(tokens);
+            tokens.pop_scope();
+            stmnt?
         }
         _ => {
             let e = expression(tokens, None)?;
@@ -389,9 +1161,28 @@ fn print_return<T: fmt::Debug>(mut f: impl FnMut() -> T) -> T {
     val
 }
 
+// the loop variable is only in scope for the loop
+fn for_stmnt(tokens: &mut TokenIter) -> Result<Stmnt, Error> {
+    tokens.consume(Token::OpenParen)?;
+    let init = for_init(tokens)?.map(Box::new);
+
+    let condition = optional_expr(tokens, Token::Semicolon)?;
+
+    let post = optional_expr(tokens, Token::CloseParen)?;
+
+    let body = Box::new(statement(tokens)?);
+
+    Ok(Stmnt::For {
+        init,
+        condition,
+        post,
+        body,
+    })
+}
+
 fn for_init(tokens: &mut TokenIter) -> Result<Option<ForInit>, Error> {
-    if let Ok(declaration) = var_declaration(tokens, None) {
-        Ok(Some(ForInit::D(declaration)))
+    if tokens.at_declaration() {
+        Ok(Some(ForInit::D(var_declaration(tokens)?)))
     } else {
         let res = optional_expr(tokens, Token::Semicolon)?.map(ForInit::E);
         Ok(res)
@@ -415,6 +1206,7 @@ fn expression(tokens: &mut TokenIter, min_precedence: Option<u8>) -> Result<Expr
             Bop::Equals => {
                 let right = Box::from(expression(tokens, Some(operator.precedence()))?);
                 left = Expr::Assignment {
+                    span: tokens.since(left.span()),
                     dst: left.into(),
                     src: right,
                 }
@@ -424,6 +1216,7 @@ fn expression(tokens: &mut TokenIter, min_precedence: Option<u8>) -> Result<Expr
                 tokens.consume(Token::Colon)?;
                 let right = expression(tokens, Some(operator.precedence()))?;
                 left = Expr::Conditional {
+                    span: tokens.since(left.span()),
                     condition: left.into(),
                     r#true: middle.into(),
                     r#false: right.into(),
@@ -432,6 +1225,7 @@ fn expression(tokens: &mut TokenIter, min_precedence: Option<u8>) -> Result<Expr
             operator if operator.compound() => {
                 let right = expression(tokens, Some(operator.precedence()))?;
                 left = Expr::Bin(Binary {
+                    span: tokens.since(left.span()),
                     left: Box::new(left),
                     right: Box::new(right),
                     operator,
@@ -440,6 +1234,7 @@ fn expression(tokens: &mut TokenIter, min_precedence: Option<u8>) -> Result<Expr
             operator => {
                 let right = expression(tokens, Some(operator.precedence() + 1))?;
                 left = Expr::Bin(Binary {
+                    span: tokens.since(left.span()),
                     left: Box::new(left),
                     right: Box::new(right),
                     operator,
@@ -450,6 +1245,11 @@ fn expression(tokens: &mut TokenIter, min_precedence: Option<u8>) -> Result<Expr
     Ok(left)
 }
 
+// anywhere a comma already separates things, like arguments and initializers
+fn assignment_expression(tokens: &mut TokenIter) -> Result<Expr, Error> {
+    expression(tokens, Some(Bop::Equals.precedence()))
+}
+
 fn binary_operator(tokens: &mut TokenIter, min_precedence: u8) -> Option<Bop> {
     let token = match tokens.peek()? {
         Token::Plus => Some(Bop::Add),
@@ -490,6 +1290,7 @@ fn binary_operator(tokens: &mut TokenIter, min_precedence: u8) -> Option<Bop> {
         Token::RightShiftEqual => Some(Bop::RightShiftEqual),
 
         Token::QuestionMark => Some(Bop::Ternary),
+        Token::Comma => Some(Bop::Comma),
 
         _ => None,
     }?;
@@ -501,10 +1302,20 @@ fn binary_operator(tokens: &mut TokenIter, min_precedence: u8) -> Option<Bop> {
     }
 }
 fn factor(tokens: &mut TokenIter) -> Result<Expr, Error> {
+    let start = tokens.span();
     match tokens.consume_any()? {
-        Token::Increment => factor(tokens).map(Expr::pre_inc),
-        Token::Decrement => factor(tokens).map(Expr::pre_dec),
-        Token::Constant(c) => Ok(Expr::Const(c)),
+        Token::Increment => factor(tokens).map(|e| Expr::pre_inc(e, tokens.since(start))),
+        Token::Decrement => factor(tokens).map(|e| Expr::pre_dec(e, tokens.since(start))),
+        Token::Constant(c) => Ok(Expr::Const(c, start)),
+        Token::StringLiteral(bytes) => {
+            let mut bytes = bytes.into_vec();
+            // `"a" "b"` is the same as `"ab"`
+            while let Some(Token::StringLiteral(next)) = tokens.peek() {
+                bytes.extend_from_slice(next);
+                tokens.next();
+            }
+            Ok(Expr::String(bytes.into(), tokens.since(start)))
+        }
 
         t @ (Token::Minus | Token::Tilde | Token::Not) => {
             let operator = if t == Token::Minus {
@@ -515,41 +1326,192 @@ fn factor(tokens: &mut TokenIter) -> Result<Expr, Error> {
                 UnOp::Not
             };
             let exp = Box::new(factor(tokens)?);
-            Ok(Expr::Unary(Unary { exp, op: operator }))
+            Ok(Expr::Unary(Unary {
+                exp,
+                op: operator,
+                span: tokens.since(start),
+            }))
+        }
+        Token::Asterisk => {
+            let exp = Box::new(factor(tokens)?);
+            Ok(Expr::Deref(exp, tokens.since(start)))
+        }
+        Token::Ampersand => {
+            let exp = Box::new(factor(tokens)?);
+            Ok(Expr::AddrOf(exp, tokens.since(start)))
         }
         Token::OpenParen => {
-            if let Some(target) = tokens.consume_type() {
+            if at_type_name(tokens) {
+                let target = type_name(tokens)?;
                 tokens.consume(Token::CloseParen)?;
+                if tokens.peek() == Some(&Token::OpenBrace) {
+                    return compound_literal(tokens, target, start);
+                }
                 let exp = factor(tokens)?.into();
-                Ok(Expr::Cast { target, exp })
+                Ok(Expr::Cast {
+                    target,
+                    exp,
+                    span: tokens.since(start),
+                })
             } else {
                 let exp = Box::new(expression(tokens, None)?);
                 tokens.consume(Token::CloseParen)?;
-                Ok(Expr::Nested(exp))
+                Ok(Expr::Nested(exp, tokens.since(start)))
+            }
+        }
+        // `sizeof (int)` is the size of a type, `sizeof (x)` is just a parenthesized expression
+        Token::Sizeof
+            if tokens.peek() == Some(&Token::OpenParen)
+                && starts_type_name(tokens, tokens.peek_peek()) =>
+        {
+            let paren = tokens.span();
+            tokens.next();
+            let ty = type_name(tokens)?;
+            tokens.consume(Token::CloseParen)?;
+            if tokens.peek() == Some(&Token::OpenBrace) {
+                let literal = compound_literal(tokens, ty, paren)?;
+                return Ok(Expr::SizeOf(literal.into(), tokens.since(start)));
             }
+            Ok(Expr::SizeOfType(ty, tokens.since(start)))
         }
-        Token::Identifier(ident) => {
-            if tokens.peek() == Some(&Token::OpenParen) {
+        Token::Sizeof => {
+            let exp = Box::new(factor(tokens)?);
+            Ok(Expr::SizeOf(exp, tokens.since(start)))
+        }
+        Token::Alignof => {
+            tokens.consume(Token::OpenParen)?;
+            let ty = type_name(tokens)?;
+            tokens.consume(Token::CloseParen)?;
+            Ok(Expr::AlignOf(ty, tokens.since(start)))
+        }
+        Token::Offsetof => {
+            tokens.consume(Token::OpenParen)?;
+            let target = type_name(tokens)?;
+            tokens.consume(Token::Comma)?;
+            let member = tokens.consume_identifier()?;
+            tokens.consume(Token::CloseParen)?;
+            Ok(Expr::OffsetOf {
+                target,
+                member,
+                span: tokens.since(start),
+            })
+        }
+        Token::Identifier(ident) => Ok(Expr::Var(ident, start)),
+        t @ (Token::VaStart | Token::VaArg | Token::VaEnd | Token::VaCopy) => {
+            va_builtin(tokens, t, start)
+        }
+
+        _ => Err(Error::ExpectedExpr(start)),
+    }
+    .and_then(|factor| postfix(tokens, factor, start))
+}
+
+// `(struct p){1, 2}`, once the type name in parentheses has been read
+fn compound_literal(tokens: &mut TokenIter, ty: VarType, start: Span) -> Result<Expr, Error> {
+    tokens.consume(Token::OpenBrace)?;
+    let init = braced_initializer(tokens, start)?;
+    let literal = Expr::CompoundLiteral {
+        ty,
+        init: Box::new(init),
+        span: tokens.since(start),
+    };
+    postfix(tokens, literal, start)
+}
+
+// calls, subscripts, member access and postfix ++/--, which bind tighter than any prefix operator
+fn postfix(tokens: &mut TokenIter, mut exp: Expr, start: Span) -> Result<Expr, Error> {
+    loop {
+        exp = match tokens.peek() {
+            Some(Token::OpenParen) => {
                 tokens.next();
                 let args = argument_list(tokens)?;
-                Ok(Expr::FunctionCall { name: ident, args })
-            } else {
-                Ok(Expr::Var(ident))
+                Expr::FunctionCall {
+                    func: exp.into(),
+                    args,
+                    span: tokens.since(start),
+                }
+            }
+            Some(Token::OpenBracket) => {
+                tokens.next();
+                let index = expression(tokens, None)?;
+                tokens.consume(Token::CloseBracket)?;
+                Expr::Subscript {
+                    array: exp.into(),
+                    index: index.into(),
+                    span: tokens.since(start),
+                }
+            }
+            Some(Token::Dot | Token::Arrow) => {
+                let arrow = tokens.next() == Some(Token::Arrow);
+                let member = tokens.consume_identifier()?;
+                let exp = exp.into();
+                let span = tokens.since(start);
+                if arrow {
+                    Expr::Arrow { exp, member, span }
+                } else {
+                    Expr::Dot { exp, member, span }
+                }
+            }
+            Some(Token::Increment) => {
+                tokens.next();
+                Expr::post_inc(exp, tokens.since(start))
+            }
+            Some(Token::Decrement) => {
+                tokens.next();
+                Expr::post_dec(exp, tokens.since(start))
             }
+            _ => return Ok(exp),
         }
+    }
+}
 
-        t => {
-            eprintln!("errored in {t:?}");
-            Err(Error::ExpectedExpr)
+// `va_end` doesn't need to do anything and `va_copy` is just a struct copy, so only `va_start` and
+// `va_arg` make it past the parser
+fn va_builtin(tokens: &mut TokenIter, builtin: Token, start: Span) -> Result<Expr, Error> {
+    tokens.consume(Token::OpenParen)?;
+    let ap = Box::new(assignment_expression(tokens)?);
+    let exp = match builtin {
+        Token::VaStart => {
+            tokens.consume(Token::Comma)?;
+            assignment_expression(tokens)?;
+            tokens.consume(Token::CloseParen)?;
+            Expr::VaStart(ap, tokens.since(start))
         }
-    }
-    .map(
-        |factor| match tokens.next_if(|x| x == &Token::Increment || x == &Token::Decrement) {
-            Some(Token::Increment) => Expr::post_inc(factor),
-            Some(Token::Decrement) => Expr::post_dec(factor),
-            _ => factor,
-        },
-    )
+        Token::VaArg => {
+            tokens.consume(Token::Comma)?;
+            let target = type_name(tokens)?;
+            tokens.consume(Token::CloseParen)?;
+            Expr::VaArg {
+                ap,
+                target,
+                span: tokens.since(start),
+            }
+        }
+        Token::VaCopy => {
+            tokens.consume(Token::Comma)?;
+            let src = assignment_expression(tokens)?;
+            tokens.consume(Token::CloseParen)?;
+            let span = tokens.since(start);
+            Expr::Cast {
+                target: VarType::Void,
+                exp: Box::new(Expr::Assignment {
+                    dst: Box::new(Expr::Deref(ap, span)),
+                    src: Box::new(Expr::Deref(src.into(), span)),
+                    span,
+                }),
+                span,
+            }
+        }
+        _ => {
+            tokens.consume(Token::CloseParen)?;
+            Expr::Cast {
+                target: VarType::Void,
+                exp: ap,
+                span: tokens.since(start),
+            }
+        }
+    };
+    Ok(exp)
 }
 
 fn argument_list(tokens: &mut TokenIter) -> Result<Box<[Expr]>, Error> {
@@ -559,13 +1521,13 @@ fn argument_list(tokens: &mut TokenIter) -> Result<Box<[Expr]>, Error> {
 
     let mut list = Vec::new();
     loop {
-        list.push(expression(tokens, None)?);
+        list.push(assignment_expression(tokens)?);
         match tokens.consume_any()? {
             Token::Comma => {}
             Token::CloseParen => {
                 break;
             }
-            _ => return Err(Error::Catchall("idk")),
+            _ => return Err(Error::Catchall("idk", tokens.prev_span())),
         }
     }
 
@@ -573,25 +1535,457 @@ fn argument_list(tokens: &mut TokenIter) -> Result<Box<[Expr]>, Error> {
 }
 
 #[derive(Debug)]
-pub struct DebugError {
-    pub e: Error,
-    pub line: usize,
+pub enum Error {
+    UnexpectedEof(Span),
+    // and whether the token is probably just missing from there, so inserting it is the fix
+    Expected(Token, Span, bool),
+    ExpectedIdentifier(Span),
+    ExpectedConstant(Span),
+    ExpectedExpr(Span),
+    ExpectedAnyKeyword(Span),
+    Catchall(&'static str, Span),
+    DoubleDef(Span),
+    NoType(Span),
+    ConflictingLinkage(Span),
+    InvalidSpecifiers(Span),
+    InvalidType(specifier_list::SpeclistFsm, Span),
+    NoStorageClass(Span),
+    NamelessParam(Span),
+    StructDefinition(Span),
+    InvalidDeclarator(&'static str, Span),
+    TypedefInit(Span),
 }
 
-#[derive(Debug)]
-pub enum Error {
-    UnexpectedEof,
-    Expected(Token),
-    ExpectedIdentifier,
-    ExpectedConstant,
-    ExpectedExpr,
-    ExpectedAnyKeyword,
-    Catchall(&'static str),
-    ExtraStuff,
-    DoubleDef,
-    NoType,
-    ConflictingLinkage,
-    InvalidSpecifiers,
-    InvalidType(specifier_list::SpeclistFsm),
-    NoStorageClass,
+impl Error {
+    pub const fn span(&self) -> Span {
+        match self {
+            Self::UnexpectedEof(span)
+            | Self::Expected(_, span, _)
+            | Self::ExpectedIdentifier(span)
+            | Self::ExpectedConstant(span)
+            | Self::ExpectedExpr(span)
+            | Self::ExpectedAnyKeyword(span)
+            | Self::Catchall(_, span)
+            | Self::DoubleDef(span)
+            | Self::NoType(span)
+            | Self::ConflictingLinkage(span)
+            | Self::InvalidSpecifiers(span)
+            | Self::InvalidType(_, span)
+            | Self::NoStorageClass(span)
+            | Self::NamelessParam(span)
+            | Self::StructDefinition(span)
+            | Self::InvalidDeclarator(_, span)
+            | Self::TypedefInit(span) => *span,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        parse, BlockItem, Bop, Dec, Designator, Enumerator, Error, Expr, Initializer, Layout,
+        Stmnt, VarType,
+    };
+    use crate::lex::{tokenize, Constant};
+
+    #[test]
+    fn recovery() {
+        let source = b"int main(void) {\n  int x = 1\n  int y = ;\n  return x\n}\nint f(int a b) { return a; }\nstruct S { int a b; };\nint g(int a { return a; }\nint h = 3;\n";
+        let (program, errors) = parse(tokenize(source).unwrap());
+        let lines: Vec<usize> = errors.iter().map(|e| e.span().line).collect();
+        // nothing after the bodies of `S` and `g` gets mistaken for the start of a declaration
+        assert_eq!(lines, [2, 3, 4, 6, 7, 8]);
+        assert!(matches!(errors[0], Error::Expected(..)));
+        assert!(matches!(errors[1], Error::ExpectedExpr(_)));
+        // `main` and `h` still make it, `f`, `S` and `g` don't
+        assert_eq!(program.0.len(), 2);
+    }
+
+    #[test]
+    fn spans() {
+        let source =
+            b"# 1 \"main.c\"\nint x;\n# 40 \"other.h\"\n  long y = 2;\nint f(void) {\n\treturn y;\n}\n";
+        let tokens = tokenize(source).unwrap();
+        let at = |i: usize| {
+            let span = tokens[i].span;
+            (span.file, span.line, span.column, span.len)
+        };
+        assert_eq!(at(0), ("main.c", 1, 1, 3));
+        assert_eq!(at(3), ("other.h", 40, 3, 4));
+
+        let (program, errors) = parse(tokens.clone());
+        assert!(errors.is_empty());
+        let [Dec::Var(x), Dec::Var(y), Dec::Fn(f)] = &program.0[..] else {
+            unreachable!()
+        };
+        assert_eq!((x.span.file, x.span.line), ("main.c", 1));
+        assert_eq!((y.span.file, y.span.line), ("other.h", 40));
+        assert_eq!((f.span.file, f.span.line), ("other.h", 41));
+        let [BlockItem::S(Stmnt::Ret(Some(value), ret))] = f.body.as_deref().unwrap() else {
+            unreachable!()
+        };
+        assert_eq!((ret.line, ret.column, ret.len), (42, 2, 6));
+        let value = value.span();
+        assert_eq!((value.file, value.line, value.column), ("other.h", 42, 9));
+    }
+
+    #[test]
+    fn pointer_declarators() {
+        let (program, errors) = parse(tokenize(b"long **pp; int *p = &*q;").unwrap());
+        assert!(errors.is_empty());
+        let types: Vec<_> = program
+            .0
+            .iter()
+            .map(|dec| match dec {
+                Dec::Var(v) => v.typ,
+                _ => unreachable!(),
+            })
+            .collect();
+        let long_ptr = VarType::Long.pointer_to();
+        assert_eq!(types, [long_ptr.pointer_to(), VarType::Int.pointer_to()]);
+        assert_eq!(types[0].to_string(), "long **");
+    }
+
+    #[test]
+    fn unsigned_specifiers() {
+        let source = b"unsigned long int a; long unsigned b; unsigned c; signed d; int unsigned e;";
+        let (program, errors) = parse(tokenize(source).unwrap());
+        assert!(errors.is_empty());
+        let types: Vec<_> = program
+            .0
+            .iter()
+            .map(|dec| match dec {
+                Dec::Var(v) => v.typ,
+                _ => unreachable!(),
+            })
+            .collect();
+        use VarType::{Int, UInt, ULong};
+        assert_eq!(types, [ULong, ULong, UInt, Int, UInt]);
+
+        let (_, errors) = parse(tokenize(b"unsigned signed x;").unwrap());
+        assert!(matches!(errors[..], [Error::InvalidType(..)]));
+    }
+
+    #[test]
+    fn array_declarators() {
+        let (program, errors) = parse(tokenize(b"int *a[2][3] = {{0}, {0, 0,},};").unwrap());
+        assert!(errors.is_empty());
+        let Dec::Var(v) = &program.0[0] else {
+            unreachable!()
+        };
+        let row = VarType::Int.pointer_to().array_of(3);
+        assert_eq!(v.typ, row.array_of(2));
+        assert_eq!(v.typ.to_string(), "int *[2][3]");
+        assert_eq!(v.typ.size(), 48);
+        assert!(matches!(&v.init, Some(Initializer::Compound(rows, _)) if rows.len() == 2));
+
+        // the length can be left out, typecheck gets it from the initializer
+        let (program, errors) =
+            parse(tokenize(b"int a[][2] = {{1}, {2}}; extern int e[];").unwrap());
+        assert!(errors.is_empty());
+        let [Dec::Var(a), Dec::Var(e)] = &program.0[..] else {
+            unreachable!()
+        };
+        assert_eq!(a.typ, VarType::Int.array_of(2).incomplete_array(None));
+        assert_eq!(e.typ.to_string(), "int []");
+    }
+
+    #[test]
+    fn double_constants() {
+        let (program, errors) =
+            parse(tokenize(b"double a = 1.5e3; double b = .5; double c = 1.;").unwrap());
+        assert!(errors.is_empty());
+        let values: Vec<_> = program
+            .0
+            .iter()
+            .map(|dec| match dec {
+                Dec::Var(v) if v.typ == VarType::Double => match v.init {
+                    Some(Initializer::Single(Expr::Const(Constant::Double(d), _))) => d,
+                    _ => unreachable!(),
+                },
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(values, [1500.0, 0.5, 1.0]);
+
+        assert!(tokenize(b"1e+").is_err());
+        assert!(tokenize(b"1.5.2").is_err());
+        let (_, errors) = parse(tokenize(b"long double x;").unwrap());
+        assert!(matches!(errors[..], [Error::InvalidType(..)]));
+    }
+
+    #[test]
+    fn strings_and_chars() {
+        let source = br#"char *s = "a\tb" "\x41\101\0"; unsigned char c = '\n'; char d = '\xff';"#;
+        let (program, errors) = parse(tokenize(source).unwrap());
+        assert!(errors.is_empty());
+        let [Dec::Var(s), Dec::Var(c), Dec::Var(d)] = &program.0[..] else {
+            unreachable!()
+        };
+        assert_eq!(s.typ, VarType::Char.pointer_to());
+        assert!(matches!(
+            &s.init,
+            Some(Initializer::Single(Expr::String(bytes, _))) if &bytes[..] == b"a\tbAA\0"
+        ));
+        assert_eq!(c.typ, VarType::UChar);
+        assert!(matches!(
+            c.init,
+            Some(Initializer::Single(Expr::Const(Constant::Int(10), _)))
+        ));
+        // plain chars are signed
+        assert!(matches!(
+            d.init,
+            Some(Initializer::Single(Expr::Const(Constant::Int(-1), _)))
+        ));
+
+        assert!(tokenize(b"'ab'").is_err());
+        assert!(tokenize(br#""\q""#).is_err());
+        assert!(tokenize(b"\"unterminated").is_err());
+    }
+
+    #[test]
+    fn struct_declarations() {
+        let source = b"struct s { char c; long l[2]; struct s *next; }; union u; struct s x; int y = x.l[1] + p->c;";
+        let (program, errors) = parse(tokenize(source).unwrap());
+        assert!(errors.is_empty());
+        let [Dec::Struct(s), Dec::Struct(u), Dec::Var(x), Dec::Var(y)] = &program.0[..] else {
+            unreachable!()
+        };
+        assert!(!s.union && u.union && u.members.is_none());
+        let members = s.members.as_deref().unwrap();
+        assert_eq!(members[2].typ, VarType::Struct("s").pointer_to());
+        assert_eq!(x.typ.to_string(), "struct s");
+        let Some(Initializer::Single(Expr::Bin(sum))) = &y.init else {
+            unreachable!()
+        };
+        assert!(
+            matches!(*sum.left, Expr::Subscript { ref array, .. } if matches!(**array, Expr::Dot { .. }))
+        );
+        assert!(matches!(*sum.right, Expr::Arrow { .. }));
+
+        // members go at the next multiple of their alignment, arrays are aligned like their
+        // elements, and the whole thing is padded out to its own alignment
+        let layout = Layout::new(
+            members.iter().map(|m| (m.name.as_ref().into(), m.typ)),
+            false,
+            s.span,
+        );
+        let offsets: Vec<_> = layout.members.iter().map(|m| m.offset).collect();
+        assert_eq!(offsets, [0, 8, 24]);
+        assert_eq!((layout.size, layout.alignment), (32, 8));
+        let union = [VarType::Char.array_of(5), VarType::Int].map(|ty| (Box::from(&b"m"[..]), ty));
+        let layout = Layout::new(union, true, u.span);
+        assert_eq!((layout.size, layout.alignment), (8, 4));
+        assert!(layout.members.iter().all(|m| m.offset == 0));
+
+        let (_, errors) = parse(tokenize(b"unsigned struct s x; struct s int y;").unwrap());
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn void() {
+        let source = b"void f(void) { return; } void *g(void *p, int n) { return p; }";
+        let (program, errors) = parse(tokenize(source).unwrap());
+        assert!(errors.is_empty());
+        let [Dec::Fn(f), Dec::Fn(g)] = &program.0[..] else {
+            unreachable!()
+        };
+        assert_eq!(f.typ.ret, VarType::Void);
+        assert!(f.params.is_empty());
+        assert!(matches!(
+            f.body.as_deref(),
+            Some([BlockItem::S(Stmnt::Ret(None, _))])
+        ));
+        assert_eq!(g.typ.ret, VarType::Void.pointer_to());
+        assert_eq!(g.typ.params[..], [VarType::Void.pointer_to(), VarType::Int]);
+
+        let (_, errors) = parse(tokenize(b"unsigned void x; void long y;").unwrap());
+        assert_eq!(errors.len(), 2);
+    }
+    #[test]
+    fn variadic() {
+        let source = b"int f(int n, ...) { __builtin_va_list ap; __builtin_va_start(ap, n); \
+                       return __builtin_va_arg(ap, long *) != 0; }";
+        let (program, errors) = parse(tokenize(source).unwrap());
+        assert!(errors.is_empty());
+        let [Dec::Fn(f)] = &program.0[..] else {
+            unreachable!()
+        };
+        assert!(f.typ.variadic);
+        assert_eq!(f.typ.params[..], [VarType::Int]);
+        let Some([BlockItem::D(Dec::Var(ap)), BlockItem::S(Stmnt::Exp(start)), _]) =
+            f.body.as_deref()
+        else {
+            unreachable!()
+        };
+        assert_eq!(ap.typ.to_string(), "struct __va_list_tag [1]");
+        assert!(matches!(start, Expr::VaStart(..)));
+
+        for source in [&b"int g(...);"[..], b"int h(int a, ..., int b);"] {
+            let (_, errors) = parse(tokenize(source).unwrap());
+            assert!(!errors.is_empty());
+        }
+    }
+
+    #[test]
+    fn declarators() {
+        let source = b"int (*fp)(long, ...), *(*table[2])[3]; char (*rows)[4]; \
+                       const int *const p; int (*g(void))(int);";
+        let (program, errors) = parse(tokenize(source).unwrap());
+        assert!(errors.is_empty());
+        let [Dec::Var(fp), Dec::Var(table), Dec::Var(rows), Dec::Var(p), Dec::Fn(g)] =
+            &program.0[..]
+        else {
+            unreachable!()
+        };
+        assert_eq!(fp.typ.to_string(), "int (*)(long, ...)");
+        assert_eq!(table.typ.to_string(), "int *(*[2])[3]");
+        assert_eq!(rows.typ.to_string(), "char (*)[4]");
+        assert_eq!(p.typ.to_string(), "const int *const");
+        assert!(g.params.is_empty());
+        assert_eq!(g.typ.ret.to_string(), "int (*)(int)");
+
+        for source in [
+            &b"int f(void)[2];"[..],
+            b"int a[2](void);",
+            b"int f(int) { return 0; }",
+        ] {
+            let (_, errors) = parse(tokenize(source).unwrap());
+            assert!(!errors.is_empty());
+        }
+    }
+
+    #[test]
+    fn typedef_names() {
+        // `T * x;` declares a pointer while `T` names a type, and multiplies once it's hidden
+        let source = b"typedef struct { int a; } T, *P; \
+                       int f(void) { T * x; { int T = 1; T * 2; } P p; return (T *)p != x; }";
+        let (program, errors) = parse(tokenize(source).unwrap());
+        assert!(errors.is_empty());
+        let [Dec::Struct(s), Dec::Fn(f)] = &program.0[..] else {
+            unreachable!()
+        };
+        let Some(
+            [BlockItem::D(Dec::Var(x)), BlockItem::S(Stmnt::Compound(inner)), BlockItem::D(Dec::Var(p)), BlockItem::S(Stmnt::Ret(Some(Expr::Bin(cmp)), _))],
+        ) = f.body.as_deref()
+        else {
+            unreachable!()
+        };
+        assert_eq!(x.typ, s.typ().pointer_to());
+        assert_eq!(p.typ, x.typ);
+        assert_eq!(x.typ.to_string(), "struct (anonymous) *");
+        assert!(matches!(
+            inner[..],
+            [BlockItem::D(_), BlockItem::S(Stmnt::Exp(Expr::Bin(_)))]
+        ));
+        assert!(matches!(*cmp.left, Expr::Cast { target, .. } if target == x.typ));
+
+        let (_, errors) = parse(tokenize(b"typedef int T = 1; typedef static int U;").unwrap());
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn enumerators() {
+        // the values are left for typecheck, it's the one that knows their types
+        let source = b"enum e { A = 2, B, C = A << B }; int a[C - 1]; int b[3];";
+        let (program, errors) = parse(tokenize(source).unwrap());
+        assert!(errors.is_empty());
+        let [Dec::Enum(e), Dec::Var(a), Dec::Var(b)] = &program.0[..] else {
+            unreachable!()
+        };
+        assert!(matches!(
+            e.enumerators[..],
+            [
+                Enumerator {
+                    value: Some(Expr::Const(..)),
+                    ..
+                },
+                Enumerator { value: None, .. },
+                Enumerator {
+                    value: Some(Expr::Bin(_)),
+                    ..
+                }
+            ]
+        ));
+        assert!(matches!(
+            a.typ,
+            VarType::IncompleteArray(VarType::Int, Some(_))
+        ));
+        assert_eq!(b.typ, VarType::Int.array_of(3));
+    }
+
+    #[test]
+    fn calls_are_postfix() {
+        let (program, errors) = parse(tokenize(b"int x = s->ops[1](2)(3);").unwrap());
+        assert!(errors.is_empty());
+        let Dec::Var(x) = &program.0[0] else {
+            unreachable!()
+        };
+        let Some(Initializer::Single(Expr::FunctionCall { func, args, .. })) = &x.init else {
+            unreachable!()
+        };
+        assert!(matches!(args[..], [Expr::Const(Constant::Int(3), _)]));
+        let Expr::FunctionCall { func, args, .. } = &**func else {
+            unreachable!()
+        };
+        assert!(matches!(args[..], [Expr::Const(Constant::Int(2), _)]));
+        assert!(matches!(**func, Expr::Subscript { .. }));
+    }
+
+    #[test]
+    fn designated_initializers() {
+        let source = b"enum { N = 2 }; int a[4] = {[N + 1] = 1, 2, .x.y[0] = 3}; \
+                       long p = (struct s){.x = 1}.x;";
+        let (program, errors) = parse(tokenize(source).unwrap());
+        assert!(errors.is_empty());
+        let [Dec::Enum(_), Dec::Var(a), Dec::Var(p)] = &program.0[..] else {
+            unreachable!()
+        };
+        let Some(Initializer::Compound(inits, _)) = &a.init else {
+            unreachable!()
+        };
+        let [Initializer::Designated(index, _), Initializer::Single(_), Initializer::Designated(members, _)] =
+            &inits[..]
+        else {
+            unreachable!()
+        };
+        assert!(matches!(index[..], [Designator::Index(Expr::Bin(_), _)]));
+        assert!(matches!(
+            members[..],
+            [
+                Designator::Member(..),
+                Designator::Member(..),
+                Designator::Index(Expr::Const(Constant::Int(0), _), _)
+            ]
+        ));
+        let Some(Initializer::Single(Expr::Dot { exp, .. })) = &p.init else {
+            unreachable!()
+        };
+        assert!(matches!(**exp, Expr::CompoundLiteral { .. }));
+    }
+
+    #[test]
+    fn comma_and_sizeof() {
+        // commas between arguments separate them, inside parentheses they're operators again
+        let source = b"int a[sizeof(long) * _Alignof(int)]; \
+                       int f(int x) { return g(x = 1, (x, 2)), sizeof x; }";
+        let (program, errors) = parse(tokenize(source).unwrap());
+        assert!(errors.is_empty());
+        let [Dec::Var(a), Dec::Fn(f)] = &program.0[..] else {
+            unreachable!()
+        };
+        assert!(a.typ.has_length());
+        let Some([BlockItem::S(Stmnt::Ret(Some(Expr::Bin(comma)), _))]) = f.body.as_deref() else {
+            unreachable!()
+        };
+        assert_eq!(comma.operator, Bop::Comma);
+        assert!(matches!(*comma.right, Expr::SizeOf(..)));
+        let Expr::FunctionCall { args, .. } = &*comma.left else {
+            unreachable!()
+        };
+        assert!(matches!(
+            args[..],
+            [Expr::Assignment { .. }, Expr::Nested(..)]
+        ));
+    }
 }
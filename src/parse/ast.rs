@@ -1,5 +1,7 @@
+use super::Length;
 use crate::lex::Constant;
 use crate::lex::Identifier;
+use crate::lex::Span;
 pub use inc_dec::*;
 use std::fmt::{self, Display, Formatter};
 pub type Arr<T> = Box<[T]>;
@@ -11,6 +13,9 @@ pub struct Program(pub Box<[Dec]>);
 pub enum Dec {
     Fn(FnDec),
     Var(VarDec),
+    Struct(StructDec),
+    Enum(EnumDec),
+    Typedef(TypedefDec),
 }
 
 #[derive(Debug)]
@@ -20,6 +25,7 @@ pub struct FnDec {
     pub body: Option<Block>,
     pub sc: Option<StorageClass>,
     pub typ: FnType,
+    pub span: Span,
 }
 
 impl From<FnDec> for Dec {
@@ -33,10 +39,19 @@ impl From<VarDec> for Dec {
         Dec::Var(dec)
     }
 }
+
+impl From<StructDec> for Dec {
+    fn from(dec: StructDec) -> Self {
+        Dec::Struct(dec)
+    }
+}
 #[derive(Debug, Copy, Clone)]
 pub enum StaticInit {
     Int(i32),
     Long(i64),
+    UInt(u32),
+    ULong(u64),
+    Double(f64),
 }
 pub type Block = Arr<BlockItem>;
 
@@ -46,14 +61,75 @@ pub type ParamList = Arr<Param>;
 pub struct Param {
     pub typ: VarType,
     pub name: Identifier,
+    pub span: Span,
+}
+
+impl Param {
+    // left out of a prototype, the parser names these by position
+    pub fn unnamed(&self) -> bool {
+        self.name.0.first().is_some_and(u8::is_ascii_digit)
+    }
 }
 
 #[derive(Debug)]
 pub struct VarDec {
     pub name: Identifier,
-    pub init: Option<Expr>,
+    pub init: Option<Initializer<Expr>>,
     pub sc: Option<StorageClass>,
     pub typ: VarType,
+    pub span: Span,
+}
+
+// `struct s;` or `union u { int a; char b[4]; };`
+#[derive(Debug, Clone)]
+pub struct StructDec {
+    // unique once `resolve` is done with it
+    pub tag: &'static str,
+    pub union: bool,
+    // `None` just declares the tag
+    pub members: Option<Arr<MemberDec>>,
+    pub span: Span,
+}
+
+impl StructDec {
+    pub const fn typ(&self) -> VarType {
+        if self.union {
+            VarType::Union(self.tag)
+        } else {
+            VarType::Struct(self.tag)
+        }
+    }
+}
+
+// `enum e { A, B = 4 };`, every enum is an int so this only declares the enumerators
+#[derive(Debug, Clone)]
+pub struct EnumDec {
+    pub enumerators: Arr<Enumerator>,
+    pub span: Span,
+}
+
+// typecheck works out the values, `None` is one more than the one before
+#[derive(Debug, Clone)]
+pub struct Enumerator {
+    pub name: Identifier,
+    pub value: Option<Expr>,
+    pub span: Span,
+}
+
+// only kept when there's an array length in the type, see `Length`, so it gets worked out where
+// the typedef is instead of wherever the type is used
+#[derive(Debug, Clone)]
+pub struct TypedefDec {
+    pub name: Identifier,
+    pub typ: VarType,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct MemberDec {
+    pub name: Identifier,
+    pub typ: VarType,
+    pub span: Span,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -62,16 +138,160 @@ pub enum StorageClass {
     Extern,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct FnType {
-    pub ret: Option<VarType>,
+    pub ret: VarType,
     pub params: Box<[VarType]>,
+    // takes more arguments after `params`
+    pub variadic: bool,
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 pub enum VarType {
+    Char,
+    SChar,
+    UChar,
     Int,
     Long,
+    UInt,
+    ULong,
+    Double,
+    // only ever a return type, or pointed to
+    Void,
+    // interned, see `VarType::pointer_to`
+    Pointer(&'static VarType),
+    Array(&'static VarType, usize),
+    // one whose length typecheck still has to work out
+    IncompleteArray(&'static VarType, Option<Length>),
+    // by tag, the layout lives in `layout`
+    Struct(&'static str),
+    Union(&'static str),
+    // only ever pointed to, functions themselves are declared with `FnDec`
+    Function(&'static FnType),
+    // never wraps an array, the elements get qualified instead, or another `Qualified`
+    Qualified(&'static VarType, Qualifiers),
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub struct Qualifiers {
+    pub r#const: bool,
+    pub volatile: bool,
+}
+
+impl Qualifiers {
+    pub const NONE: Self = Self {
+        r#const: false,
+        volatile: false,
+    };
+    pub const CONST: Self = Self {
+        r#const: true,
+        volatile: false,
+    };
+    pub const VOLATILE: Self = Self {
+        r#const: false,
+        volatile: true,
+    };
+
+    pub const fn is_empty(self) -> bool {
+        !self.r#const && !self.volatile
+    }
+
+    pub const fn union(self, other: Self) -> Self {
+        Self {
+            r#const: self.r#const || other.r#const,
+            volatile: self.volatile || other.volatile,
+        }
+    }
+
+    // has everything `other` has
+    pub const fn contains(self, other: Self) -> bool {
+        (self.r#const || !other.r#const) && (self.volatile || !other.volatile)
+    }
+}
+
+impl Display for Qualifiers {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match (self.r#const, self.volatile) {
+            (true, true) => "const volatile",
+            (true, false) => "const",
+            (false, true) => "volatile",
+            (false, false) => "",
+        })
+    }
+}
+
+// `= 1` or `= {1, {2, 3}}`, every stage of the ast uses it with its own kind of expression
+#[derive(Debug, Clone)]
+pub enum Initializer<E> {
+    Single(E),
+    Compound(Arr<Self>, Span),
+    // `.x[2] = 1`, only ever directly inside a `Compound`, typecheck puts these in order
+    Designated(Arr<Designator<E>>, Box<Self>),
+}
+
+// typecheck works out array indices, like it does array lengths
+#[derive(Debug, Clone)]
+pub enum Designator<E> {
+    Member(Identifier, Span),
+    Index(E, Span),
+}
+
+impl<E> Designator<E> {
+    pub const fn span(&self) -> Span {
+        match self {
+            Self::Member(_, span) | Self::Index(_, span) => *span,
+        }
+    }
+
+    fn map<T>(self, f: &mut impl FnMut(E) -> T) -> Designator<T> {
+        match self {
+            Self::Member(name, span) => Designator::Member(name, span),
+            Self::Index(index, span) => Designator::Index(f(index), span),
+        }
+    }
+}
+
+impl<E> Initializer<E> {
+    pub fn map<T>(self, f: &mut impl FnMut(E) -> T) -> Initializer<T> {
+        match self {
+            Self::Single(e) => Initializer::Single(f(e)),
+            Self::Compound(inits, span) => {
+                Initializer::Compound(inits.into_iter().map(|init| init.map(f)).collect(), span)
+            }
+            Self::Designated(designators, init) => Initializer::Designated(
+                designators.into_iter().map(|d| d.map(f)).collect(),
+                Box::new(init.map(f)),
+            ),
+        }
+    }
+
+    pub fn try_for_each<Err>(
+        &mut self,
+        f: &mut impl FnMut(&mut E) -> Result<(), Err>,
+    ) -> Result<(), Err> {
+        match self {
+            Self::Single(e) => f(e),
+            Self::Compound(inits, _) => inits.iter_mut().try_for_each(|init| init.try_for_each(f)),
+            Self::Designated(designators, init) => {
+                for designator in designators {
+                    if let Designator::Index(index, _) = designator {
+                        f(index)?;
+                    }
+                }
+                init.try_for_each(f)
+            }
+        }
+    }
+}
+
+impl Initializer<Expr> {
+    pub const fn span(&self) -> Span {
+        match self {
+            Self::Single(e) => e.span(),
+            Self::Compound(_, span) => *span,
+            Self::Designated(_, init) => init.span(),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -85,85 +305,172 @@ pub enum Expr {
     Assignment {
         dst: Box<Self>,
         src: Box<Self>,
+        span: Span,
     },
     Bin(Binary),
     Cast {
         target: VarType,
         exp: Box<Self>,
+        span: Span,
     },
 
     //factors
     IncDec {
         op: inc_dec::IncDec,
         exp: Box<Self>,
+        span: Span,
     },
 
-    Var(Identifier),
-    Const(Constant),
+    Var(Identifier, Span),
+    Const(Constant, Span),
+    // adjacent literals are already joined, there's no null terminator yet
+    String(Box<[u8]>, Span),
     Unary(Unary),
-    Nested(Box<Self>),
+    Nested(Box<Self>, Span),
+    Deref(Box<Self>, Span),
+    AddrOf(Box<Self>, Span),
+    Subscript {
+        array: Box<Self>,
+        index: Box<Self>,
+        span: Span,
+    },
+    Dot {
+        exp: Box<Self>,
+        member: Identifier,
+        span: Span,
+    },
+    Arrow {
+        exp: Box<Self>,
+        member: Identifier,
+        span: Span,
+    },
 
     Conditional {
         condition: Box<Self>,
         r#true: Box<Self>,
         r#false: Box<Self>,
+        span: Span,
     },
+    // anything that ends up a function pointer can be called, not just a function's name
     FunctionCall {
-        name: Identifier,
+        func: Box<Self>,
         args: Box<[Self]>,
+        span: Span,
+    },
+    // `va_start(ap, last)`, `last` doesn't tell us anything the function's type doesn't
+    VaStart(Box<Self>, Span),
+    VaArg {
+        ap: Box<Self>,
+        target: VarType,
+        span: Span,
+    },
+    // the operands of these are never evaluated, only their types matter
+    SizeOf(Box<Self>, Span),
+    SizeOfType(VarType, Span),
+    AlignOf(VarType, Span),
+    OffsetOf {
+        target: VarType,
+        member: Identifier,
+        span: Span,
+    },
+    // `(struct p){1, 2}`, an unnamed object that's initialized where it's written
+    CompoundLiteral {
+        ty: VarType,
+        init: Box<Initializer<Self>>,
+        span: Span,
     },
 }
 
 impl Expr {
-    pub fn pre_inc(e: Self) -> Self {
+    pub fn pre_inc(e: Self, span: Span) -> Self {
         Self::IncDec {
             op: PRE_INC,
             exp: Box::new(e),
+            span,
         }
     }
 
-    pub fn pre_dec(e: Self) -> Self {
+    pub fn pre_dec(e: Self, span: Span) -> Self {
         Self::IncDec {
             op: PRE_DEC,
             exp: Box::new(e),
+            span,
         }
     }
 
-    pub fn post_inc(e: Self) -> Self {
+    pub fn post_inc(e: Self, span: Span) -> Self {
         Self::IncDec {
             op: POST_INC,
             exp: Box::new(e),
+            span,
         }
     }
 
-    pub fn post_dec(e: Self) -> Self {
+    pub fn post_dec(e: Self, span: Span) -> Self {
         Self::IncDec {
             op: POST_DEC,
             exp: Box::new(e),
+            span,
+        }
+    }
+
+    pub const fn span(&self) -> Span {
+        match self {
+            Self::Assignment { span, .. }
+            | Self::Bin(Binary { span, .. })
+            | Self::Cast { span, .. }
+            | Self::IncDec { span, .. }
+            | Self::Var(_, span)
+            | Self::Const(_, span)
+            | Self::String(_, span)
+            | Self::Unary(Unary { span, .. })
+            | Self::Nested(_, span)
+            | Self::Deref(_, span)
+            | Self::AddrOf(_, span)
+            | Self::Subscript { span, .. }
+            | Self::Dot { span, .. }
+            | Self::Arrow { span, .. }
+            | Self::Conditional { span, .. }
+            | Self::FunctionCall { span, .. }
+            | Self::VaStart(_, span)
+            | Self::VaArg { span, .. }
+            | Self::CompoundLiteral { span, .. }
+            | Self::SizeOf(_, span)
+            | Self::SizeOfType(_, span)
+            | Self::AlignOf(_, span)
+            | Self::OffsetOf { span, .. } => *span,
         }
     }
 
     pub const fn lvalue(&self) -> bool {
         match self {
-            Self::Var(_) => true,
-            Self::Nested(e) => e.lvalue(),
+            Self::Var(..)
+            | Self::String(..)
+            | Self::Deref(..)
+            | Self::Subscript { .. }
+            | Self::Arrow { .. }
+            | Self::CompoundLiteral { .. } => true,
+            Self::Nested(e, _) | Self::Dot { exp: e, .. } => e.lvalue(),
             _ => false,
         }
     }
 
     pub const fn number(&self) -> Option<Constant> {
         match self {
-            Self::Nested(e) => e.number(),
-            Self::Const(c) => Some(*c),
+            Self::Nested(e, _) => e.number(),
+            Self::Const(c, _) => Some(*c),
             _ => None,
         }
     }
 
     pub const fn static_init(&self) -> Option<StaticInit> {
         match self {
-            Self::Nested(e) => e.static_init(),
-            Self::Const(Constant::Long(c)) => Some(StaticInit::Long(*c)),
-            Self::Const(Constant::Int(c)) => Some(StaticInit::Int(*c)),
+            Self::Nested(e, _) => e.static_init(),
+            Self::Const(Constant::Long(c), _) => Some(StaticInit::Long(*c)),
+            Self::Const(Constant::Int(c), _) => Some(StaticInit::Int(*c)),
+            Self::Const(Constant::UInt(c), _) => Some(StaticInit::UInt(*c)),
+            Self::Const(Constant::ULong(c), _) => Some(StaticInit::ULong(*c)),
+            Self::Const(Constant::Double(c), _) => Some(StaticInit::Double(*c)),
             _ => None,
         }
     }
@@ -173,6 +480,7 @@ impl Expr {
 pub struct Unary {
     pub exp: Box<Expr>,
     pub op: UnOp,
+    pub span: Span,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -187,6 +495,7 @@ pub struct Binary {
     pub operator: Bop,
     pub left: Box<Expr>,
     pub right: Box<Expr>,
+    pub span: Span,
 }
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
@@ -224,6 +533,7 @@ pub enum Bop {
     RightShiftEqual,
 
     Ternary,
+    Comma,
 }
 
 impl Bop {
@@ -240,6 +550,7 @@ impl Bop {
             | Self::BitXorEqual
             | Self::LeftShiftEqual
             | Self::RightShiftEqual => 1,
+            Self::Comma => 0,
             Self::Ternary => 3,
             Self::LogOr => 5,
             Self::LogAnd => 10,
@@ -274,6 +585,25 @@ impl Bop {
         )
     }
 
+    // doubles can't be operands of these
+    pub const fn integer_only(&self) -> bool {
+        matches!(
+            self,
+            Self::Remainder
+                | Self::BitAnd
+                | Self::BitOr
+                | Self::Xor
+                | Self::LeftShift
+                | Self::RightShift
+                | Self::RemEqual
+                | Self::BitAndEqual
+                | Self::BitOrEqual
+                | Self::BitXorEqual
+                | Self::LeftShiftEqual
+                | Self::RightShiftEqual
+        )
+    }
+
     pub const fn relational(&self) -> bool {
         matches!(
             self,
@@ -289,15 +619,16 @@ impl Bop {
 
 #[derive(Debug)]
 pub enum Stmnt {
-    Ret(Expr),
+    // the span is the `return` keyword, there's no expression for `return;`
+    Ret(Option<Expr>, Span),
     Exp(Expr),
     If {
         condition: Expr,
         then: Box<Self>,
         r#else: Option<Box<Self>>,
     },
-    Break,
-    Continue,
+    Break(Span),
+    Continue(Span),
     While {
         condition: Expr,
         body: Box<Self>,
@@ -307,7 +638,7 @@ pub enum Stmnt {
         condition: Expr,
     },
     For {
-        init: Option<ForInit>,
+        init: Option<Box<ForInit>>,
         condition: Option<Expr>,
         post: Option<Expr>,
         body: Box<Self>,
@@ -316,8 +647,9 @@ pub enum Stmnt {
     Label {
         label: Label,
         body: Box<Self>,
+        span: Span,
     },
-    Goto(Identifier),
+    Goto(Identifier, Span),
     Null,
     Switch {
         val: Expr,
@@ -334,7 +666,8 @@ pub enum ForInit {
 #[derive(Debug, Clone)]
 pub enum Label {
     Named(Identifier),
-    Case(Constant),
+    // `resolve_loops` works out the value once the enumerators are resolved
+    Case(Expr),
     Default,
 }
 
@@ -347,7 +680,7 @@ impl Display for Program {
 impl Display for Stmnt {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match self {
-            Stmnt::Ret(ret) => write!(f, "Return(\n{:?}\n)", ret),
+            Stmnt::Ret(ret, _) => write!(f, "Return(\n{:?}\n)", ret),
             Stmnt::Exp(e) => write!(f, "(\n{:?}\n)", e),
             Stmnt::Null => write!(f, "(\nnull\n)"),
 
@@ -356,9 +689,9 @@ impl Display for Stmnt {
                 then,
                 r#else,
             } => write!(f, "if(\n{condition:?}\n){then}{else:?}"),
-            Stmnt::Label { label, body } => write!(f, "LABEL\n{label}:{body}\n"),
+            Stmnt::Label { label, body, .. } => write!(f, "LABEL\n{label}:{body}\n"),
 
-            Stmnt::Goto(name) => write!(f, "goto\n{name}:\n"),
+            Stmnt::Goto(name, _) => write!(f, "goto\n{name}:\n"),
 
             Stmnt::Compound(block) => writeln!(f, "{{{block:?}}}"),
             _ => todo!(),
@@ -0,0 +1,90 @@
+use super::VarType;
+use crate::lex::Span;
+use std::collections::BTreeMap;
+use std::sync::{Mutex, Once};
+
+// where everything in a struct or union goes, computed once the definition has been typechecked
+#[derive(Debug)]
+pub struct Layout {
+    pub size: usize,
+    pub alignment: usize,
+    pub members: Box<[Member]>,
+    // the definition, for redefinition errors
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct Member {
+    pub name: Box<[u8]>,
+    pub typ: VarType,
+    pub offset: usize,
+}
+
+// keyed by the unique tag `resolve` gives every struct
+static LAYOUTS: Mutex<BTreeMap<&'static str, &'static Layout>> = Mutex::new(BTreeMap::new());
+
+impl Layout {
+    // system v: every member starts at the next multiple of its own alignment, the whole thing is
+    // as aligned as its most aligned member and padded out to a multiple of that
+    // union members all start at the beginning
+    pub fn new(
+        members: impl IntoIterator<Item = (Box<[u8]>, VarType)>,
+        union: bool,
+        span: Span,
+    ) -> Self {
+        let mut size = 0usize;
+        let mut alignment = 1;
+        let members = members
+            .into_iter()
+            .map(|(name, typ)| {
+                alignment = alignment.max(typ.type_alignment());
+                let offset = if union {
+                    0
+                } else {
+                    size.next_multiple_of(typ.type_alignment())
+                };
+                size = size.max(offset + typ.size());
+                Member { name, typ, offset }
+            })
+            .collect();
+        Self {
+            size: size.next_multiple_of(alignment),
+            alignment,
+            members,
+            span,
+        }
+    }
+
+    pub fn define(tag: &'static str, layout: Self) {
+        let layout = Box::leak(Box::new(layout));
+        LAYOUTS.lock().unwrap().insert(tag, layout);
+    }
+
+    // `None` while the struct is incomplete
+    pub fn get(tag: &str) -> Option<&'static Self> {
+        LAYOUTS.lock().unwrap().get(tag).copied()
+    }
+
+    pub fn member(&self, name: &[u8]) -> Option<&Member> {
+        self.members.iter().find(|member| &*member.name == name)
+    }
+}
+
+// `__builtin_va_list`, the struct system v keeps the state of `va_arg` in, as an array of one so
+// it gets passed by pointer
+pub const VA_LIST_TAG: &str = "__va_list_tag.builtin";
+
+pub fn va_list() -> VarType {
+    static DEFINED: Once = Once::new();
+    DEFINED.call_once(|| {
+        let members = [
+            ("gp_offset", VarType::UInt),
+            ("fp_offset", VarType::UInt),
+            ("overflow_arg_area", VarType::Void.pointer_to()),
+            ("reg_save_area", VarType::Void.pointer_to()),
+        ]
+        .map(|(name, typ)| (name.as_bytes().into(), typ));
+        Layout::define(VA_LIST_TAG, Layout::new(members, false, Span::default()));
+    });
+    VarType::Struct(VA_LIST_TAG).array_of(1)
+}
@@ -0,0 +1,64 @@
+use super::Expr;
+use std::cell::RefCell;
+
+// the `N + 1` in `int a[N + 1]`, which can't be worked out until typecheck knows the types in it
+// and the layouts of whatever it takes the size of. `VarType` has to stay Copy, so all it keeps
+// is where the expression is in here
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Length(usize);
+
+enum State {
+    Parsed(Expr),
+    Resolved(Expr),
+    Evaluated(usize),
+    // out with whoever is resolving or evaluating it
+    Taken,
+}
+
+thread_local! {
+    // expressions hold `Rc`s, so unlike the layouts these stay on the thread that parsed them
+    static LENGTHS: RefCell<Vec<State>> = const { RefCell::new(Vec::new()) };
+}
+
+impl Length {
+    pub fn new(exp: Expr) -> Self {
+        LENGTHS.with_borrow_mut(|lengths| {
+            lengths.push(State::Parsed(exp));
+            Self(lengths.len() - 1)
+        })
+    }
+
+    fn take(self) -> State {
+        LENGTHS.with_borrow_mut(|lengths| std::mem::replace(&mut lengths[self.0], State::Taken))
+    }
+
+    fn set(self, state: State) {
+        LENGTHS.with_borrow_mut(|lengths| lengths[self.0] = state);
+    }
+
+    // everything declared with a typedef shares its lengths, they're resolved where the typedef is
+    pub fn resolve<E>(self, f: impl FnOnce(&mut Expr) -> Result<(), E>) -> Result<(), E> {
+        match self.take() {
+            State::Parsed(mut exp) => {
+                let resolved = f(&mut exp);
+                self.set(State::Resolved(exp));
+                resolved
+            }
+            state => {
+                self.set(state);
+                Ok(())
+            }
+        }
+    }
+
+    // and evaluated there too, later uses get the same length back
+    pub fn evaluate<E>(self, f: impl FnOnce(Expr) -> Result<usize, E>) -> Result<usize, E> {
+        let len = match self.take() {
+            State::Resolved(exp) => f(exp)?,
+            State::Evaluated(len) => len,
+            State::Parsed(_) | State::Taken => unreachable!("lengths are resolved first"),
+        };
+        self.set(State::Evaluated(len));
+        Ok(len)
+    }
+}
@@ -1,10 +1,17 @@
-use super::{Error, SpecifierList, StorageClass, Token, TokenIter, VarType};
+use super::VarType;
+use super::{Dec, Error, Qualifiers, Span, SpecifierList, StorageClass, Token, TokenIter};
 
 #[derive(Debug, Clone)]
 pub struct SpeclistFsm {
     sc: Option<StorageClass>,
     int: bool,
+    // `Some(true)` for `unsigned`, `Some(false)` for `signed`
+    unsigned: Option<bool>,
     typ: Option<VarType>,
+    // a typedef name has to be the only type specifier
+    named: Option<VarType>,
+    qualifiers: Qualifiers,
+    typedef: bool,
 }
 
 impl SpeclistFsm {
@@ -13,37 +20,112 @@ impl SpeclistFsm {
             sc: None,
             typ: None,
             int: false,
+            unsigned: None,
+            named: None,
+            qualifiers: Qualifiers::NONE,
+            typedef: false,
         }
     }
 
-    pub const fn done(self) -> Result<SpecifierList, Error> {
-        if let Some(typ) = self.typ {
-            Ok(SpecifierList { sc: self.sc, typ })
+    // `unsigned` on its own means `unsigned int`
+    const fn resolved(&self) -> Option<VarType> {
+        if self.named.is_some() {
+            return self.named;
+        }
+        match (self.typ, self.unsigned) {
+            (None, Some(true)) | (Some(VarType::Int), Some(true)) => Some(VarType::UInt),
+            (Some(VarType::Long), Some(true)) => Some(VarType::ULong),
+            // plain `char` is a type of its own, even though it acts like `signed char`
+            (Some(VarType::Char), Some(true)) => Some(VarType::UChar),
+            (Some(VarType::Char), Some(false)) => Some(VarType::SChar),
+            (None, Some(false)) => Some(VarType::Int),
+            (
+                Some(
+                    VarType::Double
+                    | VarType::Void
+                    | VarType::Struct(_)
+                    | VarType::Union(_)
+                    | VarType::Array(..)
+                    | VarType::IncompleteArray(..)
+                    | VarType::Function(_)
+                    | VarType::Qualified(..),
+                ),
+                Some(_),
+            ) => None,
+            (typ, _) => typ,
+        }
+    }
+
+    pub fn done(self, span: Span) -> Result<SpecifierList, Error> {
+        if let Some(typ) = self.resolved() {
+            Ok(SpecifierList {
+                sc: self.sc,
+                typ: typ.qualified(self.qualifiers),
+                typedef: self.typedef,
+            })
+        } else {
+            Err(Error::Catchall("invalid specifier list", span))
+        }
+    }
+
+    pub fn type_specifier(self, span: Span) -> Result<VarType, Error> {
+        match (self.sc, self.typedef, self.resolved()) {
+            (Some(_), _, _) | (_, true, _) => Err(Error::NoStorageClass(span)),
+            (None, false, None) => Err(Error::InvalidSpecifiers(span)),
+            (None, false, Some(typ)) => Ok(typ.qualified(self.qualifiers)),
+        }
+    }
+
+    // a typedef name can't be combined with `unsigned` or any other type, and when there
+    // already is one, an identifier must be what's being declared
+    const fn takes_name(&self) -> bool {
+        self.typ.is_none() && self.unsigned.is_none() && self.named.is_none()
+    }
+
+    fn named(&mut self, typ: VarType) {
+        self.named = Some(typ);
+    }
+
+    // an enum is just an int, but like a typedef name it has to be the only type specifier
+    fn r#enum(&mut self, span: Span) -> Result<(), Error> {
+        if self.takes_name() {
+            self.named(VarType::Int);
+            Ok(())
+        } else {
+            self.invalid_type(span)
+        }
+    }
+
+    // repeating a qualifier is fine
+    const fn qualify(&mut self, qualifiers: Qualifiers) {
+        self.qualifiers = self.qualifiers.union(qualifiers);
+    }
+
+    fn typedef(&mut self, span: Span) -> Result<(), Error> {
+        if self.typedef || self.sc.is_some() {
+            Err(Error::InvalidSpecifiers(span))
         } else {
-            Err(Error::Catchall("invalid specifier list"))
+            self.typedef = true;
+            Ok(())
         }
     }
 
-    pub const fn type_specifier(self) -> Result<VarType, Error> {
-        match self {
-            Self { sc: Some(_), .. } => Err(Error::NoStorageClass),
-            Self {
-                sc: None,
-                typ: None,
-                int: _,
-            } => Err(Error::InvalidSpecifiers),
-            Self {
-                sc: None,
-                typ: Some(typ),
-                int: _,
-            } => Ok(typ),
+    fn sign(&mut self, unsigned: bool, span: Span) -> Result<(), Error> {
+        match self.unsigned {
+            None if self.named.is_some() => self.invalid_type(span),
+            None => {
+                self.unsigned = Some(unsigned);
+                Ok(())
+            }
+            Some(_) => self.invalid_type(span),
         }
     }
 
-    fn r#extern(&mut self) -> Result<(), Error> {
+    fn r#extern(&mut self, span: Span) -> Result<(), Error> {
         match self.sc {
-            Some(StorageClass::Static) => Err(Error::ConflictingLinkage),
-            Some(StorageClass::Extern) => Err(Error::InvalidSpecifiers),
+            None if self.typedef => Err(Error::InvalidSpecifiers(span)),
+            Some(StorageClass::Static) => Err(Error::ConflictingLinkage(span)),
+            Some(StorageClass::Extern) => Err(Error::InvalidSpecifiers(span)),
             None => {
                 self.sc = Some(StorageClass::Extern);
                 Ok(())
@@ -51,10 +133,11 @@ impl SpeclistFsm {
         }
     }
 
-    fn r#static(&mut self) -> Result<(), Error> {
+    fn r#static(&mut self, span: Span) -> Result<(), Error> {
         match self.sc {
-            Some(StorageClass::Static) => Err(Error::InvalidSpecifiers),
-            Some(StorageClass::Extern) => Err(Error::ConflictingLinkage),
+            None if self.typedef => Err(Error::InvalidSpecifiers(span)),
+            Some(StorageClass::Static) => Err(Error::InvalidSpecifiers(span)),
+            Some(StorageClass::Extern) => Err(Error::ConflictingLinkage(span)),
             None => {
                 self.sc = Some(StorageClass::Static);
                 Ok(())
@@ -62,14 +145,32 @@ impl SpeclistFsm {
         }
     }
 
-    fn long(&mut self) -> Result<(), Error> {
+    fn long(&mut self, span: Span) -> Result<(), Error> {
         match self.typ {
             Some(VarType::Int) => {
                 self.typ = Some(VarType::Long);
                 self.int = true;
                 Ok(())
             }
-            Some(VarType::Long) => self.invalid_type(),
+            // pointers only come from declarators, and signedness is tracked separately
+            Some(
+                VarType::Char
+                | VarType::SChar
+                | VarType::UChar
+                | VarType::Long
+                | VarType::UInt
+                | VarType::ULong
+                | VarType::Double
+                | VarType::Void
+                | VarType::Pointer(_)
+                | VarType::Array(..)
+                | VarType::IncompleteArray(..)
+                | VarType::Struct(_)
+                | VarType::Union(_)
+                | VarType::Function(_)
+                | VarType::Qualified(..),
+            ) => self.invalid_type(span),
+            None if self.named.is_some() => self.invalid_type(span),
             None => {
                 self.typ = Some(VarType::Long);
                 Ok(())
@@ -77,38 +178,141 @@ impl SpeclistFsm {
         }
     }
 
-    fn int(&mut self) -> Result<(), Error> {
+    fn int(&mut self, span: Span) -> Result<(), Error> {
         match self.typ {
+            None if self.named.is_some() => self.invalid_type(span),
             None => {
                 self.typ = Some(VarType::Int);
                 self.int = true;
                 Ok(())
             }
-            Some(VarType::Long) if !self.int => Ok(()),
-            Some(VarType::Int | VarType::Long) => self.invalid_type(),
+            Some(VarType::Long) if !self.int => {
+                self.int = true;
+                Ok(())
+            }
+            Some(
+                VarType::Char
+                | VarType::SChar
+                | VarType::UChar
+                | VarType::Int
+                | VarType::Long
+                | VarType::UInt
+                | VarType::ULong
+                | VarType::Double
+                | VarType::Void
+                | VarType::Pointer(_)
+                | VarType::Array(..)
+                | VarType::IncompleteArray(..)
+                | VarType::Struct(_)
+                | VarType::Union(_)
+                | VarType::Function(_)
+                | VarType::Qualified(..),
+            ) => self.invalid_type(span),
         }
     }
 
-    fn invalid_type<T>(&self) -> Result<T, Error> {
-        Err(Error::InvalidType(self.clone()))
+    fn double(&mut self, span: Span) -> Result<(), Error> {
+        match self.typ {
+            None if self.named.is_some() => self.invalid_type(span),
+            None => {
+                self.typ = Some(VarType::Double);
+                Ok(())
+            }
+            Some(_) => self.invalid_type(span),
+        }
+    }
+
+    fn char(&mut self, span: Span) -> Result<(), Error> {
+        match self.typ {
+            None if self.named.is_some() => self.invalid_type(span),
+            None => {
+                self.typ = Some(VarType::Char);
+                Ok(())
+            }
+            Some(_) => self.invalid_type(span),
+        }
+    }
+
+    fn void(&mut self, span: Span) -> Result<(), Error> {
+        match self.typ {
+            None if self.named.is_some() => self.invalid_type(span),
+            None => {
+                self.typ = Some(VarType::Void);
+                Ok(())
+            }
+            Some(_) => self.invalid_type(span),
+        }
+    }
+
+    fn r#struct(&mut self, typ: VarType, span: Span) -> Result<(), Error> {
+        match self.typ {
+            None if self.named.is_some() => self.invalid_type(span),
+            None => {
+                self.typ = Some(typ);
+                Ok(())
+            }
+            Some(_) => self.invalid_type(span),
+        }
+    }
+
+    fn invalid_type<T>(&self, span: Span) -> Result<T, Error> {
+        Err(Error::InvalidType(self.clone(), span))
     }
 }
 
-fn get_specifier(tokens: &mut TokenIter, builder: &mut SpeclistFsm) -> Result<bool, Error> {
+fn get_specifier(
+    tokens: &mut TokenIter,
+    builder: &mut SpeclistFsm,
+    defs: &mut Vec<Dec>,
+) -> Result<bool, Error> {
+    let span = tokens.span();
     match tokens.peek() {
-        Some(Token::Int) => builder.int().map(|_| true),
-        Some(Token::Long) => builder.long().map(|_| true),
-        Some(Token::Static) => builder.r#static().map(|_| true),
-        Some(Token::Extern) => builder.r#extern().map(|_| true),
-        _ => Ok(false),
-    }
+        Some(Token::Int) => builder.int(span),
+        Some(Token::Long) => builder.long(span),
+        Some(Token::Double) => builder.double(span),
+        Some(Token::Char) => builder.char(span),
+        Some(Token::Void) => builder.void(span),
+        Some(Token::Signed) => builder.sign(false, span),
+        Some(Token::Unsigned) => builder.sign(true, span),
+        Some(Token::Static) => builder.r#static(span),
+        Some(Token::Extern) => builder.r#extern(span),
+        Some(Token::Typedef) => builder.typedef(span),
+        Some(Token::Const) => {
+            builder.qualify(Qualifiers::CONST);
+            Ok(())
+        }
+        Some(Token::Volatile) => {
+            builder.qualify(Qualifiers::VOLATILE);
+            Ok(())
+        }
+        Some(Token::VaList) => builder.r#struct(super::va_list(), span),
+        // takes care of its own tokens, members and all
+        Some(Token::Struct | Token::Union) => {
+            let typ = super::struct_specifier(tokens, defs)?;
+            return builder.r#struct(typ, span).map(|_| true);
+        }
+        Some(Token::Enum) => {
+            super::enum_specifier(tokens, defs)?;
+            return builder.r#enum(span).map(|_| true);
+        }
+        Some(Token::Identifier(name)) if builder.takes_name() => match tokens.typedef(name) {
+            Some(typ) => {
+                builder.named(typ);
+                Ok(())
+            }
+            None => return Ok(false),
+        },
+        _ => return Ok(false),
+    }?;
+    tokens.next();
+    Ok(true)
 }
 
-pub fn get_specifiers(tokens: &mut TokenIter) -> Result<SpeclistFsm, Error> {
+// struct and enum definitions are pushed onto `defs`, so they can be declared ahead of whatever
+// uses them
+pub fn get_specifiers(tokens: &mut TokenIter, defs: &mut Vec<Dec>) -> Result<SpeclistFsm, Error> {
     let mut builder = SpeclistFsm::new();
 
-    while get_specifier(tokens, &mut builder)? {
-        tokens.next();
-    }
+    while get_specifier(tokens, &mut builder, defs)? {}
     Ok(builder)
 }
@@ -0,0 +1,730 @@
+mod expr;
+mod macros;
+mod tokens;
+
+use macros::{Expander, Macro, MacroTable};
+use tokens::{Kind, PpToken};
+
+use crate::diagnostics::Diagnostic;
+use crate::lex::{self, Span};
+use crate::{CVersion, Target, CONFIG};
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+// gcc gives up around here too
+const MAX_INCLUDE_DEPTH: usize = 200;
+
+pub fn preprocess(
+    path: &Path,
+    search_paths: &[PathBuf],
+    system_paths: &[PathBuf],
+) -> Result<Box<[u8]>, Error> {
+    Preprocessor::new(search_paths.into(), system_paths.into()).run(path)
+}
+
+pub struct Preprocessor {
+    // `-I`, searched before the headers that come with the compiler
+    search_paths: Vec<PathBuf>,
+    // and the system's, searched after them
+    system_paths: Vec<PathBuf>,
+    macros: MacroTable,
+    // files that said `#pragma once`
+    once: HashSet<PathBuf>,
+    depth: usize,
+    out: Output,
+    // `#warning`s, the driver reports them whether or not we finish
+    warnings: Vec<Diagnostic>,
+}
+
+// the file we're currently in, `name` and `line_delta` are what `#line` changes
+struct File {
+    path: Option<PathBuf>,
+    name: Rc<str>,
+    line_delta: isize,
+}
+
+impl File {
+    const fn line(&self, physical: usize) -> usize {
+        physical.saturating_add_signed(self.line_delta)
+    }
+
+    // for tokens whose line has been through `line` already
+    fn span(&self, token: &PpToken) -> Span {
+        Span {
+            file: lex::intern(&self.name),
+            line: token.line,
+            column: token.column,
+            len: token.text.len(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Conditional {
+    // are we emitting the current group
+    active: bool,
+    // has any group of this conditional been taken yet
+    taken: bool,
+    seen_else: bool,
+    // is the group this conditional sits in active
+    outer: bool,
+    // the `#if` that opened it
+    start: Span,
+}
+
+impl Preprocessor {
+    pub fn new(search_paths: Vec<PathBuf>, system_paths: Vec<PathBuf>) -> Self {
+        let mut preprocessor = Self {
+            search_paths,
+            system_paths,
+            macros: MacroTable::new(),
+            once: HashSet::new(),
+            depth: 0,
+            out: Output::default(),
+            warnings: Vec::new(),
+        };
+        let version = match CONFIG.get().map(|config| config.version) {
+            Some(CVersion::C23) => "202311L",
+            Some(CVersion::C17) | None => "201710L",
+        };
+        let platform = match Target::current() {
+            Target::Linux => "__linux__",
+            Target::Darwin => "__APPLE__",
+        };
+        for (name, value) in [
+            ("__STDC__", "1"),
+            ("__STDC_HOSTED__", "1"),
+            ("__STDC_VERSION__", version),
+            ("__x86_64__", "1"),
+            ("__LP64__", "1"),
+            (platform, "1"),
+        ] {
+            preprocessor.define_object(name, value);
+        }
+        preprocessor
+    }
+
+    // `-D`, either `NAME` or `NAME=value`
+    pub fn define(&mut self, definition: &str) -> Result<(), Error> {
+        let (name, value) = definition.split_once('=').unwrap_or((definition, "1"));
+        let line = format!("{name} {value}");
+        let tokens = tokens::tokenize(line.as_bytes(), "<command line>")?;
+        self.define_macro(&tokens[..tokens.len() - 1])
+    }
+
+    pub fn undefine(&mut self, name: &str) {
+        self.macros.remove(name);
+    }
+
+    fn define_object(&mut self, name: &str, value: &str) {
+        let _ = self.define(&format!("{name}={value}"));
+    }
+
+    pub fn take_warnings(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    pub fn run(&mut self, path: &Path) -> Result<Box<[u8]>, Error> {
+        let bytes = fs::read(path).map_err(Error::Io)?;
+        let mut file = File {
+            path: Some(path.into()),
+            name: path.to_string_lossy().into(),
+            line_delta: 0,
+        };
+        self.file(&mut file, &bytes)?;
+        Ok(std::mem::take(&mut self.out).finish())
+    }
+
+    // for sources that don't live on disk, `#include "..."` only looks in the search paths
+    pub fn run_bytes(&mut self, name: &str, bytes: &[u8]) -> Result<Box<[u8]>, Error> {
+        let mut file = File {
+            path: None,
+            name: name.into(),
+            line_delta: 0,
+        };
+        self.file(&mut file, bytes)?;
+        Ok(std::mem::take(&mut self.out).finish())
+    }
+
+    fn file(&mut self, file: &mut File, bytes: &[u8]) -> Result<(), Error> {
+        let tokens = tokens::tokenize(bytes, lex::intern(&file.name))?;
+        self.out.marker(1, &file.name);
+
+        let mut conditionals: Vec<Conditional> = Vec::new();
+        let mut text: Vec<PpToken> = Vec::new();
+
+        for line in tokens.split_inclusive(|token| token.kind == Kind::Newline) {
+            let (newline, line) = line.split_last().unwrap();
+            let line = line.iter().cloned().map(|mut token| {
+                token.line = file.line(token.line);
+                token
+            });
+            let line: Vec<PpToken> = line.collect();
+            let active = conditionals.last().is_none_or(|c| c.active);
+
+            if let [hash, directive @ ..] = line.as_slice()
+                && hash.is("#")
+            {
+                self.flush(&mut text, file)?;
+                let span = file.span(hash).to(file.span(line.last().unwrap()));
+                self.directive(directive, newline.line, file, &mut conditionals)
+                    .map_err(|error| error.at(span))?;
+            } else if active {
+                if let Some(quote) = line.iter().find(|token| tokens::lone_quote(token)) {
+                    return Err(Error::UnterminatedLiteral.at(file.span(quote)));
+                }
+                text.extend(line);
+            }
+        }
+        self.flush(&mut text, file)?;
+
+        match conditionals.first() {
+            Some(outermost) => Err(Error::MissingEndif.at(outermost.start)),
+            None => Ok(()),
+        }
+    }
+
+    fn expander<'a>(&'a self, file: &'a File) -> Expander<'a> {
+        Expander {
+            macros: &self.macros,
+            file: &file.name,
+        }
+    }
+
+    fn flush(&mut self, text: &mut Vec<PpToken>, file: &File) -> Result<(), Error> {
+        if text.is_empty() {
+            return Ok(());
+        }
+        let expanded = self.expander(file).expand(std::mem::take(text))?;
+        self.out.tokens(&expanded, &file.name);
+        Ok(())
+    }
+
+    fn directive(
+        &mut self,
+        tokens: &[PpToken],
+        line: usize,
+        file: &mut File,
+        conditionals: &mut Vec<Conditional>,
+    ) -> Result<(), Error> {
+        let active = conditionals.last().is_none_or(|c| c.active);
+        // the null directive
+        let Some((name, rest)) = tokens.split_first() else {
+            return Ok(());
+        };
+
+        match &*name.text {
+            "if" | "ifdef" | "ifndef" => {
+                let taken = active
+                    && match &*name.text {
+                        "if" => self.condition(rest, file)?,
+                        "ifdef" => self.macros.contains_key(macro_name(rest)?),
+                        _ => !self.macros.contains_key(macro_name(rest)?),
+                    };
+                conditionals.push(Conditional {
+                    active: taken,
+                    taken,
+                    seen_else: false,
+                    outer: active,
+                    start: file.span(name),
+                });
+            }
+            "elif" => {
+                let Some(&top) = conditionals.last() else {
+                    return Err(Error::Unbalanced("#elif without #if"));
+                };
+                if top.seen_else {
+                    return Err(Error::Unbalanced("#elif after #else"));
+                }
+                let taken = top.outer && !top.taken && self.condition(rest, file)?;
+                let top = conditionals.last_mut().unwrap();
+                top.active = taken;
+                top.taken |= taken;
+            }
+            "else" => {
+                let Some(top) = conditionals.last_mut() else {
+                    return Err(Error::Unbalanced("#else without #if"));
+                };
+                if top.seen_else {
+                    return Err(Error::Unbalanced("#else after #else"));
+                }
+                top.active = top.outer && !top.taken;
+                top.taken = true;
+                top.seen_else = true;
+            }
+            "endif" => {
+                if conditionals.pop().is_none() {
+                    return Err(Error::Unbalanced("#endif without #if"));
+                }
+            }
+            // everything else only counts in groups we're emitting
+            _ if !active => {}
+            "define" => self.define_macro(rest)?,
+            "undef" => {
+                let name = macro_name(rest)?;
+                self.macros.remove(name);
+            }
+            "include" => self.include(rest, file)?,
+            "line" => {
+                let expanded = self.expander(file).expand(rest.to_vec())?;
+                let (number, name) = match expanded.as_slice() {
+                    [number] => (number, None),
+                    [number, name] if name.kind == Kind::Str => (number, Some(name)),
+                    _ => return Err(Error::InvalidDirective("#line".into())),
+                };
+                let number: isize = number
+                    .text
+                    .parse()
+                    .map_err(|_| Error::InvalidDirective("#line".into()))?;
+                // the line after this one has the number we were given
+                file.line_delta = number - (line as isize + 1);
+                if let Some(name) = name {
+                    file.name = name.text[1..name.text.len() - 1].into();
+                }
+                self.out.marker(file.line(line + 1), &file.name);
+            }
+            "error" => return Err(Error::User(tokens::spell(rest))),
+            "warning" => {
+                let span = file.span(name).to(file.span(rest.last().unwrap_or(name)));
+                let message = format!("#warning {}", tokens::spell(rest));
+                self.warnings
+                    .push(Diagnostic::warning("W0001", message).at(span));
+            }
+            "pragma" => {
+                if matches!(rest, [once] if &*once.text == "once")
+                    && let Some(path) = &file.path
+                {
+                    self.once.insert(canonical(path));
+                }
+                // the lexer wouldn't know what to do with anything else
+            }
+            other => return Err(Error::InvalidDirective(other.into())),
+        }
+        Ok(())
+    }
+
+    fn define_macro(&mut self, tokens: &[PpToken]) -> Result<(), Error> {
+        let Some((name, rest)) = tokens.split_first() else {
+            return Err(Error::InvalidMacro("missing macro name"));
+        };
+        let Some(name) = name.ident() else {
+            return Err(Error::InvalidMacro("macro names must be identifiers"));
+        };
+        if &**name == "defined" {
+            return Err(Error::InvalidMacro("can't define `defined`"));
+        }
+
+        let (params, variadic, body) = match rest {
+            // the paren has to touch the name to make it function-like
+            [open, rest @ ..] if open.is("(") && !open.space => {
+                let (params, variadic, body) = parameters(rest)?;
+                (Some(params), variadic, body)
+            }
+            body => (None, false, body),
+        };
+
+        if body.first().is_some_and(|t| t.is("##")) || body.last().is_some_and(|t| t.is("##")) {
+            return Err(Error::InvalidMacro(
+                "`##` can't be at either end of a macro",
+            ));
+        }
+
+        let mut body = body.to_vec();
+        if let Some(first) = body.first_mut() {
+            first.space = false;
+        }
+        self.macros.insert(
+            name.clone(),
+            Macro {
+                params,
+                variadic,
+                body: body.into(),
+            },
+        );
+        Ok(())
+    }
+
+    fn condition(&self, tokens: &[PpToken], file: &File) -> Result<bool, Error> {
+        // `defined` has to go before expansion, or the macro it asks about would be expanded
+        let mut replaced = Vec::with_capacity(tokens.len());
+        let mut iter = tokens.iter();
+        while let Some(token) = iter.next() {
+            if token.ident().is_none_or(|name| &**name != "defined") {
+                replaced.push(token.clone());
+                continue;
+            }
+            let name = match iter.next() {
+                Some(open) if open.is("(") => {
+                    let name = iter.next().and_then(PpToken::ident);
+                    if !iter.next().is_some_and(|close| close.is(")")) {
+                        return Err(Error::InvalidExpression("expected `)` after defined"));
+                    }
+                    name
+                }
+                other => other.and_then(PpToken::ident),
+            };
+            let Some(name) = name else {
+                return Err(Error::InvalidExpression(
+                    "expected a macro name after defined",
+                ));
+            };
+            let value = if self.macros.contains_key(name) {
+                "1"
+            } else {
+                "0"
+            };
+            replaced.push(PpToken::new(Kind::Number, value, token.line));
+        }
+
+        let expanded = self.expander(file).expand(replaced)?;
+        expr::evaluate(&expanded)
+    }
+
+    fn include(&mut self, tokens: &[PpToken], file: &File) -> Result<(), Error> {
+        let (name, quoted) = match tokens {
+            [header] if header.kind == Kind::HeaderName => (header_name(&header.text), false),
+            [string] if string.kind == Kind::Str => (header_name(&string.text), true),
+            // `#include MACRO`
+            tokens => {
+                let expanded = self.expander(file).expand(tokens.to_vec())?;
+                match expanded.as_slice() {
+                    [string] if string.kind == Kind::Str => (header_name(&string.text), true),
+                    [open, name @ .., close] if open.is("<") && close.is(">") => {
+                        (tokens::spell(name), false)
+                    }
+                    _ => return Err(Error::InvalidDirective("#include".into())),
+                }
+            }
+        };
+
+        let local = file
+            .path
+            .as_deref()
+            .and_then(Path::parent)
+            .filter(|_| quoted)
+            .map(Path::to_path_buf);
+        let find = |dirs: &mut dyn Iterator<Item = &PathBuf>| {
+            dirs.map(|dir| dir.join(&name)).find(|path| path.is_file())
+        };
+        let ours = builtin(&name);
+        let header = find(&mut local.iter().chain(&self.search_paths))
+            .map(Header::File)
+            .or_else(|| ours.filter(|_| name != "limits.h").map(Header::Builtin))
+            .or_else(|| find(&mut self.system_paths.iter()).map(Header::File))
+            .or_else(|| ours.map(Header::Builtin));
+
+        let (mut included, bytes) = match header {
+            Some(Header::File(path)) => {
+                if self.once.contains(&canonical(&path)) {
+                    return Ok(());
+                }
+                let bytes = fs::read(&path).map_err(Error::Io)?;
+                let included = File {
+                    name: path.to_string_lossy().into(),
+                    path: Some(path),
+                    line_delta: 0,
+                };
+                (included, bytes)
+            }
+            Some(Header::Builtin(bytes)) => {
+                let included = File {
+                    name: format!("<built-in>/{name}").into(),
+                    path: None,
+                    line_delta: 0,
+                };
+                (included, bytes.as_bytes().to_vec())
+            }
+            None => return Err(Error::IncludeNotFound(name)),
+        };
+        if self.depth >= MAX_INCLUDE_DEPTH {
+            return Err(Error::IncludeDepth);
+        }
+        self.depth += 1;
+        self.file(&mut included, &bytes)?;
+        self.depth -= 1;
+
+        // pick up where we left off in the includer
+        let next = tokens.last().map_or(1, |t| t.line + 1);
+        self.out.marker(next, &file.name);
+        Ok(())
+    }
+}
+
+enum Header {
+    File(PathBuf),
+    Builtin(&'static str),
+}
+
+// headers that come with the compiler rather than libc, searched after `-I` and before the system
+// directories. libc's `limits.h` has more in it than ours, like `PATH_MAX`, so ours is only used
+// when there isn't one
+fn builtin(name: &str) -> Option<&'static str> {
+    match name {
+        "stdarg.h" => Some(include_str!("preprocess/stdarg.h")),
+        "stddef.h" => Some(include_str!("preprocess/stddef.h")),
+        "stdbool.h" => Some(include_str!("preprocess/stdbool.h")),
+        "stdint.h" => Some(include_str!("preprocess/stdint.h")),
+        "limits.h" => Some(include_str!("preprocess/limits.h")),
+        _ => None,
+    }
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.into())
+}
+
+fn header_name(text: &str) -> String {
+    text[1..text.len() - 1].into()
+}
+
+fn macro_name(tokens: &[PpToken]) -> Result<&str, Error> {
+    match tokens.first().and_then(PpToken::ident) {
+        Some(name) => Ok(name),
+        None => Err(Error::InvalidMacro("expected a macro name")),
+    }
+}
+
+type Parameters<'a> = (Box<[Rc<str>]>, bool, &'a [PpToken]);
+
+fn parameters(tokens: &[PpToken]) -> Result<Parameters<'_>, Error> {
+    let mut params = Vec::new();
+    let mut iter = tokens.iter().enumerate();
+    // `()` is fine
+    if tokens.first().is_some_and(|t| t.is(")")) {
+        return Ok((params.into(), false, &tokens[1..]));
+    }
+    while let Some((_, token)) = iter.next() {
+        let variadic = token.is("...");
+        if let Some(name) = token.ident() {
+            if params.contains(name) {
+                return Err(Error::InvalidMacro("duplicate macro parameter"));
+            }
+            params.push(name.clone());
+        } else if !variadic {
+            return Err(Error::InvalidMacro("expected a parameter name"));
+        }
+
+        match iter.next() {
+            Some((i, close)) if close.is(")") => {
+                return Ok((params.into(), variadic, &tokens[i + 1..]));
+            }
+            Some((_, comma)) if comma.is(",") && !variadic => {}
+            _ => return Err(Error::InvalidMacro("expected `,` or `)` in parameter list")),
+        }
+    }
+    Err(Error::InvalidMacro("unterminated parameter list"))
+}
+
+// keeps the output lined up with the input, so the lexer's line numbers mean something
+#[derive(Default)]
+struct Output {
+    bytes: Vec<u8>,
+    // the line and column the next byte lands on
+    line: usize,
+    column: usize,
+}
+
+impl Output {
+    // a gcc style line marker: `# 12 "file.c"`
+    fn marker(&mut self, line: usize, file: &str) {
+        if self.bytes.last().is_some_and(|&b| b != b'\n') {
+            self.bytes.push(b'\n');
+        }
+        self.bytes
+            .extend_from_slice(format!("# {line} {}\n", macros::stringify_text(file)).as_bytes());
+        self.line = line;
+        self.column = 1;
+    }
+
+    fn tokens(&mut self, tokens: &[PpToken], file: &str) {
+        let mut prev: Option<&PpToken> = None;
+        for token in tokens {
+            if token.line > self.line {
+                if token.line - self.line <= 8 {
+                    self.bytes
+                        .extend(std::iter::repeat_n(b'\n', token.line - self.line));
+                    self.line = token.line;
+                    self.column = 1;
+                } else {
+                    self.marker(token.line, file);
+                }
+                prev = None;
+            }
+            // line tokens up with where they were in the source, so columns mean something too
+            if token.column > self.column {
+                let padding = token.column - self.column;
+                self.bytes.extend(std::iter::repeat_n(b' ', padding));
+                self.column = token.column;
+            } else if prev.is_some_and(|prev| token.space || tokens::would_paste(prev, token)) {
+                self.bytes.push(b' ');
+                self.column += 1;
+            }
+            self.bytes.extend_from_slice(token.text.as_bytes());
+            self.column += token.text.len();
+            prev = Some(token);
+        }
+    }
+
+    fn finish(mut self) -> Box<[u8]> {
+        self.bytes.push(b'\n');
+        self.bytes.into()
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    IncludeNotFound(String),
+    IncludeDepth,
+    UnterminatedComment,
+    UnterminatedLiteral,
+    InvalidDirective(String),
+    InvalidMacro(&'static str),
+    MacroArgs(String),
+    UnterminatedCall(String),
+    InvalidExpression(&'static str),
+    DivideByZero,
+    Unbalanced(&'static str),
+    MissingEndif,
+    // `#error`
+    User(String),
+    // one of the others, and where it happened
+    At(Span, Box<Error>),
+}
+
+impl Error {
+    // the innermost place wins, an error in an include shouldn't point at the `#include`
+    fn at(self, span: Span) -> Self {
+        match self {
+            Self::At(..) => self,
+            error => Self::At(span, Box::new(error)),
+        }
+    }
+
+    fn unlocated(self) -> Self {
+        match self {
+            Self::At(_, error) => *error,
+            error => error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Error, Preprocessor};
+    use crate::diagnostics::Severity;
+
+    fn run(source: &str) -> String {
+        let bytes = Preprocessor::new(Vec::new(), Vec::new())
+            .run_bytes("test.c", source.as_bytes())
+            .unwrap();
+        let text = String::from_utf8(bytes.into()).unwrap();
+        // drop the line markers, we only care about the code
+        text.lines()
+            .filter(|line| !line.starts_with('#'))
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn object_and_function_macros() {
+        let out = run("#define N 10\n#define SQ(x) ((x) * (x))\nint a = SQ(N + 1);\n");
+        assert_eq!(out, "int a = ((10 + 1) * (10 + 1));");
+    }
+
+    #[test]
+    fn stringify_and_paste() {
+        let out = run("#define S(x) #x\n#define CAT(a, b) a ## b\nS(a \"b\") CAT(x, 1)\n");
+        assert_eq!(out, "\"a \\\"b\\\"\" x1");
+    }
+
+    #[test]
+    fn recursion_stops() {
+        let out = run("#define f(a) a + f(a)\n#define x x\nf(x)\n");
+        assert_eq!(out, "x + f(x)");
+    }
+
+    #[test]
+    fn conditionals() {
+        let source = "#define A 2\n#if A * 2 == 4 && !defined(B)\nyes\n#elif 1\nno\n#else\nno\n#endif\n#ifdef B\nno\n#endif\n";
+        assert_eq!(run(source), "yes");
+    }
+
+    #[test]
+    fn line_numbers() {
+        let out = run(
+            "\n\nint x = __LINE__;\n#line 100 \"other.c\"\nchar *f = __FILE__; int y = __LINE__;\n",
+        );
+        // whatever comes after a macro stays in the column it was in
+        assert_eq!(
+            out,
+            "int x = 3       ;\nchar *f = \"other.c\"; int y = 100    ;"
+        );
+    }
+
+    #[test]
+    fn variadic() {
+        let out =
+            run("#define P(fmt, ...) printf(fmt, ## __VA_ARGS__)\nP(\"a\"); P(\"b\", 1, 2);\n");
+        assert_eq!(out, "printf(\"a\"); printf(\"b\", 1, 2);");
+    }
+
+    #[test]
+    fn locations() {
+        let mut preprocessor = Preprocessor::new(Vec::new(), Vec::new());
+        let error = preprocessor
+            .run_bytes("test.c", b"int x;\n#line 10\n  #include \"missing.h\"\n")
+            .unwrap_err();
+        let Error::At(span, error) = error else {
+            panic!("{error:?}");
+        };
+        assert!(matches!(*error, Error::IncludeNotFound(_)));
+        assert_eq!((span.file, span.line, span.column), ("test.c", 10, 3));
+
+        let error = preprocessor
+            .run_bytes("test.c", b"#define f(a) a\nint y = f(1, 2);\n")
+            .unwrap_err();
+        assert!(matches!(error, Error::At(span, _) if (span.line, span.column) == (2, 9)));
+    }
+
+    #[test]
+    fn lone_quotes() {
+        assert_eq!(run("#if 0\nthis isn't code\n#endif\nint x;\n"), "int x;");
+        assert_eq!(run("#define Q(x) #x\nQ('\"')\n"), "\"'\\\"'\"");
+        let error = Preprocessor::new(Vec::new(), Vec::new())
+            .run_bytes("test.c", b"int x;\nchar c = 'a;\n")
+            .unwrap_err();
+        let Error::At(span, error) = error else {
+            panic!("{error:?}");
+        };
+        assert!(matches!(*error, Error::UnterminatedLiteral));
+        assert_eq!((span.line, span.column), (2, 10));
+    }
+
+    #[test]
+    fn builtin_headers() {
+        let out = run("#include <stddef.h>\n#include <stdint.h>\n#include <stdbool.h>\n\
+                       #include <limits.h>\nINT32_MAX\nbool\ntrue\nCHAR_BIT\n");
+        assert!(out.ends_with("2147483647\nint\n1\n8"));
+        assert!(out.contains("typedef unsigned long size_t;"));
+        assert!(out.contains("typedef long int64_t;"));
+    }
+
+    #[test]
+    fn warnings() {
+        let mut preprocessor = Preprocessor::new(Vec::new(), Vec::new());
+        preprocessor
+            .run_bytes("test.c", b"\n#warning not done yet\n")
+            .unwrap();
+        let warnings = preprocessor.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].severity, Severity::Warning);
+        assert_eq!(warnings[0].message, "#warning not done yet");
+        assert_eq!(warnings[0].span.map(|span| span.line), Some(2));
+    }
+}
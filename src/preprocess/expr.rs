@@ -0,0 +1,253 @@
+// constant expressions for `#if` and `#elif`, everything is done in intmax_t/uintmax_t
+use super::tokens::{Kind, PpToken};
+use super::Error;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Value {
+    value: i64,
+    unsigned: bool,
+}
+
+impl Value {
+    const fn signed(value: i64) -> Self {
+        Self {
+            value,
+            unsigned: false,
+        }
+    }
+
+    const fn truth(&self) -> bool {
+        self.value != 0
+    }
+}
+
+// identifiers have already been macro expanded, and `defined` has already been replaced
+pub fn evaluate(tokens: &[PpToken]) -> Result<bool, Error> {
+    let mut parser = Parser { tokens, pos: 0 };
+    let value = parser.conditional(true)?;
+    if parser.pos != tokens.len() {
+        return Err(Error::InvalidExpression(
+            "unexpected tokens after expression",
+        ));
+    }
+    Ok(value.truth())
+}
+
+struct Parser<'a> {
+    tokens: &'a [PpToken],
+    pos: usize,
+}
+
+const fn precedence(op: &str) -> Option<u8> {
+    Some(match op.as_bytes() {
+        b"||" => 1,
+        b"&&" => 2,
+        b"|" => 3,
+        b"^" => 4,
+        b"&" => 5,
+        b"==" | b"!=" => 6,
+        b"<" | b">" | b"<=" | b">=" => 7,
+        b"<<" | b">>" => 8,
+        b"+" | b"-" => 9,
+        b"*" | b"/" | b"%" => 10,
+        _ => return None,
+    })
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&PpToken> {
+        self.tokens.get(self.pos)
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        if self.peek().is_some_and(|t| t.is(punct)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, punct: &'static str) -> Result<(), Error> {
+        if self.eat(punct) {
+            Ok(())
+        } else {
+            Err(Error::InvalidExpression(punct))
+        }
+    }
+
+    // `live` is false in the branches short circuiting skips, where dividing by zero is fine
+    fn conditional(&mut self, live: bool) -> Result<Value, Error> {
+        let condition = self.binary(0, live)?;
+        if !self.eat("?") {
+            return Ok(condition);
+        }
+        let taken = condition.truth();
+        let left = self.conditional(live && taken)?;
+        self.expect(":")?;
+        let right = self.conditional(live && !taken)?;
+        let unsigned = left.unsigned || right.unsigned;
+        let value = if taken { left.value } else { right.value };
+        Ok(Value { value, unsigned })
+    }
+
+    fn binary(&mut self, min: u8, live: bool) -> Result<Value, Error> {
+        let mut left = self.unary(live)?;
+        while let Some(op) = self.peek().filter(|t| t.kind == Kind::Punct) {
+            let op = op.text.clone();
+            let Some(prec) = precedence(&op).filter(|&prec| prec > min) else {
+                break;
+            };
+            self.pos += 1;
+            left = match &*op {
+                "&&" => {
+                    let right = self.binary(prec, live && left.truth())?;
+                    Value::signed(i64::from(left.truth() && right.truth()))
+                }
+                "||" => {
+                    let right = self.binary(prec, live && !left.truth())?;
+                    Value::signed(i64::from(left.truth() || right.truth()))
+                }
+                op => {
+                    let right = self.binary(prec, live)?;
+                    apply(op, left, right, live)?
+                }
+            };
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self, live: bool) -> Result<Value, Error> {
+        let Some(token) = self.peek().cloned() else {
+            return Err(Error::InvalidExpression("expected an expression"));
+        };
+        self.pos += 1;
+        match token.kind {
+            Kind::Punct => {
+                let value = match &*token.text {
+                    "(" => {
+                        let value = self.conditional(live)?;
+                        self.expect(")")?;
+                        return Ok(value);
+                    }
+                    "+" => return self.unary(live),
+                    "-" => self.unary(live).map(|v| Value {
+                        value: v.value.wrapping_neg(),
+                        ..v
+                    })?,
+                    "~" => self.unary(live).map(|v| Value {
+                        value: !v.value,
+                        ..v
+                    })?,
+                    "!" => Value::signed(i64::from(!self.unary(live)?.truth())),
+                    _ => return Err(Error::InvalidExpression("unexpected operator")),
+                };
+                Ok(value)
+            }
+            Kind::Number => number(&token.text),
+            Kind::Char => character(&token.text).map(Value::signed),
+            // anything still an identifier after expansion is 0, except `true`
+            Kind::Ident => Ok(Value::signed(i64::from(&*token.text == "true"))),
+            _ => Err(Error::InvalidExpression("expected an expression")),
+        }
+    }
+}
+
+fn apply(op: &str, left: Value, right: Value, live: bool) -> Result<Value, Error> {
+    let unsigned = left.unsigned || right.unsigned;
+    let (l, r) = (left.value, right.value);
+    let (ul, ur) = (l as u64, r as u64);
+    let compare = |signed: bool, unsigned_result: bool| {
+        Value::signed(i64::from(if unsigned { unsigned_result } else { signed }))
+    };
+    let value = match op {
+        "*" => l.wrapping_mul(r),
+        "/" | "%" if r == 0 => {
+            if live {
+                return Err(Error::DivideByZero);
+            }
+            0
+        }
+        "/" if unsigned => (ul / ur) as i64,
+        "/" => l.wrapping_div(r),
+        "%" if unsigned => (ul % ur) as i64,
+        "%" => l.wrapping_rem(r),
+        "+" => l.wrapping_add(r),
+        "-" => l.wrapping_sub(r),
+        "<<" => l.wrapping_shl(r as u32),
+        ">>" if left.unsigned => (ul.wrapping_shr(r as u32)) as i64,
+        ">>" => l.wrapping_shr(r as u32),
+        "<" => return Ok(compare(l < r, ul < ur)),
+        ">" => return Ok(compare(l > r, ul > ur)),
+        "<=" => return Ok(compare(l <= r, ul <= ur)),
+        ">=" => return Ok(compare(l >= r, ul >= ur)),
+        "==" => return Ok(Value::signed(i64::from(l == r))),
+        "!=" => return Ok(Value::signed(i64::from(l != r))),
+        "&" => l & r,
+        "^" => l ^ r,
+        "|" => l | r,
+        _ => unreachable!(),
+    };
+    // shifts take the type of the left operand
+    let unsigned = if matches!(op, "<<" | ">>") {
+        left.unsigned
+    } else {
+        unsigned
+    };
+    Ok(Value { value, unsigned })
+}
+
+fn number(text: &str) -> Result<Value, Error> {
+    let digits = text.trim_end_matches(['u', 'U', 'l', 'L']);
+    let unsigned = text[digits.len()..].contains(['u', 'U']);
+    let (digits, radix) = if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        (hex, 16)
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        (&digits[1..], 8)
+    } else {
+        (digits, 10)
+    };
+    let value = u64::from_str_radix(digits, radix)
+        .map_err(|_| Error::InvalidExpression("invalid integer constant"))?;
+    Ok(Value {
+        value: value as i64,
+        // too big for intmax_t means it has to be unsigned
+        unsigned: unsigned || value > i64::MAX as u64,
+    })
+}
+
+fn character(text: &str) -> Result<i64, Error> {
+    let body = text.trim_start_matches(['L', 'u', 'U', '8']);
+    let body = body
+        .strip_prefix('\'')
+        .and_then(|body| body.strip_suffix('\''))
+        .unwrap_or(body);
+    let bytes = body.as_bytes();
+    let value = match bytes {
+        [b'\\', b'x', hex @ ..] => {
+            let hex = std::str::from_utf8(hex).unwrap_or_default();
+            i64::from_str_radix(hex, 16)
+                .map_err(|_| Error::InvalidExpression("invalid character constant"))?
+        }
+        [b'\\', b'0'..=b'7', ..] => bytes[1..]
+            .iter()
+            .take_while(|b| (b'0'..=b'7').contains(b))
+            .fold(0, |acc, &d| acc * 8 + i64::from(d - b'0')),
+        [b'\\', escape] => i64::from(match escape {
+            b'n' => b'\n',
+            b't' => b'\t',
+            b'r' => b'\r',
+            b'a' => 0x07,
+            b'b' => 0x08,
+            b'f' => 0x0c,
+            b'v' => 0x0b,
+            other => *other,
+        }),
+        [byte] => i64::from(*byte as i8),
+        _ => return Err(Error::InvalidExpression("invalid character constant")),
+    };
+    Ok(value)
+}
@@ -0,0 +1,321 @@
+use super::tokens::{self, HideSet, Kind, PpToken};
+use super::Error;
+use crate::lex::{self, Span};
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+
+#[derive(Debug, Clone)]
+pub struct Macro {
+    // `None` for object-like macros
+    pub params: Option<Box<[Rc<str>]>>,
+    pub variadic: bool,
+    pub body: Box<[PpToken]>,
+}
+
+pub type MacroTable = HashMap<Rc<str>, Macro>;
+
+const VA_ARGS: &str = "__VA_ARGS__";
+
+impl Macro {
+    fn param(&self, token: &PpToken) -> Option<usize> {
+        let name = token.ident()?;
+        let params = self.params.as_ref()?;
+        if let Some(i) = params.iter().position(|param| param == name) {
+            Some(i)
+        } else if self.variadic && &**name == VA_ARGS {
+            Some(params.len())
+        } else {
+            None
+        }
+    }
+
+    fn arity(&self) -> usize {
+        self.params.as_ref().map_or(0, |params| params.len()) + usize::from(self.variadic)
+    }
+}
+
+pub struct Expander<'a> {
+    pub macros: &'a MacroTable,
+    pub file: &'a str,
+}
+
+impl Expander<'_> {
+    // Prosser's algorithm: every token remembers which macros it came out of, and a macro is
+    // never expanded from a token that already has it in its hide set
+    pub fn expand(&self, tokens: Vec<PpToken>) -> Result<Vec<PpToken>, Error> {
+        let mut input: VecDeque<PpToken> = tokens.into();
+        let mut out = Vec::new();
+
+        while let Some(token) = input.pop_front() {
+            let Some(name) = token.ident().cloned() else {
+                out.push(token);
+                continue;
+            };
+            if token.hidden(&name) {
+                out.push(token);
+                continue;
+            }
+            if let Some(builtin) = self.builtin(&name, &token) {
+                out.push(builtin);
+                continue;
+            }
+            let Some(mac) = self.macros.get(&name) else {
+                out.push(token);
+                continue;
+            };
+
+            let span = Span {
+                file: lex::intern(self.file),
+                line: token.line,
+                column: token.column,
+                len: name.len(),
+            };
+            let expansion = if mac.params.is_none() {
+                let hide = with(&token.hide, &name);
+                self.subst(mac, &[], &hide, &token)
+                    .map_err(|error| error.at(span))?
+            } else if input.front().is_some_and(|t| t.is("(")) {
+                input.pop_front();
+                let (args, close) =
+                    arguments(&mut input, &name, mac).map_err(|error| error.at(span))?;
+                let hide = with(&intersect(&token.hide, &close.hide), &name);
+                self.subst(mac, &args, &hide, &token)
+                    .map_err(|error| error.at(span))?
+            } else {
+                // a function-like macro name without arguments is just an identifier
+                out.push(token);
+                continue;
+            };
+
+            for token in expansion.into_iter().rev() {
+                input.push_front(token);
+            }
+        }
+        Ok(out)
+    }
+
+    fn builtin(&self, name: &str, at: &PpToken) -> Option<PpToken> {
+        let mut token = match name {
+            "__LINE__" => PpToken::new(Kind::Number, &at.line.to_string(), at.line),
+            "__FILE__" => PpToken::new(Kind::Str, &stringify_text(self.file), at.line),
+            _ => return None,
+        };
+        token.column = at.column;
+        token.space = at.space;
+        Some(token)
+    }
+
+    fn subst(
+        &self,
+        mac: &Macro,
+        args: &[Vec<PpToken>],
+        hide: &HideSet,
+        at: &PpToken,
+    ) -> Result<Vec<PpToken>, Error> {
+        let body = &mac.body;
+        let function_like = mac.params.is_some();
+        let mut out: Vec<PpToken> = Vec::new();
+        let mut i = 0;
+
+        while i < body.len() {
+            let token = &body[i];
+            let next = body.get(i + 1);
+            let param = mac.param(token);
+
+            if function_like
+                && token.is("#")
+                && let Some(arg) = next.and_then(|next| mac.param(next))
+            {
+                let mut string = PpToken::new(Kind::Str, &stringify(&args[arg]), 0);
+                string.space = token.space;
+                out.push(string);
+                i += 2;
+                continue;
+            }
+
+            if token.is("##")
+                && let Some(next) = next
+            {
+                let comma = &*next.text == VA_ARGS && out.last().is_some_and(|t| t.is(","));
+                match mac.param(next) {
+                    // gnu extension: `, ## __VA_ARGS__` drops the comma when there's nothing
+                    // after it, and doesn't paste anything otherwise
+                    Some(arg) if comma => {
+                        if args[arg].is_empty() {
+                            out.pop();
+                        } else {
+                            let mut arg = args[arg].clone();
+                            arg[0].space = next.space;
+                            out.extend(arg);
+                        }
+                    }
+                    Some(arg) => glue(&mut out, &args[arg])?,
+                    None => glue(&mut out, std::slice::from_ref(next))?,
+                }
+                i += 2;
+                continue;
+            }
+
+            if let Some(arg) = param {
+                if next.is_some_and(|next| next.is("##")) {
+                    // operands of `##` aren't expanded
+                    let mut arg = args[arg].clone();
+                    if arg.is_empty() {
+                        arg.push(PpToken::new(Kind::Placemarker, "", token.line));
+                    }
+                    arg[0].space = token.space;
+                    out.extend(arg);
+                } else {
+                    let mut expanded = self.expand(args[arg].clone())?;
+                    if let Some(first) = expanded.first_mut() {
+                        first.space = token.space;
+                    }
+                    out.extend(expanded);
+                }
+                i += 1;
+                continue;
+            }
+
+            out.push(token.clone());
+            i += 1;
+        }
+
+        out.retain(|token| token.kind != Kind::Placemarker);
+        for token in &mut out {
+            token.hide = union(&token.hide, hide);
+            token.line = at.line;
+            token.column = 0;
+        }
+        if let Some(first) = out.first_mut() {
+            first.column = at.column;
+            first.space = at.space;
+        }
+        Ok(out)
+    }
+}
+
+fn arguments(
+    input: &mut VecDeque<PpToken>,
+    name: &str,
+    mac: &Macro,
+) -> Result<(Vec<Vec<PpToken>>, PpToken), Error> {
+    let named = mac.params.as_ref().map_or(0, |params| params.len());
+    let mut args = vec![Vec::new()];
+    let mut depth = 0;
+
+    while let Some(token) = input.pop_front() {
+        if token.is(")") && depth == 0 {
+            // `f()` passes one empty argument, which is fine for a macro with no parameters
+            if mac.arity() == 0 && args.len() == 1 && args[0].is_empty() {
+                args.clear();
+            }
+            if mac.variadic && args.len() == named {
+                args.push(Vec::new());
+            }
+            if args.len() != mac.arity() {
+                return Err(Error::MacroArgs(name.into()));
+            }
+            return Ok((args, token));
+        }
+        if token.is("(") {
+            depth += 1;
+        } else if token.is(")") {
+            depth -= 1;
+        }
+        let in_varargs = mac.variadic && args.len() > named;
+        if token.is(",") && depth == 0 && !in_varargs {
+            args.push(Vec::new());
+            continue;
+        }
+        let arg = args.last_mut().unwrap();
+        let mut token = token;
+        if arg.is_empty() {
+            token.space = false;
+        }
+        arg.push(token);
+    }
+    Err(Error::UnterminatedCall(name.into()))
+}
+
+fn glue(out: &mut Vec<PpToken>, right: &[PpToken]) -> Result<(), Error> {
+    let Some((first, rest)) = right.split_first() else {
+        return Ok(());
+    };
+    let Some(left) = out.pop() else {
+        out.extend_from_slice(right);
+        return Ok(());
+    };
+    if left.kind == Kind::Placemarker {
+        let mut right = right.to_vec();
+        right[0].space = left.space;
+        out.extend(right);
+        return Ok(());
+    }
+    let pasted = format!("{}{}", left.text, first.text);
+    let mut glued = tokens::retokenize(&pasted, left.line)?;
+    if let Some(token) = glued.first_mut() {
+        token.column = left.column;
+        token.space = left.space;
+        token.hide = left.hide.clone();
+    }
+    out.extend(glued);
+    out.extend_from_slice(rest);
+    Ok(())
+}
+
+// the `#` operator, only string and character literals get escaped
+fn stringify(tokens: &[PpToken]) -> String {
+    let mut out = String::from('"');
+    for (i, token) in tokens.iter().enumerate() {
+        if i != 0 && token.space {
+            out.push(' ');
+        }
+        match token.kind {
+            Kind::Str | Kind::Char => {
+                let escaped = stringify_text(&token.text);
+                out.push_str(&escaped[1..escaped.len() - 1]);
+            }
+            _ => out.push_str(&token.text),
+        }
+    }
+    out.push('"');
+    out
+}
+
+pub fn stringify_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        if c == '"' || c == '\\' {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out.push('"');
+    out
+}
+
+fn with(set: &HideSet, name: &Rc<str>) -> HideSet {
+    if set.contains(name) {
+        set.clone()
+    } else {
+        let mut set = (**set).clone();
+        set.push(name.clone());
+        Rc::new(set)
+    }
+}
+
+fn union(left: &HideSet, right: &HideSet) -> HideSet {
+    right
+        .iter()
+        .fold(left.clone(), |set, name| with(&set, name))
+}
+
+fn intersect(left: &HideSet, right: &HideSet) -> HideSet {
+    Rc::new(
+        left.iter()
+            .filter(|name| right.contains(name))
+            .cloned()
+            .collect(),
+    )
+}
@@ -0,0 +1,10 @@
+#ifndef __MCC_STDBOOL_H
+#define __MCC_STDBOOL_H
+
+/* there's no _Bool yet, so a bool is an int, and storing 2 in one doesn't make it 1 */
+#define bool int
+#define true 1
+#define false 0
+#define __bool_true_false_are_defined 1
+
+#endif
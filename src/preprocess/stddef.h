@@ -0,0 +1,17 @@
+#ifndef __MCC_STDDEF_H
+#define __MCC_STDDEF_H
+
+typedef unsigned long size_t;
+typedef long ptrdiff_t;
+typedef int wchar_t;
+
+#define NULL ((void *)0)
+#define offsetof(type, member) __builtin_offsetof(type, member)
+
+#endif
+
+/* libc asks for pieces of this with `__need_size_t` and the like, and gets all of it */
+#undef __need_size_t
+#undef __need_ptrdiff_t
+#undef __need_wchar_t
+#undef __need_NULL
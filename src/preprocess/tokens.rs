@@ -0,0 +1,282 @@
+use super::Error;
+use crate::lex::Span;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Ident,
+    Number,
+    Str,
+    Char,
+    Punct,
+    // only produced right after `#include`
+    HeaderName,
+    Other,
+    Newline,
+    // stands in for an empty argument next to `##`
+    Placemarker,
+}
+
+pub type HideSet = Rc<Vec<Rc<str>>>;
+
+#[derive(Debug, Clone)]
+pub struct PpToken {
+    pub kind: Kind,
+    pub text: Rc<str>,
+    pub line: usize,
+    // 0 when the token doesn't come straight from the source
+    pub column: usize,
+    // was there whitespace right before us
+    pub space: bool,
+    // macros that can't be expanded from this token anymore
+    pub hide: HideSet,
+}
+
+impl PpToken {
+    pub fn new(kind: Kind, text: &str, line: usize) -> Self {
+        Self {
+            kind,
+            text: text.into(),
+            line,
+            column: 0,
+            space: false,
+            hide: HideSet::default(),
+        }
+    }
+
+    pub fn is(&self, punct: &str) -> bool {
+        matches!(self.kind, Kind::Punct) && &*self.text == punct
+    }
+
+    pub fn ident(&self) -> Option<&Rc<str>> {
+        (self.kind == Kind::Ident).then_some(&self.text)
+    }
+
+    pub fn hidden(&self, name: &str) -> bool {
+        self.hide.iter().any(|hidden| &**hidden == name)
+    }
+}
+
+// longest first, so the first match is the one we want
+const PUNCTUATORS: [&str; 48] = [
+    "...", "<<=", ">>=", "->", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "*=",
+    "/=", "%=", "+=", "-=", "&=", "^=", "|=", "##", "[", "]", "(", ")", "{", "}", ".", "&", "*",
+    "+", "-", "~", "!", "/", "%", "<", ">", "^", "|", "?", ":", ";", "=", ",", "#",
+];
+
+// translation phases 1 and 2: drop carriage returns and splice lines, but remember where every
+// byte came from so tokens still know their physical line and column
+fn splice(bytes: &[u8]) -> (Vec<u8>, Vec<(usize, usize)>) {
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut lines = Vec::with_capacity(bytes.len());
+    let mut line = 1;
+    let mut column = 1;
+    let mut i = 0;
+    while i < bytes.len() {
+        match &bytes[i..] {
+            [b'\\', b'\n', ..] => {
+                line += 1;
+                column = 1;
+                i += 2;
+            }
+            [b'\\', b'\r', b'\n', ..] => {
+                line += 1;
+                column = 1;
+                i += 3;
+            }
+            [b'\r', b'\n', ..] => i += 1,
+            [b, ..] => {
+                out.push(*b);
+                lines.push((line, column));
+                if *b == b'\n' {
+                    line += 1;
+                    column = 1;
+                } else {
+                    column += 1;
+                }
+                i += 1;
+            }
+            [] => unreachable!(),
+        }
+    }
+    (out, lines)
+}
+
+const fn ident_start(byte: u8) -> bool {
+    byte.is_ascii_alphabetic() || byte == b'_'
+}
+
+const fn ident_char(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_'
+}
+
+// `file` only goes into the spans of errors
+pub fn tokenize(bytes: &[u8], file: &'static str) -> Result<Vec<PpToken>, Error> {
+    let (bytes, lines) = splice(bytes);
+    let mut tokens: Vec<PpToken> = Vec::new();
+    let mut space = false;
+    let mut i = 0;
+    // where the current logical line starts in `tokens`
+    let mut line_start = 0;
+
+    while i < bytes.len() {
+        let (line, column) = lines[i];
+        let here = Span {
+            file,
+            line,
+            column,
+            len: 1,
+        };
+        let rest = &bytes[i..];
+        let (kind, len) = match rest {
+            [b'\n', ..] => {
+                tokens.push(PpToken::new(Kind::Newline, "\n", line));
+                line_start = tokens.len();
+                space = false;
+                i += 1;
+                continue;
+            }
+            [b' ' | b'\t' | b'\x0b' | b'\x0c' | b'\r', ..] => {
+                space = true;
+                i += 1;
+                continue;
+            }
+            [b'/', b'/', ..] => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+                space = true;
+                continue;
+            }
+            [b'/', b'*', ..] => {
+                let Some(end) = rest[2..].windows(2).position(|w| w == b"*/") else {
+                    return Err(Error::UnterminatedComment.at(Span { len: 2, ..here }));
+                };
+                i += end + 4;
+                space = true;
+                continue;
+            }
+            [b'<', ..] if in_include(&tokens[line_start..]) => {
+                let end = rest.iter().position(|&b| b == b'>' || b == b'\n');
+                let Some(end) = end.filter(|&end| rest[end] == b'>') else {
+                    return Err(Error::UnterminatedLiteral.at(here));
+                };
+                (Kind::HeaderName, end + 1)
+            }
+            [b'L' | b'U' | b'u', b'\'' | b'"', ..] => literal(rest, 1),
+            [b'u', b'8', b'\'' | b'"', ..] => literal(rest, 2),
+            [b'\'' | b'"', ..] => literal(rest, 0),
+            [b, ..] if ident_start(*b) => {
+                let len = rest.iter().take_while(|&&b| ident_char(b)).count();
+                (Kind::Ident, len)
+            }
+            [b'0'..=b'9', ..] | [b'.', b'0'..=b'9', ..] => (Kind::Number, pp_number(rest)),
+            _ => match PUNCTUATORS
+                .iter()
+                .find(|punct| rest.starts_with(punct.as_bytes()))
+            {
+                Some(punct) => (Kind::Punct, punct.len()),
+                None => (Kind::Other, 1),
+            },
+        };
+        let text = String::from_utf8_lossy(&rest[..len]);
+        let mut token = PpToken::new(kind, &text, line);
+        token.column = column;
+        token.space = space;
+        tokens.push(token);
+        space = false;
+        i += len;
+    }
+
+    if tokens.last().is_some_and(|t| t.kind != Kind::Newline) {
+        let line = lines.last().map_or(1, |&(line, _)| line);
+        tokens.push(PpToken::new(Kind::Newline, "\n", line));
+    }
+    Ok(tokens)
+}
+
+// re-lex a single spelling, used for `##`
+pub fn retokenize(text: &str, line: usize) -> Result<Vec<PpToken>, Error> {
+    // a column in the pasted text means nothing to anyone, whoever pasted knows the line
+    let mut tokens = tokenize(text.as_bytes(), "").map_err(Error::unlocated)?;
+    tokens.retain(|t| t.kind != Kind::Newline);
+    for token in &mut tokens {
+        token.line = line;
+        token.column = 0;
+    }
+    Ok(tokens)
+}
+
+fn in_include(line: &[PpToken]) -> bool {
+    matches!(line, [hash, include] if hash.is("#") && &*include.text == "include")
+}
+
+// a quote that's never closed is a token of its own, `this isn't code` is fine in an `#if 0`, and
+// it's only an error in a group that's emitted, see `lone_quote`
+fn literal(bytes: &[u8], prefix: usize) -> (Kind, usize) {
+    let quote = bytes[prefix];
+    let mut i = prefix + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'\n' => break,
+            b if b == quote => {
+                let kind = if quote == b'"' { Kind::Str } else { Kind::Char };
+                return (kind, i + 1);
+            }
+            _ => i += 1,
+        }
+    }
+    // the prefix is an identifier then
+    match prefix {
+        0 => (Kind::Other, 1),
+        prefix => (Kind::Ident, prefix),
+    }
+}
+
+pub fn lone_quote(token: &PpToken) -> bool {
+    token.kind == Kind::Other && matches!(&*token.text, "'" | "\"")
+}
+
+fn pp_number(bytes: &[u8]) -> usize {
+    let mut i = 1;
+    while i < bytes.len() {
+        match &bytes[i..] {
+            [b'e' | b'E' | b'p' | b'P', b'+' | b'-', ..] => i += 2,
+            [b, ..] if ident_char(*b) || *b == b'.' => i += 1,
+            _ => break,
+        }
+    }
+    i
+}
+
+// the spelling of a token list, with a space wherever the source had whitespace
+pub fn spell(tokens: &[PpToken]) -> String {
+    let mut out = String::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if i != 0 && token.space {
+            out.push(' ');
+        }
+        out.push_str(&token.text);
+    }
+    out
+}
+
+// would writing these two right next to each other lex differently
+pub fn would_paste(left: &PpToken, right: &PpToken) -> bool {
+    match (left.kind, right.kind) {
+        (Kind::Ident | Kind::Number, Kind::Ident | Kind::Number) => true,
+        (Kind::Number, Kind::Punct) => right.text.starts_with('.'),
+        (Kind::Punct, Kind::Number) => right.text.starts_with('.'),
+        (Kind::Punct, Kind::Punct) => {
+            let joined = format!("{}{}", left.text, right.text);
+            // a comment is worse than a longer punctuator
+            joined.starts_with("//")
+                || joined.starts_with("/*")
+                || PUNCTUATORS
+                    .iter()
+                    .any(|punct| punct.len() > left.text.len() && joined.starts_with(punct))
+        }
+        _ => false,
+    }
+}
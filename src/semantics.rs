@@ -1,5 +1,6 @@
 pub mod ast;
 mod check_labels;
+mod const_eval;
 mod resolve;
 pub mod typecheck;
 use crate::lex::Identifier;
@@ -7,6 +8,8 @@ use crate::parse::ParamList;
 use crate::parse::Program as AstProgram;
 use crate::parse::StorageClass;
 pub use check_labels::check as check_labels;
+// the optimizer folds tacky with the same arithmetic
+pub use const_eval::{arithmetic, convert, integer, offset_of, truthy, wrap, Undefined};
 pub use typecheck::Attr;
 pub use typecheck::StaticInit;
 pub use typecheck::SymbolTable;
@@ -27,6 +30,14 @@ pub fn check(mut program: AstProgram) -> Result<(typed::Program, SymbolTable), E
     Ok((program, symbol_table))
 }
 
+// everything up to here, for the tests of what comes after
+#[cfg(test)]
+pub fn checked(source: &[u8]) -> Result<(typed::Program, SymbolTable), Error> {
+    let (program, errors) = crate::parse::parse(crate::lex::tokenize(source).unwrap());
+    assert!(errors.is_empty(), "{errors:?}");
+    check(program)
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum Type {
     Int,
@@ -44,8 +55,6 @@ pub struct StatementLabels {
     pub end: Identifier,
 }
 
-use crate::lex::Constant;
-
 impl LabelId {
     pub fn labels(&self) -> StatementLabels {
         StatementLabels {
@@ -60,8 +69,9 @@ impl LabelId {
         Identifier::from(format!("s{}b", self.0))
     }
 
-    pub fn case(&self, value: Constant) -> Identifier {
-        Identifier::from(format!("sc{}{}", self.0, value,))
+    // cases are numbered in the order they appear, their values can be negative
+    pub fn case(&self, index: usize) -> Identifier {
+        Identifier::from(format!("sc{}_{}", self.0, index))
     }
     pub fn default(&self) -> Identifier {
         Identifier::from(format!("sc{}d", self.0))
@@ -81,4 +91,4 @@ pub enum Error {
 
 pub use check_labels::Error as LabelError;
 pub use resolve::Error as ResolveError;
-use resolve_loops::Error as LoopError;
+pub use resolve_loops::Error as LoopError;
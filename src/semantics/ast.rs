@@ -9,29 +9,29 @@
  *
  */
 
-use super::StaticInit;
 pub use crate::lex::Identifier;
 use crate::parse;
 pub use parse::inc_dec::*;
 pub use parse::{Arr, ParamList};
 
 use super::LabelId;
-use crate::lex::Constant;
 #[derive(Clone, Debug)]
 pub enum Label {
     Named(Identifier),
     Default(LabelId),
-    Case { c: Constant, id: LabelId },
+    Case { index: usize, id: LabelId },
 }
 
 pub mod label_prelude {
-    pub use super::labeled::{Block, BlockItem, Dec, Expr, FnDec, ForInit, Program, Stmnt, VarDec};
+    pub use super::labeled::{
+        Block, BlockItem, Dec, Expr, FnDec, ForInit, Initializer, Program, Stmnt, VarDec,
+    };
     pub use super::{Arr, Fix, Identifier, IncDec, Label, ParamList};
 }
 
 pub mod type_prelude {
     pub use super::typechecked::{
-        Block, BlockItem, Dec, Expr, FnDec, ForInit, Program, Stmnt, VarDec,
+        Block, BlockItem, Dec, Expr, FnDec, ForInit, Initializer, Program, Stmnt, VarDec,
     };
     pub use super::{Arr, Fix, Identifier, IncDec, Label, ParamList};
 }
@@ -39,9 +39,11 @@ pub mod type_prelude {
 pub mod labeled {
 
     use super::{Arr, Identifier, IncDec, Label};
-    use crate::lex::Constant;
+    use crate::lex::Span;
     use crate::parse;
-    use parse::{Bop, FnType, ParamList, StorageClass, UnOp, VarType};
+    use parse::{
+        Bop, EnumDec, FnType, ParamList, StorageClass, StructDec, TypedefDec, UnOp, VarType,
+    };
 
     use crate::semantics::LabelId;
 
@@ -51,6 +53,9 @@ pub mod labeled {
     pub enum Dec {
         Var(VarDec),
         Fn(FnDec),
+        Struct(StructDec),
+        Enum(EnumDec),
+        Typedef(TypedefDec),
     }
 
     #[derive(Debug, Clone)]
@@ -60,16 +65,20 @@ pub mod labeled {
         pub body: Option<Block>,
         pub sc: Option<StorageClass>,
         pub typ: FnType,
+        pub span: Span,
     }
 
     #[derive(Debug, Clone)]
     pub struct VarDec {
         pub name: Identifier,
-        pub init: Option<Expr>,
+        pub init: Option<Initializer>,
         pub sc: Option<StorageClass>,
         pub typ: VarType,
+        pub span: Span,
     }
 
+    pub type Initializer = parse::Initializer<Expr>;
+
     pub type Block = Arr<BlockItem>;
 
     #[derive(Debug, Clone)]
@@ -80,15 +89,15 @@ pub mod labeled {
 
     #[derive(Debug, Clone)]
     pub enum Stmnt {
-        Ret(Expr),
+        Ret(Option<Expr>, Span),
         Exp(Expr),
         If {
             condition: Expr,
             then: Box<Self>,
             r#else: Option<Box<Self>>,
         },
-        Break(LabelId),
-        Continue(LabelId),
+        Break(LabelId, Span),
+        Continue(LabelId, Span),
         While {
             condition: Expr,
             body: Box<Self>,
@@ -110,12 +119,13 @@ pub mod labeled {
         Label {
             name: Label,
             body: Box<Self>,
+            span: Span,
         },
-        Goto(Identifier),
+        Goto(Identifier, Span),
         Switch {
             val: Expr,
             body: Box<Self>,
-            cases: Box<[Constant]>,
+            cases: Box<[Expr]>,
             default: bool,
             label: LabelId,
         },
@@ -133,39 +143,83 @@ pub mod labeled {
         Assignment {
             dst: Box<Self>,
             src: Box<Self>,
+            span: Span,
         },
 
         Binary {
             left: Box<Self>,
             operator: Bop,
             right: Box<Self>,
+            span: Span,
         },
         Cast {
             target: VarType,
             exp: Box<Self>,
+            span: Span,
         },
 
         IncDec {
             op: IncDec,
             exp: Box<Self>,
+            span: Span,
         },
 
-        Var(Identifier),
-        Const(crate::lex::Constant),
+        Var(Identifier, Span),
+        Const(crate::lex::Constant, Span),
+        String(Box<[u8]>, Span),
         Unary {
             operator: UnOp,
             operand: Box<Self>,
+            span: Span,
+        },
+        Nested(Box<Self>, Span),
+        Deref(Box<Self>, Span),
+        AddrOf(Box<Self>, Span),
+        Subscript {
+            array: Box<Self>,
+            index: Box<Self>,
+            span: Span,
+        },
+        Dot {
+            exp: Box<Self>,
+            member: Identifier,
+            span: Span,
+        },
+        Arrow {
+            exp: Box<Self>,
+            member: Identifier,
+            span: Span,
         },
-        Nested(Box<Self>),
 
         Conditional {
             condition: Box<Self>,
             r#true: Box<Self>,
             r#false: Box<Self>,
+            span: Span,
         },
         FunctionCall {
-            name: Identifier,
+            func: Box<Self>,
             args: Arr<Self>,
+            span: Span,
+        },
+        VaStart(Box<Self>, Span),
+        VaArg {
+            ap: Box<Self>,
+            target: VarType,
+            span: Span,
+        },
+        CompoundLiteral {
+            ty: VarType,
+            init: Box<Initializer>,
+            span: Span,
+        },
+        SizeOf(Box<Self>, Span),
+        SizeOfType(VarType, Span),
+        AlignOf(VarType, Span),
+        OffsetOf {
+            target: VarType,
+            member: Identifier,
+            span: Span,
         },
     }
 
@@ -174,45 +228,114 @@ pub mod labeled {
             use parse::Expr as AE;
             use Expr as E;
             match e {
-                AE::IncDec { op, exp: e } => Self::IncDec { op, exp: e.into() },
-                AE::Var(v) => E::Var(v),
-                AE::Const(c) => E::Const(c),
-                AE::Nested(e) => E::Nested(e.into()),
+                AE::IncDec { op, exp: e, span } => Self::IncDec {
+                    op,
+                    exp: e.into(),
+                    span,
+                },
+                AE::Var(v, span) => E::Var(v, span),
+                AE::Const(c, span) => E::Const(c, span),
+                AE::String(s, span) => E::String(s, span),
+                AE::Nested(e, span) => E::Nested(e.into(), span),
+                AE::Deref(e, span) => E::Deref(e.into(), span),
+                AE::AddrOf(e, span) => E::AddrOf(e.into(), span),
+                AE::Subscript { array, index, span } => E::Subscript {
+                    array: array.into(),
+                    index: index.into(),
+                    span,
+                },
+                AE::Dot { exp, member, span } => E::Dot {
+                    exp: exp.into(),
+                    member,
+                    span,
+                },
+                AE::Arrow { exp, member, span } => E::Arrow {
+                    exp: exp.into(),
+                    member,
+                    span,
+                },
+                AE::VaStart(ap, span) => E::VaStart(ap.into(), span),
+                AE::VaArg { ap, target, span } => E::VaArg {
+                    ap: ap.into(),
+                    target,
+                    span,
+                },
+                AE::CompoundLiteral { ty, init, span } => E::CompoundLiteral {
+                    ty,
+                    init: Box::new(init.map(&mut Expr::from)),
+                    span,
+                },
+                AE::SizeOf(exp, span) => E::SizeOf(exp.into(), span),
+                AE::SizeOfType(ty, span) => E::SizeOfType(ty, span),
+                AE::AlignOf(ty, span) => E::AlignOf(ty, span),
+                AE::OffsetOf {
+                    target,
+                    member,
+                    span,
+                } => E::OffsetOf {
+                    target,
+                    member,
+                    span,
+                },
 
-                AE::Assignment { dst: f, src: t } => E::Assignment {
+                AE::Assignment {
+                    dst: f,
+                    src: t,
+                    span,
+                } => E::Assignment {
                     dst: f.into(),
                     src: t.into(),
+                    span,
                 },
-                AE::Cast { target, exp: e } => E::Cast {
+                AE::Cast {
+                    target,
+                    exp: e,
+                    span,
+                } => E::Cast {
                     target,
                     exp: e.into(),
+                    span,
                 },
-                AE::Unary(parse::Unary { exp: e, op: o }) => E::Unary {
+                AE::Unary(parse::Unary {
+                    exp: e,
+                    op: o,
+                    span,
+                }) => E::Unary {
                     operator: o,
                     operand: e.into(),
+                    span,
                 },
-                AE::FunctionCall { name, args: a } => E::FunctionCall {
-                    name,
+                AE::FunctionCall {
+                    func,
+                    args: a,
+                    span,
+                } => E::FunctionCall {
+                    func: func.into(),
                     args: a.into_iter().map(E::from).collect(),
+                    span,
                 },
 
                 AE::Bin(parse::Binary {
                     operator,
                     left: l,
                     right: r,
+                    span,
                 }) => E::Binary {
                     left: l.into(),
                     operator,
                     right: r.into(),
+                    span,
                 },
                 AE::Conditional {
                     condition: c,
                     r#true: t,
                     r#false: f,
+                    span,
                 } => E::Conditional {
                     condition: c.into(),
                     r#true: t.into(),
                     r#false: f.into(),
+                    span,
                 },
             }
         }
@@ -224,14 +347,42 @@ pub mod labeled {
         }
     }
 
-    use super::StaticInit;
     impl Expr {
-        pub const fn static_init(&self) -> Option<StaticInit> {
+        pub const fn span(&self) -> Span {
             match self {
-                Expr::Const(Constant::Long(l)) => Some(StaticInit::Long(*l)),
-                Expr::Const(Constant::Int(i)) => Some(StaticInit::Int(*i)),
-                Expr::Nested(e) => e.static_init(),
-                _ => None,
+                Self::Assignment { span, .. }
+                | Self::Binary { span, .. }
+                | Self::Cast { span, .. }
+                | Self::IncDec { span, .. }
+                | Self::Var(_, span)
+                | Self::Const(_, span)
+                | Self::String(_, span)
+                | Self::Unary { span, .. }
+                | Self::Nested(_, span)
+                | Self::Deref(_, span)
+                | Self::AddrOf(_, span)
+                | Self::Subscript { span, .. }
+                | Self::Dot { span, .. }
+                | Self::Arrow { span, .. }
+                | Self::Conditional { span, .. }
+                | Self::FunctionCall { span, .. }
+                | Self::VaStart(_, span)
+                | Self::VaArg { span, .. }
+                | Self::CompoundLiteral { span, .. }
+                | Self::SizeOf(_, span)
+                | Self::SizeOfType(_, span)
+                | Self::AlignOf(_, span)
+                | Self::OffsetOf { span, .. } => *span,
+            }
+        }
+    }
+
+    impl Initializer {
+        pub const fn span(&self) -> Span {
+            match self {
+                Self::Single(e) => e.span(),
+                Self::Compound(_, span) => *span,
+                Self::Designated(_, init) => init.span(),
             }
         }
     }
@@ -239,10 +390,12 @@ pub mod labeled {
 
 pub mod typechecked {
     use super::{Arr, Identifier, IncDec, Label};
-    use crate::lex::Constant;
+    use crate::lex::{Constant, Span};
     use crate::parse;
     use crate::semantics::LabelId;
-    use parse::{Bop, FnType, ParamList, StorageClass, UnOp, VarType};
+    use parse::{
+        Bop, EnumDec, FnType, ParamList, StorageClass, StructDec, TypedefDec, UnOp, VarType,
+    };
 
     pub type Program = Arr<Dec>;
 
@@ -250,6 +403,9 @@ pub mod typechecked {
     pub enum Dec {
         Var(VarDec),
         Fn(FnDec),
+        Struct(StructDec),
+        Enum(EnumDec),
+        Typedef(TypedefDec),
     }
 
     #[derive(Debug, Clone)]
@@ -259,16 +415,20 @@ pub mod typechecked {
         pub body: Option<Block>,
         pub sc: Option<StorageClass>,
         pub typ: FnType,
+        pub span: Span,
     }
 
     #[derive(Debug, Clone)]
     pub struct VarDec {
         pub name: Identifier,
-        pub init: Option<Expr>,
+        pub init: Option<Initializer>,
         pub sc: Option<StorageClass>,
         pub typ: VarType,
+        pub span: Span,
     }
 
+    pub type Initializer = parse::Initializer<Expr>;
+
     pub type Block = Arr<BlockItem>;
 
     #[derive(Debug, Clone)]
@@ -279,15 +439,15 @@ pub mod typechecked {
 
     #[derive(Debug, Clone)]
     pub enum Stmnt {
-        Ret(Expr),
+        Ret(Option<Expr>, Span),
         Exp(Expr),
         If {
             condition: Expr,
             then: Box<Self>,
             r#else: Option<Box<Self>>,
         },
-        Break(LabelId),
-        Continue(LabelId),
+        Break(LabelId, Span),
+        Continue(LabelId, Span),
         While {
             condition: Expr,
             body: Box<Self>,
@@ -309,8 +469,9 @@ pub mod typechecked {
         Label {
             name: Label,
             body: Box<Self>,
+            span: Span,
         },
-        Goto(Identifier),
+        Goto(Identifier, Span),
         Switch {
             val: Expr,
             body: Box<Self>,
@@ -333,6 +494,7 @@ pub mod typechecked {
             dst: Box<Self>,
             src: Box<Self>,
             ty: VarType,
+            span: Span,
         },
 
         Binary {
@@ -341,12 +503,16 @@ pub mod typechecked {
             right: Box<Self>,
 
             ty: VarType,
+
+            span: Span,
         },
         Cast {
             target: VarType,
             exp: Box<Self>,
 
             ty: VarType,
+
+            span: Span,
         },
 
         IncDec {
@@ -354,24 +520,66 @@ pub mod typechecked {
             exp: Box<Self>,
 
             ty: VarType,
+
+            span: Span,
         },
 
         Var {
             name: Identifier,
             ty: VarType,
+            span: Span,
         },
         Const {
             cnst: crate::lex::Constant,
             ty: VarType,
+            span: Span,
+        },
+        // only ever initializes a char array, anywhere else it's a `Var` for a string constant
+        String {
+            value: Arr<u8>,
+            ty: VarType,
+            span: Span,
         },
         Unary {
             operator: UnOp,
             operand: Box<Self>,
             ty: VarType,
+            span: Span,
         },
         Nested {
             inner: Box<Self>,
             ty: VarType,
+            span: Span,
+        },
+        Deref {
+            exp: Box<Self>,
+            ty: VarType,
+            span: Span,
+        },
+        AddrOf {
+            exp: Box<Self>,
+            ty: VarType,
+            span: Span,
+        },
+        // `array` is always the pointer, even if it was written `1[a]`
+        Subscript {
+            array: Box<Self>,
+            index: Box<Self>,
+            ty: VarType,
+            span: Span,
+        },
+        // `offset` bytes into the struct, or into what the pointer points at for `Arrow`
+        Dot {
+            exp: Box<Self>,
+            offset: usize,
+            ty: VarType,
+            span: Span,
+        },
+        Arrow {
+            exp: Box<Self>,
+            offset: usize,
+            ty: VarType,
+            span: Span,
         },
 
         Conditional {
@@ -379,15 +587,42 @@ pub mod typechecked {
             r#true: Box<Self>,
             r#false: Box<Self>,
             ty: VarType,
+            span: Span,
         },
+        // `func` is a function pointer, which a function's name decays to
         FunctionCall {
-            name: Identifier,
+            func: Box<Self>,
             args: Arr<Self>,
             ty: VarType,
+            span: Span,
+        },
+        // `ap` is already a pointer to the va_list struct
+        VaStart {
+            ap: Box<Self>,
+            ty: VarType,
+            span: Span,
+        },
+        VaArg {
+            ap: Box<Self>,
+            ty: VarType,
+            span: Span,
+        },
+        // `name` is the unnamed object, which typecheck made up a name for
+        CompoundLiteral {
+            name: Identifier,
+            init: Box<Initializer>,
+            ty: VarType,
+            span: Span,
+        },
+        // always a constant, but const_eval is what works it out from the layout
+        OffsetOf {
+            target: VarType,
+            member: Identifier,
+            ty: VarType,
+            span: Span,
         },
     }
 
-    use super::StaticInit;
     impl Expr {
         pub const fn ty(&self) -> VarType {
             match self {
@@ -397,25 +632,70 @@ pub mod typechecked {
                 | Self::IncDec { ty, .. }
                 | Self::Var { ty, .. }
                 | Self::Const { ty, .. }
+                | Self::String { ty, .. }
+                | Self::Unary { ty, .. }
+                | Self::Nested { ty, .. }
+                | Self::Deref { ty, .. }
+                | Self::AddrOf { ty, .. }
+                | Self::Subscript { ty, .. }
+                | Self::Dot { ty, .. }
+                | Self::Arrow { ty, .. }
+                | Self::Conditional { ty, .. }
+                | Self::FunctionCall { ty, .. }
+                | Self::VaStart { ty, .. }
+                | Self::VaArg { ty, .. }
+                | Self::CompoundLiteral { ty, .. }
+                | Self::OffsetOf { ty, .. } => *ty,
+            }
+        }
+
+        pub fn ty_mut(&mut self) -> &mut VarType {
+            match self {
+                Self::Assignment { ty, .. }
+                | Self::Binary { ty, .. }
+                | Self::Cast { ty, .. }
+                | Self::IncDec { ty, .. }
+                | Self::Var { ty, .. }
+                | Self::Const { ty, .. }
+                | Self::String { ty, .. }
                 | Self::Unary { ty, .. }
                 | Self::Nested { ty, .. }
+                | Self::Deref { ty, .. }
+                | Self::AddrOf { ty, .. }
+                | Self::Subscript { ty, .. }
+                | Self::Dot { ty, .. }
+                | Self::Arrow { ty, .. }
                 | Self::Conditional { ty, .. }
-                | Self::FunctionCall { ty, .. } => *ty,
+                | Self::FunctionCall { ty, .. }
+                | Self::VaStart { ty, .. }
+                | Self::VaArg { ty, .. }
+                | Self::CompoundLiteral { ty, .. }
+                | Self::OffsetOf { ty, .. } => ty,
             }
         }
 
-        pub const fn static_init(&self) -> Option<StaticInit> {
+        pub const fn span(&self) -> Span {
             match self {
-                Expr::Const {
-                    cnst: Constant::Long(l),
-                    ..
-                } => Some(StaticInit::Long(*l)),
-                Expr::Const {
-                    cnst: Constant::Int(i),
-                    ..
-                } => Some(StaticInit::Int(*i)),
-                Expr::Nested { inner: e, .. } => e.static_init(),
-                _ => None,
+                Self::Assignment { span, .. }
+                | Self::Binary { span, .. }
+                | Self::Cast { span, .. }
+                | Self::IncDec { span, .. }
+                | Self::Var { span, .. }
+                | Self::Const { span, .. }
+                | Self::String { span, .. }
+                | Self::Unary { span, .. }
+                | Self::Nested { span, .. }
+                | Self::Deref { span, .. }
+                | Self::AddrOf { span, .. }
+                | Self::Subscript { span, .. }
+                | Self::Dot { span, .. }
+                | Self::Arrow { span, .. }
+                | Self::Conditional { span, .. }
+                | Self::FunctionCall { span, .. }
+                | Self::VaStart { span, .. }
+                | Self::VaArg { span, .. }
+                | Self::CompoundLiteral { span, .. }
+                | Self::OffsetOf { span, .. } => *span,
             }
         }
     }
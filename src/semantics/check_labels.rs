@@ -1,7 +1,10 @@
 use super::ast::type_prelude::*;
-use crate::lex::Identifier;
+use crate::lex::{Identifier, Span};
 use crate::semantics::SymbolTable;
-use std::collections::HashSet;
+use std::collections::HashMap;
+
+// every label in the function and where it was defined
+type Labels = HashMap<Identifier, Span>;
 
 pub fn check(program: &Program, vars: &SymbolTable) -> Result<(), Error> {
     for r#fn in program {
@@ -24,7 +27,7 @@ fn check_dec(dec: &Dec, vars: &SymbolTable) -> Result<(), Error> {
 }
 
 fn check_body(block: &[BlockItem], vars: &SymbolTable, fn_name: &Identifier) -> Result<(), Error> {
-    let mut labels = HashSet::new();
+    let mut labels = Labels::new();
     for item in block.iter() {
         if let BlockItem::S(statement) = item {
             check_labels(statement, vars, &mut labels, fn_name)?;
@@ -52,8 +55,9 @@ fn name_clashes(label: &Identifier, vars: &SymbolTable, fn_name: &Identifier) ->
 fn handle_label(
     label: &Label,
     body: &Stmnt,
+    span: Span,
     vars: &SymbolTable,
-    labels: &mut HashSet<Identifier>,
+    labels: &mut Labels,
     fn_name: &Identifier,
 ) -> Result<(), Error> {
     // we pass along everything else
@@ -63,18 +67,19 @@ fn handle_label(
     // if the label is already defined but it's not main bc main can be a label
 
     if name_clashes(label, vars, fn_name) {
-        Err(Error::ClashedLabel)
-    } else if labels.insert(label.clone()) {
-        check_labels(body, vars, labels, fn_name)
+        Err(Error::ClashedLabel(span))
+    } else if let Some(&previous) = labels.get(label) {
+        Err(Error::RedefinedLabel { span, previous })
     } else {
-        Err(Error::RedefinedLabel)
+        labels.insert(label.clone(), span);
+        check_labels(body, vars, labels, fn_name)
     }
 }
 
 fn check_labels(
     statement: &Stmnt,
     vars: &SymbolTable,
-    labels: &mut HashSet<Identifier>,
+    labels: &mut Labels,
     fn_name: &Identifier,
 ) -> Result<(), Error> {
     match statement {
@@ -90,7 +95,7 @@ fn check_labels(
             check_labels(body, vars, labels, fn_name)
         }
 
-        Stmnt::Label { name, body } => handle_label(name, body, vars, labels, fn_name),
+        Stmnt::Label { name, body, span } => handle_label(name, body, *span, vars, labels, fn_name),
         Stmnt::If {
             condition: _,
             then,
@@ -103,22 +108,22 @@ fn check_labels(
             Ok(())
         }
         Stmnt::Switch { body, .. } => check_labels(body, vars, labels, fn_name),
-        Stmnt::Ret(_)
+        Stmnt::Ret(..)
         | Stmnt::Exp(_)
         | Stmnt::Null
-        | Stmnt::Goto(_)
-        | Stmnt::Continue(_)
-        | Stmnt::Break(_) => Ok(()),
+        | Stmnt::Goto(..)
+        | Stmnt::Continue(..)
+        | Stmnt::Break(..) => Ok(()),
     }
 }
 
-fn check_gotos(statement: &Stmnt, labels: &HashSet<Identifier>) -> Result<(), Error> {
+fn check_gotos(statement: &Stmnt, labels: &Labels) -> Result<(), Error> {
     match statement {
-        Stmnt::Goto(goto) => {
-            if labels.contains(goto) {
+        Stmnt::Goto(goto, span) => {
+            if labels.contains_key(goto) {
                 Ok(())
             } else {
-                Err(Error::UndefinedLabel)
+                Err(Error::UndefinedLabel(*span))
             }
         }
         Stmnt::If {
@@ -146,7 +151,7 @@ fn check_gotos(statement: &Stmnt, labels: &HashSet<Identifier>) -> Result<(), Er
         | Stmnt::Switch { body, .. }
         | Stmnt::For { body, .. } => check_gotos(body, labels),
 
-        Stmnt::Ret(_) | Stmnt::Exp(_) | Stmnt::Null | Stmnt::Break(_) | Stmnt::Continue(_) => {
+        Stmnt::Ret(..) | Stmnt::Exp(_) | Stmnt::Null | Stmnt::Break(..) | Stmnt::Continue(..) => {
             Ok(())
         }
     }
@@ -154,7 +159,17 @@ fn check_gotos(statement: &Stmnt, labels: &HashSet<Identifier>) -> Result<(), Er
 
 #[derive(Debug)]
 pub enum Error {
-    RedefinedLabel,
-    ClashedLabel,
-    UndefinedLabel,
+    RedefinedLabel { span: Span, previous: Span },
+    ClashedLabel(Span),
+    UndefinedLabel(Span),
+}
+
+impl Error {
+    pub const fn span(&self) -> Span {
+        match self {
+            Self::RedefinedLabel { span, .. }
+            | Self::ClashedLabel(span)
+            | Self::UndefinedLabel(span) => *span,
+        }
+    }
 }
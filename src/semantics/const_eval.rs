@@ -0,0 +1,492 @@
+// works out constant expressions in the typed AST the same way they'd run, so typecheck has
+// already done the promotions and conversions and this only has to keep values in range
+use super::typecheck::Error;
+use super::typed::Expr;
+use crate::lex::{Constant, Identifier, Span};
+use crate::parse::{Bop, UnOp, VarType};
+
+// `not_constant` is what to report when part of `exp` can't be worked out before the program runs
+pub fn evaluate(exp: &Expr, not_constant: fn(Span) -> Error) -> Result<Constant, Error> {
+    match exp {
+        Expr::Const { cnst, .. } => Ok(*cnst),
+        Expr::OffsetOf { target, member, .. } => Ok(offset_of(*target, member)),
+        Expr::Nested { inner, .. } => evaluate(inner, not_constant),
+        Expr::Cast { target, exp, .. } if target.arithmetic() || target.is_pointer() => {
+            Ok(convert(evaluate(exp, not_constant)?, *target))
+        }
+        Expr::Unary {
+            operator,
+            operand,
+            ty,
+            span,
+        } => {
+            let value = evaluate(operand, not_constant)?;
+            match (operator, value) {
+                (UnOp::Not, value) => Ok(Constant::Int(!truthy(value) as i32)),
+                (UnOp::Negate, Constant::Double(d)) => Ok(Constant::Double(-d)),
+                (UnOp::Negate, value) => {
+                    fit(-integer(value), *ty).map_err(|_| Error::ConstantOverflow {
+                        span: *span,
+                        ty: *ty,
+                    })
+                }
+                (UnOp::Complement, value) => Ok(wrap(!integer(value), *ty)),
+            }
+        }
+        Expr::Binary {
+            left,
+            operator,
+            right,
+            ty,
+            span,
+        } => binary(left, *operator, right, *ty, *span, not_constant),
+        // only the branch that gets picked has to be constant, like `&&` and `||`
+        Expr::Conditional {
+            condition,
+            r#true,
+            r#false,
+            ty,
+            ..
+        } if ty.arithmetic() => {
+            let branch = if truthy(evaluate(condition, not_constant)?) {
+                r#true
+            } else {
+                r#false
+            };
+            Ok(convert(evaluate(branch, not_constant)?, *ty))
+        }
+        exp => Err(not_constant(exp.span())),
+    }
+}
+
+// typecheck has made sure `target` is complete and has `member`
+pub fn offset_of(target: VarType, member: &Identifier) -> Constant {
+    let layout = target.layout().expect("offsetof needs a complete type");
+    let member = layout
+        .member(member.as_ref())
+        .expect("typecheck found the member");
+    Constant::ULong(member.offset as u64)
+}
+
+fn binary(
+    left: &Expr,
+    operator: Bop,
+    right: &Expr,
+    ty: VarType,
+    span: Span,
+    not_constant: fn(Span) -> Error,
+) -> Result<Constant, Error> {
+    if operator.compound() || matches!(operator, Bop::Equals | Bop::Ternary | Bop::Comma) {
+        return Err(not_constant(span));
+    }
+    if let Some(pointer) = [left, right].into_iter().find(|e| e.ty().is_pointer()) {
+        return Err(not_constant(pointer.span()));
+    }
+    let left_value = evaluate(left, not_constant)?;
+    match operator {
+        Bop::LogAnd if !truthy(left_value) => return Ok(Constant::Int(0)),
+        Bop::LogOr if truthy(left_value) => return Ok(Constant::Int(1)),
+        Bop::LogAnd | Bop::LogOr => {
+            return Ok(Constant::Int(truthy(evaluate(right, not_constant)?) as i32));
+        }
+        _ => (),
+    }
+    let right_value = evaluate(right, not_constant)?;
+    arithmetic(operator, left_value, right_value, left.ty()).map_err(|undefined| match undefined {
+        Undefined::Trap => not_constant(right.span()),
+        Undefined::Overflow(_) => Error::ConstantOverflow { span, ty },
+    })
+}
+
+// why `arithmetic` has no value to give
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Undefined {
+    // dividing by zero, `INT_MIN / -1` and shifting by the width or more
+    Trap,
+    // a signed result that doesn't fit in its type, with what it wraps around to at runtime
+    Overflow(Constant),
+}
+
+// both operands have been converted to `ty` already, apart from the count of a shift. comparisons
+// give an int and everything else gives `ty`
+pub fn arithmetic(
+    operator: Bop,
+    a: Constant,
+    b: Constant,
+    ty: VarType,
+) -> Result<Constant, Undefined> {
+    if let (Constant::Double(a), Constant::Double(b)) = (a, b) {
+        return Ok(match operator {
+            Bop::Add => Constant::Double(a + b),
+            Bop::Subtract => Constant::Double(a - b),
+            Bop::Multiply => Constant::Double(a * b),
+            Bop::Divide => Constant::Double(a / b),
+            Bop::EqualTo => Constant::Int((a == b) as i32),
+            Bop::NotEqual => Constant::Int((a != b) as i32),
+            Bop::LessThan => Constant::Int((a < b) as i32),
+            Bop::GreaterThan => Constant::Int((a > b) as i32),
+            Bop::Leq => Constant::Int((a <= b) as i32),
+            Bop::Geq => Constant::Int((a >= b) as i32),
+            _ => unreachable!("typecheck only allows arithmetic and comparisons on doubles"),
+        });
+    }
+    let (a, b) = (integer(a), integer(b));
+    let value = match operator {
+        Bop::Add => a + b,
+        Bop::Subtract => a - b,
+        // two unsigned longs can overflow an i128, but only the low bits matter for those
+        Bop::Multiply => a.wrapping_mul(b),
+        Bop::Divide | Bop::Remainder if b == 0 || integer(wrap(a / b, ty)) != a / b => {
+            return Err(Undefined::Trap);
+        }
+        Bop::Divide => a / b,
+        Bop::Remainder => a % b,
+        Bop::BitAnd => a & b,
+        Bop::BitOr => a | b,
+        Bop::Xor => a ^ b,
+        Bop::LeftShift | Bop::RightShift if !(0..ty.size() as i128 * 8).contains(&b) => {
+            return Err(Undefined::Trap);
+        }
+        Bop::LeftShift => a << b,
+        Bop::RightShift => a >> b,
+        Bop::EqualTo => return Ok(Constant::Int((a == b) as i32)),
+        Bop::NotEqual => return Ok(Constant::Int((a != b) as i32)),
+        Bop::LessThan => return Ok(Constant::Int((a < b) as i32)),
+        Bop::GreaterThan => return Ok(Constant::Int((a > b) as i32)),
+        Bop::Leq => return Ok(Constant::Int((a <= b) as i32)),
+        Bop::Geq => return Ok(Constant::Int((a >= b) as i32)),
+        operator => unreachable!("{operator:?} doesn't work on two values"),
+    };
+    fit(value, ty)
+}
+
+pub fn truthy(value: Constant) -> bool {
+    match value {
+        Constant::Double(d) => d != 0.0,
+        value => integer(value) != 0,
+    }
+}
+
+// wide enough that nothing two longs can do to each other besides `*` overflows it
+pub fn integer(value: Constant) -> i128 {
+    match value {
+        Constant::Int(i) => i as i128,
+        Constant::Long(l) => l as i128,
+        Constant::UInt(u) => u as i128,
+        Constant::ULong(u) => u as i128,
+        Constant::Double(d) => d as i128,
+        Constant::Char(c) => c as i128,
+        Constant::UChar(c) => c as i128,
+    }
+}
+
+// signed arithmetic that doesn't fit is undefined, unsigned arithmetic wraps around
+fn fit(value: i128, ty: VarType) -> Result<Constant, Undefined> {
+    let constant = wrap(value, ty);
+    if ty.signed() && integer(constant) != value {
+        return Err(Undefined::Overflow(constant));
+    }
+    Ok(constant)
+}
+
+// conversions wrap around the same way they do at runtime, pointers are just addresses
+pub fn convert(value: Constant, ty: VarType) -> Constant {
+    match value {
+        Constant::Double(d) if ty.unqualified() == VarType::Double => Constant::Double(d),
+        value => wrap(integer(value), ty),
+    }
+}
+
+pub fn wrap(value: i128, ty: VarType) -> Constant {
+    match ty.unqualified() {
+        VarType::Char | VarType::SChar => Constant::Char(value as i8),
+        VarType::UChar => Constant::UChar(value as u8),
+        VarType::Int => Constant::Int(value as i32),
+        VarType::UInt => Constant::UInt(value as u32),
+        VarType::Long => Constant::Long(value as i64),
+        VarType::ULong | VarType::Pointer(_) => Constant::ULong(value as u64),
+        VarType::Double => Constant::Double(value as f64),
+        ty => unreachable!("{ty} isn't a scalar"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::semantics::typecheck::Error as TypeCheckError;
+    use crate::semantics::{checked, Attr, Error, StaticInit};
+    use crate::Target;
+
+    fn initial(source: &[u8], name: &str) -> Result<Vec<String>, TypeCheckError> {
+        let table = match checked(source) {
+            Ok((_, table)) => table,
+            Err(Error::TypeCheck(e)) => return Err(e),
+            Err(e) => panic!("{e:?}"),
+        };
+        let Some(Attr::Static {
+            init: Some(init),
+            typ,
+            ..
+        }) = table.get(&name.into())
+        else {
+            unreachable!()
+        };
+        Ok(init
+            .get_static(*typ)
+            .iter()
+            .map(StaticInit::to_string)
+            .collect())
+    }
+
+    #[test]
+    fn static_initializers() {
+        let init = |source: &[u8]| initial(source, "x").unwrap();
+        assert_eq!(init(b"int x = 1 + 2 * 3;"), [".long 7"]);
+        assert_eq!(
+            init(b"enum { A = 4 }; long x = (long)A << 40 | 1;"),
+            [".quad 4398046511105"]
+        );
+        // unsigned arithmetic wraps, and conversions wrap like they do at runtime
+        assert_eq!(init(b"unsigned x = 0u - 1;"), [".long 4294967295"]);
+        assert_eq!(init(b"char x = 200 + 100;"), [".byte 44"]);
+        assert_eq!(init(b"int x = 1 ? -7 / 2 : 1 / 0;"), [".long -3"]);
+        assert_eq!(
+            init(b"double x = 3 / 2 + .5;"),
+            [".quad 4609434218613702656"]
+        );
+
+        let error = |source: &[u8]| initial(source, "x").unwrap_err();
+        assert!(matches!(
+            error(b"int x = 2147483647 + 1;"),
+            TypeCheckError::ConstantOverflow { .. }
+        ));
+        assert!(matches!(
+            error(b"int x = 1 % 0;"),
+            TypeCheckError::NotConstInitialized(_)
+        ));
+        assert!(matches!(
+            error(b"int y; int x = y;"),
+            TypeCheckError::NotConstInitialized(_)
+        ));
+    }
+
+    #[test]
+    fn case_labels() {
+        let switch = |cases: &str| {
+            let source = format!("int x; int f(int v) {{ switch (v) {{ {cases} }} return 0; }}");
+            initial(source.as_bytes(), "x")
+        };
+        assert!(switch("case 1 << 3: case -1: case 'a': case 4000000000u: ;").is_ok());
+        assert!(matches!(
+            switch("case 8: case 1 << 3: ;"),
+            Err(TypeCheckError::DuplicateCase { .. })
+        ));
+        assert!(matches!(
+            switch("case 1.0: ;"),
+            Err(TypeCheckError::InvalidCase(_))
+        ));
+    }
+    #[test]
+    fn enumerators_and_lengths() {
+        let init = |source: &[u8]| initial(source, "x").unwrap();
+        // the later enumerators count on from the earlier ones, which can be used right away
+        assert_eq!(
+            init(b"enum { A = 2, B, C = A << B }; int x = C;"),
+            [".long 16"]
+        );
+        assert_eq!(
+            init(b"enum { A = 1 }; int f(void) { enum { A = A + 1 }; return A; } int x = A;"),
+            [".long 1"]
+        );
+        assert_eq!(init(b"int a[3]; long x = sizeof a;"), [".quad 12"]);
+        // they're evaluated with the usual arithmetic conversions, like everything else
+        assert_eq!(
+            init(b"enum { E1 = (0u - 1 > 0), E2 = (-1 < 0u) }; int x = E1 * 10 + E2;"),
+            [".long 10"]
+        );
+        assert_eq!(
+            init(b"int a[1 + (0u - 1 > 0)]; long x = sizeof a;"),
+            [".quad 8"]
+        );
+        assert_eq!(
+            init(b"enum { N = 2 }; int a[N + 2]; typedef int T[N]; T t; long x = sizeof a + sizeof t;"),
+            [".quad 24"]
+        );
+        // and `sizeof` is a constant wherever one is needed
+        assert_eq!(
+            init(
+                b"struct S { int i; char c; }; enum { K = sizeof(struct S) }; \
+                  int a[sizeof(struct S)]; int b[sizeof a / sizeof a[0]]; double d; \
+                  char c[sizeof d]; long x = K * 1000 + sizeof b + sizeof c;"
+            ),
+            [".quad 8040"]
+        );
+        assert_eq!(
+            init(b"enum { N = 2 }; int x[4] = {[N + 1] = 1, [0] = 2};"),
+            [".long 2", ".zero 8", ".long 1"]
+        );
+
+        assert_eq!(
+            init(b"int a[] = {1, [4] = 2, 3}; extern int e[]; int e[2]; long x = sizeof a + sizeof e;"),
+            [".quad 32"]
+        );
+        assert_eq!(init(b"char s[] = \"hey\"; long x = sizeof s;"), [".quad 4"]);
+
+        let error = |source: &[u8]| initial(source, "x").unwrap_err();
+        assert!(matches!(
+            error(b"enum { X = 2147483647, Y }; int x;"),
+            TypeCheckError::EnumeratorRange(_)
+        ));
+        assert!(matches!(
+            error(b"int y = 2; int x[y];"),
+            TypeCheckError::InvalidArraySize(_)
+        ));
+        assert!(matches!(
+            error(b"int x[2 - 2];"),
+            TypeCheckError::InvalidArraySize(_)
+        ));
+        assert!(matches!(
+            error(b"int x[2] = {[-1] = 1};"),
+            TypeCheckError::InvalidDesignator(_)
+        ));
+    }
+
+    #[test]
+    fn designated_initializers() {
+        let init = |source: &[u8]| initial(source, "x").unwrap();
+        // members are placed at their offsets whatever order they're named in, and the padding
+        // between and after them is zeroed
+        assert_eq!(
+            init(b"struct s { char c; int i; char d; } x = {.i = 2, .c = 1};"),
+            [".byte 1", ".zero 3", ".long 2", ".zero 4"]
+        );
+        // a later designator overrides an earlier one, and the next one without a designator
+        // carries on after it
+        assert_eq!(
+            init(b"int x[4] = {[2] = 1, 2, [2] = 3};"),
+            [".zero 8", ".long 3", ".long 2"]
+        );
+        assert_eq!(
+            init(b"struct p { int a, b; }; struct p x[2] = {[1].b = 5, [0] = {.a = 4}};"),
+            [".long 4", ".zero 8", ".long 5"]
+        );
+        assert_eq!(
+            init(b"union u { char c; long l; } x = {.l = 7};"),
+            [".quad 7"]
+        );
+    }
+
+    #[test]
+    fn sizes_and_alignments() {
+        let init = |source: &[u8]| initial(source, "x").unwrap();
+        assert_eq!(
+            init(b"long x = _Alignof(double) * 10 + _Alignof(char);"),
+            [".quad 81"]
+        );
+        // as a member a big array is only aligned like its elements, 16 bytes is for variables
+        assert_eq!(
+            init(
+                b"struct s { char c; char a[20]; }; \
+                  long x = _Alignof(struct s) * 100 + sizeof(struct s);"
+            ),
+            [".quad 121"]
+        );
+        assert_eq!(
+            init(b"long x = sizeof(char) + sizeof 1.0 + sizeof(int[3]);"),
+            [".quad 21"]
+        );
+        // parentheses around the operand don't make an array decay
+        let arrays = b"char a[10]; int m[3][4]; int (*pa)[4];";
+        let size = |exp: &str| {
+            let source = [&arrays[..], format!("long x = {exp};").as_bytes()].concat();
+            init(&source)
+        };
+        assert_eq!(size("sizeof(a)"), [".quad 10"]);
+        assert_eq!(size("sizeof(m) / sizeof(m[0])"), [".quad 3"]);
+        assert_eq!(size("sizeof(m[0])"), [".quad 16"]);
+        assert_eq!(size("sizeof(*pa)"), [".quad 16"]);
+        // and a string literal's size counts the null characters inside it too
+        assert_eq!(size("sizeof(\"ab\\0c\")"), [".quad 5"]);
+        // the operand isn't evaluated, so a comma is fine there and has the right side's type
+        assert_eq!(init(b"int i; long x = sizeof(i, 'a');"), [".quad 4"]);
+    }
+
+    #[test]
+    fn offsets() {
+        // `offsetof` is an integer constant anywhere one is needed
+        let init = |source: &[u8]| initial(source, "x").unwrap();
+        let s = "struct s { char c; int i; double d; }; union u { char c; long l; };";
+        let offset = |rest: &str| init(format!("{s} {rest}").as_bytes());
+        assert_eq!(
+            offset("static unsigned long x = __builtin_offsetof(struct s, d);"),
+            [".quad 8"]
+        );
+        assert_eq!(
+            offset("int a[__builtin_offsetof(struct s, i)]; long x = sizeof a;"),
+            [".quad 16"]
+        );
+        assert_eq!(
+            offset(
+                "long x = 1 + __builtin_offsetof(union u, l) + __builtin_offsetof(struct s, c);"
+            ),
+            [".quad 1"]
+        );
+        let switch = |cases: &str| {
+            let source =
+                format!("{s} int x; int f(int v) {{ switch (v) {{ {cases} }} return 0; }}");
+            initial(source.as_bytes(), "x")
+        };
+        assert!(switch("case __builtin_offsetof(struct s, i): case 8: ;").is_ok());
+        assert!(matches!(
+            switch("case __builtin_offsetof(struct s, d): case 8: ;"),
+            Err(TypeCheckError::DuplicateCase { .. })
+        ));
+
+        let error = |rest: &str| initial(format!("{s} {rest}").as_bytes(), "x").unwrap_err();
+        assert!(matches!(
+            error("long x = __builtin_offsetof(struct s, j);"),
+            TypeCheckError::NoMember { .. }
+        ));
+        assert!(matches!(
+            error("struct t; long x = __builtin_offsetof(struct t, j);"),
+            TypeCheckError::IncompleteType { .. }
+        ));
+    }
+
+    #[test]
+    fn address_constants() {
+        // anything with static storage has an address the linker can fill in, and a constant
+        // number of bytes past it is just as good
+        Target::set_for_test(Target::Linux);
+        let objects = "int y; int a[4]; struct v { long l; int m[3]; } v; int f(void);";
+        let init = |rest: &str| initial(format!("{objects} {rest}").as_bytes(), "x");
+        assert_eq!(init("int *x = &y;").unwrap(), [".quad y"]);
+        assert_eq!(init("int *x = a;").unwrap(), [".quad a"]);
+        assert_eq!(init("int *x = &a[1];").unwrap(), [".quad a+4"]);
+        assert_eq!(init("int *x = (a + 3) - 1;").unwrap(), [".quad a+8"]);
+        assert_eq!(init("int *x = &v.m[2];").unwrap(), [".quad v+16"]);
+        assert_eq!(init("char *x = (char *)&v + 8;").unwrap(), [".quad v+8"]);
+        assert_eq!(init("int (*x)(void) = &f;").unwrap(), [".quad f"]);
+        Target::set_for_test(Target::Darwin);
+        assert_eq!(init("int *x = &a[1];").unwrap(), [".quad _a+4"]);
+        Target::set_for_test(Target::Linux);
+        // a local static can point at another static, just not at an automatic variable
+        let source = format!("{objects} int *x; int *g(void) {{ static int *p = &y; return p; }}");
+        assert!(initial(source.as_bytes(), "x").is_ok());
+        assert!(matches!(
+            init("int *x; int *g(void) { int l; static int *p = &l; return p; }"),
+            Err(TypeCheckError::NotConstInitialized(_))
+        ));
+        assert!(matches!(
+            init("int *x = a + y;"),
+            Err(TypeCheckError::NotConstInitialized(_))
+        ));
+        assert!(matches!(
+            init("long x = (long)&y;"),
+            Err(TypeCheckError::NotConstInitialized(_))
+        ));
+        assert!(matches!(
+            init("long *x = &y;"),
+            Err(TypeCheckError::IncompatibleTypes { .. })
+        ));
+    }
+}
@@ -1,10 +1,11 @@
-use crate::lex::Identifier;
+use crate::lex::{Identifier, Span};
 use crate::parse;
 use crate::parse::BlockItem as AstBlockItem;
 
 use crate::parse::Block as AstBlock;
 use crate::parse::Expr as AstExpression;
 use crate::parse::ForInit as AstForInit;
+use crate::parse::Initializer as AstInitializer;
 use crate::parse::Program as AstProgram;
 use crate::parse::Stmnt as AstStatement;
 
@@ -14,6 +15,7 @@ use crate::parse::FnDec as AstFnDec;
 use parse::Binary as AstBinary;
 use parse::Dec as AstDeclaration;
 use parse::StorageClass;
+use parse::{FnType, StructDec, VarType, VA_LIST_TAG};
 
 use std::sync::atomic::{AtomicU32, Ordering};
 
@@ -21,33 +23,57 @@ static COUNTER: AtomicU32 = AtomicU32::new(0);
 
 use std::collections::HashMap;
 type VarMap = HashMap<Identifier, Var>;
+type TagMap = HashMap<&'static str, Tag>;
+
+// struct tags live in a namespace of their own
+#[derive(Clone, Debug, Default)]
+struct Scope {
+    vars: VarMap,
+    tags: TagMap,
+}
+
+#[derive(Clone, Debug)]
+struct Tag {
+    name: &'static str,
+    union: bool,
+    from_current_block: bool,
+    span: Span,
+}
 
 #[derive(Clone, Debug, PartialEq)]
 struct Var {
     name: Identifier,
     from_current_block: bool,
     has_external_linkage: bool,
+    // where it was declared
+    span: Span,
+    // typecheck works out what the value is
+    enumerator: bool,
 }
 
 impl Var {
-    fn new_var(name: &Identifier, sc: &Option<StorageClass>) -> Self {
+    fn new_var(name: &Identifier, sc: &Option<StorageClass>, span: Span) -> Self {
         Self {
             name: name.clone(),
             from_current_block: true,
             has_external_linkage: sc.is_some_and(|x| x == StorageClass::Extern),
+            span,
+            enumerator: false,
         }
     }
-    fn new_fn(name: &Identifier, sc: &Option<StorageClass>) -> Self {
+    fn new_fn(name: &Identifier, sc: &Option<StorageClass>, span: Span) -> Self {
         Self {
             name: name.clone(),
             from_current_block: true,
             has_external_linkage: sc.is_some_and(|x| x == StorageClass::Extern),
+            span,
+            enumerator: false,
         }
     }
 }
 
 pub fn resolve(AstProgram(decs): &mut AstProgram) -> Result<(), Error> {
-    let mut map: VarMap = VarMap::new();
+    let mut map = Scope::default();
     for dec in decs {
         resolve_top_level_dec(dec, &mut map)?;
     }
@@ -55,11 +81,13 @@ pub fn resolve(AstProgram(decs): &mut AstProgram) -> Result<(), Error> {
 }
 
 fn insert_fndec(
-    map: &mut VarMap,
+    map: &mut Scope,
     name: &mut Identifier,
     storage_class: &mut Option<StorageClass>,
+    span: Span,
 ) -> Result<(), Error> {
-    map.insert(name.clone(), Var::new_fn(name, storage_class));
+    map.vars
+        .insert(name.clone(), Var::new_fn(name, storage_class, span));
     Ok(())
 
     /*
@@ -76,35 +104,42 @@ fn insert_fndec(
 }
 
 fn insert_local_var(
-    map: &mut VarMap,
+    map: &mut Scope,
     name: &mut Identifier,
     storage_class: &mut Option<StorageClass>,
+    span: Span,
 ) -> Result<(), Error> {
-    if let Some(prev_decl) = map.get(name) {
-        if prev_decl.from_current_block
-            && !(prev_decl.has_external_linkage && *storage_class == Some(StorageClass::Extern))
-        {
-            return Err(Error::ConflictingDec);
-        }
+    if let Some(prev_decl) = map.vars.get(name)
+        && prev_decl.from_current_block
+        && !(prev_decl.has_external_linkage && *storage_class == Some(StorageClass::Extern))
+    {
+        return Err(Error::ConflictingDec {
+            span,
+            previous: prev_decl.span,
+        });
     }
 
     if *storage_class == Some(StorageClass::Extern) {
-        map.insert(
+        map.vars.insert(
             name.clone(),
             Var {
                 name: name.clone(),
                 from_current_block: true,
                 has_external_linkage: true,
+                span,
+                enumerator: false,
             },
         );
     } else {
         let unique: Identifier = new_var(&name.0);
-        map.insert(
+        map.vars.insert(
             name.clone(),
             Var {
                 name: unique.clone(),
                 from_current_block: true,
                 has_external_linkage: false,
+                span,
+                enumerator: false,
             },
         );
         *name = unique.clone();
@@ -112,17 +147,31 @@ fn insert_local_var(
     Ok(())
 }
 
-fn resolve_top_level_dec(dec: &mut AstDeclaration, map: &mut VarMap) -> Result<(), Error> {
+fn resolve_top_level_dec(dec: &mut AstDeclaration, map: &mut Scope) -> Result<(), Error> {
     match dec {
-        AstDeclaration::Var(AstVDec { name, .. }) => {
-            map.insert(
+        AstDeclaration::Var(AstVDec {
+            name,
+            typ,
+            span,
+            init,
+            ..
+        }) => {
+            resolve_type(typ, *span, map)?;
+            check_enumerator(name, *span, map)?;
+            map.vars.insert(
                 name.clone(),
                 Var {
                     name: name.clone(),
                     from_current_block: true,
                     has_external_linkage: true,
+                    span: *span,
+                    enumerator: false,
                 },
             );
+            // typecheck works out what it is, but enumerators in it have to be replaced first
+            if let Some(init) = init {
+                init.try_for_each(&mut |exp| resolve_expression(exp, map))?;
+            }
         }
 
         AstDeclaration::Fn(AstFnDec {
@@ -130,15 +179,64 @@ fn resolve_top_level_dec(dec: &mut AstDeclaration, map: &mut VarMap) -> Result<(
             params,
             sc,
             body,
-            typ: _,
+            typ,
+            span,
         }) => {
-            map.insert(name.clone(), Var::new_fn(name, sc));
+            resolve_fn_type(typ, params, *span, map)?;
+            check_enumerator(name, *span, map)?;
+            map.vars.insert(name.clone(), Var::new_fn(name, sc, *span));
             let mut inner_map = new_scope(map);
             resolve_param(params, &mut inner_map)?;
             if let Some(body) = body {
                 resolve_block(body, &mut inner_map)?;
             }
         }
+
+        AstDeclaration::Struct(dec) => resolve_struct_dec(dec, map)?,
+        AstDeclaration::Enum(dec) => resolve_enum_dec(dec, map)?,
+        AstDeclaration::Typedef(dec) => resolve_type(&mut dec.typ, dec.span, map)?,
+    }
+    Ok(())
+}
+
+// file scope declarations can be repeated, but not once the name is taken by an enumerator
+fn check_enumerator(name: &Identifier, span: Span, map: &Scope) -> Result<(), Error> {
+    match map.vars.get(name) {
+        Some(previous) if previous.enumerator => Err(Error::ConflictingDec {
+            span,
+            previous: previous.span,
+        }),
+        _ => Ok(()),
+    }
+}
+
+// an enumerator is in scope from the end of its own definition, so `A = A + 1` means the one
+// from further out
+fn resolve_enum_dec(dec: &mut parse::EnumDec, map: &mut Scope) -> Result<(), Error> {
+    for parse::Enumerator { name, value, span } in dec.enumerators.iter_mut() {
+        if let Some(value) = value {
+            resolve_expression(value, map)?;
+        }
+        if let Some(previous) = map.vars.get(name)
+            && previous.from_current_block
+        {
+            return Err(Error::ConflictingDec {
+                span: *span,
+                previous: previous.span,
+            });
+        }
+        let unique = new_var(&name.0);
+        map.vars.insert(
+            name.clone(),
+            Var {
+                name: unique.clone(),
+                from_current_block: true,
+                has_external_linkage: false,
+                span: *span,
+                enumerator: true,
+            },
+        );
+        *name = unique;
     }
     Ok(())
 }
@@ -148,9 +246,12 @@ fn resolve_fn_dec(
     body: &mut Option<AstBlock>,
     params: &mut super::ParamList,
     storage_class: &mut Option<StorageClass>,
-    map: &mut VarMap,
+    typ: &mut FnType,
+    span: Span,
+    map: &mut Scope,
 ) -> Result<(), Error> {
-    insert_fndec(map, name, storage_class)?;
+    resolve_fn_type(typ, params, span, map)?;
+    insert_fndec(map, name, storage_class, span)?;
     let mut inner_map = new_scope(map);
     resolve_param(params, &mut inner_map)?;
     if let Some(body) = body {
@@ -159,31 +260,36 @@ fn resolve_fn_dec(
     Ok(())
 }
 
-fn resolve_param(params: &mut super::ParamList, map: &mut VarMap) -> Result<(), Error> {
-    for param in params.iter_mut().map(|x| &mut x.name) {
-        if map
-            .get(param)
-            .is_some_and(|param| !param.has_external_linkage && param.from_current_block)
+fn resolve_param(params: &mut super::ParamList, map: &mut Scope) -> Result<(), Error> {
+    for parse::Param { name, span, .. } in params.iter_mut() {
+        if let Some(previous) = map
+            .vars
+            .get(name)
+            .filter(|param| !param.has_external_linkage && param.from_current_block)
         {
-            return Err(Error::DuplicateDeclaration);
+            return Err(Error::DuplicateDeclaration {
+                span: *span,
+                previous: previous.span,
+            });
         }
 
-        let unique: Identifier = new_var(&param.0);
-        map.insert(param.clone(), Var::new_var(&unique, &None));
+        let unique: Identifier = new_var(&name.0);
+        map.vars
+            .insert(name.clone(), Var::new_var(&unique, &None, *span));
 
-        *param = unique;
+        *name = unique;
     }
     Ok(())
 }
 
-fn resolve_block(block: &mut AstBlock, map: &mut VarMap) -> Result<(), Error> {
+fn resolve_block(block: &mut AstBlock, map: &mut Scope) -> Result<(), Error> {
     for item in block {
         resolve_block_item(item, map)?;
     }
     Ok(())
 }
 
-fn resolve_block_item(block: &mut AstBlockItem, map: &mut VarMap) -> Result<(), Error> {
+fn resolve_block_item(block: &mut AstBlockItem, map: &mut Scope) -> Result<(), Error> {
     match block {
         AstBlockItem::S(statement) => resolve_statement(statement, map)?,
         AstBlockItem::D(statement) => resolve_declaration(statement, map)?,
@@ -193,29 +299,37 @@ fn resolve_block_item(block: &mut AstBlockItem, map: &mut VarMap) -> Result<(),
 
 fn resolve_var_dec(
     name: &mut Identifier,
-    init: &mut Option<AstExpression>,
+    init: &mut Option<AstInitializer<AstExpression>>,
     sc: &mut Option<StorageClass>,
-    map: &mut VarMap,
+    typ: &mut VarType,
+    span: Span,
+    map: &mut Scope,
 ) -> Result<(), Error> {
-    insert_local_var(map, name, sc)?;
+    resolve_type(typ, span, map)?;
+    insert_local_var(map, name, sc, span)?;
     if let Some(init) = init {
-        resolve_expression(init, map)?;
+        init.try_for_each(&mut |exp| resolve_expression(exp, map))?;
     }
     Ok(())
 }
 
-fn resolve_declaration(dec: &mut AstDeclaration, map: &mut VarMap) -> Result<(), Error> {
+fn resolve_declaration(dec: &mut AstDeclaration, map: &mut Scope) -> Result<(), Error> {
     match dec {
         AstDeclaration::Var(AstVDec {
             name,
             init,
             sc,
-            typ: _,
-        }) => resolve_var_dec(name, init, sc, map),
+            typ,
+            span,
+        }) => resolve_var_dec(name, init, sc, typ, *span, map),
 
-        AstDeclaration::Fn(AstFnDec { body: Some(_), .. }) => Err(Error::LocalFnDecBody),
-        AstDeclaration::Fn(AstFnDec { sc, .. }) if *sc == Some(StorageClass::Static) => {
-            Err(Error::StaticBlockScopeFn)
+        AstDeclaration::Fn(AstFnDec {
+            body: Some(_),
+            span,
+            ..
+        }) => Err(Error::LocalFnDecBody(*span)),
+        AstDeclaration::Fn(AstFnDec { sc, span, .. }) if *sc == Some(StorageClass::Static) => {
+            Err(Error::StaticBlockScopeFn(*span))
         }
 
         AstDeclaration::Fn(AstFnDec {
@@ -223,14 +337,20 @@ fn resolve_declaration(dec: &mut AstDeclaration, map: &mut VarMap) -> Result<(),
             name,
             params,
             sc,
-            typ: _,
-        }) => resolve_fn_dec(name, &mut None, params, sc, map),
+            typ,
+            span,
+        }) => resolve_fn_dec(name, &mut None, params, sc, typ, *span, map),
+
+        AstDeclaration::Struct(dec) => resolve_struct_dec(dec, map),
+        AstDeclaration::Enum(dec) => resolve_enum_dec(dec, map),
+        AstDeclaration::Typedef(dec) => resolve_type(&mut dec.typ, dec.span, map),
     }
 }
 
-fn resolve_statement(statement: &mut AstStatement, map: &mut VarMap) -> Result<(), Error> {
+fn resolve_statement(statement: &mut AstStatement, map: &mut Scope) -> Result<(), Error> {
     match statement {
-        AstStatement::Ret(exp) => resolve_expression(exp, map),
+        AstStatement::Ret(Some(exp), _) => resolve_expression(exp, map),
+        AstStatement::Ret(None, _) => Ok(()),
         AstStatement::Null => Ok(()),
         AstStatement::Exp(exp) => resolve_expression(exp, map),
         AstStatement::If {
@@ -245,6 +365,14 @@ fn resolve_statement(statement: &mut AstStatement, map: &mut VarMap) -> Result<(
             }
             Ok(())
         }
+        AstStatement::Label {
+            label: parse::Label::Case(value),
+            body,
+            ..
+        } => {
+            resolve_expression(value, map)?;
+            resolve_statement(body, map)
+        }
         AstStatement::Label { body, .. } => resolve_statement(body, map),
         AstStatement::Compound(block) => {
             let mut new_scope = new_scope(map);
@@ -286,43 +414,172 @@ fn resolve_statement(statement: &mut AstStatement, map: &mut VarMap) -> Result<(
             resolve_statement(body, map)
         }
 
-        AstStatement::Goto(_) | AstStatement::Break | AstStatement::Continue => Ok(()),
+        AstStatement::Goto(..) | AstStatement::Break(_) | AstStatement::Continue(_) => Ok(()),
     }
 }
 
-fn new_scope(map: &VarMap) -> VarMap {
+fn new_scope(map: &Scope) -> Scope {
     let mut new_scope = map.clone();
-    for var in new_scope.values_mut() {
+    for var in new_scope.vars.values_mut() {
         var.from_current_block = false;
     }
+    for tag in new_scope.tags.values_mut() {
+        tag.from_current_block = false;
+    }
     new_scope
 }
 
-fn resolve_init(init: &mut Option<AstForInit>, map: &mut VarMap) -> Result<(), Error> {
-    match init {
+// a definition or a bare `struct s;` always declares the tag in the current block, even if
+// there's one with the same name further out
+fn resolve_struct_dec(dec: &mut StructDec, map: &mut Scope) -> Result<(), Error> {
+    dec.tag = match map.tags.get(dec.tag) {
+        Some(tag) if tag.from_current_block && tag.union != dec.union => {
+            return Err(Error::WrongTagKind {
+                span: dec.span,
+                previous: tag.span,
+            });
+        }
+        Some(tag) if tag.from_current_block => tag.name,
+        _ => declare_tag(dec.tag, dec.union, dec.span, map),
+    };
+    for member in dec.members.iter_mut().flatten() {
+        resolve_type(&mut member.typ, member.span, map)?;
+    }
+    Ok(())
+}
+
+// gives every struct in the type its unique tag, one we haven't seen yet gets declared right here
+fn resolve_type(typ: &mut VarType, span: Span, map: &mut Scope) -> Result<(), Error> {
+    *typ = match *typ {
+        VarType::Pointer(inner) => {
+            let mut inner = *inner;
+            resolve_type(&mut inner, span, map)?;
+            inner.pointer_to()
+        }
+        VarType::Array(inner, len) => {
+            let mut inner = *inner;
+            resolve_type(&mut inner, span, map)?;
+            inner.array_of(len)
+        }
+        VarType::IncompleteArray(inner, len) => {
+            let mut inner = *inner;
+            resolve_type(&mut inner, span, map)?;
+            if let Some(len) = len {
+                len.resolve(|exp| resolve_expression(exp, map))?;
+            }
+            inner.incomplete_array(len)
+        }
+        VarType::Qualified(inner, qualifiers) => {
+            let mut inner = *inner;
+            resolve_type(&mut inner, span, map)?;
+            inner.qualified(qualifiers)
+        }
+        VarType::Function(fn_type) => {
+            let mut fn_type = fn_type.clone();
+            resolve_type(&mut fn_type.ret, span, map)?;
+            for param in &mut fn_type.params {
+                resolve_type(param, span, map)?;
+            }
+            VarType::function(fn_type)
+        }
+        // already unique
+        VarType::Struct(VA_LIST_TAG) => return Ok(()),
+        VarType::Struct(tag) | VarType::Union(tag) => {
+            let union = matches!(typ, VarType::Union(_));
+            let name = match map.tags.get(tag) {
+                Some(previous) if previous.union != union => {
+                    return Err(Error::WrongTagKind {
+                        span,
+                        previous: previous.span,
+                    });
+                }
+                Some(previous) => previous.name,
+                None => declare_tag(tag, union, span, map),
+            };
+            if union {
+                VarType::Union(name)
+            } else {
+                VarType::Struct(name)
+            }
+        }
+        _ => return Ok(()),
+    };
+    Ok(())
+}
+
+fn resolve_fn_type(
+    typ: &mut FnType,
+    params: &mut super::ParamList,
+    span: Span,
+    map: &mut Scope,
+) -> Result<(), Error> {
+    resolve_type(&mut typ.ret, span, map)?;
+    // the parameters keep their qualifiers, the function's type doesn't
+    for (typ, param) in typ.params.iter_mut().zip(params.iter_mut()) {
+        resolve_type(&mut param.typ, param.span, map)?;
+        *typ = param.typ.unqualified();
+    }
+    Ok(())
+}
+
+fn declare_tag(tag: &'static str, union: bool, span: Span, map: &mut Scope) -> &'static str {
+    let name = crate::lex::intern(&format!(
+        "{tag}.{number}",
+        number = COUNTER.fetch_add(1, Ordering::SeqCst)
+    ));
+    map.tags.insert(
+        tag,
+        Tag {
+            name,
+            union,
+            from_current_block: true,
+            span,
+        },
+    );
+    name
+}
+
+fn resolve_init(init: &mut Option<Box<AstForInit>>, map: &mut Scope) -> Result<(), Error> {
+    match init.as_deref_mut() {
         None => Ok(()),
-        Some(AstForInit::D(dec)) => resolve_var_dec(&mut dec.name, &mut dec.init, &mut dec.sc, map),
+        Some(AstForInit::D(dec)) => resolve_var_dec(
+            &mut dec.name,
+            &mut dec.init,
+            &mut dec.sc,
+            &mut dec.typ,
+            dec.span,
+            map,
+        ),
         Some(AstForInit::E(exp)) => resolve_expression(exp, map),
     }
 }
 
-fn resolve_expression(exp: &mut AstExpression, map: &mut VarMap) -> Result<(), Error> {
+// enumerators are spelled like variables, but they're values and can't be assigned to
+fn lvalue(exp: &AstExpression, map: &Scope) -> bool {
     match exp {
-        AstExpression::Assignment { dst, src } => {
-            if dst.lvalue() {
-                resolve_expression(dst, map)?;
-                resolve_expression(src, map)
-            } else {
-                Err(Error::InvalidLval)
+        AstExpression::Var(name, _) => map.vars.get(name).is_none_or(|var| !var.enumerator),
+        AstExpression::Nested(exp, _) => lvalue(exp, map),
+        exp => exp.lvalue(),
+    }
+}
+
+fn resolve_expression(exp: &mut AstExpression, map: &mut Scope) -> Result<(), Error> {
+    match exp {
+        AstExpression::Assignment { dst, src, .. } => {
+            if !lvalue(dst, map) {
+                return Err(Error::InvalidLval(dst.span()));
             }
+            resolve_expression(dst, map)?;
+            resolve_expression(src, map)
         }
         AstExpression::Bin(AstBinary {
             left,
             right,
             operator,
+            ..
         }) if operator.compound() => {
-            if !left.lvalue() {
-                return Err(Error::InvalidLval);
+            if !lvalue(left, map) {
+                return Err(Error::InvalidLval(left.span()));
             }
             resolve_expression(left, map)?;
             resolve_expression(right, map)
@@ -332,6 +589,7 @@ fn resolve_expression(exp: &mut AstExpression, map: &mut VarMap) -> Result<(), E
             condition,
             r#true,
             r#false,
+            ..
         } => {
             resolve_expression(condition, map)?;
             resolve_expression(r#true, map)?;
@@ -342,37 +600,69 @@ fn resolve_expression(exp: &mut AstExpression, map: &mut VarMap) -> Result<(), E
             resolve_expression(left, map)?;
             resolve_expression(right, map)
         }
-        AstExpression::IncDec { op: _, exp } => {
-            resolve_expression(exp, map)?;
-            if exp.lvalue() {
-                Ok(())
-            } else {
-                Err(Error::InvalidLval)
+        AstExpression::IncDec { op: _, exp, .. } => {
+            if !lvalue(exp, map) {
+                return Err(Error::InvalidLval(exp.span()));
             }
+            resolve_expression(exp, map)
         }
 
-        AstExpression::Nested(inner) => resolve_expression(inner, map),
-        AstExpression::Var(var) => match map.get(var) {
+        AstExpression::Nested(inner, _) => resolve_expression(inner, map),
+        AstExpression::Deref(inner, _) => resolve_expression(inner, map),
+        AstExpression::Subscript { array, index, .. } => {
+            resolve_expression(array, map)?;
+            resolve_expression(index, map)
+        }
+        AstExpression::AddrOf(inner, _) => {
+            if !lvalue(inner, map) {
+                return Err(Error::AddrOfRvalue(inner.span()));
+            }
+            resolve_expression(inner, map)
+        }
+        AstExpression::Dot { exp, .. } | AstExpression::Arrow { exp, .. } => {
+            resolve_expression(exp, map)
+        }
+        AstExpression::Var(var, span) => match map.vars.get(var) {
             Some(new_name) => {
                 *var = new_name.name.clone();
                 Ok(())
             }
-            None => Err(Error::UndeclaredVar),
+            None => Err(Error::UndeclaredVar(*span)),
         },
-        AstExpression::Const(_) => Ok(()),
+        AstExpression::Const(..) | AstExpression::String(..) => Ok(()),
         AstExpression::Unary(inner) => resolve_expression(&mut inner.exp, map),
-        AstExpression::FunctionCall { name, args } => {
-            if let Some(new_name) = map.get(name) {
-                *name = new_name.name.clone();
-                for arg in args {
-                    resolve_expression(arg, map)?;
-                }
-                Ok(())
-            } else {
-                Err(Error::UndeclaredFn)
+        AstExpression::FunctionCall { func, args, span } => {
+            if let AstExpression::Var(name, _) = &**func
+                && !map.vars.contains_key(name)
+            {
+                return Err(Error::UndeclaredFn(*span));
             }
+            resolve_expression(func, map)?;
+            for arg in args {
+                resolve_expression(arg, map)?;
+            }
+            Ok(())
+        }
+        AstExpression::Cast { target, exp, span }
+        | AstExpression::VaArg {
+            ap: exp,
+            target,
+            span,
+        } => {
+            resolve_type(target, *span, map)?;
+            resolve_expression(exp, map)
+        }
+        AstExpression::VaStart(ap, _) => resolve_expression(ap, map),
+        AstExpression::CompoundLiteral { ty, init, span } => {
+            resolve_type(ty, *span, map)?;
+            init.try_for_each(&mut |exp| resolve_expression(exp, map))
         }
-        AstExpression::Cast { target: _, exp } => resolve_expression(exp, map),
+        AstExpression::SizeOf(exp, _) => resolve_expression(exp, map),
+        AstExpression::SizeOfType(ty, span)
+        | AstExpression::AlignOf(ty, span)
+        | AstExpression::OffsetOf {
+            target: ty, span, ..
+        } => resolve_type(ty, *span, map),
     }
 }
 
@@ -386,11 +676,67 @@ fn new_var(name: &[u8]) -> Identifier {
 
 #[derive(Debug)]
 pub enum Error {
-    DuplicateDeclaration,
-    InvalidLval,
-    UndeclaredVar,
-    UndeclaredFn,
-    LocalFnDecBody,
-    ConflictingDec,
-    StaticBlockScopeFn,
+    DuplicateDeclaration { span: Span, previous: Span },
+    InvalidLval(Span),
+    UndeclaredVar(Span),
+    UndeclaredFn(Span),
+    LocalFnDecBody(Span),
+    ConflictingDec { span: Span, previous: Span },
+    StaticBlockScopeFn(Span),
+    AddrOfRvalue(Span),
+    WrongTagKind { span: Span, previous: Span },
+}
+
+impl Error {
+    pub const fn span(&self) -> Span {
+        match self {
+            Self::DuplicateDeclaration { span, .. }
+            | Self::InvalidLval(span)
+            | Self::UndeclaredVar(span)
+            | Self::UndeclaredFn(span)
+            | Self::LocalFnDecBody(span)
+            | Self::ConflictingDec { span, .. }
+            | Self::StaticBlockScopeFn(span)
+            | Self::AddrOfRvalue(span)
+            | Self::WrongTagKind { span, .. } => *span,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Error;
+    use crate::semantics::{self, checked};
+
+    fn error(source: &[u8]) -> Error {
+        match checked(source) {
+            Err(semantics::Error::Resolve(e)) => e,
+            Err(e) => panic!("{e:?}"),
+            Ok(_) => panic!("nothing wrong with {}", String::from_utf8_lossy(source)),
+        }
+    }
+
+    #[test]
+    fn enumerators() {
+        // they're constants in the same namespace as variables, which an inner scope can shadow
+        let source = b"enum e { A, B = A + 2 }; \
+                       int f(int v) { switch (v) { case A | B: return 1; } int A = 3; return A; }";
+        assert!(checked(source).is_ok());
+        assert!(matches!(
+            error(b"enum { A }; int A;"),
+            Error::ConflictingDec { .. }
+        ));
+        assert!(matches!(
+            error(b"enum { A, A };"),
+            Error::ConflictingDec { .. }
+        ));
+        assert!(matches!(
+            error(b"enum { A }; int f(void) { A = 1; return 0; }"),
+            Error::InvalidLval(_)
+        ));
+        assert!(matches!(
+            error(b"enum { A }; int *f(void) { return &A; }"),
+            Error::AddrOfRvalue(_)
+        ));
+    }
 }
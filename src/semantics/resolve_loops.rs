@@ -1,4 +1,4 @@
-use crate::lex::Constant;
+use crate::lex::Span;
 use std::sync::atomic::{AtomicUsize, Ordering};
 static LOOPS: AtomicUsize = AtomicUsize::new(0);
 use super::labeled::*;
@@ -9,12 +9,21 @@ use parse::BlockItem as AstBlockItem;
 
 #[derive(Debug)]
 pub enum Error {
-    Break,
-    Continue,
-    Switch,
-    Case,
-    DoubleDefault,
-    DupliCase,
+    Break(Span),
+    Continue(Span),
+    Switch(Span),
+    DoubleDefault { span: Span, previous: Span },
+}
+
+impl Error {
+    pub const fn span(&self) -> Span {
+        match self {
+            Self::Break(span)
+            | Self::Continue(span)
+            | Self::Switch(span)
+            | Self::DoubleDefault { span, .. } => *span,
+        }
+    }
 }
 
 fn new_label() -> LabelId {
@@ -37,11 +46,13 @@ fn label_declaration(dec: parse::Dec, cur_loop: &mut Scope) -> Result<Dec, Error
             init,
             sc,
             typ,
+            span,
         }) => Ok(Dec::Var(VarDec {
             name,
-            init: init.map(|x| x.into()),
+            init: init.map(|init| init.map(&mut Expr::from)),
             sc,
             typ,
+            span,
         })),
 
         parse::Dec::Fn(parse::FnDec {
@@ -50,12 +61,14 @@ fn label_declaration(dec: parse::Dec, cur_loop: &mut Scope) -> Result<Dec, Error
             body: None,
             sc,
             typ,
+            span,
         }) => Ok(Dec::Fn(FnDec {
             name,
             params,
             body: None,
             sc,
             typ,
+            span,
         })),
         parse::Dec::Fn(parse::FnDec {
             name,
@@ -63,6 +76,7 @@ fn label_declaration(dec: parse::Dec, cur_loop: &mut Scope) -> Result<Dec, Error
             body: Some(body),
             sc,
             typ,
+            span,
         }) => label_blocks(body, cur_loop).map(|body| {
             Dec::Fn(FnDec {
                 name,
@@ -70,8 +84,12 @@ fn label_declaration(dec: parse::Dec, cur_loop: &mut Scope) -> Result<Dec, Error
                 body: Some(body),
                 sc,
                 typ,
+                span,
             })
         }),
+        parse::Dec::Struct(dec) => Ok(Dec::Struct(dec)),
+        parse::Dec::Enum(dec) => Ok(Dec::Enum(dec)),
+        parse::Dec::Typedef(dec) => Ok(Dec::Typedef(dec)),
     }
 }
 
@@ -127,7 +145,7 @@ fn exp(exp: Option<parse::Expr>) -> Option<Expr> {
 fn for_stmnt(
     body: parse::Stmnt,
     post: Option<parse::Expr>,
-    r#init: Option<parse::ForInit>,
+    r#init: Option<Box<parse::ForInit>>,
     condition: Option<parse::Expr>,
     cur: &mut Scope,
 ) -> Result<Stmnt, Error> {
@@ -139,17 +157,19 @@ fn for_stmnt(
     cur.normal = prev_normal;
 
     let init = init
-        .map(|init| match init {
+        .map(|init| match *init {
             parse::ForInit::D(parse::VarDec {
                 name,
                 init,
                 sc,
                 typ,
+                span,
             }) => ForInit::D(VarDec {
                 name,
-                init: exp(init),
+                init: init.map(|init| init.map(&mut Expr::from)),
                 sc,
                 typ,
+                span,
             }),
             parse::ForInit::E(e) => ForInit::E(e.into()),
         })
@@ -180,26 +200,28 @@ fn label_statement(statement: parse::Stmnt, cur: &mut Scope) -> Result<Stmnt, Er
             r#else: e,
         } => if_stmnt(c, *t, e.map(|e| *e), cur),
 
-        parse::Stmnt::Break => match cur.cur() {
-            Some(cur) => Ok(Stmnt::Break(cur)),
-            None => Err(Error::Break),
+        parse::Stmnt::Break(span) => match cur.cur() {
+            Some(cur) => Ok(Stmnt::Break(cur, span)),
+            None => Err(Error::Break(span)),
         },
-        parse::Stmnt::Continue => match cur.normal {
-            Some(cur) => Ok(Stmnt::Continue(cur)),
-            None => Err(Error::Continue),
+        parse::Stmnt::Continue(span) => match cur.normal {
+            Some(cur) => Ok(Stmnt::Continue(cur, span)),
+            None => Err(Error::Continue(span)),
         },
 
         parse::Stmnt::Compound(block) => label_blocks(block, cur).map(Stmnt::Compound),
-        parse::Stmnt::Ret(e) => Ok(Stmnt::Ret(e.into())),
+        parse::Stmnt::Ret(e, span) => Ok(Stmnt::Ret(e.map(Into::into), span)),
         parse::Stmnt::Exp(e) => Ok(Stmnt::Exp(e.into())),
-        parse::Stmnt::Goto(g) => Ok(Stmnt::Goto(g)),
+        parse::Stmnt::Goto(g, span) => Ok(Stmnt::Goto(g, span)),
         parse::Stmnt::Label {
             label: AstLabel::Named(name),
             body,
+            span,
         } => label_statement(*body, cur).map(|body| {
             Ok(Stmnt::Label {
                 name: Label::Named(name),
                 body: Box::new(body),
+                span,
             })
         })?,
         // current plan is to pass a struct called scope which keeps the loop id for switch
@@ -208,12 +230,19 @@ fn label_statement(statement: parse::Stmnt, cur: &mut Scope) -> Result<Stmnt, Er
         parse::Stmnt::Label {
             label: parse::Label::Default,
             body,
+            span,
         } => {
             // borrowck is evil and won't let me copy the lable id properly so instead i'm doing
             // this evil thing
             let label = match cur.switch.as_mut() {
                 // this means we got default in an env with an alreadu defined default. Illegal.
-                Some(SwitchState { default: true, .. }) => Err(Error::DoubleDefault),
+                Some(SwitchState {
+                    default: Some(previous),
+                    ..
+                }) => Err(Error::DoubleDefault {
+                    span,
+                    previous: *previous,
+                }),
 
                 // this means we got
                 Some(SwitchState {
@@ -221,30 +250,35 @@ fn label_statement(statement: parse::Stmnt, cur: &mut Scope) -> Result<Stmnt, Er
                     cases: _,
                     label,
                 }) => {
-                    *default = true;
+                    *default = Some(span);
                     Ok(*label)
                 }
-                None => Err(Error::Switch),
+                None => Err(Error::Switch(span)),
             }?;
             let body = label_statement(*body, cur)?.into();
             Ok(Stmnt::Label {
                 name: Label::Default(label),
                 body,
+                span,
             })
         }
         parse::Stmnt::Label {
             label: AstLabel::Case(c),
             body,
+            span,
         } => {
             let Some(switch) = &mut cur.switch else {
-                return Err(Error::Switch);
+                return Err(Error::Switch(span));
             };
-            switch.cases.push(c);
+            // typecheck works out the values, once it knows the type of the switch
+            let index = switch.cases.len();
+            switch.cases.push(c.into());
             let body = label_statement(*body, cur)?.into();
             let id = cur.switch.as_ref().unwrap().label;
             Ok(Stmnt::Label {
-                name: Label::Case { c, id },
+                name: Label::Case { index, id },
                 body,
+                span,
             })
         }
 
@@ -252,23 +286,17 @@ fn label_statement(statement: parse::Stmnt, cur: &mut Scope) -> Result<Stmnt, Er
             let label = new_label();
             let prev_switch = cur.switch.replace(SwitchState::new(label));
             let body = label_statement(*body, cur)?.into();
-            let mut switch_info = if let Some(prev) = prev_switch {
+            let switch_info = if let Some(prev) = prev_switch {
                 cur.switch.replace(prev)
             } else {
                 cur.switch.take()
             }
             .unwrap();
-            switch_info.cases.sort();
-            if switch_info.cases.windows(2).any(|x| x[0] == x[1]) {
-                return Err(Error::DupliCase);
-            }
-            // make sure the cases are unique, hmm.
-            //
             Ok(Stmnt::Switch {
                 val: val.into(),
                 body,
                 cases: switch_info.cases.into(),
-                default: switch_info.default,
+                default: switch_info.default.is_some(),
                 label,
             })
         }
@@ -303,15 +331,17 @@ struct Scope {
 
 #[derive(Clone)]
 struct SwitchState {
-    default: bool,
-    cases: Vec<Constant>,
+    // where the `default:` was, if we've seen one
+    default: Option<Span>,
+    // in the order they appear, which is how their labels are numbered
+    cases: Vec<Expr>,
     label: LabelId,
 }
 
 impl SwitchState {
     const fn new(id: LabelId) -> Self {
         Self {
-            default: false,
+            default: None,
             cases: Vec::new(),
             label: id,
         }
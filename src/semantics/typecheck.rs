@@ -1,12 +1,17 @@
 use super::ast::label_prelude as ast;
 use super::ast::type_prelude::*;
+use super::const_eval::{evaluate, integer};
 use crate::parse::Bop;
 use crate::semantics::StorageClass;
 
-use crate::parse::{FnType, VarType};
+use crate::lex::Span;
+use crate::parse::{
+    Designator, EnumDec, Enumerator, FnType, Layout, StructDec, TypedefDec, VarType, VA_LIST_TAG,
+};
 use std::collections::HashMap;
 
 use std::collections::hash_map::Entry;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 pub type SymbolTable = HashMap<Identifier, Attr>;
 #[derive(Debug)]
@@ -15,97 +20,241 @@ pub enum Attr {
         typ: VarType,
         init: Option<InitialVal>,
         global: bool,
+        span: Span,
     },
     Automatic(VarType),
+    // read only data we made up ourselves, like string literals
+    Constant {
+        typ: VarType,
+        init: StaticInit,
+    },
     Fn {
         defined: bool,
         global: bool,
         typ: FnType,
+        // the definition if there is one, otherwise the latest declaration
+        span: Span,
     },
+    // only kept until typecheck is done with the constant expressions that use it
+    Enumerator(i32),
 }
 
 impl Attr {
     pub const fn global(&self) -> bool {
         match self {
             Self::Static { global, .. } | Self::Fn { global, .. } => *global,
-            Self::Automatic(_) => false,
+            Self::Automatic(_) | Self::Constant { .. } | Self::Enumerator(_) => false,
         }
     }
 
-    pub const fn var_type(&self) -> Result<&VarType, Error> {
+    // automatic variables are resolved away before anyone could conflict with them
+    pub const fn span(&self) -> Option<Span> {
         match self {
-            Self::Static { typ, .. } | Self::Automatic(typ) => Ok(typ),
-            Self::Fn { .. } => Err(Error::ExpectedVarType),
+            Self::Static { span, .. } | Self::Fn { span, .. } => Some(*span),
+            Self::Automatic(_) | Self::Constant { .. } | Self::Enumerator(_) => None,
         }
     }
 
-    pub const fn fn_type(&self) -> Result<&FnType, Error> {
+    pub const fn var_type(&self, span: Span) -> Result<&VarType, Error> {
+        match self {
+            Self::Static { typ, .. } | Self::Automatic(typ) | Self::Constant { typ, .. } => Ok(typ),
+            Self::Enumerator(_) => Ok(&VarType::Int),
+            Self::Fn { .. } => Err(Error::ExpectedVarType(span)),
+        }
+    }
+
+    pub const fn fn_type(&self, span: Span) -> Result<&FnType, Error> {
         match self {
             Self::Fn { typ, .. } => Ok(typ),
-            Self::Static { .. } | Self::Automatic(_) => Err(Error::ExpectedFnType),
+            Self::Static { .. }
+            | Self::Automatic(_)
+            | Self::Constant { .. }
+            | Self::Enumerator(_) => Err(Error::ExpectedFnType(span)),
         }
     }
 }
 
 use crate::lex::Constant;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 pub enum InitialVal {
     Tentative,
-    Initial(StaticInit),
+    // laid out in order, arrays are flattened into their elements
+    Initial(Arr<StaticInit>),
 }
 
 #[derive(Debug, Copy, Clone)]
 pub enum StaticInit {
     Int(i32),
     Long(i64),
+    UInt(u32),
+    ULong(u64),
+    Double(f64),
+    Char(i8),
+    UChar(u8),
+    // leaked like `VarType`s are, so this can stay Copy
+    String {
+        bytes: &'static [u8],
+        null_terminated: bool,
+    },
+    // the address of a string constant
+    Pointer(&'static str),
+    // the address of a function, or this many bytes into an object with static storage
+    Symbol(&'static str, i64),
+    // this many bytes of zeroes
+    Zero(usize),
 }
 
 impl std::fmt::Display for StaticInit {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            Self::Int(0) => f.write_str(".zero 4"),
-            Self::Long(0) => f.write_str(".zero 8"),
+            _ if self.is_zero() => write!(f, ".zero {}", self.size()),
             Self::Int(i) => write!(f, ".long {i}"),
             Self::Long(i) => write!(f, ".quad {i}"),
+            Self::UInt(i) => write!(f, ".long {i}"),
+            Self::ULong(i) => write!(f, ".quad {i}"),
+            // the bit pattern, so nothing gets lost printing it
+            Self::Double(d) => write!(f, ".quad {}", d.to_bits()),
+            Self::Char(c) => write!(f, ".byte {c}"),
+            Self::UChar(c) => write!(f, ".byte {c}"),
+            Self::String {
+                bytes,
+                null_terminated,
+            } => {
+                let directive = if *null_terminated { "asciz" } else { "ascii" };
+                write!(f, ".{directive} \"")?;
+                // the assembler understands octal escapes, so use them for anything unusual
+                for &byte in *bytes {
+                    match byte {
+                        b'"' | b'\\' => write!(f, "\\{}", byte as char)?,
+                        b' '..=b'~' => write!(f, "{}", byte as char)?,
+                        _ => write!(f, "\\{byte:03o}")?,
+                    }
+                }
+                f.write_str("\"")
+            }
+            // string constants have local labels, see `codegen::assembly::LocalLabel`
+            Self::Pointer(name) => match crate::Target::current() {
+                crate::Target::Darwin => write!(f, ".quad L{name}"),
+                crate::Target::Linux => write!(f, ".quad .L{name}"),
+            },
+            Self::Symbol(name, offset) => {
+                match crate::Target::current() {
+                    crate::Target::Darwin => write!(f, ".quad _{name}")?,
+                    crate::Target::Linux => write!(f, ".quad {name}")?,
+                }
+                match offset {
+                    0 => Ok(()),
+                    offset => write!(f, "{offset:+}"),
+                }
+            }
+            Self::Zero(n) => write!(f, ".zero {n}"),
         }
     }
 }
 
-impl From<Constant> for StaticInit {
-    fn from(c: Constant) -> Self {
+impl StaticInit {
+    pub const fn size(&self) -> usize {
+        match self {
+            Self::Char(_) | Self::UChar(_) => 1,
+            Self::Int(_) | Self::UInt(_) => 4,
+            Self::Long(_)
+            | Self::ULong(_)
+            | Self::Double(_)
+            | Self::Pointer(_)
+            | Self::Symbol(..) => 8,
+            Self::String {
+                bytes,
+                null_terminated,
+            } => bytes.len() + *null_terminated as usize,
+            Self::Zero(n) => *n,
+        }
+    }
+
+    // -0.0 isn't all zeroes, so it can't go in .bss
+    pub const fn is_zero(&self) -> bool {
+        match self {
+            Self::Double(d) => d.to_bits() == 0,
+            _ => matches!(
+                self,
+                Self::Char(0)
+                    | Self::UChar(0)
+                    | Self::Int(0)
+                    | Self::Long(0)
+                    | Self::UInt(0)
+                    | Self::ULong(0)
+                    | Self::Zero(_)
+            ),
+        }
+    }
+
+    pub const fn from_constant(c: Constant) -> Self {
         match c {
             Constant::Int(i) => Self::Int(i),
-
             Constant::Long(i) => Self::Long(i),
+            Constant::UInt(i) => Self::UInt(i),
+            Constant::ULong(i) => Self::ULong(i),
+            Constant::Double(d) => Self::Double(d),
+            Constant::Char(c) => Self::Char(c),
+            Constant::UChar(c) => Self::UChar(c),
         }
     }
-}
 
-impl InitialVal {
-    pub const fn get_static(&self, ty: VarType) -> StaticInit {
-        match (self, ty) {
-            (InitialVal::Initial(s), _) => *s,
-            (InitialVal::Tentative, VarType::Int) => StaticInit::Int(0),
-            (InitialVal::Tentative, VarType::Long) => StaticInit::Long(0),
+    // the same value once it's been converted to the scalar type `ty`, which wraps around the
+    // same way a cast at runtime would
+    pub const fn cast(self, ty: VarType) -> Self {
+        let value = match (self, ty) {
+            (Self::Double(d), VarType::Double) => return Self::Double(d),
+            (Self::Double(d), VarType::ULong) => return Self::ULong(d as u64),
+            (Self::Double(d), _) => d as i64,
+            (Self::ULong(u), VarType::Double) => return Self::Double(u as f64),
+            (Self::Int(i), _) => i as i64,
+            (Self::Long(l), _) => l,
+            (Self::UInt(u), _) => u as i64,
+            (Self::ULong(u), _) => u as i64,
+            (Self::Char(c), _) => c as i64,
+            (Self::UChar(c), _) => c as i64,
+            (Self::Zero(_), _) => 0,
+            (Self::String { .. } | Self::Pointer(_) | Self::Symbol(..), _) => {
+                panic!("addresses aren't arithmetic")
+            }
+        };
+        match ty.unqualified() {
+            VarType::Char | VarType::SChar => Self::Char(value as i8),
+            VarType::UChar => Self::UChar(value as u8),
+            VarType::Int => Self::Int(value as i32),
+            VarType::Long | VarType::Pointer(_) => Self::Long(value),
+            VarType::UInt => Self::UInt(value as u32),
+            VarType::ULong => Self::ULong(value as u64),
+            VarType::Double => Self::Double(value as f64),
+            VarType::Array(..)
+            | VarType::IncompleteArray(..)
+            | VarType::Struct(_)
+            | VarType::Union(_) => panic!("aggregates aren't scalars"),
+            VarType::Void => panic!("nothing is void"),
+            VarType::Function(_) => panic!("functions aren't scalars"),
+            VarType::Qualified(..) => unreachable!(),
         }
     }
+}
 
-    pub fn as_long(&self) -> i64 {
-        match self {
-            Self::Initial(StaticInit::Long(i)) => *i,
-            Self::Initial(StaticInit::Int(i)) => (*i).into(),
-            Self::Tentative => 0,
-        }
+impl From<Constant> for StaticInit {
+    fn from(c: Constant) -> Self {
+        Self::from_constant(c)
     }
+}
 
-    pub const fn as_int(&self) -> i32 {
+impl InitialVal {
+    pub fn get_static(&self, ty: VarType) -> Arr<StaticInit> {
         match self {
-            Self::Initial(StaticInit::Int(i)) => *i,
-            Self::Initial(StaticInit::Long(i)) => *i as i32,
-            Self::Tentative => 0,
+            Self::Initial(init) => init.clone(),
+            Self::Tentative => Box::new([StaticInit::Zero(ty.size())]),
         }
     }
+
+    fn zero(ty: VarType) -> Self {
+        Self::Initial(Box::new([StaticInit::Zero(ty.size())]))
+    }
 }
 
 // check FunctionDeclaration, VariableDeclaration,
@@ -116,6 +265,8 @@ pub fn typecheck(p: ast::Program) -> Result<(SymbolTable, Program), Error> {
     for dec in p {
         decs.push(top_level_declaration(dec, &mut table)?);
     }
+    // every use of an enumerator has been replaced by its value by now
+    table.retain(|_, attr| !matches!(attr, Attr::Enumerator(_)));
 
     Ok((table, decs.into()))
 }
@@ -124,6 +275,9 @@ fn top_level_declaration(dec: ast::Dec, table: &mut SymbolTable) -> Result<Dec,
     match dec {
         ast::Dec::Fn(f) => function_declaration(f, table, false).map(Dec::Fn),
         ast::Dec::Var(v) => top_level_var(v, table).map(Dec::Var),
+        ast::Dec::Struct(s) => struct_declaration(s, table).map(Dec::Struct),
+        ast::Dec::Enum(e) => enum_declaration(e, table).map(Dec::Enum),
+        ast::Dec::Typedef(t) => typedef_declaration(t, table).map(Dec::Typedef),
     }
 }
 
@@ -133,13 +287,18 @@ fn top_level_var(
         init,
         typ,
         sc,
+        span,
     }: ast::VarDec,
     table: &mut SymbolTable,
 ) -> Result<VarDec, Error> {
+    let mut typ = initialized_type(array_lengths(typ, table)?, init.as_ref(), span, table)?;
+    if sc != Some(StorageClass::Extern) || typ == VarType::Void {
+        check_complete(typ, span)?;
+    }
     let mut initial = if sc == Some(StorageClass::Extern) {
-        extern_initializer(&init)?
+        extern_initializer(&init, typ, table)?
     } else {
-        Some(top_level_initializer(init.as_ref())?)
+        Some(top_level_initializer(init.as_ref(), typ, table)?)
     };
 
     // we're global unless static
@@ -151,15 +310,21 @@ fn top_level_var(
         init: old_init,
         global: old_global,
         typ: old_type,
+        span: previous,
     }) = table_entry
     {
-        check_linkage(&mut global, *old_global, &sc)?;
-        check_initializer_conflict(old_init, &mut initial)?;
-        if typ != *old_type {
-            return Err(Error::ConflictingType);
-        }
-    } else if table_entry.is_some() {
-        return Err(Error::ConflictingType);
+        let previous = *previous;
+        check_linkage(&mut global, *old_global, &sc, span, previous)?;
+        check_initializer_conflict(old_init, &mut initial, span, previous)?;
+        typ = composite(typ, *old_type).ok_or(Error::ConflictingType {
+            span,
+            previous: Some(previous),
+        })?;
+    } else if let Some(attr) = table_entry {
+        return Err(Error::ConflictingType {
+            span,
+            previous: attr.span(),
+        });
     };
 
     table.insert(
@@ -168,51 +333,540 @@ fn top_level_var(
             init: initial,
             global,
             typ,
+            span,
         },
     );
 
     let init = init
-        .map(|init| typecheck_expression(init, table))
+        .map(|init| typecheck_initializer(init, typ, false, table))
         .transpose()?;
     Ok(VarDec {
         name,
         init,
         sc,
         typ,
+        span,
     })
 }
 
-fn extern_initializer(init: &Option<ast::Expr>) -> Result<Option<InitialVal>, Error> {
+fn extern_initializer(
+    init: &Option<ast::Initializer>,
+    typ: VarType,
+    table: &mut SymbolTable,
+) -> Result<Option<InitialVal>, Error> {
     init.as_ref()
-        .map(|expr| {
-            expr.static_init()
-                .map(InitialVal::Initial)
-                .ok_or(Error::NotConstInitialized)
-        })
+        .map(|init| static_initializer(init, typ, table))
         .transpose()
 }
 
-fn top_level_initializer(init: Option<&ast::Expr>) -> Result<InitialVal, Error> {
+fn top_level_initializer(
+    init: Option<&ast::Initializer>,
+    typ: VarType,
+    table: &mut SymbolTable,
+) -> Result<InitialVal, Error> {
     if let Some(init) = init {
-        init.static_init()
-            .map(InitialVal::Initial)
-            .ok_or(Error::NotConstInitialized)
+        static_initializer(init, typ, table)
     } else {
         Ok(InitialVal::Tentative)
     }
 }
 
+fn static_initializer(
+    init: &ast::Initializer,
+    typ: VarType,
+    table: &mut SymbolTable,
+) -> Result<InitialVal, Error> {
+    let mut values = Vec::new();
+    flatten_static_init(init, typ, &mut values, table)?;
+    Ok(InitialVal::Initial(values.into()))
+}
+
+fn flatten_static_init(
+    init: &ast::Initializer,
+    typ: VarType,
+    values: &mut Vec<StaticInit>,
+    table: &mut SymbolTable,
+) -> Result<(), Error> {
+    let typ = typ.unqualified();
+    match (init, typ) {
+        (ast::Initializer::Single(ast::Expr::String(bytes, span)), VarType::Array(elem, len))
+            if elem.is_character() =>
+        {
+            check_string_len(bytes, typ, *span)?;
+            values.push(StaticInit::String {
+                bytes: Box::leak(bytes.clone()),
+                null_terminated: bytes.len() < len,
+            });
+            if len > bytes.len() + 1 {
+                values.push(StaticInit::Zero(len - bytes.len() - 1));
+            }
+            Ok(())
+        }
+        (ast::Initializer::Single(ast::Expr::String(bytes, span)), typ) => {
+            if !matches!(typ, VarType::Pointer(elem) if elem.unqualified() == VarType::Char) {
+                return Err(Error::IncompatibleTypes {
+                    span: *span,
+                    from: VarType::Char.array_of(bytes.len() + 1),
+                    to: typ,
+                });
+            }
+            let (name, _) = string_constant(bytes, table);
+            values.push(StaticInit::Pointer(Box::leak(name.to_string().into())));
+            Ok(())
+        }
+        (ast::Initializer::Compound(inits, _), VarType::Array(&elem, _)) => {
+            for init in positional(inits, typ, table)? {
+                match init {
+                    Some(init) => flatten_static_init(&init, elem, values, table)?,
+                    None => push_zero(values, elem.size()),
+                }
+            }
+            Ok(())
+        }
+        (ast::Initializer::Compound(inits, _), VarType::Struct(_) | VarType::Union(_)) => {
+            let members = &typ.layout().expect("declarations are complete").members;
+            // padding, and members that aren't mentioned, are zero
+            let mut offset = 0;
+            for (init, member) in positional(inits, typ, table)?.into_iter().zip(members) {
+                let Some(init) = init else {
+                    continue;
+                };
+                push_zero(values, member.offset - offset);
+                flatten_static_init(&init, member.typ, values, table)?;
+                offset = member.offset + member.typ.size();
+            }
+            push_zero(values, typ.size() - offset);
+            Ok(())
+        }
+        (ast::Initializer::Compound(_, span), typ) => Err(Error::BracedScalar {
+            span: *span,
+            ty: typ,
+        }),
+        (ast::Initializer::Designated(..), _) => unreachable!("`positional` follows designators"),
+        // a compound literal is just another way of writing the initializer
+        (ast::Initializer::Single(ast::Expr::CompoundLiteral { ty, init, .. }), typ)
+            if ty.unqualified() == typ =>
+        {
+            flatten_static_init(init, typ, values, table)
+        }
+        (ast::Initializer::Single(exp), VarType::Array(..)) => Err(Error::ArrayInitializer {
+            span: exp.span(),
+            ty: typ,
+        }),
+        (ast::Initializer::Single(exp), typ) => {
+            if let Some((name, from)) = static_literal(exp, table)? {
+                if !pointer_convertible(from, typ) {
+                    return Err(Error::IncompatibleTypes {
+                        span: exp.span(),
+                        from,
+                        to: typ,
+                    });
+                }
+                values.push(StaticInit::Symbol(Box::leak(name.to_string().into()), 0));
+                return Ok(());
+            }
+            let mut exp = typecheck_expression(exp.clone(), false, table)?;
+            convert_by_assignment(&mut exp, typ)?;
+            if typ.is_pointer()
+                && let Some((name, offset)) = address_constant(&exp, table)
+            {
+                values.push(StaticInit::Symbol(Box::leak(name.to_string().into()), offset));
+                return Ok(());
+            }
+            let value = evaluate(&exp, Error::NotConstInitialized)?;
+            values.push(StaticInit::from_constant(value).cast(typ));
+            Ok(())
+        }
+    }
+}
+
+// `&x`, `a`, `&a[1]`, `a + 1`, `&v.m` or `f`, where `x`, `a` and `v` have static storage, so it
+// points a constant number of bytes past a symbol the linker knows about
+fn address_constant(exp: &Expr, table: &SymbolTable) -> Option<(Identifier, i64)> {
+    match exp {
+        Expr::Nested { inner, .. } => address_constant(inner, table),
+        Expr::Cast { exp, ty, .. } if ty.is_pointer() => address_constant(exp, table),
+        Expr::AddrOf { exp, .. } => object_address(exp, table),
+        Expr::Binary {
+            left,
+            operator: operator @ (Bop::Add | Bop::Subtract),
+            right,
+            ty,
+            ..
+        } if ty.is_pointer() => {
+            let (name, offset) = address_constant(left, table)?;
+            let elements = integer(evaluate(right, Error::NotConstInitialized).ok()?) as i64;
+            let bytes = elements * ty.element()?.size() as i64;
+            match operator {
+                Bop::Add => Some((name, offset + bytes)),
+                _ => Some((name, offset - bytes)),
+            }
+        }
+        _ => None,
+    }
+}
+
+// where the object `exp` designates starts, if that's known before the program runs
+fn object_address(exp: &Expr, table: &SymbolTable) -> Option<(Identifier, i64)> {
+    match exp {
+        Expr::Nested { inner, .. } => object_address(inner, table),
+        Expr::Var { name, .. } => match table.get(name) {
+            Some(Attr::Static { .. } | Attr::Fn { .. }) => Some((name.clone(), 0)),
+            _ => None,
+        },
+        Expr::Deref { exp, .. } => address_constant(exp, table),
+        Expr::Subscript {
+            array, index, ty, ..
+        } => {
+            let (name, offset) = address_constant(array, table)?;
+            let index = integer(evaluate(index, Error::NotConstInitialized).ok()?) as i64;
+            Some((name, offset + index * ty.size() as i64))
+        }
+        Expr::Dot { exp, offset, .. } => {
+            object_address(exp, table).map(|(name, start)| (name, start + *offset as i64))
+        }
+        Expr::Arrow { exp, offset, .. } => {
+            address_constant(exp, table).map(|(name, start)| (name, start + *offset as i64))
+        }
+        _ => None,
+    }
+}
+
+// `&(struct p){1, 2}` or `(int[2]){1, 2}` outside of a function, where the compound literal is a
+// static object of its own
+fn static_literal(
+    exp: &ast::Expr,
+    table: &mut SymbolTable,
+) -> Result<Option<(Identifier, VarType)>, Error> {
+    let (exp, decayed) = match unparenthesized(exp) {
+        ast::Expr::AddrOf(exp, _) => (unparenthesized(exp), false),
+        exp => (exp, true),
+    };
+    let ast::Expr::CompoundLiteral { ty, init, span } = exp else {
+        return Ok(None);
+    };
+    let span = *span;
+    let ty = initialized_type(array_lengths(*ty, table)?, Some(init), span, table)?;
+    if decayed && !ty.is_array() {
+        return Ok(None);
+    }
+    check_complete(ty, span)?;
+    let init = static_initializer(init, ty, table)?;
+    let name = literal_name();
+    table.insert(
+        name.clone(),
+        Attr::Static {
+            typ: ty,
+            init: Some(init),
+            global: false,
+            span,
+        },
+    );
+    let pointer = match ty.unqualified() {
+        VarType::Array(&elem, _) if decayed => elem.qualified(ty.qualifiers()).pointer_to(),
+        _ => ty.pointer_to(),
+    };
+    Ok(Some((name, pointer)))
+}
+
+fn unparenthesized(exp: &ast::Expr) -> &ast::Expr {
+    match exp {
+        ast::Expr::Nested(exp, _) => unparenthesized(exp),
+        exp => exp,
+    }
+}
+
+fn literal_name() -> Identifier {
+    static LITERALS: AtomicUsize = AtomicUsize::new(0);
+    Identifier::from(format!(
+        "literal.{}",
+        LITERALS.fetch_add(1, Ordering::SeqCst)
+    ))
+}
+
+// what each element or member of an aggregate is initialized with once the designators have been
+// followed, and `None` for the ones that aren't mentioned
+fn positional(
+    inits: &[ast::Initializer],
+    ty: VarType,
+    table: &mut SymbolTable,
+) -> Result<Vec<Option<ast::Initializer>>, Error> {
+    let (len, union) = match ty {
+        VarType::Array(_, len) => (len, false),
+        // `int a[] = {1, 2}` is as long as its initializer makes it
+        VarType::IncompleteArray(..) => (usize::MAX, false),
+        _ => (
+            ty.layout()
+                .expect("declarations are complete")
+                .members
+                .len(),
+            matches!(ty, VarType::Union(_)),
+        ),
+    };
+    let mut slots = if ty.complete() {
+        vec![None; len]
+    } else {
+        Vec::new()
+    };
+    let mut next = 0;
+    for init in inits {
+        let (slot, init) = match init {
+            ast::Initializer::Designated(designators, init) => {
+                let [first, rest @ ..] = &designators[..] else {
+                    unreachable!("the parser only makes these with a designator")
+                };
+                let slot = designate(first, ty, table)?;
+                if slot >= slots.len() {
+                    slots.resize(slot + 1, None);
+                }
+                let init = if rest.is_empty() {
+                    (**init).clone()
+                } else {
+                    // `.a.b = 1` is `.a = {.b = 1}`, along with whatever else went into `.a`
+                    let nested = ast::Initializer::Designated(rest.into(), init.clone());
+                    match slots[slot].take() {
+                        Some(ast::Initializer::Compound(inits, span)) => {
+                            ast::Initializer::Compound(
+                                inits.into_iter().chain([nested]).collect(),
+                                span,
+                            )
+                        }
+                        _ => ast::Initializer::Compound(Box::new([nested]), init.span()),
+                    }
+                };
+                (slot, init)
+            }
+            init if next < len => {
+                if next == slots.len() {
+                    slots.push(None);
+                }
+                (next, init.clone())
+            }
+            init => {
+                return Err(Error::ExcessElements {
+                    span: init.span(),
+                    len,
+                    ty,
+                });
+            }
+        };
+        // a union only holds one member, and nothing comes after it
+        if union {
+            slots.fill(None);
+            next = len;
+        } else {
+            next = slot + 1;
+        }
+        slots[slot] = Some(init);
+    }
+    Ok(slots)
+}
+
+fn designate(
+    designator: &Designator<ast::Expr>,
+    ty: VarType,
+    table: &mut SymbolTable,
+) -> Result<usize, Error> {
+    match (designator, ty) {
+        (Designator::Index(index, span), VarType::Array(..) | VarType::IncompleteArray(..)) => {
+            let index = integer_value(index.clone(), table, Error::InvalidDesignator)?;
+            match (usize::try_from(index), ty) {
+                (Ok(index), VarType::Array(_, len)) if index >= len => {
+                    Err(Error::DesignatorRange {
+                        span: *span,
+                        len,
+                        ty,
+                    })
+                }
+                (Ok(index), _) => Ok(index),
+                (Err(_), _) => Err(Error::InvalidDesignator(*span)),
+            }
+        }
+        (Designator::Member(name, span), VarType::Struct(_) | VarType::Union(_)) => ty
+            .layout()
+            .expect("declarations are complete")
+            .members
+            .iter()
+            .position(|member| &*member.name == name.as_ref())
+            .ok_or(Error::NoMember {
+                span: *span,
+                ty,
+                member: name.clone(),
+            }),
+        (designator, ty) => Err(Error::WrongDesignator {
+            span: designator.span(),
+            ty,
+        }),
+    }
+}
+
+// runs of zeroes are merged, so `[100] = 1` doesn't need a hundred of them
+fn push_zero(values: &mut Vec<StaticInit>, len: usize) {
+    match values.last_mut() {
+        _ if len == 0 => (),
+        Some(StaticInit::Zero(zeroes)) => *zeroes += len,
+        _ => values.push(StaticInit::Zero(len)),
+    }
+}
+
+// only the first member of a union is zeroed
+fn zeroed_members(ty: VarType) -> &'static [crate::parse::layout::Member] {
+    let members = &ty.layout().expect("declarations are complete").members;
+    match ty {
+        VarType::Union(_) => &members[..1],
+        _ => members,
+    }
+}
+
+fn check_string_len(bytes: &[u8], typ: VarType, span: Span) -> Result<(), Error> {
+    if bytes.len() > typ.size() {
+        Err(Error::StringTooLong { span, ty: typ })
+    } else {
+        Ok(())
+    }
+}
+
+// string literals that aren't initializing a char array are anonymous arrays in read only data
+fn string_constant(bytes: &[u8], table: &mut SymbolTable) -> (Identifier, VarType) {
+    static STRINGS: AtomicUsize = AtomicUsize::new(0);
+    let name = Identifier::from(format!("string.{}", STRINGS.fetch_add(1, Ordering::SeqCst)));
+    let typ = VarType::Char.array_of(bytes.len() + 1);
+    let init = StaticInit::String {
+        bytes: Box::leak(bytes.into()),
+        null_terminated: true,
+    };
+    table.insert(name.clone(), Attr::Constant { typ, init });
+    (name, typ)
+}
+
+fn typecheck_initializer(
+    init: ast::Initializer,
+    typ: VarType,
+    variadic: bool,
+    table: &mut SymbolTable,
+) -> Result<Initializer, Error> {
+    let typ = typ.unqualified();
+    match (init, typ) {
+        (ast::Initializer::Single(ast::Expr::String(bytes, span)), VarType::Array(elem, _))
+            if elem.is_character() =>
+        {
+            check_string_len(&bytes, typ, span)?;
+            Ok(Initializer::Single(Expr::String {
+                value: bytes,
+                ty: typ,
+                span,
+            }))
+        }
+        (ast::Initializer::Compound(inits, span), VarType::Array(&elem, _)) => {
+            let mut typed = Vec::new();
+            for init in positional(&inits, typ, table)? {
+                typed.push(match init {
+                    Some(init) => typecheck_initializer(init, elem, variadic, table)?,
+                    // whatever isn't mentioned starts out as zero
+                    None => zero_initializer(elem, span),
+                });
+            }
+            Ok(Initializer::Compound(typed.into(), span))
+        }
+        (ast::Initializer::Compound(inits, span), VarType::Struct(_) | VarType::Union(_)) => {
+            let members = &typ.layout().expect("declarations are complete").members;
+            let mut slots = positional(&inits, typ, table)?;
+            // the members of a union all start at the same place, so the ones before the one
+            // that's initialized are zeroed and then written over
+            if let VarType::Union(_) = typ {
+                let end = slots.iter().rposition(Option::is_some).map_or(1, |m| m + 1);
+                slots.truncate(end);
+            }
+            let mut typed = Vec::with_capacity(slots.len());
+            for (init, member) in slots.into_iter().zip(members) {
+                typed.push(match init {
+                    Some(init) => typecheck_initializer(init, member.typ, variadic, table)?,
+                    None => zero_initializer(member.typ, span),
+                });
+            }
+            Ok(Initializer::Compound(typed.into(), span))
+        }
+        (ast::Initializer::Compound(_, span), typ) => Err(Error::BracedScalar { span, ty: typ }),
+        (ast::Initializer::Designated(..), _) => unreachable!("`positional` follows designators"),
+        (ast::Initializer::Single(exp), VarType::Array(..)) => Err(Error::ArrayInitializer {
+            span: exp.span(),
+            ty: typ,
+        }),
+        (ast::Initializer::Single(exp), typ) => {
+            let mut exp = typecheck_expression(exp, variadic, table)?;
+            convert_by_assignment(&mut exp, typ)?;
+            Ok(Initializer::Single(exp))
+        }
+    }
+}
+
+fn zero_initializer(typ: VarType, span: Span) -> Initializer {
+    let typ = typ.unqualified();
+    match typ {
+        VarType::Array(&elem, len) => Initializer::Compound(
+            (0..len).map(|_| zero_initializer(elem, span)).collect(),
+            span,
+        ),
+        VarType::Struct(_) | VarType::Union(_) => Initializer::Compound(
+            zeroed_members(typ)
+                .iter()
+                .map(|member| zero_initializer(member.typ, span))
+                .collect(),
+            span,
+        ),
+        VarType::Char | VarType::SChar => Initializer::Single(Expr::Const {
+            cnst: Constant::Char(0),
+            ty: typ,
+            span,
+        }),
+        VarType::UChar => Initializer::Single(Expr::Const {
+            cnst: Constant::UChar(0),
+            ty: typ,
+            span,
+        }),
+        VarType::Int => Initializer::Single(Expr::Const {
+            cnst: Constant::Int(0),
+            ty: typ,
+            span,
+        }),
+        VarType::UInt => Initializer::Single(Expr::Const {
+            cnst: Constant::UInt(0),
+            ty: typ,
+            span,
+        }),
+        VarType::ULong => Initializer::Single(Expr::Const {
+            cnst: Constant::ULong(0),
+            ty: typ,
+            span,
+        }),
+        VarType::Long | VarType::Pointer(_) => Initializer::Single(Expr::Const {
+            cnst: Constant::Long(0),
+            ty: typ,
+            span,
+        }),
+        VarType::Double => Initializer::Single(Expr::Const {
+            cnst: Constant::Double(0.0),
+            ty: typ,
+            span,
+        }),
+        VarType::Void | VarType::Function(_) => unreachable!("variables can't be void"),
+        VarType::IncompleteArray(..) | VarType::Qualified(..) => unreachable!(),
+    }
+}
+
 fn check_linkage(
     global: &mut bool,
     old_global: bool,
     sc: &Option<StorageClass>,
+    span: Span,
+    previous: Span,
 ) -> Result<(), Error> {
     // if it's extern, then we go with it, otherwise they should be the same
     if *sc == Some(StorageClass::Extern) {
         *global = old_global;
         Ok(())
     } else if *global != old_global {
-        Err(Error::ConflictingLinkage)
+        Err(Error::ConflictingLinkage { span, previous })
     } else {
         Ok(())
     }
@@ -221,15 +875,17 @@ fn check_linkage(
 fn check_initializer_conflict(
     old: &Option<InitialVal>,
     new: &mut Option<InitialVal>,
+    span: Span,
+    previous: Span,
 ) -> Result<(), Error> {
     match (&new, old) {
         // if they're both declared something's wrong, even if it's the same definition
         (Some(InitialVal::Initial(_)), Some(InitialVal::Initial(_))) => {
-            Err(Error::ConflictingDeclaration)
+            Err(Error::ConflictingDeclaration { span, previous })
         }
         // if we have an initial pass then we take it
         (Some(InitialVal::Initial(c)), _) | (_, Some(InitialVal::Initial(c))) => {
-            *new = Some(InitialVal::Initial(*c));
+            *new = Some(InitialVal::Initial(c.clone()));
             Ok(())
         }
         // if we have a tentative we take it
@@ -242,10 +898,198 @@ fn check_initializer_conflict(
     }
 }
 
-fn declaration(dec: ast::Dec, table: &mut SymbolTable) -> Result<Dec, Error> {
+fn declaration(dec: ast::Dec, variadic: bool, table: &mut SymbolTable) -> Result<Dec, Error> {
     match dec {
         ast::Dec::Fn(f) => function_declaration(f, table, true).map(Dec::Fn),
-        ast::Dec::Var(v) => variable_declaration(v, table).map(Dec::Var),
+        ast::Dec::Var(v) => variable_declaration(v, variadic, table).map(Dec::Var),
+        ast::Dec::Struct(s) => struct_declaration(s, table).map(Dec::Struct),
+        ast::Dec::Enum(e) => enum_declaration(e, table).map(Dec::Enum),
+        ast::Dec::Typedef(t) => typedef_declaration(t, table).map(Dec::Typedef),
+    }
+}
+
+// `resolve` gave every definition its own tag, so the layout only has to be worked out once
+fn struct_declaration(mut dec: StructDec, table: &mut SymbolTable) -> Result<StructDec, Error> {
+    for member in dec.members.iter_mut().flatten() {
+        member.typ = array_lengths(member.typ, table)?;
+    }
+    let Some(members) = &dec.members else {
+        return Ok(dec);
+    };
+    if let Some(previous) = dec.typ().layout() {
+        return Err(Error::StructRedefinition {
+            span: dec.span,
+            previous: previous.span,
+            ty: dec.typ(),
+        });
+    }
+    for (i, member) in members.iter().enumerate() {
+        if let Some(previous) = members[..i].iter().find(|m| m.name == member.name) {
+            return Err(Error::DuplicateMember {
+                span: member.span,
+                previous: previous.span,
+            });
+        }
+        check_complete(member.typ, member.span)?;
+    }
+    let members = members
+        .iter()
+        .map(|member| (member.name.as_ref().into(), member.typ));
+    Layout::define(dec.tag, Layout::new(members, dec.union, dec.span));
+    Ok(dec)
+}
+
+// every enumerator is an int, one more than the one before it unless it's given a value
+fn enum_declaration(dec: EnumDec, table: &mut SymbolTable) -> Result<EnumDec, Error> {
+    let mut next = Some(0);
+    for Enumerator { name, value, span } in &dec.enumerators {
+        if let Some(exp) = value {
+            let exp = ast::Expr::from(exp.clone());
+            next = i32::try_from(integer_value(exp, table, Error::ExpectedConstant)?).ok();
+        }
+        let value = next.ok_or(Error::EnumeratorRange(*span))?;
+        table.insert(name.clone(), Attr::Enumerator(value));
+        next = value.checked_add(1);
+    }
+    Ok(dec)
+}
+
+fn typedef_declaration(dec: TypedefDec, table: &mut SymbolTable) -> Result<TypedefDec, Error> {
+    Ok(TypedefDec {
+        typ: array_lengths(dec.typ, table)?,
+        ..dec
+    })
+}
+
+// `int a[] = {1, 2, 3}` takes its length from the initializer, and `char s[] = "hey"` has room
+// for the null
+fn initialized_type(
+    ty: VarType,
+    init: Option<&ast::Initializer>,
+    span: Span,
+    table: &mut SymbolTable,
+) -> Result<VarType, Error> {
+    match (ty, init) {
+        (
+            VarType::IncompleteArray(&elem, None),
+            Some(ast::Initializer::Single(ast::Expr::String(bytes, _))),
+        ) if elem.is_character() => Ok(elem.array_of(bytes.len() + 1)),
+        (VarType::IncompleteArray(&elem, None), Some(ast::Initializer::Compound(inits, _))) => {
+            match positional(inits, ty, table)?.len() {
+                0 => Err(Error::InvalidArraySize(span)),
+                len => Ok(elem.array_of(len)),
+            }
+        }
+        _ => Ok(ty),
+    }
+}
+
+// `extern int a[];` and `int a[3];` declare the same array, and it's the length that sticks
+fn composite(ty: VarType, old: VarType) -> Option<VarType> {
+    match (ty, old) {
+        _ if ty == old => Some(ty),
+        (VarType::IncompleteArray(elem, None), VarType::Array(old_elem, _)) if elem == old_elem => {
+            Some(old)
+        }
+        (VarType::Array(elem, _), VarType::IncompleteArray(old_elem, None)) if elem == old_elem => {
+            Some(ty)
+        }
+        _ => None,
+    }
+}
+
+// enumerator values, array lengths and designators have to be integer constant expressions,
+// `error` is what to report when `exp` isn't one
+fn integer_value(
+    exp: ast::Expr,
+    table: &mut SymbolTable,
+    error: fn(Span) -> Error,
+) -> Result<i128, Error> {
+    let exp = typecheck_expression(exp, false, table)?;
+    if !exp.ty().integer() {
+        return Err(error(exp.span()));
+    }
+    evaluate(&exp, error).map(integer)
+}
+
+// `ty` with the lengths of its arrays worked out, see `Length`
+fn array_lengths(ty: VarType, table: &mut SymbolTable) -> Result<VarType, Error> {
+    Ok(match ty {
+        VarType::Pointer(&inner) => array_lengths(inner, table)?.pointer_to(),
+        VarType::Array(&inner, len) => array_lengths(inner, table)?.array_of(len),
+        VarType::IncompleteArray(&inner, Some(len)) => {
+            let inner = array_lengths(inner, table)?;
+            inner.array_of(len.evaluate(|exp| array_length(exp, table))?)
+        }
+        VarType::IncompleteArray(&inner, None) => {
+            array_lengths(inner, table)?.incomplete_array(None)
+        }
+        VarType::Qualified(&inner, qualifiers) => {
+            array_lengths(inner, table)?.qualified(qualifiers)
+        }
+        VarType::Function(typ) => VarType::function(function_lengths(typ, table)?),
+        ty => ty,
+    })
+}
+
+fn function_lengths(typ: &FnType, table: &mut SymbolTable) -> Result<FnType, Error> {
+    Ok(FnType {
+        ret: array_lengths(typ.ret, table)?,
+        params: typ
+            .params
+            .iter()
+            .map(|&param| array_lengths(param, table))
+            .collect::<Result<_, _>>()?,
+        variadic: typ.variadic,
+    })
+}
+
+fn array_length(exp: crate::parse::Expr, table: &mut SymbolTable) -> Result<usize, Error> {
+    let span = exp.span();
+    let len = integer_value(ast::Expr::from(exp), table, Error::InvalidArraySize)?;
+    usize::try_from(len)
+        .ok()
+        .filter(|&len| len > 0)
+        .ok_or(Error::InvalidArraySize(span))
+}
+
+// the `ap` of `va_start` and `va_arg`, which is a `va_list` that has decayed to a pointer
+fn va_list(ap: ast::Expr, variadic: bool, table: &mut SymbolTable) -> Result<Box<Expr>, Error> {
+    let ap = check_boxed_expr(ap, variadic, table)?;
+    let expected = VarType::Struct(VA_LIST_TAG).pointer_to();
+    if ap.ty() == expected {
+        Ok(ap)
+    } else {
+        Err(Error::IncompatibleTypes {
+            span: ap.span(),
+            from: ap.ty(),
+            to: expected,
+        })
+    }
+}
+
+fn check_complete(ty: VarType, span: Span) -> Result<(), Error> {
+    if ty.complete() {
+        Ok(())
+    } else {
+        Err(Error::IncompleteType { span, ty })
+    }
+}
+
+// the type and offset of `member` in `ty`
+// the member of a const struct is const too
+fn member(ty: VarType, member: &Identifier, span: Span) -> Result<(VarType, usize), Error> {
+    if !ty.is_struct() {
+        return Err(Error::NotAStruct { span, ty });
+    }
+    let layout = ty.layout().ok_or(Error::IncompleteType { span, ty })?;
+    match layout.member(member.as_ref()) {
+        Some(member) => Ok((member.typ.qualified(ty.qualifiers()), member.offset)),
+        None => Err(Error::NoMember {
+            span,
+            ty,
+            member: member.clone(),
+        }),
     }
 }
 
@@ -255,75 +1099,70 @@ fn variable_declaration(
         init,
         typ,
         sc,
+        span,
     }: ast::VarDec,
+    variadic: bool,
     table: &mut SymbolTable,
 ) -> Result<VarDec, Error> {
+    let typ = initialized_type(array_lengths(typ, table)?, init.as_ref(), span, table)?;
+    if sc != Some(StorageClass::Extern) || typ == VarType::Void {
+        check_complete(typ, span)?;
+    }
     let init = match (sc, &init) {
-        (Some(StorageClass::Extern), Some(_)) => return Err(Error::DeclaredExtern),
+        (Some(StorageClass::Extern), Some(_)) => return Err(Error::DeclaredExtern(span)),
         (Some(StorageClass::Extern), None) => {
             match table.entry(name.clone()) {
-                Entry::Occupied(e) => {
-                    match e.get() {
-                        Attr::Fn { .. } => return Err(Error::FnAsVar),
-                        Attr::Static { typ: old_type, .. } | Attr::Automatic(old_type) => {
-                            if *old_type != typ {
-                                return Err(Error::ConflictingType);
-                            }
-                        }
+                Entry::Occupied(e) => match e.get() {
+                    Attr::Fn { span: previous, .. } => {
+                        return Err(Error::FnAsVar {
+                            span,
+                            previous: *previous,
+                        })
                     }
-                    if let Attr::Fn { .. } = e.get() {
-                        return Err(Error::FnAsVar);
+                    attr @ (Attr::Static { typ: old_type, .. }
+                    | Attr::Automatic(old_type)
+                    | Attr::Constant { typ: old_type, .. }) => {
+                        if composite(typ, *old_type).is_none() {
+                            return Err(Error::ConflictingType {
+                                span,
+                                previous: attr.span(),
+                            });
+                        }
                     }
-                }
+                    Attr::Enumerator(_) => unreachable!("enumerators get names of their own"),
+                },
                 Entry::Vacant(e) => {
                     e.insert(Attr::Static {
                         init: None,
                         global: true,
                         typ,
+                        span,
                     });
                 }
             }
             None
         }
-        (Some(StorageClass::Static), Some(ast::Expr::Const(c))) => {
-            table.insert(
-                name.clone(),
-                Attr::Static {
-                    init: Some(InitialVal::Initial(StaticInit::from(*c))),
-                    global: false,
-                    typ,
-                },
-            );
-            Some(Expr::Const {
-                cnst: *c,
-                ty: VarType::Int,
-            })
-        }
-        (Some(StorageClass::Static), Some(_)) => return Err(Error::NotConstInitialized),
-        (Some(StorageClass::Static), None) => {
+        (Some(StorageClass::Static), _) => {
+            let initial = match &init {
+                Some(init) => static_initializer(init, typ, table)?,
+                None => InitialVal::zero(typ),
+            };
             table.insert(
                 name.clone(),
                 Attr::Static {
-                    init: Some(InitialVal::Initial(StaticInit::Long(0))),
+                    init: Some(initial),
                     global: false,
                     typ,
+                    span,
                 },
             );
-
-            Some(Expr::Const {
-                cnst: Constant::Long(0),
-                ty: VarType::Int,
-            })
+            init.map(|init| typecheck_initializer(init, typ, variadic, table))
+                .transpose()?
         }
         (None, _) => {
             table.insert(name.clone(), Attr::Automatic(typ));
-            if let Some(init) = init {
-                let mut exp = typecheck_expression(init, table)?;
-                convert_to(&mut exp, &typ);
-                Some(exp)
-            } else {
-                None
-            }
+            init.map(|init| typecheck_initializer(init, typ, variadic, table))
+                .transpose()?
         }
     };
     Ok(VarDec {
@@ -331,93 +1170,507 @@ fn variable_declaration(
         init,
         sc,
         typ,
+        span,
     })
 }
 
-fn check_boxed_expr(expression: ast::Expr, table: &mut SymbolTable) -> Result<Box<Expr>, Error> {
-    typecheck_expression(expression, table).map(Box::new)
+fn check_boxed_expr(
+    expression: ast::Expr,
+    variadic: bool,
+    table: &mut SymbolTable,
+) -> Result<Box<Expr>, Error> {
+    typecheck_expression(expression, variadic, table).map(Box::new)
+}
+
+// arrays decay into a pointer to their first element everywhere except under `&`, and on the left
+// of an assignment where they aren't allowed anyway
+fn typecheck_expression(
+    expression: ast::Expr,
+    variadic: bool,
+    table: &mut SymbolTable,
+) -> Result<Expr, Error> {
+    let exp = typecheck_without_decay(expression, variadic, table)?;
+    if exp.ty() == VarType::Void {
+        return Err(Error::VoidValue(exp.span()));
+    }
+    decay(exp)
+}
+
+// expression statements, the ends of a `for` header and casts to void throw the value away, so
+// they're the only places a void expression can go
+fn typecheck_discarded(
+    expression: ast::Expr,
+    variadic: bool,
+    table: &mut SymbolTable,
+) -> Result<Expr, Error> {
+    let exp = typecheck_without_decay(expression, variadic, table)?;
+    if exp.ty() == VarType::Void {
+        Ok(exp)
+    } else {
+        decay(exp)
+    }
+}
+
+// the value read out of an object doesn't keep the object's qualifiers
+fn decay(mut exp: Expr) -> Result<Expr, Error> {
+    // a function designator becomes a pointer to the function
+    if exp.ty().is_function() {
+        return Ok(Expr::AddrOf {
+            ty: exp.ty().pointer_to(),
+            span: exp.span(),
+            exp: Box::new(exp),
+        });
+    }
+    // all that's missing from `extern int a[]` is how long it is, it still decays
+    let complete = match exp.ty() {
+        VarType::IncompleteArray(elem, _) => elem.complete(),
+        ty => ty.complete(),
+    };
+    if !complete {
+        return Err(Error::IncompleteType {
+            span: exp.span(),
+            ty: exp.ty(),
+        });
+    }
+    let ty = exp.ty_mut();
+    *ty = ty.unqualified();
+    match exp.ty() {
+        VarType::Array(&elem, _) | VarType::IncompleteArray(&elem, _) => Ok(Expr::AddrOf {
+            ty: elem.pointer_to(),
+            span: exp.span(),
+            exp: Box::new(exp),
+        }),
+        _ => Ok(exp),
+    }
+}
+
+// something that's about to be assigned to
+fn typecheck_lvalue(
+    exp: ast::Expr,
+    variadic: bool,
+    table: &mut SymbolTable,
+) -> Result<Box<Expr>, Error> {
+    let mut exp = typecheck_without_decay(exp, variadic, table)?;
+    if exp.ty().is_function() {
+        return Err(Error::FnAssignment {
+            span: exp.span(),
+            ty: exp.ty(),
+        });
+    }
+    if exp.ty().is_array() {
+        return Err(Error::ArrayAssignment {
+            span: exp.span(),
+            ty: exp.ty(),
+        });
+    }
+    check_complete(exp.ty(), exp.span())?;
+    if has_const(exp.ty()) {
+        return Err(Error::ConstAssignment {
+            span: exp.span(),
+            ty: exp.ty(),
+        });
+    }
+    let ty = exp.ty_mut();
+    *ty = ty.unqualified();
+    Ok(Box::new(exp))
+}
+
+// a struct with a const member can't be assigned to as a whole either
+fn has_const(ty: VarType) -> bool {
+    match ty.unqualified() {
+        _ if ty.qualifiers().r#const => true,
+        VarType::Array(&elem, _) => has_const(elem),
+        VarType::Struct(_) | VarType::Union(_) => ty
+            .layout()
+            .is_some_and(|layout| layout.members.iter().any(|member| has_const(member.typ))),
+        _ => false,
+    }
+}
+
+// anything that gets compared against zero
+fn typecheck_condition(
+    exp: ast::Expr,
+    variadic: bool,
+    table: &mut SymbolTable,
+) -> Result<Expr, Error> {
+    let exp = typecheck_expression(exp, variadic, table)?;
+    check_scalar(&exp)?;
+    Ok(exp)
+}
+
+fn check_scalar(exp: &Expr) -> Result<(), Error> {
+    if exp.ty().scalar() {
+        Ok(())
+    } else {
+        Err(Error::InvalidOperand {
+            span: exp.span(),
+            ty: exp.ty(),
+        })
+    }
 }
 
-fn typecheck_expression(expression: ast::Expr, table: &mut SymbolTable) -> Result<Expr, Error> {
+fn typecheck_without_decay(
+    expression: ast::Expr,
+    variadic: bool,
+    table: &mut SymbolTable,
+) -> Result<Expr, Error> {
     match expression {
-        ast::Expr::FunctionCall { name, args } => typecheck_fn_call(name, args, table),
-        ast::Expr::Var(name) => typecheck_var(name, table),
-        ast::Expr::Assignment { dst, src } => {
-            let dst = check_boxed_expr(*dst, table)?;
-            let mut src = check_boxed_expr(*src, table)?;
+        ast::Expr::FunctionCall { func, args, span } => {
+            typecheck_fn_call(*func, args, span, variadic, table)
+        }
+        ast::Expr::Var(name, span) => typecheck_var(name, span, table),
+        ast::Expr::Assignment { dst, src, span } => {
+            let dst = typecheck_lvalue(*dst, variadic, table)?;
+            let mut src = check_boxed_expr(*src, variadic, table)?;
 
             let ty = dst.ty();
-            convert_to(&mut src, &dst.ty());
-            Ok(Expr::Assignment { dst, src, ty })
+            convert_by_assignment(&mut src, ty)?;
+            Ok(Expr::Assignment { dst, src, ty, span })
         }
         ast::Expr::Binary {
             left,
             right,
             operator,
+            span,
         } => {
+            // the left side is only there for its side effects
+            if operator == Bop::Comma {
+                let left = Box::new(typecheck_discarded(*left, variadic, table)?);
+                let right = check_boxed_expr(*right, variadic, table)?;
+                return Ok(Expr::Binary {
+                    ty: right.ty(),
+                    left,
+                    operator,
+                    right,
+                    span,
+                });
+            }
             // if it's relational or logical and or logical or then it's gonna be int
-            let mut left = typecheck_expression(*left, table).map(Box::from)?;
-            let mut right = typecheck_expression(*right, table).map(Box::from)?;
-            // result of this expression is an int
-            let Some(ty) = left.ty().common_type(&right.ty()) else {
-                return Err(Error::InvalidCast);
+            let mut left = if operator.compound() {
+                typecheck_lvalue(*left, variadic, table)?
+            } else {
+                check_boxed_expr(*left, variadic, table)?
+            };
+            let mut right = check_boxed_expr(*right, variadic, table)?;
+            check_scalar(&left)?;
+            check_scalar(&right)?;
+            // each side is just tested against zero, so they don't need a common type
+            if matches!(operator, Bop::LogAnd | Bop::LogOr) {
+                return Ok(Expr::Binary {
+                    left,
+                    operator,
+                    right,
+                    ty: VarType::Int,
+                    span,
+                });
+            }
+            let pointers = left.ty().is_pointer() || right.ty().is_pointer();
+            if pointers && !operator.relational() {
+                return pointer_arithmetic(operator, left, right, span);
+            }
+            if operator.integer_only()
+                && let Some(double) = [&left, &right]
+                    .into_iter()
+                    .find(|e| e.ty() == VarType::Double)
+            {
+                return Err(Error::InvalidOperand {
+                    span: double.span(),
+                    ty: double.ty(),
+                });
+            }
+            let ty = if pointers {
+                common_pointer_type(&left, &right).ok_or(Error::MismatchedOperands {
+                    span,
+                    left: left.ty(),
+                    right: right.ty(),
+                })?
+            } else {
+                let Some(ty) = left.ty().common_type(&right.ty()) else {
+                    return Err(Error::InvalidCast(span));
+                };
+                ty
             };
-            convert_to(&mut left, &ty);
             convert_to(&mut right, &ty);
+            // a compound assignment stores back into the left side, so that keeps its own type
+            // and tacky converts it to the common type and back
+            let ty = if operator.compound() {
+                left.ty()
+            } else {
+                convert_to(&mut left, &ty);
+                ty
+            };
             Ok(Expr::Binary {
                 left,
                 operator,
                 right,
-                ty: if operator.relational() || matches!(operator, Bop::LogAnd | Bop::LogOr) {
+                ty: if operator.relational() {
                     VarType::Int
                 } else {
                     ty
                 },
+                span,
             })
         }
-        ast::Expr::Nested(exp) => typecheck_expression(*exp, table),
-        ast::Expr::Const(cnst @ Constant::Int(_)) => Ok(Expr::Const {
+        ast::Expr::Nested(exp, _) => typecheck_without_decay(*exp, variadic, table),
+        ast::Expr::Const(cnst @ Constant::Int(_), span) => Ok(Expr::Const {
             cnst,
             ty: VarType::Int,
+            span,
         }),
 
-        ast::Expr::Const(cnst @ Constant::Long(_)) => Ok(Expr::Const {
+        ast::Expr::Const(cnst @ Constant::Long(_), span) => Ok(Expr::Const {
             cnst,
             ty: VarType::Long,
+            span,
         }),
 
-        ast::Expr::Cast { target, exp } => typecheck_expression(*exp, table).map(|e| Expr::Cast {
-            ty: target,
-            target,
-            exp: Box::new(e),
+        ast::Expr::Const(cnst @ Constant::UInt(_), span) => Ok(Expr::Const {
+            cnst,
+            ty: VarType::UInt,
+            span,
+        }),
+
+        ast::Expr::Const(cnst @ Constant::ULong(_), span) => Ok(Expr::Const {
+            cnst,
+            ty: VarType::ULong,
+            span,
+        }),
+
+        ast::Expr::Const(cnst @ Constant::Double(_), span) => Ok(Expr::Const {
+            cnst,
+            ty: VarType::Double,
+            span,
+        }),
+
+        ast::Expr::Const(cnst @ Constant::Char(_), span) => Ok(Expr::Const {
+            cnst,
+            ty: VarType::Char,
+            span,
+        }),
+
+        ast::Expr::Const(cnst @ Constant::UChar(_), span) => Ok(Expr::Const {
+            cnst,
+            ty: VarType::UChar,
+            span,
+        }),
+
+        ast::Expr::String(bytes, span) => {
+            let (name, ty) = string_constant(&bytes, table);
+            Ok(Expr::Var { name, ty, span })
+        }
+
+        ast::Expr::Cast {
+            target: VarType::Void,
+            exp,
+            span,
+        } => Ok(Expr::Cast {
+            ty: VarType::Void,
+            target: VarType::Void,
+            exp: Box::new(typecheck_discarded(*exp, variadic, table)?),
+            span,
         }),
+        ast::Expr::Cast { target, exp, span } => {
+            let target = array_lengths(target, table)?.unqualified();
+            let exp = typecheck_expression(*exp, variadic, table)?;
+            if !exp.ty().scalar() || !target.scalar() {
+                return Err(Error::IncompatibleTypes {
+                    span,
+                    from: exp.ty(),
+                    to: target,
+                });
+            }
+            // there's no sensible way to turn a double into an address, or back
+            if matches!(
+                (exp.ty(), target),
+                (VarType::Double, VarType::Pointer(_)) | (VarType::Pointer(_), VarType::Double)
+            ) {
+                return Err(Error::IncompatibleTypes {
+                    span,
+                    from: exp.ty(),
+                    to: target,
+                });
+            }
+            Ok(Expr::Cast {
+                ty: target,
+                target,
+                exp: Box::new(exp),
+                span,
+            })
+        }
 
-        ast::Expr::Unary { operator, operand } => {
+        ast::Expr::Unary {
+            operator,
+            operand,
+            span,
+        } => {
             use crate::parse::UnOp;
-            typecheck_expression(*operand, table)
-                .map(Box::new)
-                .map(|operand| Expr::Unary {
-                    ty: if operator == UnOp::Not {
-                        VarType::Int
-                    } else {
-                        operand.ty()
-                    },
-                    operand,
-                    operator,
-                })
+            let mut operand = check_boxed_expr(*operand, variadic, table)?;
+            check_scalar(&operand)?;
+            if operator != UnOp::Not {
+                let promoted = operand.ty().promoted();
+                convert_to(&mut operand, &promoted);
+            }
+            let double_complement = operator == UnOp::Complement && operand.ty() == VarType::Double;
+            if (operator != UnOp::Not && operand.ty().is_pointer()) || double_complement {
+                return Err(Error::InvalidOperand {
+                    span: operand.span(),
+                    ty: operand.ty(),
+                });
+            }
+            Ok(Expr::Unary {
+                ty: if operator == UnOp::Not {
+                    VarType::Int
+                } else {
+                    operand.ty()
+                },
+                operand,
+                operator,
+                span,
+            })
+        }
+        ast::Expr::Dot { exp, member, span } => {
+            let exp = Box::new(typecheck_without_decay(*exp, variadic, table)?);
+            let (ty, offset) = self::member(exp.ty(), &member, span)?;
+            Ok(Expr::Dot {
+                exp,
+                offset,
+                ty,
+                span,
+            })
+        }
+        ast::Expr::VaStart(ap, span) => {
+            if !variadic {
+                return Err(Error::VaStartNotVariadic(span));
+            }
+            let ap = va_list(*ap, variadic, table)?;
+            Ok(Expr::VaStart {
+                ap,
+                ty: VarType::Void,
+                span,
+            })
+        }
+        // only the type of the operand matters, so none of it makes it past typecheck
+        ast::Expr::SizeOf(exp, span) => {
+            let exp = typecheck_without_decay(*exp, variadic, table)?;
+            size_of(exp.ty(), exp.span()).map(|size| size_constant(size, span))
+        }
+        ast::Expr::SizeOfType(ty, span) => {
+            let ty = array_lengths(ty, table)?;
+            size_of(ty, span).map(|size| size_constant(size, span))
+        }
+        ast::Expr::AlignOf(ty, span) => {
+            let ty = array_lengths(ty, table)?;
+            size_of(ty, span)?;
+            Ok(size_constant(ty.type_alignment(), span))
+        }
+        ast::Expr::OffsetOf {
+            target,
+            member: name,
+            span,
+        } => {
+            let target = array_lengths(target, table)?;
+            member(target, &name, span)?;
+            Ok(Expr::OffsetOf {
+                target,
+                member: name,
+                ty: VarType::ULong,
+                span,
+            })
+        }
+        // inside a function a compound literal is a local variable, `static_literal` takes care of
+        // the ones in static initializers
+        ast::Expr::CompoundLiteral { ty, init, span } => {
+            let ty = initialized_type(array_lengths(ty, table)?, Some(&init), span, table)?;
+            check_complete(ty, span)?;
+            if ty.is_function() {
+                return Err(Error::InvalidOperand { span, ty });
+            }
+            let init = typecheck_initializer(*init, ty, variadic, table)?;
+            let name = literal_name();
+            table.insert(name.clone(), Attr::Automatic(ty.unqualified()));
+            Ok(Expr::CompoundLiteral {
+                name,
+                init: Box::new(init),
+                ty,
+                span,
+            })
+        }
+        ast::Expr::VaArg { ap, target, span } => {
+            let ap = va_list(*ap, variadic, table)?;
+            let target = array_lengths(target, table)?;
+            check_complete(target, span)?;
+            if target.is_array() {
+                return Err(Error::InvalidOperand { span, ty: target });
+            }
+            Ok(Expr::VaArg {
+                ap,
+                ty: target.unqualified(),
+                span,
+            })
+        }
+        ast::Expr::Arrow { exp, member, span } => {
+            let exp = check_boxed_expr(*exp, variadic, table)?;
+            let VarType::Pointer(&target) = exp.ty() else {
+                return Err(Error::NotAPointer {
+                    span: exp.span(),
+                    ty: exp.ty(),
+                });
+            };
+            let (ty, offset) = self::member(target, &member, span)?;
+            Ok(Expr::Arrow {
+                exp,
+                offset,
+                ty,
+                span,
+            })
+        }
+        ast::Expr::Deref(exp, span) => {
+            let exp = check_boxed_expr(*exp, variadic, table)?;
+            let VarType::Pointer(&ty) = exp.ty() else {
+                return Err(Error::NotAPointer {
+                    span: exp.span(),
+                    ty: exp.ty(),
+                });
+            };
+            if ty.unqualified() == VarType::Void {
+                return Err(Error::IncompleteType { span, ty });
+            }
+            Ok(Expr::Deref { exp, ty, span })
+        }
+        // resolve already made sure this is an lvalue
+        ast::Expr::AddrOf(exp, span) => {
+            let exp = Box::new(typecheck_without_decay(*exp, variadic, table)?);
+            Ok(Expr::AddrOf {
+                ty: exp.ty().pointer_to(),
+                exp,
+                span,
+            })
         }
 
         ast::Expr::Conditional {
             condition,
             r#true,
             r#false,
+            span,
         } => {
-            let condition = check_boxed_expr(*condition, table)?;
-            let mut r#true = check_boxed_expr(*r#true, table)?;
-            let mut r#false = check_boxed_expr(*r#false, table)?;
-
-            let Some(common) = r#true.ty().common_type(&r#false.r#ty()) else {
-                return Err(Error::InvalidCast);
+            let condition = Box::new(typecheck_condition(*condition, variadic, table)?);
+            let mut r#true = Box::new(typecheck_discarded(*r#true, variadic, table)?);
+            let mut r#false = Box::new(typecheck_discarded(*r#false, variadic, table)?);
+
+            let common = match (r#true.ty(), r#false.ty()) {
+                (VarType::Void, VarType::Void) => Some(VarType::Void),
+                (VarType::Void, _) | (_, VarType::Void) => None,
+                (left, right) if left.is_pointer() || right.is_pointer() => {
+                    common_pointer_type(&r#true, &r#false)
+                }
+                (left, right) => left.common_type(&right),
+            };
+            let Some(common) = common else {
+                return Err(Error::MismatchedOperands {
+                    span,
+                    left: r#true.ty(),
+                    right: r#false.ty(),
+                });
             };
 
             convert_to(&mut r#true, &common);
@@ -428,74 +1681,273 @@ fn typecheck_expression(expression: ast::Expr, table: &mut SymbolTable) -> Resul
                 condition,
                 r#true,
                 r#false,
+                span,
             })
         }
-        ast::Expr::IncDec { op, exp } => {
+        ast::Expr::IncDec { op, exp, span } => {
             //Expression::PostfixIncrement(
-            let exp = check_boxed_expr(*exp, table)?;
+            let exp = typecheck_lvalue(*exp, variadic, table)?;
+            check_scalar(&exp)?;
+            if let Some(elem) = exp.ty().element() {
+                check_complete(elem, exp.span())?;
+            }
             Ok(Expr::IncDec {
                 op,
                 ty: exp.ty(),
                 exp,
+                span,
+            })
+        }
+        ast::Expr::Subscript { array, index, span } => {
+            let mut array = check_boxed_expr(*array, variadic, table)?;
+            let mut index = check_boxed_expr(*index, variadic, table)?;
+            // `1[arr]` is the same as `arr[1]`
+            if index.ty().is_pointer() {
+                std::mem::swap(&mut array, &mut index);
+            }
+            let VarType::Pointer(&ty) = array.ty() else {
+                return Err(Error::NotSubscriptable {
+                    span: array.span(),
+                    ty: array.ty(),
+                });
+            };
+            if !index.ty().integer() {
+                return Err(Error::InvalidOperand {
+                    span: index.span(),
+                    ty: index.ty(),
+                });
+            }
+            check_complete(ty, span)?;
+            convert_to(&mut index, &VarType::Long);
+            Ok(Expr::Subscript {
+                array,
+                index,
+                ty,
+                span,
             })
         }
     }
 }
 
+// `ptr + n`, `n + ptr`, `ptr - n`, `ptr - ptr` and their compound versions, the pointer always
+// ends up on the left with the integer converted to a long
+fn pointer_arithmetic(
+    operator: Bop,
+    mut left: Box<Expr>,
+    mut right: Box<Expr>,
+    span: Span,
+) -> Result<Expr, Error> {
+    if operator == Bop::Add && !left.ty().is_pointer() {
+        std::mem::swap(&mut left, &mut right);
+    }
+    let (left_ty, right_ty) = (left.ty(), right.ty());
+    if let Some(elem) = left_ty.element() {
+        check_complete(elem, left.span())?;
+    }
+    let ty = match operator {
+        Bop::Add | Bop::Subtract | Bop::PlusEquals | Bop::MinusEquals
+            if left_ty.is_pointer() && right_ty.integer() =>
+        {
+            convert_to(&mut right, &VarType::Long);
+            left_ty
+        }
+        Bop::Subtract if left_ty.is_pointer() && left_ty == right_ty => VarType::Long,
+        Bop::Subtract if left_ty.is_pointer() && right_ty.is_pointer() => {
+            return Err(Error::MismatchedOperands {
+                span,
+                left: left_ty,
+                right: right_ty,
+            });
+        }
+        _ => {
+            let pointer = if left_ty.is_pointer() { left } else { right };
+            return Err(Error::InvalidOperand {
+                span: pointer.span(),
+                ty: pointer.ty(),
+            });
+        }
+    };
+    Ok(Expr::Binary {
+        left,
+        operator,
+        right,
+        ty,
+        span,
+    })
+}
+
 fn convert_to(exp: &mut Expr, ty: &VarType) {
     if &exp.ty() != ty {
         *exp = Expr::Cast {
             ty: *ty,
             target: *ty,
+            span: exp.span(),
             exp: Box::from(exp.clone()),
         };
     }
 }
 
+// an integer constant that's zero, which is the only integer that converts to a pointer implicitly
+fn is_null_pointer(exp: &Expr) -> bool {
+    exp.ty().integer() && evaluate(exp, Error::NotConstInitialized).is_ok_and(|c| c.long() == 0)
+}
+
+// `void *` converts to and from any other pointer without a cast
+fn is_void_pointer(ty: VarType) -> bool {
+    matches!(ty, VarType::Pointer(pointee) if pointee.unqualified() == VarType::Void)
+}
+
+// pointers to the same type, or to void, with the result pointing at something qualified by both
+fn common_pointer_type(left: &Expr, right: &Expr) -> Option<VarType> {
+    let (left_ty, right_ty) = (left.ty(), right.ty());
+    if left_ty == right_ty || is_null_pointer(right) {
+        return Some(left_ty);
+    } else if is_null_pointer(left) {
+        return Some(right_ty);
+    }
+    let (VarType::Pointer(left_pointee), VarType::Pointer(right_pointee)) = (left_ty, right_ty)
+    else {
+        return None;
+    };
+    let qualifiers = left_pointee.qualifiers().union(right_pointee.qualifiers());
+    if left_pointee.unqualified() == right_pointee.unqualified() {
+        Some(
+            left_pointee
+                .unqualified()
+                .qualified(qualifiers)
+                .pointer_to(),
+        )
+    } else if is_void_pointer(left_ty) || is_void_pointer(right_ty) {
+        Some(VarType::Void.qualified(qualifiers).pointer_to())
+    } else {
+        None
+    }
+}
+
+// pointers can pick up qualifiers on what they point to, but not lose them
+fn pointer_convertible(from: VarType, to: VarType) -> bool {
+    let (VarType::Pointer(from_pointee), VarType::Pointer(to_pointee)) = (from, to) else {
+        return false;
+    };
+    to_pointee.qualifiers().contains(from_pointee.qualifiers())
+        && (from_pointee.unqualified() == to_pointee.unqualified()
+            || is_void_pointer(from)
+            || is_void_pointer(to))
+}
+
+// conversions that happen without a cast: assignment, initializers, arguments and return values
+fn convert_by_assignment(exp: &mut Expr, ty: VarType) -> Result<(), Error> {
+    let ty = ty.unqualified();
+    let from = exp.ty();
+    if from == ty
+        || (from.arithmetic() && ty.arithmetic())
+        || (ty.is_pointer() && is_null_pointer(exp))
+        || pointer_convertible(from, ty)
+    {
+        convert_to(exp, &ty);
+        Ok(())
+    } else {
+        Err(Error::IncompatibleTypes {
+            span: exp.span(),
+            from,
+            to: ty,
+        })
+    }
+}
+
+// `sizeof` and `_Alignof` need a complete object type
+fn size_of(ty: VarType, span: Span) -> Result<usize, Error> {
+    if ty.is_function() {
+        return Err(Error::InvalidOperand { span, ty });
+    }
+    check_complete(ty, span)?;
+    Ok(ty.size())
+}
+
+fn size_constant(size: usize, span: Span) -> Expr {
+    Expr::Const {
+        cnst: Constant::ULong(size as u64),
+        ty: VarType::ULong,
+        span,
+    }
+}
+
 fn typecheck_fn_call(
-    name: Identifier,
+    func: ast::Expr,
     args: Box<[ast::Expr]>,
+    span: Span,
+    variadic: bool,
     table: &mut SymbolTable,
 ) -> Result<Expr, Error> {
-    // cloning for now, need to find a nicer way to do this
-    let Some(typ) = table.get(&name) else {
-        return Err(Error::UndefinedFn);
+    let func = Box::new(typecheck_expression(func, variadic, table)?);
+    let VarType::Pointer(&VarType::Function(typ)) = func.ty() else {
+        return Err(Error::ExpectedFnType(func.span()));
     };
-
-    let FnType { ret, params } = typ.fn_type()?.clone();
-
-    if params.len() != args.len() {
-        Err(Error::WrongArgs)
+    let FnType {
+        ret,
+        params,
+        variadic: takes_rest,
+    } = typ.clone();
+
+    if params.len() != args.len() && !(takes_rest && args.len() > params.len()) {
+        // only a call by name has a declaration to point at
+        let declaration = match &*func {
+            Expr::AddrOf { exp, .. } => match &**exp {
+                Expr::Var { name, .. } => table.get(name).and_then(Attr::span),
+                _ => None,
+            },
+            _ => None,
+        };
+        Err(Error::WrongArgs { span, declaration })
     } else {
         let mut new_args = Vec::with_capacity(args.len());
-        for (param, arg) in args.into_iter().zip(params) {
-            let mut typed_param = typecheck_expression(param, table)?;
-            convert_to(&mut typed_param, &arg);
-            new_args.push(typed_param);
+        let mut args = args.into_iter();
+        // params first, so zip stops before it takes an extra argument
+        for (param, arg) in params.into_iter().zip(args.by_ref()) {
+            let mut typed_arg = typecheck_expression(arg, variadic, table)?;
+            convert_by_assignment(&mut typed_arg, param)?;
+            new_args.push(typed_arg);
+        }
+        // whatever lands in the `...` gets the default argument promotions
+        for arg in args {
+            let mut typed_arg = typecheck_expression(arg, variadic, table)?;
+            let promoted = typed_arg.ty().promoted();
+            convert_to(&mut typed_arg, &promoted);
+            new_args.push(typed_arg);
         }
-
-        let ty = if let Some(ty) = ret { ty } else { VarType::Int };
 
         Ok(Expr::FunctionCall {
-            ty,
-            name,
+            ty: ret,
+            func,
             args: new_args.into(),
+            span,
         })
     }
 }
 
-fn typecheck_var(name: Identifier, table: &mut SymbolTable) -> Result<Expr, Error> {
-    let ty = *table
-        .get(&name)
-        .map(Attr::var_type)
-        .ok_or(Error::UndefinedVar)??;
-    Ok(Expr::Var { ty, name })
+fn typecheck_var(name: Identifier, span: Span, table: &mut SymbolTable) -> Result<Expr, Error> {
+    let ty = match table.get(&name) {
+        Some(Attr::Fn { typ, .. }) => VarType::function(typ.clone()),
+        Some(Attr::Enumerator(value)) => {
+            return Ok(Expr::Const {
+                cnst: Constant::Int(*value),
+                ty: VarType::Int,
+                span,
+            })
+        }
+        Some(attr) => *attr.var_type(span)?,
+        None => return Err(Error::UndefinedVar(span)),
+    };
+    Ok(Expr::Var { ty, name, span })
 }
 
 fn check_entry(
     entry: &Entry<Identifier, Attr>,
     new_params: &ParamList,
+    new_ret: VarType,
+    new_variadic: bool,
     has_body: bool,
+    span: Span,
 ) -> Result<(), Error> {
     let Entry::Occupied(e) = entry else {
         return Ok(());
@@ -503,27 +1955,55 @@ fn check_entry(
     let Attr::Fn {
         defined,
         global: _,
-        typ: FnType { ret: _, params },
+        typ: FnType {
+            ret,
+            params,
+            variadic,
+        },
+        span: previous,
     } = e.get()
     else {
-        return Err(Error::ConflictingType);
+        return Err(Error::ConflictingType {
+            span,
+            previous: e.get().span(),
+        });
     };
 
-    if *defined && has_body {
-        Err(Error::DuplicateDefinition)
+    if *ret != new_ret {
+        Err(Error::ConflictingType {
+            span,
+            previous: Some(*previous),
+        })
+    } else if *defined && has_body {
+        Err(Error::DuplicateDefinition {
+            span,
+            previous: *previous,
+        })
+    } else if *variadic != new_variadic {
+        Err(Error::WrongParams {
+            span,
+            previous: *previous,
+        })
     } else {
-        param_typecheck(new_params, params)
+        param_typecheck(new_params, params, span, *previous)
     }
 }
 
-fn param_typecheck(new: &ParamList, old: &[VarType]) -> Result<(), Error> {
+fn param_typecheck(
+    new: &ParamList,
+    old: &[VarType],
+    span: Span,
+    previous: Span,
+) -> Result<(), Error> {
     if new.len() != old.len() {
-        return Err(Error::WrongParams);
+        return Err(Error::WrongParams { span, previous });
     }
-    let new_iter = new.iter().map(|param| param.typ);
-    for (new, &old) in new_iter.zip(old) {
-        if new != old {
-            return Err(Error::WrongParams);
+    for (new, &old) in new.iter().zip(old) {
+        if new.typ.unqualified() != old {
+            return Err(Error::WrongParams {
+                span: new.span,
+                previous,
+            });
         }
     }
     Ok(())
@@ -533,20 +2013,35 @@ fn function_declaration(
     ast::FnDec {
         name,
         body,
-        params,
+        mut params,
         typ,
         mut sc,
+        span,
     }: ast::FnDec,
     table: &mut SymbolTable,
     block_scope: bool,
 ) -> Result<FnDec, Error> {
+    let typ = function_lengths(&typ, table)?;
+    for param in params.iter_mut() {
+        param.typ = array_lengths(param.typ, table)?;
+    }
     let global = sc.is_none_or(|sc| sc == StorageClass::Extern);
 
     let has_body = body.is_some();
 
     let entry = table.entry(name.clone());
 
-    check_entry(&entry, &params, has_body)?;
+    check_entry(&entry, &params, typ.ret, typ.variadic, has_body, span)?;
+
+    // structs get passed and returned by value, so a definition needs to know how big they are
+    if has_body && typ.ret != VarType::Void {
+        check_complete(typ.ret, span)?;
+    }
+    for param in params.iter() {
+        if has_body || param.typ == VarType::Void {
+            check_complete(param.typ, param.span)?;
+        }
+    }
 
     match entry {
         Entry::Occupied(mut e) if !block_scope => {
@@ -554,6 +2049,7 @@ fn function_declaration(
                 defined,
                 global: was_global,
                 r#typ: _,
+                span: previous,
             } = e.get_mut()
             else {
                 unreachable!()
@@ -562,6 +2058,10 @@ fn function_declaration(
             if has_body {
                 *defined = true;
             }
+            let declared = *previous;
+            if has_body || !*defined {
+                *previous = span;
+            }
             /*
              * a function declared without the 'static'
              * keyword always has external linkage
@@ -576,7 +2076,10 @@ fn function_declaration(
                 // is static now, was previously extern or none
                 // should conflict but we'll keep it
                 (Some(StorageClass::Static), true) => {
-                    return Err(Error::StaticRedec);
+                    return Err(Error::StaticRedec {
+                        span,
+                        previous: declared,
+                    });
                 }
                 // is static now, was previously static
                 (Some(StorageClass::Static), false) => {}
@@ -602,6 +2105,7 @@ fn function_declaration(
                 typ: typ.clone(),
                 defined: has_body,
                 global,
+                span,
             });
         }
     };
@@ -613,7 +2117,7 @@ fn function_declaration(
     let body = if let Some(body) = body {
         let mut new_body = Vec::new();
         for item in body {
-            new_body.push(typecheck_blockitem(item, typ.ret, table)?);
+            new_body.push(typecheck_blockitem(item, &typ, table)?);
         }
         Some(new_body.into_boxed_slice())
     } else {
@@ -625,48 +2129,48 @@ fn function_declaration(
         body,
         sc,
         typ,
+        span,
     })
 }
 
+// `function` is the one whose body this is, for its return type and whether `va_start` is allowed
 fn typecheck_blockitem(
     block_item: ast::BlockItem,
-    ret: Option<VarType>,
+    function: &FnType,
     table: &mut SymbolTable,
 ) -> Result<BlockItem, Error> {
     match block_item {
-        ast::BlockItem::D(dec) => declaration(dec, table).map(BlockItem::D),
-        ast::BlockItem::S(s) => typecheck_statement(s, ret, table).map(BlockItem::S),
+        ast::BlockItem::D(dec) => declaration(dec, function.variadic, table).map(BlockItem::D),
+        ast::BlockItem::S(s) => typecheck_statement(s, function, table).map(BlockItem::S),
     }
 }
 
 fn typecheck_statement(
     stmt: ast::Stmnt,
-    return_type: Option<VarType>,
+    function: &FnType,
     table: &mut SymbolTable,
 ) -> Result<Stmnt, Error> {
     match stmt {
         ast::Stmnt::Compound(stmts) => {
             let mut statements = Vec::with_capacity(stmts.len());
             for item in stmts {
-                statements.push(typecheck_blockitem(item, return_type, table)?);
+                statements.push(typecheck_blockitem(item, function, table)?);
             }
             let statements = statements.into_boxed_slice();
 
             Ok(Stmnt::Compound(statements))
         }
-        ast::Stmnt::Exp(e) => typecheck_expression(e, table).map(Stmnt::Exp),
+        ast::Stmnt::Exp(e) => typecheck_discarded(e, function.variadic, table).map(Stmnt::Exp),
         ast::Stmnt::If {
             condition,
             then,
             r#else,
-        } => typecheck_expression(condition, table).and_then(|condition| {
-            typecheck_statement(*then, return_type, table)
+        } => typecheck_condition(condition, function.variadic, table).and_then(|condition| {
+            typecheck_statement(*then, function, table)
                 .map(Box::new)
                 .and_then(|then| {
                     r#else
-                        .map(|r#else| {
-                            typecheck_statement(*r#else, return_type, table).map(Box::new)
-                        })
+                        .map(|r#else| typecheck_statement(*r#else, function, table).map(Box::new))
                         .transpose()
                         .map(|r#else| Stmnt::If {
                             condition,
@@ -680,8 +2184,8 @@ fn typecheck_statement(
             condition,
             label,
         } => {
-            let body = typecheck_statement(*body, return_type, table)?;
-            let condition = typecheck_expression(condition, table)?;
+            let body = typecheck_statement(*body, function, table)?;
+            let condition = typecheck_condition(condition, function.variadic, table)?;
             Ok(Stmnt::DoWhile {
                 body: body.into(),
                 condition,
@@ -693,17 +2197,18 @@ fn typecheck_statement(
             condition,
             label,
         } => {
-            let body = typecheck_statement(*body, return_type, table)?;
-            let condition = typecheck_expression(condition, table)?;
+            let body = typecheck_statement(*body, function, table)?;
+            let condition = typecheck_condition(condition, function.variadic, table)?;
             Ok(Stmnt::While {
                 body: body.into(),
                 condition,
                 label,
             })
         }
-        ast::Stmnt::Label { body, name } => Ok(Stmnt::Label {
+        ast::Stmnt::Label { body, name, span } => Ok(Stmnt::Label {
             name,
-            body: typecheck_statement(*body, return_type, table)?.into(),
+            body: typecheck_statement(*body, function, table)?.into(),
+            span,
         }),
         ast::Stmnt::For {
             init,
@@ -714,19 +2219,25 @@ fn typecheck_statement(
         } => {
             let init = init
                 .map(|init| match *init {
-                    ast::ForInit::D(v) => variable_declaration(v, table).map(ForInit::D),
+                    ast::ForInit::D(v) => {
+                        variable_declaration(v, function.variadic, table).map(ForInit::D)
+                    }
 
-                    ast::ForInit::E(e) => typecheck_expression(e, table).map(ForInit::E),
+                    ast::ForInit::E(e) => {
+                        typecheck_discarded(e, function.variadic, table).map(ForInit::E)
+                    }
                 })
                 .transpose()?
                 .map(Box::new);
 
             let condition = condition
-                .map(|c| typecheck_expression(c, table))
+                .map(|c| typecheck_condition(c, function.variadic, table))
+                .transpose()?;
+            let post = post
+                .map(|p| typecheck_discarded(p, function.variadic, table))
                 .transpose()?;
-            let post = post.map(|p| typecheck_expression(p, table)).transpose()?;
 
-            let body = Box::new(typecheck_statement(*body, return_type, table)?);
+            let body = Box::new(typecheck_statement(*body, function, table)?);
             Ok(Stmnt::For {
                 init,
                 condition,
@@ -735,58 +2246,620 @@ fn typecheck_statement(
                 label,
             })
         }
-        ast::Stmnt::Ret(e) => {
-            if let Some(return_type) = return_type {
-                let mut r = typecheck_expression(e, table)?;
-                convert_to(&mut r, &return_type);
-                Ok(Stmnt::Ret(r))
-            } else {
-                panic!()
+        ast::Stmnt::Ret(e, span) => match (e, function.ret) {
+            (None, VarType::Void) => Ok(Stmnt::Ret(None, span)),
+            (Some(_), VarType::Void) => Err(Error::ReturnInVoid(span)),
+            (None, ty) => Err(Error::MissingReturnValue { span, ty }),
+            (Some(e), return_type) => {
+                let mut r = typecheck_expression(e, function.variadic, table)?;
+                convert_by_assignment(&mut r, return_type)?;
+                Ok(Stmnt::Ret(Some(r), span))
             }
-        }
+        },
         ast::Stmnt::Switch {
             val: v,
             body: b,
             label,
             cases,
             default,
-        } => Ok(Stmnt::Switch {
-            val: typecheck_expression(v, table)?,
-            body: Box::new(typecheck_statement(*b, return_type, table)?),
-            label,
-            cases,
-            default,
-        }),
+        } => {
+            let mut val = typecheck_expression(v, function.variadic, table)?;
+            if !val.ty().integer() {
+                return Err(Error::InvalidOperand {
+                    span: val.span(),
+                    ty: val.ty(),
+                });
+            }
+            let promoted = val.ty().promoted();
+            convert_to(&mut val, &promoted);
+            let cases = cases
+                .into_iter()
+                .map(|case| {
+                    let mut case = typecheck_expression(case, function.variadic, table)?;
+                    if !case.ty().integer() {
+                        return Err(Error::InvalidCase(case.span()));
+                    }
+                    convert_to(&mut case, &promoted);
+                    Ok((evaluate(&case, Error::InvalidCase)?, case.span()))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            // stable, so the first of any duplicates is the one that came first
+            let mut sorted = cases.clone();
+            sorted.sort_by_key(|(c, _)| *c);
+            if let Some([(_, previous), (_, span)]) = sorted.windows(2).find(|x| x[0].0 == x[1].0) {
+                return Err(Error::DuplicateCase {
+                    span: *span,
+                    previous: *previous,
+                });
+            }
+            Ok(Stmnt::Switch {
+                val,
+                body: Box::new(typecheck_statement(*b, function, table)?),
+                label,
+                cases: cases.into_iter().map(|(c, _)| c).collect(),
+                default,
+            })
+        }
 
         ast::Stmnt::Null => Ok(Stmnt::Null),
-        ast::Stmnt::Goto(g) => Ok(Stmnt::Goto(g)),
-        ast::Stmnt::Break(l) => Ok(Stmnt::Break(l)),
-        ast::Stmnt::Continue(c) => Ok(Stmnt::Continue(c)),
+        ast::Stmnt::Goto(g, span) => Ok(Stmnt::Goto(g, span)),
+        ast::Stmnt::Break(l, span) => Ok(Stmnt::Break(l, span)),
+        ast::Stmnt::Continue(c, span) => Ok(Stmnt::Continue(c, span)),
     }
 }
 
 #[derive(Debug)]
 pub enum Error {
-    DuplicateDefinition,
-    ConflictingDeclaration,
+    DuplicateDefinition {
+        span: Span,
+        previous: Span,
+    },
+    ConflictingDeclaration {
+        span: Span,
+        previous: Span,
+    },
+
+    UndefinedVar(Span),
+
+    UndefinedFn(Span),
+
+    WrongParams {
+        span: Span,
+        previous: Span,
+    },
+
+    FnAsVar {
+        span: Span,
+        previous: Span,
+    },
+    VarAsFn(Span),
+    WrongArgs {
+        span: Span,
+        declaration: Option<Span>,
+    },
+    // points at the declaration that doesn't match
+    ConflictingType {
+        span: Span,
+        previous: Option<Span>,
+    },
+    StaticGlobal(Span),
+
+    NotConstInitialized(Span),
+    ConflictingLinkage {
+        span: Span,
+        previous: Span,
+    },
+    DeclaredExtern(Span),
+    StaticRedec {
+        span: Span,
+        previous: Span,
+    },
+    InvalidCast(Span),
+    ExpectedVarType(Span),
+    ExpectedFnType(Span),
+    NotAPointer {
+        span: Span,
+        ty: VarType,
+    },
+    // implicitly converting `from` to `to` isn't allowed
+    IncompatibleTypes {
+        span: Span,
+        from: VarType,
+        to: VarType,
+    },
+    // comparisons and conditionals between pointers that don't match
+    MismatchedOperands {
+        span: Span,
+        left: VarType,
+        right: VarType,
+    },
+    InvalidOperand {
+        span: Span,
+        ty: VarType,
+    },
+    // `int arr[3] = 1;`
+    ArrayInitializer {
+        span: Span,
+        ty: VarType,
+    },
+    // more initializers than the array has elements
+    ExcessElements {
+        span: Span,
+        len: usize,
+        ty: VarType,
+    },
+    // `int x = {1};`
+    BracedScalar {
+        span: Span,
+        ty: VarType,
+    },
+    ArrayAssignment {
+        span: Span,
+        ty: VarType,
+    },
+    FnAssignment {
+        span: Span,
+        ty: VarType,
+    },
+    // `[3] = 1` for an array with fewer elements than that
+    DesignatorRange {
+        span: Span,
+        len: usize,
+        ty: VarType,
+    },
+    // `.x = 1` for an array, or `[0] = 1` for a struct
+    WrongDesignator {
+        span: Span,
+        ty: VarType,
+    },
+    // a case label that isn't an integer constant
+    InvalidCase(Span),
+    DuplicateCase {
+        span: Span,
+        previous: Span,
+    },
+    // signed arithmetic in a constant expression that doesn't fit in its type
+    ConstantOverflow {
+        span: Span,
+        ty: VarType,
+    },
+    NotSubscriptable {
+        span: Span,
+        ty: VarType,
+    },
+    // `char s[2] = "abc";`, there's room to drop the null terminator but nothing else
+    StringTooLong {
+        span: Span,
+        ty: VarType,
+    },
+    // `struct s;` without a definition, or one that's still being defined
+    IncompleteType {
+        span: Span,
+        ty: VarType,
+    },
+    StructRedefinition {
+        span: Span,
+        previous: Span,
+        ty: VarType,
+    },
+    DuplicateMember {
+        span: Span,
+        previous: Span,
+    },
+    // `.` or `->` on something that isn't a struct
+    NotAStruct {
+        span: Span,
+        ty: VarType,
+    },
+    NoMember {
+        span: Span,
+        ty: VarType,
+        member: Identifier,
+    },
+    // the result of a void function, or a cast to void, used for something
+    VoidValue(Span),
+    // `return 1;` in a void function
+    ReturnInVoid(Span),
+    // `return;` in a function that isn't void
+    MissingReturnValue {
+        span: Span,
+        ty: VarType,
+    },
+    VaStartNotVariadic(Span),
+    // assigning to, or incrementing, something const
+    ConstAssignment {
+        span: Span,
+        ty: VarType,
+    },
+    // an enumerator's value that isn't an integer constant expression
+    ExpectedConstant(Span),
+    InvalidArraySize(Span),
+    EnumeratorRange(Span),
+    // `[-1] = 1`, or an index that isn't an integer constant expression
+    InvalidDesignator(Span),
+}
+
+impl Error {
+    pub const fn span(&self) -> Span {
+        match self {
+            Self::DuplicateDefinition { span, .. }
+            | Self::ConflictingDeclaration { span, .. }
+            | Self::UndefinedVar(span)
+            | Self::UndefinedFn(span)
+            | Self::WrongParams { span, .. }
+            | Self::FnAsVar { span, .. }
+            | Self::VarAsFn(span)
+            | Self::WrongArgs { span, .. }
+            | Self::ConflictingType { span, .. }
+            | Self::StaticGlobal(span)
+            | Self::NotConstInitialized(span)
+            | Self::ConflictingLinkage { span, .. }
+            | Self::DeclaredExtern(span)
+            | Self::StaticRedec { span, .. }
+            | Self::InvalidCast(span)
+            | Self::ExpectedVarType(span)
+            | Self::ExpectedFnType(span)
+            | Self::NotAPointer { span, .. }
+            | Self::IncompatibleTypes { span, .. }
+            | Self::MismatchedOperands { span, .. }
+            | Self::InvalidOperand { span, .. }
+            | Self::ArrayInitializer { span, .. }
+            | Self::ExcessElements { span, .. }
+            | Self::BracedScalar { span, .. }
+            | Self::ArrayAssignment { span, .. }
+            | Self::FnAssignment { span, .. }
+            | Self::DesignatorRange { span, .. }
+            | Self::WrongDesignator { span, .. }
+            | Self::InvalidCase(span)
+            | Self::DuplicateCase { span, .. }
+            | Self::ConstantOverflow { span, .. }
+            | Self::NotSubscriptable { span, .. }
+            | Self::StringTooLong { span, .. }
+            | Self::IncompleteType { span, .. }
+            | Self::StructRedefinition { span, .. }
+            | Self::DuplicateMember { span, .. }
+            | Self::NotAStruct { span, .. }
+            | Self::NoMember { span, .. }
+            | Self::VoidValue(span)
+            | Self::ReturnInVoid(span)
+            | Self::MissingReturnValue { span, .. }
+            | Self::ConstAssignment { span, .. }
+            | Self::ExpectedConstant(span)
+            | Self::InvalidArraySize(span)
+            | Self::EnumeratorRange(span)
+            | Self::InvalidDesignator(span)
+            | Self::VaStartNotVariadic(span) => *span,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Error;
+    use crate::semantics::{self, checked};
+
+    // the first thing typecheck finds wrong with `source`
+    fn error(source: &[u8]) -> Error {
+        match checked(source) {
+            Err(semantics::Error::TypeCheck(e)) => e,
+            Err(e) => panic!("{e:?}"),
+            Ok(_) => panic!("nothing wrong with {}", String::from_utf8_lossy(source)),
+        }
+    }
+
+    #[test]
+    fn pointers() {
+        // zero is the only integer that converts to a pointer without a cast
+        assert!(checked(
+            b"int *f(int *p) { int *q = 0; long *l = (long *)p; return p == 0 ? q : p; }"
+        )
+        .is_ok());
+        assert!(matches!(
+            error(b"int f(void) { int *p = 1; return 0; }"),
+            Error::IncompatibleTypes { .. }
+        ));
+        assert!(matches!(
+            error(b"long *f(int *p) { return p; }"),
+            Error::IncompatibleTypes { .. }
+        ));
+        assert!(matches!(
+            error(b"int f(int *p, long *q) { return p == q; }"),
+            Error::MismatchedOperands { .. }
+        ));
+        assert!(matches!(
+            error(b"int f(int x) { return *x; }"),
+            Error::NotAPointer { .. }
+        ));
+        assert!(matches!(
+            error(b"int f(int *p) { return p * 2; }"),
+            Error::InvalidOperand { .. }
+        ));
+    }
+
+    #[test]
+    fn arrays() {
+        // arrays decay to pointers to their first element, which can be subtracted from each other
+        assert!(checked(
+            b"long f(int (*p)[3], int (*q)[3]) { int a[2][3]; return p - q + (a + 1 - a); }"
+        )
+        .is_ok());
+        // parentheses don't make them decay, that's up to what's around them
+        assert!(checked(
+            b"int f(void) { int a[4]; int (*p)[4] = &(a); (a)[1] = 2; return (*p)[1]; }"
+        )
+        .is_ok());
+        assert!(matches!(
+            error(b"int f(void) { int a[2]; int b[2]; (a) = b; return 0; }"),
+            Error::ArrayAssignment { .. }
+        ));
+        assert!(matches!(
+            error(b"long f(int *p, long *q) { return p - q; }"),
+            Error::MismatchedOperands { .. }
+        ));
+        assert!(matches!(
+            error(b"int *f(int *p, int *q) { return p + q; }"),
+            Error::InvalidOperand { .. }
+        ));
+        assert!(matches!(
+            error(b"int f(int x) { return x[0]; }"),
+            Error::NotSubscriptable { .. }
+        ));
+        assert!(matches!(
+            error(b"int f(void) { int a[2] = {1, 2, 3}; return 0; }"),
+            Error::ExcessElements { len: 2, .. }
+        ));
+        assert!(matches!(
+            error(b"int a[2] = 1;"),
+            Error::ArrayInitializer { .. }
+        ));
+        assert!(matches!(
+            error(b"int f(void) { int a[2]; int b[2]; a = b; return 0; }"),
+            Error::ArrayAssignment { .. }
+        ));
+    }
+
+    #[test]
+    fn doubles() {
+        // integers convert to double and back, but doubles don't have bits to work on or convert
+        // to pointers
+        assert!(
+            checked(b"double f(int i, unsigned long u) { return i / 2.0 + u + (long)1.5e3; }")
+                .is_ok()
+        );
+        for source in [
+            &b"double f(double d) { return d % 2; }"[..],
+            b"double f(double d) { return ~d; }",
+            b"double f(double d) { return d << 1; }",
+        ] {
+            assert!(matches!(error(source), Error::InvalidOperand { .. }));
+        }
+        assert!(matches!(
+            error(b"int *f(double d) { return (int *)d; }"),
+            Error::IncompatibleTypes { .. }
+        ));
+    }
+
+    #[test]
+    fn strings() {
+        // there's room to leave off the null terminator, but not anything else
+        assert!(checked(
+            b"char a[3] = \"abc\"; unsigned char b[] = \"\\x41\\n\"; char *p = \"hi\";"
+        )
+        .is_ok());
+        assert!(matches!(
+            error(b"char a[2] = \"abc\";"),
+            Error::StringTooLong { .. }
+        ));
+        assert!(matches!(
+            error(b"long a[3] = \"ab\";"),
+            Error::IncompatibleTypes { .. }
+        ));
+        assert!(matches!(
+            error(b"int *p = \"hi\";"),
+            Error::IncompatibleTypes { .. }
+        ));
+    }
+
+    #[test]
+    fn structs() {
+        // tags have their own namespace, and an inner scope can define its own
+        let source = b"struct s { int a; struct s *next; }; int s;
+            int f(struct s x, struct s *p) {
+                struct s y = x;
+                y = *p;
+                { struct s { long b; } z; z.b = 1; }
+                return y.a + p->next->a;
+            }";
+        assert!(checked(source).is_ok());
+        assert!(checked(
+            b"union u { int i; double d; }; double f(union u x) { x.i = 1; return x.d; }"
+        )
+        .is_ok());
+        assert!(matches!(
+            error(b"struct s { int a; }; int f(struct s x) { return x.b; }"),
+            Error::NoMember { .. }
+        ));
+        assert!(matches!(
+            error(b"int f(int x) { return x.a; }"),
+            Error::NotAStruct { .. }
+        ));
+        assert!(matches!(
+            error(b"struct s; int f(void) { struct s x; return 0; }"),
+            Error::IncompleteType { .. }
+        ));
+        assert!(matches!(
+            error(b"struct s { int a; long a; };"),
+            Error::DuplicateMember { .. }
+        ));
+        assert!(matches!(
+            error(b"struct s { int a; }; struct s { int a; };"),
+            Error::StructRedefinition { .. }
+        ));
+    }
+
+    #[test]
+    fn void() {
+        // void pointers convert to and from any other object pointer without a cast
+        assert!(checked(
+            b"void g(void); void f(int *p) { void *v = p; p = v; (void)p; g(); return; }"
+        )
+        .is_ok());
+        assert!(matches!(
+            error(b"void g(void); int f(void) { int x = g(); return x; }"),
+            Error::VoidValue(_)
+        ));
+        assert!(matches!(
+            error(b"int f(int x) { return (void)x; }"),
+            Error::VoidValue(_)
+        ));
+        assert!(matches!(
+            error(b"void f(void) { return 1; }"),
+            Error::ReturnInVoid(_)
+        ));
+        assert!(matches!(
+            error(b"int f(void) { return; }"),
+            Error::MissingReturnValue { .. }
+        ));
+    }
 
-    UndefinedVar,
+    #[test]
+    fn variadic() {
+        let source = b"int g(int n, ...) { __builtin_va_list ap; __builtin_va_start(ap, n); \
+                       return __builtin_va_arg(ap, int); } \
+                       int f(void) { return g(1) + g(2, 3.0, 'c'); }";
+        assert!(checked(source).is_ok());
+        assert!(matches!(
+            error(b"int g(int n, ...); int f(void) { return g(); }"),
+            Error::WrongArgs { .. }
+        ));
+        // whether `va_start` is allowed goes with the function it's in, not the last one checked
+        let source = b"int g(int n, ...) { return n; } \
+                       int f(int n) { __builtin_va_list ap; __builtin_va_start(ap, n); return 0; }";
+        assert!(matches!(error(source), Error::VaStartNotVariadic(_)));
+    }
+
+    #[test]
+    fn qualifiers() {
+        // qualifiers can be added on the way through a pointer, but not taken away
+        let source = b"typedef const int T; typedef int (*F)(long); int g(long l) { return l; } \
+                       int f(int *p, volatile int v) { T *c = p; F h = g; v = *c; return h(v); }";
+        assert!(checked(source).is_ok());
+        for source in [
+            &b"int f(void) { const int x = 1; x = 2; return x; }"[..],
+            b"int f(void) { const int x = 1; return x++; }",
+            b"int f(const int *p) { *p = 1; return 0; }",
+            b"struct s { int a; }; int f(const struct s *p) { p->a = 1; return 0; }",
+            b"typedef const long L; int f(void) { L x = 1; x += 1; return 0; }",
+        ] {
+            assert!(matches!(error(source), Error::ConstAssignment { .. }));
+        }
+        assert!(matches!(
+            error(b"int *f(const int *p) { return p; }"),
+            Error::IncompatibleTypes { .. }
+        ));
+    }
 
-    UndefinedFn,
+    #[test]
+    fn function_pointers() {
+        // a function decays to a pointer to itself, `&` and `*` on one don't change anything
+        let source = b"int g(int x) { return x; } int (*table[2])(int) = {g, &g}; \
+                       int f(int i) { int (*p)(int) = *g; return table[i](i) + (*p)(i) + p(i); }";
+        assert!(checked(source).is_ok());
+        assert!(matches!(
+            error(b"int g(int x); long (*p)(int) = g;"),
+            Error::IncompatibleTypes { .. }
+        ));
+        assert!(matches!(
+            error(b"int f(int (*p)(int)) { return p(1, 2); }"),
+            Error::WrongArgs { .. }
+        ));
+        assert!(matches!(
+            error(b"int f(int x) { return x(1); }"),
+            Error::ExpectedFnType(_)
+        ));
+    }
 
-    WrongParams,
+    #[test]
+    fn enumerator_values() {
+        // every enumerator has to be an int, worked out from an integer constant expression
+        assert!(matches!(
+            error(b"enum { A = 2147483647, B };"),
+            Error::EnumeratorRange(_)
+        ));
+        assert!(matches!(
+            error(b"enum { A = 4000000000u };"),
+            Error::EnumeratorRange(_)
+        ));
+        assert!(matches!(
+            error(b"int x; enum { A = x };"),
+            Error::ExpectedConstant(_)
+        ));
+        assert!(matches!(
+            error(b"enum { A = 1.5 };"),
+            Error::ExpectedConstant(_)
+        ));
+    }
 
-    FnAsVar,
-    VarAsFn,
-    WrongArgs,
-    ConflictingType,
-    StaticGlobal,
+    #[test]
+    fn designators() {
+        assert!(matches!(
+            error(b"int a[2] = {[2] = 1};"),
+            Error::DesignatorRange { len: 2, .. }
+        ));
+        assert!(matches!(
+            error(b"int a[2] = {[-1] = 1};"),
+            Error::InvalidDesignator(_)
+        ));
+        assert!(matches!(
+            error(b"int n; int a[2] = {[n] = 1};"),
+            Error::InvalidDesignator(_)
+        ));
+        assert!(matches!(
+            error(b"int a[2] = {.x = 1};"),
+            Error::WrongDesignator { .. }
+        ));
+        assert!(matches!(
+            error(b"struct s { int x; } v = {[0] = 1};"),
+            Error::WrongDesignator { .. }
+        ));
+        assert!(matches!(
+            error(b"struct s { int x; } v = {.y = 1};"),
+            Error::NoMember { .. }
+        ));
+        // a compound literal is an lvalue with the type it's given
+        let source = b"struct s { int x; }; \
+                       int f(void) { int *p = (int[]){1, 2}; (struct s){1}.x = 2; return p[1]; }";
+        assert!(checked(source).is_ok());
+        assert!(matches!(
+            error(b"int f(void) { int *p = (int[1]){1, 2}; return 0; }"),
+            Error::ExcessElements { len: 1, .. }
+        ));
+    }
 
-    NotConstInitialized,
-    ConflictingLinkage,
-    DeclaredExtern,
-    StaticRedec,
-    InvalidCast,
-    ExpectedVarType,
-    ExpectedFnType,
+    #[test]
+    fn sizes() {
+        assert!(matches!(
+            error(b"int f(void); long n = sizeof f;"),
+            Error::InvalidOperand { .. }
+        ));
+        assert!(matches!(
+            error(b"struct s; long n = sizeof(struct s);"),
+            Error::IncompleteType { .. }
+        ));
+        assert!(matches!(
+            error(b"long n = _Alignof(void);"),
+            Error::IncompleteType { .. }
+        ));
+        // parentheses don't make it decay either
+        assert!(matches!(
+            error(b"int f(void); long n = sizeof(f);"),
+            Error::InvalidOperand { .. }
+        ));
+        assert!(matches!(
+            error(b"int f(void) { extern int a[]; return sizeof(a); }"),
+            Error::IncompleteType { .. }
+        ));
+        // but the function designator on the right of a comma has decayed
+        assert!(checked(b"int f(void); int g(int i) { return sizeof(i, f) == 8; }").is_ok());
+    }
 }
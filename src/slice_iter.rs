@@ -1,5 +1,7 @@
 use crate::lex::Token;
-use crate::lex::{Constant, Identifier};
+use crate::lex::{Constant, DebugToken, Identifier, Span};
+use crate::parse::VarType;
+use std::collections::HashMap;
 use std::iter::Iterator;
 use std::slice::Iter;
 pub struct SliceIter<'a, T: Copy>(Iter<'a, T>);
@@ -43,7 +45,19 @@ impl<'a, T: Copy> SliceIter<'a, T> {
     }
 }
 
-pub struct TokenIter(std::vec::IntoIter<Token>);
+pub struct TokenIter {
+    tokens: std::vec::IntoIter<Token>,
+    // moves in lockstep with `tokens`
+    spans: std::vec::IntoIter<Span>,
+    prev: Span,
+    // how many `{` we're inside of, so recovery knows how far out it has to go
+    depth: usize,
+    // everything we recovered from so far
+    errors: Vec<parse::Error>,
+    // typedef names, with `None` for an ordinary identifier hiding one from further out, since
+    // `T * x;` means something different depending on what `T` is
+    scopes: Vec<HashMap<Identifier, Option<VarType>>>,
+}
 
 use fmt::Debug;
 use fmt::Formatter;
@@ -57,7 +71,6 @@ impl Debug for TokenIter {
     }
 }
 use super::parse;
-use parse::VarType;
 
 impl TokenIter {
     #[allow(dead_code)]
@@ -65,17 +78,97 @@ impl TokenIter {
         eprintln!("next: {:?}", self.peek());
     }
 
-    pub fn consume_type(&mut self) -> Option<VarType> {
-        match self.next_if(|x| x == &Token::Int || x == &Token::Long) {
-            Some(Token::Int) => Some(VarType::Int),
-            Some(Token::Long) => Some(VarType::Long),
-            _ => None,
+    pub fn new(tokens: Box<[DebugToken]>) -> Self {
+        let (tokens, spans): (Vec<Token>, Vec<Span>) =
+            tokens.into_iter().map(DebugToken::into_inner).unzip();
+        Self {
+            tokens: tokens.into_iter(),
+            spans: spans.into_iter(),
+            prev: Span::default(),
+            depth: 0,
+            errors: Vec::new(),
+            scopes: vec![HashMap::new()],
+        }
+    }
+
+    pub fn report(&mut self, error: parse::Error) {
+        self.errors.push(error);
+    }
+
+    pub fn take_errors(&mut self) -> Vec<parse::Error> {
+        std::mem::take(&mut self.errors)
+    }
+
+    pub fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    pub fn pop_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    // a variable, function, parameter or enumerator
+    pub fn declare(&mut self, name: &Identifier) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.clone(), None);
+        }
+    }
+
+    pub fn define_type(&mut self, name: &Identifier, typ: VarType) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.clone(), Some(typ));
+        }
+    }
+
+    // what `name` stands for, if it's a typedef name here
+    pub fn typedef(&self, name: &Identifier) -> Option<VarType> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .copied()
+            .flatten()
+    }
+
+    // whether the next token can start a declaration
+    pub fn at_declaration(&self) -> bool {
+        match self.peek() {
+            Some(Token::Identifier(name)) => self.typedef(name).is_some(),
+            Some(token) => token.specifier(),
+            None => false,
         }
     }
 
-    pub fn new(tokens: Box<[Token]>) -> Self {
-        let tokens: Vec<Token> = tokens.into();
-        Self(tokens.into_iter())
+    // where the next token is, or just past the last one if we're out
+    pub fn span(&self) -> Span {
+        self.spans
+            .as_slice()
+            .first()
+            .copied()
+            .unwrap_or_else(|| self.after_prev())
+    }
+
+    // right after the token we just consumed, where a missing `;` would go
+    pub const fn after_prev(&self) -> Span {
+        Span {
+            column: self.prev.column + self.prev.len,
+            len: 1,
+            ..self.prev
+        }
+    }
+
+    // the token we just consumed
+    pub const fn prev_span(&self) -> Span {
+        self.prev
+    }
+
+    // from `start` up to and including the token we just consumed
+    pub fn since(&self, start: Span) -> Span {
+        start.to(self.prev)
+    }
+
+    pub const fn depth(&self) -> usize {
+        self.depth
     }
 
     pub fn is_empty(&self) -> bool {
@@ -83,7 +176,7 @@ impl TokenIter {
     }
 
     pub fn peek(&self) -> Option<&Token> {
-        self.0.as_slice().first()
+        self.tokens.as_slice().first()
     }
 
     pub fn next_if(&mut self, f: impl Fn(&Token) -> bool) -> Option<Token> {
@@ -99,15 +192,15 @@ impl TokenIter {
     }
 
     pub fn peek_any(&self) -> Result<&Token, parse::Error> {
-        self.peek().ok_or(parse::Error::UnexpectedEof)
+        self.peek().ok_or(parse::Error::UnexpectedEof(self.span()))
     }
 
     pub fn as_slice(&self) -> &[Token] {
-        self.0.as_slice()
+        self.tokens.as_slice()
     }
 
     pub fn peek_peek(&self) -> Option<&Token> {
-        self.0.as_slice().get(1)
+        self.tokens.as_slice().get(1)
     }
 
     pub fn consume(&mut self, token: impl Into<Token>) -> Result<(), parse::Error> {
@@ -115,12 +208,45 @@ impl TokenIter {
         if self.peek().is_some_and(|x| x == &token) {
             self.next();
         } else {
-            return Err(parse::Error::Expected(token));
+            let (span, missing) = if self.prev == Span::default() {
+                (self.span(), false)
+            } else {
+                (self.after_prev(), self.missing(&token))
+            };
+            return Err(parse::Error::Expected(token, span, missing));
         }
 
         Ok(())
     }
 
+    // whether `token` was most likely left out right after the token we just consumed, rather than
+    // something else being in its way: `int x = 1` at the end of a line, `{ return x }`, `f(a;`
+    fn missing(&self, token: &Token) -> bool {
+        let end_of_line = self
+            .spans
+            .as_slice()
+            .first()
+            .is_none_or(|next| next.file != self.prev.file || next.line != self.prev.line);
+        let next = self.peek();
+        match token {
+            Token::Semicolon => end_of_line || next == Some(&Token::CloseBrace),
+            Token::CloseParen | Token::CloseBracket | Token::CloseBrace => {
+                end_of_line
+                    || matches!(
+                        next,
+                        Some(
+                            Token::Semicolon
+                                | Token::OpenBrace
+                                | Token::CloseParen
+                                | Token::CloseBracket
+                                | Token::CloseBrace
+                        )
+                    )
+            }
+            _ => false,
+        }
+    }
+
     pub fn consume_arr(
         &mut self,
         iter: impl IntoIterator<Item = Token>,
@@ -134,20 +260,21 @@ impl TokenIter {
     pub fn consume_identifier(&mut self) -> Result<Identifier, parse::Error> {
         match self.next_if(Token::identifier) {
             Some(Token::Identifier(ident)) => Ok(ident),
-            _ => Err(parse::Error::ExpectedIdentifier),
+            _ => Err(parse::Error::ExpectedIdentifier(self.span())),
         }
     }
 
     pub fn consume_constant(&mut self) -> Result<Constant, parse::Error> {
         match self.next_if(Token::constant) {
             Some(Token::Constant(c)) => Ok(c),
-            None => Err(parse::Error::UnexpectedEof),
-            _ => Err(parse::Error::ExpectedConstant),
+            None if self.is_empty() => Err(parse::Error::UnexpectedEof(self.span())),
+            _ => Err(parse::Error::ExpectedConstant(self.span())),
         }
     }
 
     pub fn consume_any(&mut self) -> Result<Token, parse::Error> {
-        self.next().ok_or(parse::Error::UnexpectedEof)
+        let span = self.span();
+        self.next().ok_or(parse::Error::UnexpectedEof(span))
     }
     pub fn token_slice(&self) -> &[Token] {
         self.as_slice()
@@ -170,6 +297,15 @@ impl TokenIter {
 impl Iterator for TokenIter {
     type Item = Token;
     fn next(&mut self) -> Option<Token> {
-        self.0.next()
+        if let Some(span) = self.spans.next() {
+            self.prev = span;
+        }
+        let token = self.tokens.next();
+        match token {
+            Some(Token::OpenBrace) => self.depth += 1,
+            Some(Token::CloseBrace) => self.depth = self.depth.saturating_sub(1),
+            _ => {}
+        }
+        token
     }
 }